@@ -53,6 +53,29 @@ fn main() -> Result<(), io::Error> {
             "paint-bucket-regular",
             "page-fit-regular",
             "resize-large-regular",
+            "ruler-regular",
+            "eraser-regular",
+            "stamp-regular",
+            "color-fill-regular",
+            "filter-regular",
+            "cube-regular",
+            "star-regular",
+            "checkmark-circle-regular",
+            "flowchart-regular",
+            "diamond-regular",
+            "table-regular",
+            "qr-code-regular",
+            "scan-text-regular",
+            "sparkle-regular",
+            "layer-regular",
+            "options-regular",
+            "data-histogram-regular",
+            "clock-regular",
+            "eye-regular",
+            "eye-off-regular",
+            "lock-closed-regular",
+            "lock-open-regular",
+            "drag-regular",
         ],
     );
 
@@ -0,0 +1,36 @@
+/// Brightness, contrast and saturation applied to the image, each from -1.0 to 1.0 with
+/// 0.0 leaving the image unchanged.
+///
+/// Adjustments are not part of the undo history, the original image is kept and they can
+/// be changed or reset at any time.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Adjustments {
+    pub brightness: f32,
+    pub contrast: f32,
+    pub saturation: f32,
+}
+
+impl Adjustments {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Adjusts the pixels in `buffer`, which holds `bytes_per_pixel` bytes per pixel
+    /// starting with red, green and blue. Alpha is left as is.
+    pub fn apply(&self, buffer: &mut [u8], bytes_per_pixel: usize) {
+        // brightness and contrast only depend on the channel value itself
+        let contrast = (1.0 + self.contrast).powi(2);
+        let levels: Vec<f32> = (0..=255)
+            .map(|v| ((v as f32 + self.brightness * 255.0) - 128.0) * contrast + 128.0)
+            .collect();
+        let saturation = 1.0 + self.saturation;
+
+        for pixel in buffer.chunks_exact_mut(bytes_per_pixel) {
+            let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|v| levels[v as usize]);
+            let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+            for (channel, value) in pixel.iter_mut().zip([r, g, b]) {
+                *channel = (luma + (value - luma) * saturation).clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::{fs, io};
+
+use anyhow::{Context, Result};
+use hex_color::HexColor;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    math::{self, Vec2D},
+    style::{Color, Size, Style},
+    tools::{Arrow, Drawable, Ellipse, Line, Rectangle, Text},
+};
+
+/// An annotation described by another program or saved as a snippet, positions are in
+/// image coordinates.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AnnotationSpec {
+    Arrow {
+        start: [f32; 2],
+        end: [f32; 2],
+        #[serde(flatten)]
+        style: StyleSpec,
+    },
+    Line {
+        start: [f32; 2],
+        end: [f32; 2],
+        #[serde(flatten)]
+        style: StyleSpec,
+    },
+    /// Spans the box between the two corners `start` and `end`.
+    Rectangle {
+        start: [f32; 2],
+        end: [f32; 2],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        corner_radius: Option<f32>,
+        #[serde(flatten)]
+        style: StyleSpec,
+    },
+    /// Fits into the box between the two corners `start` and `end`.
+    Ellipse {
+        start: [f32; 2],
+        end: [f32; 2],
+        #[serde(flatten)]
+        style: StyleSpec,
+    },
+    Text {
+        position: [f32; 2],
+        text: String,
+        #[serde(flatten)]
+        style: StyleSpec,
+    },
+}
+
+/// Style properties that fall back to the defaults when omitted.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct StyleSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<HexColor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<Size>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fill: Option<bool>,
+}
+
+impl From<Style> for StyleSpec {
+    fn from(style: Style) -> Self {
+        let Color { r, g, b, a } = style.color;
+        Self {
+            color: Some(HexColor::rgba(r, g, b, a)),
+            size: Some(style.size),
+            fill: Some(style.fill),
+        }
+    }
+}
+
+impl StyleSpec {
+    fn to_style(self) -> Style {
+        let mut style = Style::default();
+        if let Some(color) = self.color {
+            style.color = Color::from(color);
+        }
+        if let Some(size) = self.size {
+            style.size = size;
+        }
+        if let Some(fill) = self.fill {
+            style.fill = fill;
+        }
+        style
+    }
+}
+
+fn point([x, y]: [f32; 2]) -> Vec2D {
+    Vec2D::new(x, y)
+}
+
+/// Returns the top left corner and the size of the box between two corners.
+fn bounding_box(start: [f32; 2], end: [f32; 2]) -> (Vec2D, Vec2D) {
+    let top_left = Vec2D::new(start[0].min(end[0]), start[1].min(end[1]));
+    let size = Vec2D::new((end[0] - start[0]).abs(), (end[1] - start[1]).abs());
+    (top_left, size)
+}
+
+/// Parses a JSON list of annotations.
+pub fn parse(json: &str) -> Result<Vec<AnnotationSpec>> {
+    serde_json::from_str(json).context("Invalid annotation list")
+}
+
+/// Reads the annotations of a `--template` file, none while it doesn't exist yet.
+pub fn load_template(path: &str) -> Result<Vec<AnnotationSpec>> {
+    match fs::read_to_string(path) {
+        Ok(json) => parse(&json).with_context(|| format!("Invalid template {path}")),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read template {path}")),
+    }
+}
+
+/// Writes annotations to a `--template` file, in the format `parse` reads.
+pub fn store_template(path: &str, specs: &[AnnotationSpec]) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(specs)?)
+        .with_context(|| format!("Failed to write template {path}"))
+}
+
+/// Returns the top left corner and the size of the box around the positions of `specs`.
+pub fn bounds(specs: &[AnnotationSpec]) -> Option<(Vec2D, Vec2D)> {
+    let points: Vec<Vec2D> = specs
+        .iter()
+        .flat_map(|spec| spec.positions().into_iter().map(point))
+        .collect();
+    (!points.is_empty()).then(|| math::rect_around_points(&points))
+}
+
+impl AnnotationSpec {
+    fn positions(&self) -> Vec<[f32; 2]> {
+        match self {
+            AnnotationSpec::Arrow { start, end, .. }
+            | AnnotationSpec::Line { start, end, .. }
+            | AnnotationSpec::Rectangle { start, end, .. }
+            | AnnotationSpec::Ellipse { start, end, .. } => vec![*start, *end],
+            AnnotationSpec::Text { position, .. } => vec![*position],
+        }
+    }
+
+    pub fn translate(&mut self, offset: Vec2D) {
+        let positions = match self {
+            AnnotationSpec::Arrow { start, end, .. }
+            | AnnotationSpec::Line { start, end, .. }
+            | AnnotationSpec::Rectangle { start, end, .. }
+            | AnnotationSpec::Ellipse { start, end, .. } => vec![start, end],
+            AnnotationSpec::Text { position, .. } => vec![position],
+        };
+        for [x, y] in positions {
+            *x += offset.x;
+            *y += offset.y;
+        }
+    }
+
+    pub fn into_drawable(self) -> Box<dyn Drawable> {
+        match self {
+            AnnotationSpec::Arrow { start, end, style } => {
+                Box::new(Arrow::new(point(start), point(end), style.to_style()))
+            }
+            AnnotationSpec::Line { start, end, style } => {
+                Box::new(Line::new(point(start), point(end), style.to_style()))
+            }
+            AnnotationSpec::Rectangle {
+                start,
+                end,
+                corner_radius,
+                style,
+            } => {
+                let (top_left, size) = bounding_box(start, end);
+                let mut style = style.to_style();
+                if let Some(corner_radius) = corner_radius {
+                    style.corner_radius = corner_radius;
+                }
+                Box::new(Rectangle::new(top_left, size, style))
+            }
+            AnnotationSpec::Ellipse { start, end, style } => {
+                let (top_left, size) = bounding_box(start, end);
+                let radii = size * 0.5;
+                Box::new(Ellipse::new(top_left + radii, radii, style.to_style()))
+            }
+            AnnotationSpec::Text {
+                position,
+                text,
+                style,
+            } => Box::new(Text::with_text(point(position), &text, style.to_style())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const SPECS: &str = r##"[
+        {"type": "rectangle", "start": [10, 20], "end": [110, 70], "corner_radius": 4, "color": "#ff0000", "fill": true},
+        {"type": "arrow", "start": [0, 0], "end": [50, 50], "size": "large"},
+        {"type": "text", "position": [5, 5], "text": "note"}
+    ]"##;
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("satty-{}-{name}", std::process::id()))
+    }
+
+    fn to_json(specs: &[AnnotationSpec]) -> serde_json::Value {
+        serde_json::to_value(specs).unwrap()
+    }
+
+    #[test]
+    fn template_round_trip() {
+        let specs = parse(SPECS).unwrap();
+        let path = temp_file("template.json");
+        let path = path.to_str().unwrap();
+
+        store_template(path, &specs).unwrap();
+        let loaded = load_template(path);
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(to_json(&loaded.unwrap()), to_json(&specs));
+    }
+
+    #[test]
+    fn missing_template_is_empty() {
+        let path = temp_file("missing.json");
+        assert!(load_template(path.to_str().unwrap()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_template_is_an_error() {
+        let path = temp_file("invalid.json");
+        let path = path.to_str().unwrap();
+
+        fs::write(path, "[{\"type\": \"circle\"}]").unwrap();
+        let loaded = load_template(path);
+        fs::remove_file(path).unwrap();
+
+        assert!(loaded.is_err());
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    env, fs,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde_derive::Deserialize;
+
+use crate::{configuration::APP_CONFIG, external_command::shell_quote};
+
+/// A captured image in a temporary file, which is removed once this is dropped.
+pub struct Capture {
+    pub path: PathBuf,
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Lets the user select a region with the region command and captures it with the capture
+/// command. Returns `None` if the selection was cancelled.
+pub fn capture_region() -> Result<Option<Capture>> {
+    let region_command = APP_CONFIG.read().region_command().to_string();
+    let output = run(&region_command)?;
+    // slurp exits with 1 and prints nothing when the selection is cancelled
+    if output.stdout.trim_ascii().is_empty() && matches!(output.status.code(), Some(0 | 1)) {
+        return Ok(None);
+    }
+    let geometry = String::from_utf8_lossy(&checked(&region_command, output)?)
+        .trim()
+        .to_string();
+    capture(&geometry).map(Some)
+}
+
+/// Captures the focused window, found by the IPC of Hyprland or Sway. Returns the image and
+/// the title of the window.
+pub fn capture_window() -> Result<(Capture, String)> {
+    let window = if let Ok(signature) = env::var("HYPRLAND_INSTANCE_SIGNATURE") {
+        hyprland_window(&signature)?
+    } else if env::var_os("SWAYSOCK").is_some() {
+        sway_window()?
+    } else {
+        return Err(anyhow!(
+            "Capturing the active window needs Hyprland or Sway"
+        ));
+    };
+    let geometry = format!(
+        "{},{} {}x{}",
+        window.x, window.y, window.width, window.height
+    );
+    Ok((capture(&geometry)?, window.title))
+}
+
+struct Window {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    title: String,
+}
+
+#[derive(Deserialize)]
+struct HyprlandWindow {
+    at: [i32; 2],
+    size: [i32; 2],
+    title: String,
+}
+
+/// Finds the name of the output under the cursor by the IPC of Hyprland. Sway can't tell
+/// where the cursor is, its focused output is used instead.
+pub fn output_under_cursor() -> Result<String> {
+    if let Ok(signature) = env::var("HYPRLAND_INSTANCE_SIGNATURE") {
+        hyprland_cursor_output(&signature)
+    } else if env::var_os("SWAYSOCK").is_some() {
+        sway_focused_output()
+    } else {
+        Err(anyhow!(
+            "Finding the output under the cursor needs Hyprland or Sway"
+        ))
+    }
+}
+
+// sends `command` to the socket of Hyprland and returns the reply
+fn hyprland_request(signature: &str, command: &str) -> Result<String> {
+    // older versions of Hyprland keep the socket in /tmp
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+    let socket = [runtime_dir, Some(env::temp_dir())]
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join("hypr").join(signature).join(".socket.sock"))
+        .find(|path| path.exists())
+        .ok_or(anyhow!("Hyprland socket not found"))?;
+
+    let mut stream =
+        UnixStream::connect(&socket).with_context(|| format!("Failed to connect to {socket:?}"))?;
+    stream.write_all(command.as_bytes())?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    Ok(reply)
+}
+
+fn hyprland_window(signature: &str) -> Result<Window> {
+    let json = hyprland_request(signature, "j/activewindow")?;
+    // without a focused window the reply is an empty object
+    let window: HyprlandWindow =
+        serde_json::from_str(&json).map_err(|_| anyhow!("No focused window"))?;
+    Ok(Window {
+        x: window.at[0],
+        y: window.at[1],
+        width: window.size[0],
+        height: window.size[1],
+        title: window.title,
+    })
+}
+
+#[derive(Deserialize)]
+struct HyprlandCursor {
+    x: f64,
+    y: f64,
+}
+
+#[derive(Deserialize)]
+struct HyprlandMonitor {
+    name: String,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    scale: f64,
+    transform: u8,
+}
+
+impl HyprlandMonitor {
+    // the position is in logical pixels, the size in physical pixels before the rotation
+    fn contains(&self, x: f64, y: f64) -> bool {
+        let (width, height) = if self.transform % 2 == 1 {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        };
+        (0.0..width as f64 / self.scale).contains(&(x - self.x as f64))
+            && (0.0..height as f64 / self.scale).contains(&(y - self.y as f64))
+    }
+}
+
+fn hyprland_cursor_output(signature: &str) -> Result<String> {
+    let cursor: HyprlandCursor = serde_json::from_str(&hyprland_request(signature, "j/cursorpos")?)
+        .context("Invalid reply of Hyprland")?;
+    let monitors: Vec<HyprlandMonitor> =
+        serde_json::from_str(&hyprland_request(signature, "j/monitors")?)
+            .context("Invalid reply of Hyprland")?;
+    monitors
+        .into_iter()
+        .find(|monitor| monitor.contains(cursor.x, cursor.y))
+        .map(|monitor| monitor.name)
+        .ok_or(anyhow!("No output under the cursor"))
+}
+
+#[derive(Deserialize)]
+struct SwayNode {
+    #[serde(rename = "type")]
+    node_type: String,
+    name: Option<String>,
+    focused: bool,
+    rect: SwayRect,
+    #[serde(default)]
+    nodes: Vec<SwayNode>,
+    #[serde(default)]
+    floating_nodes: Vec<SwayNode>,
+}
+
+#[derive(Deserialize)]
+struct SwayRect {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl SwayNode {
+    fn focused(self) -> Option<SwayNode> {
+        if self.focused {
+            return Some(self);
+        }
+        self.nodes
+            .into_iter()
+            .chain(self.floating_nodes)
+            .find_map(SwayNode::focused)
+    }
+}
+
+fn sway_window() -> Result<Window> {
+    let tree: SwayNode = serde_json::from_slice(&output("swaymsg -t get_tree")?)
+        .context("Invalid reply of swaymsg")?;
+    // an empty workspace is focused when there is no window
+    let node = tree
+        .focused()
+        .filter(|node| matches!(node.node_type.as_str(), "con" | "floating_con"))
+        .ok_or(anyhow!("No focused window"))?;
+    Ok(Window {
+        x: node.rect.x,
+        y: node.rect.y,
+        width: node.rect.width,
+        height: node.rect.height,
+        title: node.name.unwrap_or_default(),
+    })
+}
+
+#[derive(Deserialize)]
+struct SwayOutput {
+    name: String,
+    focused: bool,
+}
+
+fn sway_focused_output() -> Result<String> {
+    let outputs: Vec<SwayOutput> = serde_json::from_slice(&output("swaymsg -t get_outputs")?)
+        .context("Invalid reply of swaymsg")?;
+    outputs
+        .into_iter()
+        .find(|output| output.focused)
+        .map(|output| output.name)
+        .ok_or(anyhow!("No focused output"))
+}
+
+// `{geometry}` in the capture command is replaced by the region, e.g. `10,20 300x200`
+fn capture(geometry: &str) -> Result<Capture> {
+    let command = APP_CONFIG
+        .read()
+        .capture_command()
+        .replace("{geometry}", &shell_quote(geometry));
+    let png = output(&command)?;
+    if png.is_empty() {
+        return Err(anyhow!("'{command}' printed no image"));
+    }
+
+    let path = env::temp_dir().join(format!(
+        "satty-capture-{}.png",
+        chrono::Local::now().format("%Y%m%d-%H%M%S%.3f")
+    ));
+    fs::write(&path, png).with_context(|| format!("Failed to write {path:?}"))?;
+    Ok(Capture { path })
+}
+
+// runs `command` by `sh -c` and returns what it printed
+fn output(command: &str) -> Result<Vec<u8>> {
+    checked(command, run(command)?)
+}
+
+fn run(command: &str) -> Result<Output> {
+    Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .with_context(|| format!("Failed to run '{command}'"))
+}
+
+// what `command` printed, if it succeeded
+fn checked(command: &str, output: Output) -> Result<Vec<u8>> {
+    if !output.status.success() {
+        return Err(anyhow!(
+            "'{command}' failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
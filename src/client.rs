@@ -1,25 +1,91 @@
-use anyhow::{Context, Result};
-use std::io::Read;
+use anyhow::{anyhow, Context, Result};
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::{self, Command, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{env, fs, io};
 
-use crate::ipc::{IpcClient, IpcMessage};
+use crate::configuration::SessionOptions;
+use crate::ipc::{IpcClient, IpcMessage, IpcResponse};
+
+// how often and how long to wait for a spawned daemon to answer
+const DAEMON_START_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const DAEMON_START_ATTEMPTS: u32 = 100;
+
+// names to try for the temporary file before giving up
+const TEMP_FILE_ATTEMPTS: u32 = 100;
 
 pub struct Client;
 
+/// A file only the current user can read, removed when dropped.
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn create(data: &[u8]) -> Result<Self> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+
+        for attempt in 0..TEMP_FILE_ATTEMPTS {
+            let path =
+                env::temp_dir().join(format!("satty-{}-{timestamp}-{attempt}.png", process::id()));
+
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            options.mode(0o600);
+
+            match options.open(&path) {
+                Ok(mut file) => {
+                    let temp_file = Self { path };
+                    file.write_all(data)
+                        .context("Failed to write temporary file")?;
+                    return Ok(temp_file);
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e).context("Failed to create temporary file"),
+            }
+        }
+        Err(anyhow!(
+            "Failed to find an unused name for the temporary file"
+        ))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            eprintln!(
+                "Failed to remove temporary file {}: {e}",
+                self.path.display()
+            );
+        }
+    }
+}
+
 impl Client {
-    pub async fn send_image(filename: &str) -> Result<()> {
+    pub async fn send_image(
+        filename: &str,
+        options: SessionOptions,
+        spawn_daemon: bool,
+    ) -> Result<()> {
+        // kept until the daemon answered, it has loaded the image by then
+        let mut temp_file = None;
+        let mut source = None;
         let filename = if filename == "-" {
-            let temp_dir = env::temp_dir();
-            let temp_path = temp_dir.join(format!("satty-{}.png", std::process::id()));
-
             let mut buf = Vec::<u8>::new();
             io::stdin().lock().read_to_end(&mut buf)
                 .context("Failed to read image from stdin")?;
 
-            fs::write(&temp_path, buf)
-                .context("Failed to write temporary file")?;
-
-            temp_path.to_string_lossy().to_string()
+            Self::check_answers_when_loaded(spawn_daemon).await?;
+            source = Some("stdin".to_string());
+            let temp_file = temp_file.insert(TempFile::create(&buf)?);
+            temp_file.path.to_string_lossy().to_string()
         } else {
             fs::canonicalize(filename)
                 .context(format!("Failed to resolve image file path: {}", filename))?
@@ -27,11 +93,30 @@ impl Client {
                 .to_string()
         };
 
-        let message = IpcMessage::LoadImage { filename };
+        let message = IpcMessage::LoadImage {
+            filename,
+            options,
+            source,
+        };
 
-        match IpcClient::send_message(&message).await {
-            Ok(_) => {
+        let mut result = IpcClient::send_message(&message).await;
+        if spawn_daemon && result.as_ref().is_err_and(IpcClient::is_daemon_missing) {
+            eprintln!("No daemon running, starting one");
+            Self::spawn_daemon().await?;
+            result = IpcClient::send_message(&message).await;
+        }
+
+        match result {
+            Ok(IpcResponse::Error(e)) => {
+                eprintln!("The daemon failed to load the image: {e}");
+                Err(anyhow!(e))
+            }
+            Ok(response) => {
                 eprintln!("Image sent to daemon successfully");
+                // daemons before protocol version 3 don't tell the window
+                if let Some(window) = Self::window_id(&response) {
+                    println!("{window}");
+                }
                 Ok(())
             }
             Err(e) => {
@@ -41,13 +126,124 @@ impl Client {
         }
     }
 
+    /// Makes sure the daemon answers only once it loaded the image, so that the temporary file
+    /// may be removed then, starting one if allowed and none is running.
+    async fn check_answers_when_loaded(spawn_daemon: bool) -> Result<()> {
+        let version = match IpcClient::protocol_version().await {
+            Err(e) if spawn_daemon && IpcClient::is_daemon_missing(&e) => {
+                eprintln!("No daemon running, starting one");
+                Self::spawn_daemon().await?;
+                return Ok(());
+            }
+            result => result?,
+        };
+        if version < 2 {
+            return Err(anyhow!(
+                "The daemon uses protocol version {version} and may read the image after it was removed, please restart the daemon to send images from stdin"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Id of the window showing an image sent to the daemon, from its answer.
+    fn window_id(response: &IpcResponse) -> Option<u64> {
+        let IpcResponse::Text(info) = response else {
+            return None;
+        };
+        serde_json::from_str::<serde_json::Value>(info)
+            .ok()?
+            .get("window")?
+            .as_u64()
+    }
+
+    /// Starts a daemon in the background and waits until it answers.
+    async fn spawn_daemon() -> Result<()> {
+        Command::new(env::current_exe().context("Failed to locate the satty executable")?)
+            .arg("--daemon")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("Failed to start the daemon")?;
+
+        for _ in 0..DAEMON_START_ATTEMPTS {
+            glib::timeout_future(DAEMON_START_POLL_INTERVAL).await;
+            if IpcClient::send_message(&IpcMessage::Ping).await.is_ok() {
+                return Ok(());
+            }
+        }
+        Err(anyhow!("The daemon did not start in time"))
+    }
+
     pub async fn ping() -> Result<()> {
         let message = IpcMessage::Ping;
-        IpcClient::send_message(&message).await?;
-        eprintln!("Daemon is running");
+        if let IpcResponse::Text(info) = IpcClient::send_message(&message).await? {
+            println!("{info}");
+
+            let version = serde_json::from_str::<serde_json::Value>(&info)
+                .ok()
+                .and_then(|info| info["version"].as_str().map(String::from));
+            match version {
+                Some(version) if version != env!("CARGO_PKG_VERSION") => eprintln!(
+                    "Warning: the daemon runs version {version}, this is version {}",
+                    env!("CARGO_PKG_VERSION")
+                ),
+                Some(_) => {}
+                None => eprintln!("Warning: the daemon does not report its version"),
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn list_recent() -> Result<()> {
+        match IpcClient::send_message(&IpcMessage::ListRecent).await? {
+            IpcResponse::Text(list) if !list.is_empty() => println!("{list}"),
+            _ => eprintln!("The daemon has not received any images yet"),
+        }
         Ok(())
     }
 
+    pub async fn load_recent(index: u32) -> Result<()> {
+        match IpcClient::send_message(&IpcMessage::LoadRecent { index }).await? {
+            IpcResponse::Error(e) => Err(anyhow!(e)),
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn print_state() -> Result<()> {
+        if let IpcResponse::Text(state) = IpcClient::send_message(&IpcMessage::GetState).await? {
+            println!("{state}");
+        }
+        Ok(())
+    }
+
+    /// Sends the annotations to the window with id `window`, or the most recently opened one.
+    pub async fn add_annotations(filename: &str, window: Option<usize>) -> Result<()> {
+        let json = if filename == "-" {
+            let mut json = String::new();
+            io::stdin()
+                .lock()
+                .read_to_string(&mut json)
+                .context("Failed to read annotations from stdin")?;
+            json
+        } else {
+            fs::read_to_string(filename)
+                .with_context(|| format!("Failed to read annotations from file: {filename}"))?
+        };
+
+        let message = match window {
+            Some(window) => IpcMessage::ControlWindow {
+                window,
+                message: Box::new(IpcMessage::AddAnnotations { json }),
+            },
+            None => IpcMessage::AddAnnotations { json },
+        };
+        match IpcClient::send_message(&message).await? {
+            IpcResponse::Error(e) => Err(anyhow!(e)),
+            _ => Ok(()),
+        }
+    }
+
     pub async fn shutdown() -> Result<()> {
         let message = IpcMessage::Shutdown;
         IpcClient::send_message(&message).await?;
@@ -0,0 +1,270 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, Context, Result};
+use gdk_pixbuf::glib::Bytes;
+use gdk_pixbuf::{Pixbuf, PixbufLoader};
+use relm4::gtk::gdk::{ContentProvider, Display, DisplayManager, Texture};
+use relm4::gtk::{gio, prelude::*};
+use serde_derive::Deserialize;
+use xdg::BaseDirectories;
+
+use crate::command_line;
+use crate::configuration::APP_CONFIG;
+
+/// Directory in the cache the images dragged out of the window are saved in.
+const DRAG_DIR: &str = "drag";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClipboardBackend {
+    /// The clipboard of the GTK display, only available while Satty runs.
+    Gdk,
+    /// Runs `wl-copy`, which keeps serving the clipboard after Satty exited.
+    WlCopy,
+}
+
+impl From<command_line::ClipboardBackend> for ClipboardBackend {
+    fn from(backend: command_line::ClipboardBackend) -> Self {
+        match backend {
+            command_line::ClipboardBackend::Gdk => Self::Gdk,
+            command_line::ClipboardBackend::WlCopy => Self::WlCopy,
+        }
+    }
+}
+
+pub trait Clipboard {
+    /// `png` is the texture encoded as PNG, for backends passing the data on.
+    fn copy_image(&self, texture: &Texture, png: &[u8]) -> Result<()>;
+
+    /// Copies `paths` as `text/uri-list` and as plain text. Backends that can offer several
+    /// types at once keep offering `texture` as well.
+    fn copy_paths(&self, paths: &[PathBuf], texture: Option<&Texture>) -> Result<()>;
+
+    fn copy_text(&self, text: &str) -> Result<()>;
+}
+
+/// Returns the clipboard selected in the configuration, `copy-command` takes precedence.
+pub fn from_config() -> Box<dyn Clipboard> {
+    let config = APP_CONFIG.read();
+    match (config.copy_command(), config.clipboard_backend()) {
+        (Some(command), _) => Box::new(CommandClipboard {
+            command: command.clone(),
+        }),
+        (None, ClipboardBackend::Gdk) => Box::new(GdkClipboard),
+        (None, ClipboardBackend::WlCopy) => Box::new(WlCopyClipboard),
+    }
+}
+
+/// Reads the image in the clipboard of the display, whichever backend is configured for
+/// copying.
+pub async fn read_image() -> Result<Pixbuf> {
+    let texture = GdkClipboard::clipboard()?
+        .read_texture_future()
+        .await
+        .context("The clipboard holds no image")?
+        .ok_or(anyhow!("The clipboard holds no image"))?;
+    let loader = PixbufLoader::new();
+    loader.write(&texture.save_to_png_bytes())?;
+    loader.close()?;
+    loader
+        .pixbuf()
+        .ok_or(anyhow!("Conversion to Pixbuf failed"))
+}
+
+/// Offers `texture` to drag it into other applications, along with `path` it is saved to by
+/// `write_drag_file`, since many of them only accept files.
+pub fn drag_content(path: &Path, texture: &Texture) -> Result<ContentProvider> {
+    paths_content(&[path.to_path_buf()], Some(texture))
+}
+
+/// Saves `png` to a file to drag out of the window. Drop targets may read it any time later,
+/// so the files are kept until `remove_drag_files`.
+pub fn write_drag_file(png: &[u8]) -> Result<PathBuf> {
+    let name = chrono::Local::now()
+        .format("satty-%Y%m%d-%H%M%S%.3f.png")
+        .to_string();
+    let path = BaseDirectories::with_prefix(env!("CARGO_PKG_NAME"))
+        .place_cache_file(drag_dir().join(name))?;
+    fs::write(&path, png).with_context(|| format!("Failed to write {path:?}"))?;
+    Ok(path)
+}
+
+/// Removes the files dragged out of the window, once Satty exits.
+pub fn remove_drag_files() {
+    let dirs = BaseDirectories::with_prefix(env!("CARGO_PKG_NAME"));
+    if let Some(dir) = dirs.find_cache_file(drag_dir()) {
+        if let Err(e) = fs::remove_dir_all(&dir) {
+            log::warn!("Failed to remove {dir:?}: {e}");
+        }
+    }
+}
+
+// each process keeps its own files, other instances may still be dragging theirs
+fn drag_dir() -> PathBuf {
+    Path::new(DRAG_DIR).join(std::process::id().to_string())
+}
+
+/// Sets the primary selection, pasted with the middle mouse button, to `text`. The
+/// `copy-command` only serves the regular clipboard, so the backend is used either way.
+pub fn copy_primary_text(text: &str) -> Result<()> {
+    let backend = APP_CONFIG.read().clipboard_backend();
+    match backend {
+        ClipboardBackend::Gdk => {
+            GdkClipboard::display()?.primary_clipboard().set_text(text);
+            Ok(())
+        }
+        ClipboardBackend::WlCopy => write_to_process(
+            Command::new("wl-copy").args(["--primary", "--type", "text/plain;charset=utf-8"]),
+            "wl-copy",
+            text.as_bytes(),
+        ),
+    }
+}
+
+/// Sets the primary selection to `paths`, one per line.
+pub fn copy_primary_paths(paths: &[PathBuf]) -> Result<()> {
+    copy_primary_text(&path_text(&absolute_paths(paths)?))
+}
+
+// `paths` as `text/uri-list` and as plain text, and `texture` if given
+fn paths_content(paths: &[PathBuf], texture: Option<&Texture>) -> Result<ContentProvider> {
+    let paths = absolute_paths(paths)?;
+    let mut providers = vec![
+        ContentProvider::for_bytes("text/uri-list", &Bytes::from_owned(uri_list(&paths))),
+        ContentProvider::for_value(&path_text(&paths).to_value()),
+    ];
+    if let Some(texture) = texture {
+        providers.push(ContentProvider::for_value(&texture.to_value()));
+    }
+    Ok(ContentProvider::new_union(&providers))
+}
+
+fn absolute_paths(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    paths.iter().map(std::path::absolute).collect()
+}
+
+fn uri_list(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| format!("{}\r\n", gio::File::for_path(path).uri()))
+        .collect()
+}
+
+fn path_text(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+struct GdkClipboard;
+
+impl GdkClipboard {
+    fn display() -> Result<Display> {
+        DisplayManager::get()
+            .default_display()
+            .ok_or(anyhow!("Cannot open default display for clipboard."))
+    }
+
+    fn clipboard() -> Result<relm4::gtk::gdk::Clipboard> {
+        Ok(Self::display()?.clipboard())
+    }
+}
+
+impl Clipboard for GdkClipboard {
+    fn copy_image(&self, texture: &Texture, _png: &[u8]) -> Result<()> {
+        Self::clipboard()?.set_texture(texture);
+        Ok(())
+    }
+
+    fn copy_paths(&self, paths: &[PathBuf], texture: Option<&Texture>) -> Result<()> {
+        Self::clipboard()?.set_content(Some(&paths_content(paths, texture)?))?;
+        Ok(())
+    }
+
+    fn copy_text(&self, text: &str) -> Result<()> {
+        Self::clipboard()?.set_text(text);
+        Ok(())
+    }
+}
+
+fn write_to_process(command: &mut Command, name: &str, data: &[u8]) -> Result<()> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    let child_stdin = child.stdin.as_mut().unwrap();
+    child_stdin.write_all(data)?;
+
+    if !child.wait()?.success() {
+        return Err(anyhow!("Writing to process '{name}' failed."));
+    }
+
+    Ok(())
+}
+
+/// Pipes the content into the configured `copy-command`.
+struct CommandClipboard {
+    command: String,
+}
+
+impl CommandClipboard {
+    fn run(&self, data: &[u8]) -> Result<()> {
+        write_to_process(
+            Command::new("sh").arg("-c").arg(&self.command),
+            &self.command,
+            data,
+        )
+    }
+}
+
+impl Clipboard for CommandClipboard {
+    fn copy_image(&self, _texture: &Texture, png: &[u8]) -> Result<()> {
+        self.run(png)
+    }
+
+    /// Only plain text is copied, the command decides on its type.
+    fn copy_paths(&self, paths: &[PathBuf], _texture: Option<&Texture>) -> Result<()> {
+        let paths = absolute_paths(paths)?;
+        self.run(path_text(&paths).as_bytes())
+    }
+
+    fn copy_text(&self, text: &str) -> Result<()> {
+        self.run(text.as_bytes())
+    }
+}
+
+struct WlCopyClipboard;
+
+impl Clipboard for WlCopyClipboard {
+    fn copy_image(&self, _texture: &Texture, png: &[u8]) -> Result<()> {
+        write_to_process(
+            Command::new("wl-copy").args(["--type", "image/png"]),
+            "wl-copy",
+            png,
+        )
+    }
+
+    /// `wl-copy` offers a single type, so only the URIs are copied.
+    fn copy_paths(&self, paths: &[PathBuf], _texture: Option<&Texture>) -> Result<()> {
+        let paths = absolute_paths(paths)?;
+        write_to_process(
+            Command::new("wl-copy").args(["--type", "text/uri-list"]),
+            "wl-copy",
+            uri_list(&paths).as_bytes(),
+        )
+    }
+
+    fn copy_text(&self, text: &str) -> Result<()> {
+        write_to_process(
+            Command::new("wl-copy").args(["--type", "text/plain;charset=utf-8"]),
+            "wl-copy",
+            text.as_bytes(),
+        )
+    }
+}
@@ -1,25 +1,33 @@
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct CommandLine {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Path to the config file. Otherwise will be read from XDG_CONFIG_DIR/satty/config.toml
     #[arg(short, long)]
     pub config: Option<String>,
 
-    /// Path to input image or '-' to read from stdin
-    #[arg(short, long)]
-    pub filename: Option<String>,
+    /// Path to input image or '-' to read from stdin.
+    /// Since NEXTRELEASE, takes several images or patterns with `*` and `?` in the file name, which are annotated one after another
+    #[arg(short, long, num_args = 1..)]
+    pub filename: Vec<String>,
 
     /// Run in daemon mode (keeps GTK initialized and listens for IPC connections)
     #[arg(long)]
     pub daemon: bool,
 
+    /// Run in daemon mode as a systemd service, notifying systemd once the daemon is ready
+    #[arg(long)]
+    pub systemd: bool,
+
     /// Send image to running daemon instead of opening new window
     #[arg(long)]
     pub send_to_daemon: bool,
 
-    /// Ping the daemon to check if it's running
+    /// Ping the daemon to check if it's running, prints its version and supported methods as JSON
     #[arg(long)]
     pub ping_daemon: bool,
 
@@ -27,20 +35,46 @@ pub struct CommandLine {
     #[arg(long)]
     pub shutdown_daemon: bool,
 
+    /// List the images recently received by the daemon, newest first
+    #[arg(long)]
+    pub list_recent: bool,
+
+    /// Reopen a recently received image in the daemon, by its index in --list-recent
+    #[arg(long, value_name = "INDEX")]
+    pub load_recent: Option<u32>,
+
+    /// Print the state of the daemon as JSON
+    #[arg(long)]
+    pub daemon_state: bool,
+
+    /// Add annotations described as JSON to the image open in the daemon, read from a file or '-' for stdin
+    #[arg(long, value_name = "FILE")]
+    pub add_annotations: Option<String>,
+
+    /// Window of the daemon --add-annotations applies to, by the id printed by --send-to-daemon. The most recently opened one if omitted
+    #[arg(long, value_name = "ID", requires = "add_annotations")]
+    pub window: Option<usize>,
+
     /// Start Satty in fullscreen mode
     #[arg(long)]
     pub fullscreen: bool,
 
     /// Filename to use for saving action or '-' to print to stdout. Omit to disable saving to file. Might contain format
     /// specifiers: <https://docs.rs/chrono/latest/chrono/format/strftime/index.html>.
-    /// Since 0.20.0, can contain tilde (~) for home dir
+    /// Since 0.20.0, can contain tilde (~) for home dir.
+    /// Since NEXTRELEASE, can be given multiple times to save to several files at once, and `{input}` is replaced
+    /// by the name of the input file without extension
     #[arg(short, long)]
-    pub output_filename: Option<String>,
+    pub output_filename: Vec<String>,
 
     /// Exit directly after copy/save action
     #[arg(long)]
     pub early_exit: bool,
 
+    /// Experimental feature (NEXTRELEASE): Add the annotations of this JSON file to the image and write the annotations back to it whenever the image is saved, to annotate recurring screenshots the same way
+    #[arg(long, value_name = "FILE")]
+    pub template: Option<String>,
+
     /// Draw corners of rectangles round if the value is greater than 0
     /// (Defaults to 12) (0 disables rounded corners)
     #[arg(long)]
@@ -107,6 +141,10 @@ pub struct CommandLine {
     #[arg(long)]
     pub profile_startup: bool,
 
+    /// Experimental feature (NEXTRELEASE): Write the startup phases as a Chrome trace (chrome://tracing, Perfetto) to this file
+    #[arg(long, value_name = "FILE")]
+    pub profile_trace: Option<String>,
+
     /// Disable the window decoration (title bar, borders, etc.)
     /// Please note that the compositor has the final say in this.
     /// Requires xdg-decoration-unstable-v1
@@ -120,6 +158,147 @@ pub struct CommandLine {
     #[arg(long)]
     pub brush_smooth_history_size: Option<usize>,
 
+    /// Experimental feature (NEXTRELEASE): Maximum distance in pixels over which rectangle and
+    /// crop snap to detected edges while holding Alt.
+    /// 0 disables snapping.
+    /// defaults to 10.0
+    #[arg(long)]
+    pub edge_snap_distance: Option<f32>,
+
+    /// Experimental feature (NEXTRELEASE): Replace brush strokes that closely match a line, arrow,
+    /// ellipse or rectangle with a clean shape.
+    #[arg(long)]
+    pub shape_recognition: bool,
+
+    /// Experimental feature (NEXTRELEASE): How much a brush stroke may deviate from a shape to still
+    /// be recognized, relative to its size.
+    /// defaults to 0.15
+    #[arg(long)]
+    pub shape_recognition_tolerance: Option<f32>,
+
+    /// Experimental feature (NEXTRELEASE): Screen resolution used by the ruler tool to show
+    /// measurements in millimeters next to pixels.
+    #[arg(long)]
+    pub ruler_dpi: Option<f32>,
+
+    /// Experimental feature (NEXTRELEASE): Show a status bar with cursor position, size of the shape
+    /// being drawn and zoom level.
+    #[arg(long)]
+    pub show_status_bar: bool,
+
+    /// Experimental feature (NEXTRELEASE): Don't show the minimap in the bottom right corner when the
+    /// image doesn't fit the window.
+    #[arg(long)]
+    pub disable_minimap: bool,
+
+    /// Experimental feature (NEXTRELEASE): Place the exported image on a padded background with
+    /// rounded corners and a drop shadow. Can be toggled in the toolbar.
+    #[arg(long)]
+    pub beautify: bool,
+
+    /// Experimental feature (NEXTRELEASE): Space in pixels around the image when beautifying.
+    /// defaults to 64.0
+    #[arg(long)]
+    pub beautify_padding: Option<f32>,
+
+    /// Experimental feature (NEXTRELEASE): Start a daemon when sending an image with --send-to-daemon
+    /// and none is running.
+    #[arg(long)]
+    pub daemon_auto_spawn: bool,
+
+    /// Experimental feature (NEXTRELEASE): After saving, also put the path of the saved file on the
+    /// clipboard as text and URI, next to the image when it was copied as well.
+    #[arg(long)]
+    pub copy_file_path: bool,
+
+    /// Experimental feature (NEXTRELEASE): Where to copy images to, ignored when --copy-command is set.
+    #[arg(long)]
+    pub clipboard_backend: Option<ClipboardBackend>,
+
+    /// Experimental feature (NEXTRELEASE): Show a fullscreen layer-shell overlay or a regular window sized to the image.
+    #[arg(long)]
+    pub window_mode: Option<WindowMode>,
+
+    /// Experimental feature (NEXTRELEASE): Output (monitor connector name, e.g. `DP-1`, or `cursor` for the one under the cursor) to show the layer-shell overlay on.
+    #[arg(long)]
+    pub output: Option<String>,
+
+    /// Experimental feature (NEXTRELEASE): Whether the layer-shell overlay takes all keyboard input or only while focused.
+    #[arg(long)]
+    pub keyboard_mode: Option<KeyboardMode>,
+
+    /// Experimental feature (NEXTRELEASE): Let clicks pass through the layer-shell overlay while the toolbars are hidden.
+    #[arg(long)]
+    pub click_through: bool,
+
+    /// Experimental feature (NEXTRELEASE): Escape clears the selection first, before running the actions on escape.
+    #[arg(long)]
+    pub escape_deselects: bool,
+
+    /// Experimental feature (NEXTRELEASE): With unsaved changes, exiting with Escape needs a second press.
+    #[arg(long)]
+    pub confirm_escape: bool,
+
+    /// Experimental feature (NEXTRELEASE): Smoothing of brush and freehand highlighter strokes, its strength is set with --brush-smooth-history-size.
+    #[arg(long)]
+    pub brush_smoothing: Option<SmoothingMode>,
+
+    /// Experimental feature (NEXTRELEASE): How far colors may differ from the picked one per channel (0-255) to be replaced by the recolor tool (Defaults to 32)
+    #[arg(long)]
+    pub recolor_tolerance: Option<u8>,
+
+    /// Experimental feature (NEXTRELEASE): Which filter the filter tool starts with (Defaults to grayscale)
+    #[arg(long)]
+    pub region_filter: Option<FilterKind>,
+
+    /// Experimental feature (NEXTRELEASE): How far colors may differ from the corner per channel (0-255) to still count as a uniform border when trimming with Ctrl+B (Defaults to 16)
+    #[arg(long)]
+    pub trim_tolerance: Option<u8>,
+
+    /// Experimental feature (NEXTRELEASE): Number of corners (or points of a star) the polygon tool starts with, from 3 to 24 (Defaults to 5)
+    #[arg(long)]
+    pub polygon_corners: Option<u8>,
+
+    /// Experimental feature (NEXTRELEASE): Number of rows the table tool starts with, from 1 to 50 (Defaults to 3)
+    #[arg(long)]
+    pub table_rows: Option<u8>,
+
+    /// Experimental feature (NEXTRELEASE): Number of columns the table tool starts with, from 1 to 50 (Defaults to 3)
+    #[arg(long)]
+    pub table_columns: Option<u8>,
+
+    /// Experimental feature (NEXTRELEASE): Command that reads a PNG image on stdin and prints the recognized words in tesseract's TSV format (Defaults to `tesseract stdin stdout tsv`)
+    #[arg(long)]
+    pub ocr_command: Option<String>,
+
+    /// Experimental feature (NEXTRELEASE): Least severe messages to log (Defaults to info)
+    #[arg(long)]
+    pub log_level: Option<LogLevel>,
+
+    /// Experimental feature (NEXTRELEASE): Append log messages to this file instead of printing them to stderr
+    #[arg(long, value_name = "FILE")]
+    pub log_file: Option<String>,
+
+    /// Experimental feature (NEXTRELEASE): Directory relative output filenames are saved in, and where the save dialog opens
+    #[arg(long, value_name = "DIR")]
+    pub output_dir: Option<String>,
+
+    /// Experimental feature (NEXTRELEASE): Choose where to save with a dialog when saving to file, starting at the output filename. Without an output filename the dialog is always used
+    #[arg(long)]
+    pub save_dialog: bool,
+
+    /// Experimental feature (NEXTRELEASE): Print the file:// URI of every saved image to stdout, one per line, to hand the result back to the program that started Satty
+    #[arg(long)]
+    pub print_saved_uri: bool,
+
+    /// Experimental feature (NEXTRELEASE): Select a region with the region-command (slurp by default), capture it with the capture-command (grim by default) and annotate it
+    #[arg(long)]
+    pub capture_region: bool,
+
+    /// Experimental feature (NEXTRELEASE): Capture the focused window of Hyprland or Sway with the capture-command (grim by default) and annotate it. `{title}` in output filenames is replaced by the window title
+    #[arg(long, conflicts_with = "capture_region")]
+    pub capture_window: bool,
+
     // --- deprecated options ---
     /// Right click to copy.
     /// Preferably use the `action_on_right_click` option instead.
@@ -143,6 +322,22 @@ pub struct CommandLine {
     // ---
 }
 
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Composite saved annotations onto an image without opening a window (NEXTRELEASE)
+    Render {
+        /// JSON file with the annotations, in the format of --add-annotations
+        #[arg(long)]
+        project: String,
+        /// Image to annotate or '-' to read from stdin
+        #[arg(long)]
+        input: String,
+        /// PNG file to write or '-' to print to stdout
+        #[arg(long)]
+        output: String,
+    },
+}
+
 #[derive(Debug, Clone, Copy, Default, ValueEnum)]
 pub enum Tools {
     #[default]
@@ -157,12 +352,26 @@ pub enum Tools {
     Blur,
     Highlight,
     Brush,
+    Ruler,
+    Eraser,
+    Patch,
+    Recolor,
+    Filter,
+    Perspective,
+    Polygon,
+    Stamp,
+    Connector,
+    Flowchart,
+    Table,
+    Qr,
+    Ocr,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum Action {
     SaveToClipboard,
     SaveToFile,
+    CopyFilePath,
     Exit,
 }
 
@@ -173,6 +382,46 @@ pub enum Highlighters {
     Freehand,
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ClipboardBackend {
+    Gdk,
+    WlCopy,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum WindowMode {
+    LayerShell,
+    Normal,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum KeyboardMode {
+    Exclusive,
+    OnDemand,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum SmoothingMode {
+    MovingAverage,
+    PullString,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum FilterKind {
+    Grayscale,
+    Invert,
+    Sepia,
+}
+
 impl std::fmt::Display for Tools {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use Tools::*;
@@ -188,6 +437,19 @@ impl std::fmt::Display for Tools {
             Blur => "blur",
             Highlight => "highlight",
             Brush => "brush",
+            Ruler => "ruler",
+            Eraser => "eraser",
+            Patch => "patch",
+            Recolor => "recolor",
+            Filter => "filter",
+            Perspective => "perspective",
+            Polygon => "polygon",
+            Stamp => "stamp",
+            Connector => "connector",
+            Flowchart => "flowchart",
+            Table => "table",
+            Qr => "qr",
+            Ocr => "ocr",
         };
         f.write_str(s)
     }
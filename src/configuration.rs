@@ -5,21 +5,44 @@ use std::{
     path::Path,
 };
 
-use clap::Parser;
+use clap::{error::ErrorKind, CommandFactory, Parser};
 use hex_color::HexColor;
-use relm4::SharedState;
+use relm4::{
+    gtk::{
+        gdk::ModifierType,
+        gio::{self, prelude::FileExt},
+    },
+    SharedState,
+};
 use serde_derive::Deserialize;
 use thiserror::Error;
 use xdg::{BaseDirectories, BaseDirectoriesError};
 
 use crate::{
-    command_line::{Action as CommandLineAction, CommandLine},
+    clipboard::ClipboardBackend,
+    command_line::{
+        Action as CommandLineAction, CommandLine, KeyboardMode as CommandLineKeyboardMode,
+        LogLevel as CommandLineLogLevel, WindowMode as CommandLineWindowMode,
+    },
+    sketch_board::MouseButton,
     style::Color,
-    tools::{Highlighters, Tools},
+    tools::{Curve, DynamicsTarget, FilterKind, Highlighters, SmoothingMode, Tools},
 };
 
 pub static APP_CONFIG: SharedState<Configuration> = SharedState::new();
 
+// emails, IPv4 addresses, labeled secrets and long tokens
+const DEFAULT_REDACT_PATTERNS: [&str; 4] = [
+    // email addresses
+    r"[\w.+-]+@[\w-]+\.[\w.-]*\w",
+    // IPv4 addresses
+    r"\b(?:(?:25[0-5]|2[0-4]\d|1?\d?\d)\.){3}(?:25[0-5]|2[0-4]\d|1?\d?\d)\b",
+    // values of credentials
+    r"(?i)\b(?:password|passwd|secret|token|api[_-]?key)\s*[:=]\s*\S+",
+    // words too long for prose, such as API keys
+    r"\b[A-Za-z0-9_-]{32,}\b",
+];
+
 #[derive(Error, Debug)]
 enum ConfigurationFileError {
     #[error("XDG context error: {0}")]
@@ -30,13 +53,22 @@ enum ConfigurationFileError {
 
     #[error("Decoding toml failed: {0}")]
     TomlDecoding(#[from] toml::de::Error),
+
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
 }
 
 pub struct Configuration {
-    input_filename: String,
-    output_filename: Option<String>,
+    input_filenames: Vec<String>,
+    systemd: bool,
+    capture_region: bool,
+    capture_window: bool,
+    // title of the captured window, replaces `{title}` in output filenames
+    window_title: Option<String>,
+    outputs: Vec<OutputTarget>,
     fullscreen: bool,
     early_exit: bool,
+    template: Option<String>,
     corner_roundness: f32,
     initial_tool: Tools,
     copy_command: Option<String>,
@@ -50,84 +82,280 @@ pub struct Configuration {
     focus_toggles_toolbars: bool,
     default_fill_shapes: bool,
     font: FontConfiguration,
+    scroll: ScrollConfiguration,
+    mouse: MouseConfiguration,
     primary_highlighter: Highlighters,
     disable_notifications: bool,
+    toast_position: ToastPosition,
+    toast_duration: u64,
+    toast_max_stacked: usize,
     profile_startup: bool,
+    profile_trace: Option<String>,
     no_window_decoration: bool,
     brush_smooth_history_size: usize,
     keybinds: Keybinds,
     zoom_factor: f32,
     pan_step_size: f32,
+    edge_snap_distance: f32,
+    shape_recognition: bool,
+    shape_recognition_tolerance: f32,
+    ruler_dpi: Option<f32>,
+    show_status_bar: bool,
+    disable_minimap: bool,
+    transparency_backdrop: Option<Color>,
+    export_background: Option<Color>,
+    canvas_extension_color: Color,
+    beautify: bool,
+    beautify_padding: f32,
+    beautify_corner_radius: f32,
+    beautify_background: Vec<Color>,
+    undo_memory_limit: usize,
+    recording_step_duration: u64,
+    caption_template: String,
+    caption_corner: CaptionCorner,
+    external_editor: Option<String>,
+    external_editor_reload: bool,
+    modal_editing: bool,
+    output_dir: Option<String>,
+    save_dialog: bool,
+    overwrite_policy: OverwritePolicy,
+    print_saved_uri: bool,
+    region_command: String,
+    capture_command: String,
+    daemon_history_size: usize,
+    daemon_multiple_windows: bool,
+    daemon_tray_icon: bool,
+    daemon_idle_timeout: u64,
+    daemon_auto_spawn: bool,
+    copy_file_path: bool,
+    clipboard_backend: ClipboardBackend,
+    primary_selection: bool,
+    window_mode: WindowMode,
+    output: Option<String>,
+    keyboard_mode: KeyboardMode,
+    click_through: bool,
+    scrim_color: Option<Color>,
+    escape_deselects: bool,
+    confirm_escape: bool,
+    brush_smoothing: SmoothingMode,
+    pressure_curve: Option<Curve>,
+    velocity_curve: Option<Curve>,
+    brush_dynamics: DynamicsTarget,
+    recolor_tolerance: u8,
+    region_filter: FilterKind,
+    trim_tolerance: u8,
+    polygon_corners: u8,
+    check_color: Color,
+    cross_color: Color,
+    table_rows: u8,
+    table_columns: u8,
+    ocr_command: String,
+    redact_patterns: Vec<String>,
+    log_level: LogLevel,
+    log_file: Option<String>,
+    quick_actions: Vec<QuickAction>,
+}
+
+/// A file the image is written to by the save action.
+#[derive(Debug, Clone)]
+pub struct OutputTarget {
+    pub filename: String,
+    /// Factor the image is resized by before saving, 1.0 keeps the full resolution.
+    pub scale: f32,
+}
+
+impl OutputTarget {
+    pub fn new(filename: String) -> Self {
+        Self {
+            filename,
+            scale: 1.0,
+        }
+    }
+}
+
+/// Overrides sent to the daemon along with an image, they apply to the window showing it
+/// until it shows the next image.
+#[derive(Debug, Clone, Default)]
+pub struct SessionOptions {
+    pub output_filenames: Option<Vec<String>>,
+    pub early_exit: Option<bool>,
+    pub initial_tool: Option<Tools>,
+    pub fullscreen: Option<bool>,
+}
+
+impl SessionOptions {
+    pub fn outputs(&self, config: &Configuration) -> Vec<OutputTarget> {
+        match &self.output_filenames {
+            Some(filenames) => filenames.iter().cloned().map(OutputTarget::new).collect(),
+            None => config.outputs().to_vec(),
+        }
+    }
+
+    pub fn early_exit(&self, config: &Configuration) -> bool {
+        self.early_exit.unwrap_or(config.early_exit())
+    }
+
+    pub fn initial_tool(&self, config: &Configuration) -> Tools {
+        self.initial_tool.unwrap_or(config.initial_tool())
+    }
+
+    pub fn fullscreen(&self, config: &Configuration) -> bool {
+        self.fullscreen.unwrap_or(config.fullscreen())
+    }
+}
+
+/// What a keybind does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyCommand {
+    Tool(Tools),
+    Action(Action),
+}
+
+/// How typed keys match the keybinds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyMatch {
+    /// The keys are a keybind and no longer one starts with them.
+    Command(KeyCommand),
+    /// Longer keybinds start with the keys. If they are a keybind as well, it applies once
+    /// no key follows within the timeout.
+    Pending(Option<KeyCommand>),
+    None,
 }
 
 pub struct Keybinds {
-    shortcuts: HashMap<char, Tools>,
+    leader: char,
+    sequence_timeout: u64,
+    // the typed characters of each keybind, with the leader filled in
+    sequences: HashMap<String, KeyCommand>,
 }
 
 impl Keybinds {
-    pub fn get_tool(&self, key: char) -> Option<Tools> {
-        self.shortcuts.get(&key).copied()
+    pub fn lookup(&self, keys: &str) -> KeyMatch {
+        let command = self.sequences.get(keys).copied();
+        let longer = self
+            .sequences
+            .keys()
+            .any(|sequence| sequence.len() > keys.len() && sequence.starts_with(keys));
+        match (command, longer) {
+            (_, true) => KeyMatch::Pending(command),
+            (Some(command), false) => KeyMatch::Command(command),
+            (None, false) => KeyMatch::None,
+        }
+    }
+
+    /// Keys of the keybind for `command`, if it has one.
+    pub fn keys(&self, command: KeyCommand) -> Option<&str> {
+        self.sequences
+            .iter()
+            .find(|(_, c)| **c == command)
+            .map(|(keys, _)| keys.as_str())
     }
 
-    pub fn shortcuts(&self) -> &HashMap<char, Tools> {
-        &self.shortcuts
+    /// Milliseconds to wait for the next key of a keybind.
+    pub fn sequence_timeout(&self) -> u64 {
+        self.sequence_timeout
     }
 
     /// Update a single keybind, only if it is valid
-    fn update_keybind(&mut self, key: Option<String>, tool: Tools) {
-        if let Some(key_str) = key {
-            if let Some(validated_key) = Self::validate_keybind(&key_str, tool) {
-                self.shortcuts.retain(|_, v| *v != tool);
-                self.shortcuts.insert(validated_key, tool);
+    fn update_keybind(&mut self, keys: Option<String>, command: KeyCommand) {
+        if let Some(keys) = keys {
+            if let Some(sequence) = self.parse_keybind(&keys) {
+                self.sequences.retain(|_, c| *c != command);
+                self.sequences.insert(sequence, command);
+            } else {
+                log::warn!("Invalid keybind: '{command:?} = {keys}'. Keybinds must be characters, optionally separated by spaces, or <leader>. Using default keybind instead.");
             }
         }
     }
 
-    /// A shortcut keybinding is only valid if it is one char
-    fn validate_keybind(key: &str, tool: Tools) -> Option<char> {
-        let mut chars = key.chars();
-        match (chars.next(), chars.next()) {
-            (Some(c), None) => Some(c),
-            _ => {
-                eprintln!("Warning: Invalid keybind: '{} = {}'. Keybinds must be single characters. Using default keybind instead.", tool,key);
-                None
-            }
-        }
+    // the characters typed for `keys`, spaces separate keys and `<leader>` is replaced
+    fn parse_keybind(&self, keys: &str) -> Option<String> {
+        let sequence: String = keys
+            .split_whitespace()
+            .map(|key| key.replace("<leader>", &self.leader.to_string()))
+            .collect();
+        (!sequence.is_empty()).then_some(sequence)
     }
 
     /// Merge keybindings with default
     /// Only replaces defaults if they are set
     fn merge(&mut self, file_keybinds: KeybindsFile) {
-        self.update_keybind(file_keybinds.pointer, Tools::Pointer);
-        self.update_keybind(file_keybinds.crop, Tools::Crop);
-        self.update_keybind(file_keybinds.brush, Tools::Brush);
-        self.update_keybind(file_keybinds.line, Tools::Line);
-        self.update_keybind(file_keybinds.arrow, Tools::Arrow);
-        self.update_keybind(file_keybinds.rectangle, Tools::Rectangle);
-        self.update_keybind(file_keybinds.ellipse, Tools::Ellipse);
-        self.update_keybind(file_keybinds.text, Tools::Text);
-        self.update_keybind(file_keybinds.marker, Tools::Marker);
-        self.update_keybind(file_keybinds.blur, Tools::Blur);
-        self.update_keybind(file_keybinds.highlight, Tools::Highlight);
+        if let Some(v) = file_keybinds.leader {
+            let mut chars = v.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => self.leader = c,
+                _ => log::warn!("Invalid leader '{v}', it must be a single character."),
+            }
+        }
+        if let Some(v) = file_keybinds.sequence_timeout {
+            self.sequence_timeout = v;
+        }
+        self.update_keybind(file_keybinds.pointer, KeyCommand::Tool(Tools::Pointer));
+        self.update_keybind(file_keybinds.crop, KeyCommand::Tool(Tools::Crop));
+        self.update_keybind(file_keybinds.brush, KeyCommand::Tool(Tools::Brush));
+        self.update_keybind(file_keybinds.line, KeyCommand::Tool(Tools::Line));
+        self.update_keybind(file_keybinds.arrow, KeyCommand::Tool(Tools::Arrow));
+        self.update_keybind(file_keybinds.rectangle, KeyCommand::Tool(Tools::Rectangle));
+        self.update_keybind(file_keybinds.ellipse, KeyCommand::Tool(Tools::Ellipse));
+        self.update_keybind(file_keybinds.text, KeyCommand::Tool(Tools::Text));
+        self.update_keybind(file_keybinds.marker, KeyCommand::Tool(Tools::Marker));
+        self.update_keybind(file_keybinds.blur, KeyCommand::Tool(Tools::Blur));
+        self.update_keybind(file_keybinds.highlight, KeyCommand::Tool(Tools::Highlight));
+        self.update_keybind(file_keybinds.ruler, KeyCommand::Tool(Tools::Ruler));
+        self.update_keybind(file_keybinds.eraser, KeyCommand::Tool(Tools::Eraser));
+        self.update_keybind(file_keybinds.patch, KeyCommand::Tool(Tools::Patch));
+        self.update_keybind(file_keybinds.recolor, KeyCommand::Tool(Tools::Recolor));
+        self.update_keybind(file_keybinds.filter, KeyCommand::Tool(Tools::Filter));
+        self.update_keybind(
+            file_keybinds.perspective,
+            KeyCommand::Tool(Tools::Perspective),
+        );
+        self.update_keybind(file_keybinds.polygon, KeyCommand::Tool(Tools::Polygon));
+        self.update_keybind(file_keybinds.stamp, KeyCommand::Tool(Tools::Stamp));
+        self.update_keybind(file_keybinds.connector, KeyCommand::Tool(Tools::Connector));
+        self.update_keybind(file_keybinds.flowchart, KeyCommand::Tool(Tools::Flowchart));
+        self.update_keybind(file_keybinds.table, KeyCommand::Tool(Tools::Table));
+        self.update_keybind(file_keybinds.qr, KeyCommand::Tool(Tools::Qr));
+        self.update_keybind(file_keybinds.ocr, KeyCommand::Tool(Tools::Ocr));
+        for (action, keys) in file_keybinds.actions.unwrap_or_default() {
+            self.update_keybind(Some(keys), KeyCommand::Action(action));
+        }
     }
 }
 
 impl Default for Keybinds {
     fn default() -> Self {
-        let mut shortcuts = HashMap::new();
-        shortcuts.insert('p', Tools::Pointer);
-        shortcuts.insert('c', Tools::Crop);
-        shortcuts.insert('b', Tools::Brush);
-        shortcuts.insert('i', Tools::Line);
-        shortcuts.insert('z', Tools::Arrow);
-        shortcuts.insert('r', Tools::Rectangle);
-        shortcuts.insert('e', Tools::Ellipse);
-        shortcuts.insert('t', Tools::Text);
-        shortcuts.insert('m', Tools::Marker);
-        shortcuts.insert('u', Tools::Blur);
-        shortcuts.insert('g', Tools::Highlight);
+        let mut sequences = HashMap::new();
+        sequences.insert(String::from('p'), KeyCommand::Tool(Tools::Pointer));
+        sequences.insert(String::from('c'), KeyCommand::Tool(Tools::Crop));
+        sequences.insert(String::from('b'), KeyCommand::Tool(Tools::Brush));
+        sequences.insert(String::from('i'), KeyCommand::Tool(Tools::Line));
+        sequences.insert(String::from('z'), KeyCommand::Tool(Tools::Arrow));
+        sequences.insert(String::from('r'), KeyCommand::Tool(Tools::Rectangle));
+        sequences.insert(String::from('e'), KeyCommand::Tool(Tools::Ellipse));
+        sequences.insert(String::from('t'), KeyCommand::Tool(Tools::Text));
+        sequences.insert(String::from('m'), KeyCommand::Tool(Tools::Marker));
+        sequences.insert(String::from('u'), KeyCommand::Tool(Tools::Blur));
+        sequences.insert(String::from('g'), KeyCommand::Tool(Tools::Highlight));
+        sequences.insert(String::from('l'), KeyCommand::Tool(Tools::Ruler));
+        sequences.insert(String::from('x'), KeyCommand::Tool(Tools::Eraser));
+        sequences.insert(String::from('k'), KeyCommand::Tool(Tools::Patch));
+        sequences.insert(String::from('o'), KeyCommand::Tool(Tools::Recolor));
+        sequences.insert(String::from('f'), KeyCommand::Tool(Tools::Filter));
+        sequences.insert(String::from('v'), KeyCommand::Tool(Tools::Perspective));
+        sequences.insert(String::from('n'), KeyCommand::Tool(Tools::Polygon));
+        sequences.insert(String::from('d'), KeyCommand::Tool(Tools::Stamp));
+        sequences.insert(String::from('w'), KeyCommand::Tool(Tools::Connector));
+        sequences.insert(String::from('q'), KeyCommand::Tool(Tools::Flowchart));
+        sequences.insert(String::from('a'), KeyCommand::Tool(Tools::Table));
+        sequences.insert(String::from('j'), KeyCommand::Tool(Tools::Qr));
+        sequences.insert(String::from('s'), KeyCommand::Tool(Tools::Ocr));
 
-        Self { shortcuts }
+        Self {
+            leader: ' ',
+            sequence_timeout: 1000,
+            sequences,
+        }
     }
 }
 
@@ -154,6 +382,203 @@ impl FontConfiguration {
     }
 }
 
+/// What scrolling on the canvas does.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScrollAction {
+    Zoom,
+    /// Changes the annotation size, zooms with tools that have none.
+    Size,
+    PanVertical,
+    PanHorizontal,
+    None,
+}
+
+/// What a mouse button other than the primary one does on the canvas. Dragging pans with
+/// `Pan`, the others act on a click.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ButtonAction {
+    Pan,
+    ActionsOnRightClick,
+    Undo,
+    Redo,
+    /// Removes the topmost annotation under the cursor.
+    DeleteUnderCursor,
+    /// Selects the next color of the palette.
+    NextColor,
+    PreviousColor,
+    /// Passes the button on to the active tool.
+    None,
+}
+
+/// What saving to file does when the file exists already.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverwritePolicy {
+    Overwrite,
+    /// Appends `-1`, `-2`, ... to the name until it is unused.
+    Increment,
+    /// Opens the save dialog instead.
+    Ask,
+}
+
+/// Corner of the crop or image a caption is placed in.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptionCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Where toasts are shown on top of the image.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToastPosition {
+    Top,
+    Bottom,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Scroll actions by the modifier held, Ctrl goes before Shift and Shift before Alt.
+pub struct ScrollConfiguration {
+    plain: ScrollAction,
+    ctrl: ScrollAction,
+    shift: ScrollAction,
+    alt: ScrollAction,
+}
+
+impl Default for ScrollConfiguration {
+    fn default() -> Self {
+        Self {
+            plain: ScrollAction::Zoom,
+            ctrl: ScrollAction::Size,
+            shift: ScrollAction::Size,
+            alt: ScrollAction::Size,
+        }
+    }
+}
+
+impl ScrollConfiguration {
+    pub fn action(&self, modifier: ModifierType) -> ScrollAction {
+        if modifier.contains(ModifierType::CONTROL_MASK) {
+            self.ctrl
+        } else if modifier.contains(ModifierType::SHIFT_MASK) {
+            self.shift
+        } else if modifier.contains(ModifierType::ALT_MASK) {
+            self.alt
+        } else {
+            self.plain
+        }
+    }
+
+    fn merge(&mut self, file_scroll: ScrollFile) {
+        if let Some(v) = file_scroll.plain {
+            self.plain = v;
+        }
+        if let Some(v) = file_scroll.ctrl {
+            self.ctrl = v;
+        }
+        if let Some(v) = file_scroll.shift {
+            self.shift = v;
+        }
+        if let Some(v) = file_scroll.alt {
+            self.alt = v;
+        }
+    }
+}
+
+/// Direction of a stroke while holding the right mouse button.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum GestureDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HistoryStep {
+    Undo,
+    Redo,
+}
+
+/// What a mouse gesture does: undo or redo, select a tool or run an action.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum GestureAction {
+    History(HistoryStep),
+    Tool(Tools),
+    Action(Action),
+}
+
+/// Actions by mouse button, and by the direction of gestures.
+pub struct MouseConfiguration {
+    middle: ButtonAction,
+    right: ButtonAction,
+    back: ButtonAction,
+    forward: ButtonAction,
+    gestures: HashMap<GestureDirection, GestureAction>,
+}
+
+impl Default for MouseConfiguration {
+    fn default() -> Self {
+        Self {
+            middle: ButtonAction::Pan,
+            right: ButtonAction::ActionsOnRightClick,
+            back: ButtonAction::None,
+            forward: ButtonAction::None,
+            gestures: HashMap::new(),
+        }
+    }
+}
+
+impl MouseConfiguration {
+    pub fn action(&self, button: MouseButton) -> ButtonAction {
+        match button {
+            MouseButton::Primary => ButtonAction::None,
+            MouseButton::Middle => self.middle,
+            MouseButton::Secondary => self.right,
+            MouseButton::Back => self.back,
+            MouseButton::Forward => self.forward,
+        }
+    }
+
+    /// Whether dragging with the right button makes gestures.
+    pub fn has_gestures(&self) -> bool {
+        !self.gestures.is_empty()
+    }
+
+    pub fn gesture(&self, direction: GestureDirection) -> Option<GestureAction> {
+        self.gestures.get(&direction).copied()
+    }
+
+    fn merge(&mut self, file_mouse: MouseFile) {
+        if let Some(v) = file_mouse.middle {
+            self.middle = v;
+        }
+        if let Some(v) = file_mouse.right {
+            self.right = v;
+        }
+        if let Some(v) = file_mouse.back {
+            self.back = v;
+        }
+        if let Some(v) = file_mouse.forward {
+            self.forward = v;
+        }
+        if let Some(v) = file_mouse.gestures {
+            self.gestures.extend(v);
+        }
+    }
+}
+
 pub struct ColorPalette {
     palette: Vec<Color>,
     custom: Vec<Color>,
@@ -178,13 +603,32 @@ impl ColorPalette {
     }
 }
 
-#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "kebab-case")]
 pub enum Action {
     SaveToClipboard,
     SaveToFile,
     SaveToFileAs,
+    SaveAnnotationsToFileAs,
+    CopyFilePath,
     Exit,
+    /// Opens the image in the `external-editor`.
+    OpenInEditor,
+    /// Runs the quick action at the index, from its toolbar button.
+    #[serde(skip)]
+    RunQuickAction(usize),
+}
+
+/// A toolbar button that runs a command with the rendered image.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct QuickAction {
+    /// Shown as tooltip, and on the button if it has no icon.
+    pub name: String,
+    pub icon: Option<String>,
+    /// Run by `sh -c`. `{path}` is replaced by a temporary PNG file, without it the PNG is
+    /// piped to stdin.
+    pub command: String,
 }
 
 impl From<CommandLineAction> for Action {
@@ -192,11 +636,70 @@ impl From<CommandLineAction> for Action {
         match action {
             CommandLineAction::SaveToClipboard => Self::SaveToClipboard,
             CommandLineAction::SaveToFile => Self::SaveToFile,
+            CommandLineAction::CopyFilePath => Self::CopyFilePath,
             CommandLineAction::Exit => Self::Exit,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WindowMode {
+    /// Overlay covering the whole output, where layer-shell is available.
+    LayerShell,
+    /// Regular window sized to the image.
+    Normal,
+}
+
+impl From<CommandLineWindowMode> for WindowMode {
+    fn from(mode: CommandLineWindowMode) -> Self {
+        match mode {
+            CommandLineWindowMode::LayerShell => Self::LayerShell,
+            CommandLineWindowMode::Normal => Self::Normal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyboardMode {
+    /// Takes all keyboard input while the overlay is shown.
+    Exclusive,
+    /// Takes keyboard input only while focused.
+    OnDemand,
+}
+
+impl From<CommandLineKeyboardMode> for KeyboardMode {
+    fn from(mode: CommandLineKeyboardMode) -> Self {
+        match mode {
+            CommandLineKeyboardMode::Exclusive => Self::Exclusive,
+            CommandLineKeyboardMode::OnDemand => Self::OnDemand,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<CommandLineLogLevel> for LogLevel {
+    fn from(level: CommandLineLogLevel) -> Self {
+        match level {
+            CommandLineLogLevel::Error => Self::Error,
+            CommandLineLogLevel::Warn => Self::Warn,
+            CommandLineLogLevel::Info => Self::Info,
+            CommandLineLogLevel::Debug => Self::Debug,
+            CommandLineLogLevel::Trace => Self::Trace,
+        }
+    }
+}
+
 impl Configuration {
     pub fn load() {
         // parse commandline options and exit if error
@@ -204,16 +707,27 @@ impl Configuration {
             Ok(cmd) => cmd,
             Err(e) => e.exit(),
         };
+        if let Some(message) = command_line
+            .filename
+            .iter()
+            .find_map(|filename| unsupported_glob(filename))
+        {
+            CommandLine::command()
+                .error(ErrorKind::ValueValidation, message)
+                .exit();
+        }
 
         // read configuration file and exit on error
         let file = match ConfigurationFile::try_read(&command_line.config) {
             Ok(c) => c,
             Err(ConfigurationFileError::ReadFile(e)) if e.kind() == io::ErrorKind::NotFound => {
-                eprintln!("config file not found");
+                log::info!("config file not found");
                 None
             }
             Err(e) => {
-                eprintln!("Error reading config file: {e}");
+                log::error!("Error reading config file: {e}");
+                // to stderr, the configured log file is unknown
+                crate::logging::init(false);
 
                 // swallow broken pipes
                 let _ = std::io::stdout().lock().flush();
@@ -243,7 +757,7 @@ impl Configuration {
             self.copy_command = Some(v);
         }
         if let Some(v) = general.output_filename {
-            self.output_filename = Some(v);
+            self.outputs = v.into_targets();
         }
         if let Some(v) = general.annotation_size_factor {
             self.annotation_size_factor = v;
@@ -275,6 +789,15 @@ impl Configuration {
         if let Some(v) = general.disable_notifications {
             self.disable_notifications = v;
         }
+        if let Some(v) = general.toast_position {
+            self.toast_position = v;
+        }
+        if let Some(v) = general.toast_duration {
+            self.toast_duration = v;
+        }
+        if let Some(v) = general.toast_max_stacked {
+            self.toast_max_stacked = v.max(1);
+        }
         if let Some(v) = general.no_window_decoration {
             self.no_window_decoration = v;
         }
@@ -287,6 +810,177 @@ impl Configuration {
         if let Some(v) = general.pan_step_size {
             self.pan_step_size = v;
         }
+        if let Some(v) = general.edge_snap_distance {
+            self.edge_snap_distance = v;
+        }
+        if let Some(v) = general.shape_recognition {
+            self.shape_recognition = v;
+        }
+        if let Some(v) = general.shape_recognition_tolerance {
+            self.shape_recognition_tolerance = v;
+        }
+        if let Some(v) = general.ruler_dpi {
+            self.ruler_dpi = Some(v);
+        }
+        if let Some(v) = general.show_status_bar {
+            self.show_status_bar = v;
+        }
+        if let Some(v) = general.disable_minimap {
+            self.disable_minimap = v;
+        }
+        if let Some(v) = general.transparency_backdrop {
+            self.transparency_backdrop = Some(v.into());
+        }
+        if let Some(v) = general.export_background {
+            self.export_background = Some(v.into());
+        }
+        if let Some(v) = general.canvas_extension_color {
+            self.canvas_extension_color = v.into();
+        }
+        if let Some(v) = general.beautify {
+            self.beautify = v;
+        }
+        if let Some(v) = general.beautify_padding {
+            self.beautify_padding = v;
+        }
+        if let Some(v) = general.beautify_corner_radius {
+            self.beautify_corner_radius = v;
+        }
+        if let Some(v) = general.beautify_background {
+            self.beautify_background = v.into_iter().map(Color::from).collect();
+        }
+        if let Some(v) = general.undo_memory_limit {
+            self.undo_memory_limit = v;
+        }
+        if let Some(v) = general.recording_step_duration {
+            self.recording_step_duration = v;
+        }
+        if let Some(v) = general.caption_template {
+            self.caption_template = v;
+        }
+        if let Some(v) = general.caption_corner {
+            self.caption_corner = v;
+        }
+        if let Some(v) = general.external_editor {
+            self.external_editor = Some(v);
+        }
+        if let Some(v) = general.external_editor_reload {
+            self.external_editor_reload = v;
+        }
+        if let Some(v) = general.modal_editing {
+            self.modal_editing = v;
+        }
+        if let Some(v) = general.output_dir {
+            self.output_dir = Some(v);
+        }
+        if let Some(v) = general.save_dialog {
+            self.save_dialog = v;
+        }
+        if let Some(v) = general.overwrite_policy {
+            self.overwrite_policy = v;
+        }
+        if let Some(v) = general.print_saved_uri {
+            self.print_saved_uri = v;
+        }
+        if let Some(v) = general.region_command {
+            self.region_command = v;
+        }
+        if let Some(v) = general.capture_command {
+            self.capture_command = v;
+        }
+        if let Some(v) = general.daemon_history_size {
+            self.daemon_history_size = v;
+        }
+        if let Some(v) = general.daemon_multiple_windows {
+            self.daemon_multiple_windows = v;
+        }
+        if let Some(v) = general.daemon_tray_icon {
+            self.daemon_tray_icon = v;
+        }
+        if let Some(v) = general.daemon_idle_timeout {
+            self.daemon_idle_timeout = v;
+        }
+        if let Some(v) = general.daemon_auto_spawn {
+            self.daemon_auto_spawn = v;
+        }
+        if let Some(v) = general.copy_file_path {
+            self.copy_file_path = v;
+        }
+        if let Some(v) = general.clipboard_backend {
+            self.clipboard_backend = v;
+        }
+        if let Some(v) = general.primary_selection {
+            self.primary_selection = v;
+        }
+        if let Some(v) = general.window_mode {
+            self.window_mode = v;
+        }
+        if let Some(v) = general.output {
+            self.output = Some(v);
+        }
+        if let Some(v) = general.keyboard_mode {
+            self.keyboard_mode = v;
+        }
+        if let Some(v) = general.click_through {
+            self.click_through = v;
+        }
+        if let Some(v) = general.scrim_color {
+            self.scrim_color = Some(v.into());
+        }
+        if let Some(v) = general.escape_deselects {
+            self.escape_deselects = v;
+        }
+        if let Some(v) = general.confirm_escape {
+            self.confirm_escape = v;
+        }
+        if let Some(v) = general.brush_smoothing {
+            self.brush_smoothing = v;
+        }
+        if let Some(v) = general.pressure_curve {
+            self.pressure_curve = Some(v.pressure());
+        }
+        if let Some(v) = general.velocity_curve {
+            self.velocity_curve = Some(v.velocity());
+        }
+        if let Some(v) = general.brush_dynamics {
+            self.brush_dynamics = v;
+        }
+        if let Some(v) = general.recolor_tolerance {
+            self.recolor_tolerance = v;
+        }
+        if let Some(v) = general.region_filter {
+            self.region_filter = v;
+        }
+        if let Some(v) = general.trim_tolerance {
+            self.trim_tolerance = v;
+        }
+        if let Some(v) = general.polygon_corners {
+            self.polygon_corners = v;
+        }
+        if let Some(v) = general.check_color {
+            self.check_color = v.into();
+        }
+        if let Some(v) = general.cross_color {
+            self.cross_color = v.into();
+        }
+        if let Some(v) = general.table_rows {
+            self.table_rows = v;
+        }
+        if let Some(v) = general.table_columns {
+            self.table_columns = v;
+        }
+        if let Some(v) = general.ocr_command {
+            self.ocr_command = v;
+        }
+        if let Some(v) = general.redact_patterns {
+            self.redact_patterns = v;
+        }
+        if let Some(v) = general.log_level {
+            self.log_level = v;
+        }
+        if let Some(v) = general.log_file {
+            self.log_file = Some(v);
+        }
 
         // --- deprecated options ---
         if let Some(v) = general.right_click_copy {
@@ -305,9 +999,12 @@ impl Configuration {
     }
     fn merge(&mut self, file: Option<ConfigurationFile>, command_line: CommandLine) {
         // input_filename is optional in daemon/client modes
-        if let Some(filename) = command_line.filename {
-            self.input_filename = filename;
+        if !command_line.filename.is_empty() {
+            self.input_filenames = expand_globs(command_line.filename);
         }
+        self.systemd = command_line.systemd;
+        self.capture_region = command_line.capture_region;
+        self.capture_window = command_line.capture_window;
 
         // overwrite with all specified values from config file
         if let Some(file) = file {
@@ -320,9 +1017,18 @@ impl Configuration {
             if let Some(v) = file.font {
                 self.font.merge(v);
             }
+            if let Some(v) = file.scroll {
+                self.scroll.merge(v);
+            }
+            if let Some(v) = file.mouse {
+                self.mouse.merge(v);
+            }
             if let Some(v) = file.keybinds {
                 self.keybinds.merge(v);
             }
+            if let Some(v) = file.quick_actions {
+                self.quick_actions = v;
+            }
         }
 
         // overwrite with all specified values from command line
@@ -332,6 +1038,9 @@ impl Configuration {
         if command_line.early_exit {
             self.early_exit = command_line.early_exit;
         }
+        if let Some(v) = command_line.template {
+            self.template = Some(v);
+        }
         if let Some(v) = command_line.corner_roundness {
             self.corner_roundness = v;
         }
@@ -350,8 +1059,21 @@ impl Configuration {
         if let Some(v) = command_line.copy_command {
             self.copy_command = Some(v);
         }
-        if let Some(v) = command_line.output_filename {
-            self.output_filename = Some(v);
+        if let Some(v) = command_line.output_dir {
+            self.output_dir = Some(v);
+        }
+        if command_line.save_dialog {
+            self.save_dialog = command_line.save_dialog;
+        }
+        if command_line.print_saved_uri {
+            self.print_saved_uri = command_line.print_saved_uri;
+        }
+        if !command_line.output_filename.is_empty() {
+            self.outputs = command_line
+                .output_filename
+                .into_iter()
+                .map(OutputTarget::new)
+                .collect();
         }
         if let Some(v) = command_line.annotation_size_factor {
             self.annotation_size_factor = v;
@@ -383,6 +1105,9 @@ impl Configuration {
         if command_line.profile_startup {
             self.profile_startup = command_line.profile_startup;
         }
+        if let Some(v) = command_line.profile_trace {
+            self.profile_trace = Some(v);
+        }
         if command_line.no_window_decoration {
             self.no_window_decoration = command_line.no_window_decoration;
         }
@@ -395,6 +1120,87 @@ impl Configuration {
         if let Some(v) = command_line.pan_step_size {
             self.pan_step_size = v;
         }
+        if let Some(v) = command_line.edge_snap_distance {
+            self.edge_snap_distance = v;
+        }
+        if command_line.shape_recognition {
+            self.shape_recognition = command_line.shape_recognition;
+        }
+        if let Some(v) = command_line.shape_recognition_tolerance {
+            self.shape_recognition_tolerance = v;
+        }
+        if let Some(v) = command_line.ruler_dpi {
+            self.ruler_dpi = Some(v);
+        }
+        if command_line.show_status_bar {
+            self.show_status_bar = command_line.show_status_bar;
+        }
+        if command_line.disable_minimap {
+            self.disable_minimap = command_line.disable_minimap;
+        }
+        if command_line.beautify {
+            self.beautify = command_line.beautify;
+        }
+        if let Some(v) = command_line.beautify_padding {
+            self.beautify_padding = v;
+        }
+        if command_line.daemon_auto_spawn {
+            self.daemon_auto_spawn = command_line.daemon_auto_spawn;
+        }
+        if command_line.copy_file_path {
+            self.copy_file_path = command_line.copy_file_path;
+        }
+        if let Some(v) = command_line.clipboard_backend {
+            self.clipboard_backend = v.into();
+        }
+        if let Some(v) = command_line.window_mode {
+            self.window_mode = v.into();
+        }
+        if let Some(v) = command_line.output {
+            self.output = Some(v);
+        }
+        if let Some(v) = command_line.keyboard_mode {
+            self.keyboard_mode = v.into();
+        }
+        if command_line.click_through {
+            self.click_through = command_line.click_through;
+        }
+        if command_line.escape_deselects {
+            self.escape_deselects = command_line.escape_deselects;
+        }
+        if command_line.confirm_escape {
+            self.confirm_escape = command_line.confirm_escape;
+        }
+        if let Some(v) = command_line.brush_smoothing {
+            self.brush_smoothing = v.into();
+        }
+        if let Some(v) = command_line.recolor_tolerance {
+            self.recolor_tolerance = v;
+        }
+        if let Some(v) = command_line.region_filter {
+            self.region_filter = v.into();
+        }
+        if let Some(v) = command_line.trim_tolerance {
+            self.trim_tolerance = v;
+        }
+        if let Some(v) = command_line.polygon_corners {
+            self.polygon_corners = v;
+        }
+        if let Some(v) = command_line.table_rows {
+            self.table_rows = v;
+        }
+        if let Some(v) = command_line.table_columns {
+            self.table_columns = v;
+        }
+        if let Some(v) = command_line.ocr_command {
+            self.ocr_command = v;
+        }
+        if let Some(v) = command_line.log_level {
+            self.log_level = v.into();
+        }
+        if let Some(v) = command_line.log_file {
+            self.log_file = Some(v);
+        }
 
         // --- deprecated options ---
         if command_line.right_click_copy
@@ -415,6 +1221,10 @@ impl Configuration {
         self.early_exit
     }
 
+    pub fn template(&self) -> Option<&str> {
+        self.template.as_deref()
+    }
+
     pub fn corner_roundness(&self) -> f32 {
         self.corner_roundness
     }
@@ -431,12 +1241,38 @@ impl Configuration {
         self.fullscreen
     }
 
-    pub fn output_filename(&self) -> Option<&String> {
-        self.output_filename.as_ref()
+    pub fn outputs(&self) -> &[OutputTarget] {
+        &self.outputs
+    }
+
+    /// The images to annotate, one after another.
+    pub fn input_filenames(&self) -> &[String] {
+        &self.input_filenames
+    }
+
+    /// Replaces the images to annotate, e.g. by a capture.
+    pub fn set_input_filenames(&mut self, filenames: Vec<String>) {
+        self.input_filenames = filenames;
+    }
+
+    pub fn systemd(&self) -> bool {
+        self.systemd
     }
 
-    pub fn input_filename(&self) -> &str {
-        self.input_filename.as_ref()
+    pub fn capture_region(&self) -> bool {
+        self.capture_region
+    }
+
+    pub fn capture_window(&self) -> bool {
+        self.capture_window
+    }
+
+    pub fn set_window_title(&mut self, title: String) {
+        self.window_title = Some(title);
+    }
+
+    pub fn window_title(&self) -> Option<&str> {
+        self.window_title.as_deref()
     }
 
     pub fn annotation_size_factor(&self) -> f32 {
@@ -483,10 +1319,27 @@ impl Configuration {
         self.disable_notifications
     }
 
+    pub fn toast_position(&self) -> ToastPosition {
+        self.toast_position
+    }
+
+    /// How long a toast is shown, in milliseconds.
+    pub fn toast_duration(&self) -> u64 {
+        self.toast_duration
+    }
+
+    pub fn toast_max_stacked(&self) -> usize {
+        self.toast_max_stacked
+    }
+
     pub fn profile_startup(&self) -> bool {
         self.profile_startup
     }
 
+    pub fn profile_trace(&self) -> Option<&str> {
+        self.profile_trace.as_deref()
+    }
+
     pub fn no_window_decoration(&self) -> bool {
         self.no_window_decoration
     }
@@ -495,6 +1348,14 @@ impl Configuration {
         &self.font
     }
 
+    pub fn scroll(&self) -> &ScrollConfiguration {
+        &self.scroll
+    }
+
+    pub fn mouse(&self) -> &MouseConfiguration {
+        &self.mouse
+    }
+
     pub fn brush_smooth_history_size(&self) -> usize {
         self.brush_smooth_history_size
     }
@@ -510,15 +1371,367 @@ impl Configuration {
     pub fn pan_step_size(&self) -> f32 {
         self.pan_step_size
     }
+
+    pub fn edge_snap_distance(&self) -> f32 {
+        self.edge_snap_distance
+    }
+
+    pub fn shape_recognition(&self) -> bool {
+        self.shape_recognition
+    }
+
+    pub fn shape_recognition_tolerance(&self) -> f32 {
+        self.shape_recognition_tolerance
+    }
+
+    pub fn ruler_dpi(&self) -> Option<f32> {
+        self.ruler_dpi
+    }
+
+    pub fn show_status_bar(&self) -> bool {
+        self.show_status_bar
+    }
+
+    pub fn disable_minimap(&self) -> bool {
+        self.disable_minimap
+    }
+
+    pub fn transparency_backdrop(&self) -> Option<Color> {
+        self.transparency_backdrop
+    }
+
+    pub fn export_background(&self) -> Option<Color> {
+        self.export_background
+    }
+
+    pub fn canvas_extension_color(&self) -> Color {
+        self.canvas_extension_color
+    }
+
+    pub fn beautify(&self) -> bool {
+        self.beautify
+    }
+
+    pub fn beautify_padding(&self) -> f32 {
+        self.beautify_padding
+    }
+
+    pub fn beautify_corner_radius(&self) -> f32 {
+        self.beautify_corner_radius
+    }
+
+    pub fn beautify_background(&self) -> &[Color] {
+        &self.beautify_background
+    }
+
+    /// Memory the undo history may hold in bytes, `None` if it is unlimited.
+    pub fn undo_memory_limit(&self) -> Option<usize> {
+        Some(self.undo_memory_limit * 1024 * 1024).filter(|&v| v > 0)
+    }
+
+    pub fn recording_step_duration(&self) -> u64 {
+        self.recording_step_duration
+    }
+
+    pub fn caption_template(&self) -> &str {
+        &self.caption_template
+    }
+
+    pub fn caption_corner(&self) -> CaptionCorner {
+        self.caption_corner
+    }
+
+    pub fn external_editor(&self) -> Option<&str> {
+        self.external_editor.as_deref()
+    }
+
+    pub fn external_editor_reload(&self) -> bool {
+        self.external_editor_reload
+    }
+
+    pub fn modal_editing(&self) -> bool {
+        self.modal_editing
+    }
+
+    pub fn output_dir(&self) -> Option<&str> {
+        self.output_dir.as_deref()
+    }
+
+    pub fn save_dialog(&self) -> bool {
+        self.save_dialog
+    }
+
+    pub fn overwrite_policy(&self) -> OverwritePolicy {
+        self.overwrite_policy
+    }
+
+    pub fn print_saved_uri(&self) -> bool {
+        self.print_saved_uri
+    }
+
+    pub fn region_command(&self) -> &str {
+        &self.region_command
+    }
+
+    pub fn capture_command(&self) -> &str {
+        &self.capture_command
+    }
+
+    pub fn daemon_history_size(&self) -> usize {
+        self.daemon_history_size
+    }
+
+    pub fn daemon_multiple_windows(&self) -> bool {
+        self.daemon_multiple_windows
+    }
+
+    pub fn daemon_tray_icon(&self) -> bool {
+        self.daemon_tray_icon
+    }
+
+    pub fn daemon_idle_timeout(&self) -> u64 {
+        self.daemon_idle_timeout
+    }
+
+    pub fn daemon_auto_spawn(&self) -> bool {
+        self.daemon_auto_spawn
+    }
+
+    pub fn copy_file_path(&self) -> bool {
+        self.copy_file_path
+    }
+
+    pub fn clipboard_backend(&self) -> ClipboardBackend {
+        self.clipboard_backend
+    }
+
+    pub fn primary_selection(&self) -> bool {
+        self.primary_selection
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        self.window_mode
+    }
+
+    pub fn output(&self) -> Option<&String> {
+        self.output.as_ref()
+    }
+
+    pub fn keyboard_mode(&self) -> KeyboardMode {
+        self.keyboard_mode
+    }
+
+    pub fn click_through(&self) -> bool {
+        self.click_through
+    }
+
+    pub fn scrim_color(&self) -> Option<Color> {
+        self.scrim_color
+    }
+
+    pub fn escape_deselects(&self) -> bool {
+        self.escape_deselects
+    }
+
+    pub fn confirm_escape(&self) -> bool {
+        self.confirm_escape
+    }
+
+    pub fn brush_smoothing(&self) -> SmoothingMode {
+        self.brush_smoothing
+    }
+
+    pub fn pressure_curve(&self) -> Option<&Curve> {
+        self.pressure_curve.as_ref()
+    }
+
+    pub fn velocity_curve(&self) -> Option<&Curve> {
+        self.velocity_curve.as_ref()
+    }
+
+    pub fn brush_dynamics(&self) -> DynamicsTarget {
+        self.brush_dynamics
+    }
+
+    pub fn recolor_tolerance(&self) -> u8 {
+        self.recolor_tolerance
+    }
+
+    pub fn region_filter(&self) -> FilterKind {
+        self.region_filter
+    }
+
+    pub fn trim_tolerance(&self) -> u8 {
+        self.trim_tolerance
+    }
+
+    pub fn polygon_corners(&self) -> u8 {
+        self.polygon_corners
+    }
+
+    pub fn check_color(&self) -> Color {
+        self.check_color
+    }
+
+    pub fn cross_color(&self) -> Color {
+        self.cross_color
+    }
+
+    pub fn table_rows(&self) -> u8 {
+        self.table_rows
+    }
+
+    pub fn table_columns(&self) -> u8 {
+        self.table_columns
+    }
+
+    pub fn ocr_command(&self) -> &str {
+        &self.ocr_command
+    }
+
+    pub fn redact_patterns(&self) -> &[String] {
+        &self.redact_patterns
+    }
+
+    pub fn log_level(&self) -> LogLevel {
+        self.log_level
+    }
+
+    pub fn log_file(&self) -> Option<&str> {
+        self.log_file.as_deref()
+    }
+
+    pub fn quick_actions(&self) -> &[QuickAction] {
+        &self.quick_actions
+    }
+}
+
+/// Replaces patterns with `*` and `?` in the file name by the files they match, in
+/// alphabetical order. Existing files and patterns that match nothing are kept, so loading
+/// them reports the missing file. Patterns are checked by `unsupported_glob` beforehand.
+fn expand_globs(filenames: Vec<String>) -> Vec<String> {
+    filenames
+        .into_iter()
+        .map(uri_to_path)
+        .flat_map(|filename| {
+            if !filename.contains(['*', '?']) || Path::new(&filename).exists() {
+                return vec![filename];
+            }
+            let mut matches = glob_matches(Path::new(&filename));
+            if matches.is_empty() {
+                vec![filename]
+            } else {
+                matches.sort();
+                matches
+            }
+        })
+        .collect()
+}
+
+// the files in the directory of `pattern` whose names match its file name, the directories
+// are taken literally
+fn glob_matches(pattern: &Path) -> Vec<String> {
+    let (Some(dir), Some(name)) = (pattern.parent(), pattern.file_name()) else {
+        return Vec::new();
+    };
+    let name: Vec<char> = name.to_string_lossy().chars().collect();
+    let search_dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let Ok(entries) = fs::read_dir(search_dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| {
+            let file_name: Vec<char> = entry.file_name().to_string_lossy().chars().collect();
+            // like in shells, hidden files only match patterns starting with a dot
+            (file_name.first() != Some(&'.') || name.first() == Some(&'.'))
+                && wildcard_match(&name, &file_name)
+        })
+        .map(|entry| dir.join(entry.file_name()).to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Why `filename` can't be expanded by `expand_globs`, if it is a pattern: only `*` and `?`
+/// in the file name are supported, not in directories, nor `**` or `[...]`.
+fn unsupported_glob(filename: &str) -> Option<String> {
+    let filename = uri_to_path(filename.to_string());
+    let path = Path::new(&filename);
+    if !filename.contains(['*', '?']) || path.exists() {
+        return None;
+    }
+    let name = path.file_name()?.to_string_lossy();
+    let reason = if path
+        .parent()
+        .is_some_and(|dir| dir.to_string_lossy().contains(['*', '?']))
+    {
+        "wildcards are only supported in the file name, not in directories"
+    } else if name.contains("**") {
+        "'**' is not supported, only '*' and '?'"
+    } else if name.contains(['[', ']']) {
+        "'[...]' is not supported, only '*' and '?'"
+    } else {
+        return None;
+    };
+    Some(format!("Unsupported pattern '{filename}': {reason}"))
+}
+
+/// Whether `name` matches `pattern`, in which `*` stands for any characters and `?` for one.
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // position after the last `*` and the part of the name it covers up to then
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            // let the last `*` cover one more character
+            _ => match star {
+                Some((after_star, covered)) => {
+                    p = after_star;
+                    n = covered + 1;
+                    star = Some((after_star, covered + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// The path of a `file://` URI, as handed over by portals and file managers. Other names
+/// are kept as they are.
+fn uri_to_path(filename: String) -> String {
+    if !filename.starts_with("file://") {
+        return filename;
+    }
+    match gio::File::for_uri(&filename).path() {
+        Some(path) => path.to_string_lossy().into_owned(),
+        None => filename,
+    }
 }
 
 impl Default for Configuration {
     fn default() -> Self {
         Self {
-            input_filename: String::new(),
-            output_filename: None,
+            input_filenames: Vec::new(),
+            systemd: false,
+            capture_region: false,
+            capture_window: false,
+            window_title: None,
+            outputs: Vec::new(),
             fullscreen: false,
             early_exit: false,
+            template: None,
             corner_roundness: 12.0,
             initial_tool: Tools::Pointer,
             copy_command: None,
@@ -532,14 +1745,84 @@ impl Default for Configuration {
             focus_toggles_toolbars: false,
             default_fill_shapes: false,
             font: FontConfiguration::default(),
+            scroll: ScrollConfiguration::default(),
+            mouse: MouseConfiguration::default(),
             primary_highlighter: Highlighters::Block,
             disable_notifications: false,
+            toast_position: ToastPosition::Top,
+            toast_duration: 3000,
+            toast_max_stacked: 3,
             profile_startup: false,
+            profile_trace: None,
             no_window_decoration: false,
             brush_smooth_history_size: 0, // default to 0, no history
             keybinds: Keybinds::default(),
             zoom_factor: 1.1,
             pan_step_size: 50.,
+            edge_snap_distance: 10.0,
+            shape_recognition: false,
+            shape_recognition_tolerance: 0.15,
+            ruler_dpi: None,
+            show_status_bar: false,
+            disable_minimap: false,
+            transparency_backdrop: None,
+            export_background: None,
+            canvas_extension_color: Color::new(255, 255, 255, 255),
+            beautify: false,
+            beautify_padding: 64.0,
+            beautify_corner_radius: 12.0,
+            beautify_background: vec![
+                Color::new(102, 126, 234, 255),
+                Color::new(118, 75, 162, 255),
+            ],
+            undo_memory_limit: 256,
+            recording_step_duration: 800,
+            caption_template: "%Y-%m-%d %H:%M:%S {user}@{hostname}".to_string(),
+            caption_corner: CaptionCorner::BottomRight,
+            external_editor: None,
+            external_editor_reload: true,
+            modal_editing: false,
+            output_dir: None,
+            save_dialog: false,
+            overwrite_policy: OverwritePolicy::Overwrite,
+            print_saved_uri: false,
+            region_command: String::from("slurp"),
+            capture_command: String::from("grim -g {geometry} -"),
+            daemon_history_size: 10,
+            daemon_multiple_windows: false,
+            daemon_tray_icon: false,
+            daemon_idle_timeout: 0,
+            daemon_auto_spawn: false,
+            copy_file_path: false,
+            clipboard_backend: ClipboardBackend::Gdk,
+            primary_selection: false,
+            window_mode: WindowMode::LayerShell,
+            output: None,
+            keyboard_mode: KeyboardMode::Exclusive,
+            click_through: false,
+            scrim_color: None,
+            escape_deselects: false,
+            confirm_escape: false,
+            brush_smoothing: SmoothingMode::MovingAverage,
+            pressure_curve: None,
+            velocity_curve: None,
+            brush_dynamics: DynamicsTarget::Width,
+            recolor_tolerance: 32,
+            region_filter: FilterKind::Grayscale,
+            trim_tolerance: 16,
+            polygon_corners: 5,
+            check_color: Color::green(),
+            cross_color: Color::red(),
+            table_rows: 3,
+            table_columns: 3,
+            ocr_command: String::from("tesseract stdin stdout tsv"),
+            redact_patterns: DEFAULT_REDACT_PATTERNS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            log_level: LogLevel::Info,
+            log_file: None,
+            quick_actions: Vec::new(),
         }
     }
 }
@@ -565,7 +1848,10 @@ struct ConfigurationFile {
     general: Option<ConfigurationFileGeneral>,
     color_palette: Option<ColorPaletteFile>,
     font: Option<FontFile>,
+    scroll: Option<ScrollFile>,
+    mouse: Option<MouseFile>,
     keybinds: Option<KeybindsFile>,
+    quick_actions: Option<Vec<QuickAction>>,
 }
 
 #[derive(Deserialize)]
@@ -582,6 +1868,22 @@ struct KeybindsFile {
     marker: Option<String>,
     blur: Option<String>,
     highlight: Option<String>,
+    ruler: Option<String>,
+    eraser: Option<String>,
+    patch: Option<String>,
+    recolor: Option<String>,
+    filter: Option<String>,
+    perspective: Option<String>,
+    polygon: Option<String>,
+    stamp: Option<String>,
+    connector: Option<String>,
+    flowchart: Option<String>,
+    table: Option<String>,
+    qr: Option<String>,
+    ocr: Option<String>,
+    leader: Option<String>,
+    sequence_timeout: Option<u64>,
+    actions: Option<HashMap<Action, String>>,
 }
 
 #[derive(Deserialize)]
@@ -601,7 +1903,7 @@ struct ConfigurationFileGeneral {
     copy_command: Option<String>,
     annotation_size_factor: Option<f32>,
     save_after_copy: Option<bool>,
-    output_filename: Option<String>,
+    output_filename: Option<OutputFilenameFile>,
     actions_on_enter: Option<Vec<Action>>,
     actions_on_escape: Option<Vec<Action>>,
     actions_on_right_click: Option<Vec<Action>>,
@@ -610,10 +1912,70 @@ struct ConfigurationFileGeneral {
     default_fill_shapes: Option<bool>,
     primary_highlighter: Option<Highlighters>,
     disable_notifications: Option<bool>,
+    toast_position: Option<ToastPosition>,
+    toast_duration: Option<u64>,
+    toast_max_stacked: Option<usize>,
     no_window_decoration: Option<bool>,
     brush_smooth_history_size: Option<usize>,
     zoom_factor: Option<f32>,
     pan_step_size: Option<f32>,
+    edge_snap_distance: Option<f32>,
+    shape_recognition: Option<bool>,
+    shape_recognition_tolerance: Option<f32>,
+    ruler_dpi: Option<f32>,
+    show_status_bar: Option<bool>,
+    disable_minimap: Option<bool>,
+    transparency_backdrop: Option<HexColor>,
+    export_background: Option<HexColor>,
+    canvas_extension_color: Option<HexColor>,
+    beautify: Option<bool>,
+    beautify_padding: Option<f32>,
+    beautify_corner_radius: Option<f32>,
+    beautify_background: Option<Vec<HexColor>>,
+    undo_memory_limit: Option<usize>,
+    recording_step_duration: Option<u64>,
+    caption_template: Option<String>,
+    caption_corner: Option<CaptionCorner>,
+    external_editor: Option<String>,
+    external_editor_reload: Option<bool>,
+    modal_editing: Option<bool>,
+    output_dir: Option<String>,
+    save_dialog: Option<bool>,
+    overwrite_policy: Option<OverwritePolicy>,
+    print_saved_uri: Option<bool>,
+    region_command: Option<String>,
+    capture_command: Option<String>,
+    daemon_history_size: Option<usize>,
+    daemon_multiple_windows: Option<bool>,
+    daemon_tray_icon: Option<bool>,
+    daemon_idle_timeout: Option<u64>,
+    daemon_auto_spawn: Option<bool>,
+    copy_file_path: Option<bool>,
+    clipboard_backend: Option<ClipboardBackend>,
+    primary_selection: Option<bool>,
+    window_mode: Option<WindowMode>,
+    output: Option<String>,
+    keyboard_mode: Option<KeyboardMode>,
+    click_through: Option<bool>,
+    scrim_color: Option<HexColor>,
+    escape_deselects: Option<bool>,
+    confirm_escape: Option<bool>,
+    brush_smoothing: Option<SmoothingMode>,
+    pressure_curve: Option<CurveFile>,
+    velocity_curve: Option<CurveFile>,
+    brush_dynamics: Option<DynamicsTarget>,
+    recolor_tolerance: Option<u8>,
+    region_filter: Option<FilterKind>,
+    trim_tolerance: Option<u8>,
+    polygon_corners: Option<u8>,
+    check_color: Option<HexColor>,
+    cross_color: Option<HexColor>,
+    table_rows: Option<u8>,
+    table_columns: Option<u8>,
+    ocr_command: Option<String>,
+    redact_patterns: Option<Vec<String>>,
+    log_level: Option<LogLevel>,
+    log_file: Option<String>,
 
     // --- deprecated options ---
     right_click_copy: Option<bool>,
@@ -621,6 +1983,100 @@ struct ConfigurationFileGeneral {
     // ---
 }
 
+/// A single filename or a list of targets, each a filename or a table with a scale.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OutputFilenameFile {
+    Single(String),
+    Multiple(Vec<OutputTargetFile>),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OutputTargetFile {
+    Filename(String),
+    Scaled { filename: String, scale: f32 },
+}
+
+impl OutputFilenameFile {
+    fn into_targets(self) -> Vec<OutputTarget> {
+        match self {
+            OutputFilenameFile::Single(filename) => vec![OutputTarget::new(filename)],
+            OutputFilenameFile::Multiple(targets) => targets
+                .into_iter()
+                .map(|target| match target {
+                    OutputTargetFile::Filename(filename) => OutputTarget::new(filename),
+                    OutputTargetFile::Scaled { filename, scale } => {
+                        OutputTarget { filename, scale }
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A preset or a list of `[input, factor]` points.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CurveFile {
+    Preset(CurvePreset),
+    Points(Vec<[f32; 2]>),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum CurvePreset {
+    Linear,
+    /// Reaches the full factor with less pressure, loses less of it when drawing fast.
+    Soft,
+    /// Needs more pressure for the full factor, loses more of it when drawing fast.
+    Hard,
+}
+
+impl CurveFile {
+    fn pressure(self) -> Curve {
+        Curve::new(match self {
+            CurveFile::Preset(CurvePreset::Linear) => vec![[0.0, 0.0], [1.0, 1.0]],
+            CurveFile::Preset(CurvePreset::Soft) => vec![[0.0, 0.0], [0.3, 0.6], [1.0, 1.0]],
+            CurveFile::Preset(CurvePreset::Hard) => vec![[0.0, 0.0], [0.6, 0.3], [1.0, 1.0]],
+            CurveFile::Points(points) => points,
+        })
+    }
+
+    // speeds in pixels per second, the factor falls to 0.4 at a fast stroke
+    fn velocity(self) -> Curve {
+        Curve::new(match self {
+            CurveFile::Preset(CurvePreset::Linear) => vec![[0.0, 1.0], [3000.0, 0.4]],
+            CurveFile::Preset(CurvePreset::Soft) => {
+                vec![[0.0, 1.0], [1800.0, 0.85], [3000.0, 0.4]]
+            }
+            CurveFile::Preset(CurvePreset::Hard) => {
+                vec![[0.0, 1.0], [600.0, 0.6], [3000.0, 0.4]]
+            }
+            CurveFile::Points(points) => points,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct ScrollFile {
+    plain: Option<ScrollAction>,
+    ctrl: Option<ScrollAction>,
+    shift: Option<ScrollAction>,
+    alt: Option<ScrollAction>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct MouseFile {
+    middle: Option<ButtonAction>,
+    right: Option<ButtonAction>,
+    back: Option<ButtonAction>,
+    forward: Option<ButtonAction>,
+    gestures: Option<HashMap<GestureDirection, GestureAction>>,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 struct ColorPaletteFile {
@@ -650,6 +2106,65 @@ impl ConfigurationFile {
         path: P,
     ) -> Result<Option<ConfigurationFile>, ConfigurationFileError> {
         let content = fs::read_to_string(path)?;
-        Ok(Some(toml::from_str::<ConfigurationFile>(&content)?))
+        let file = toml::from_str::<ConfigurationFile>(&content)?;
+        file.validate()?;
+        Ok(Some(file))
+    }
+
+    // values that decode fine but can't be used
+    fn validate(&self) -> Result<(), ConfigurationFileError> {
+        let targets = self
+            .general
+            .as_ref()
+            .and_then(|general| general.output_filename.as_ref());
+        if let Some(OutputFilenameFile::Multiple(targets)) = targets {
+            for target in targets {
+                if let OutputTargetFile::Scaled { filename, scale } = target {
+                    // would encode an empty image
+                    if scale.is_nan() || *scale <= 0.0 {
+                        return Err(ConfigurationFileError::InvalidValue(format!(
+                            "scale of '{filename}' has to be greater than 0, not {scale}"
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        wildcard_match(&chars(pattern), &chars(name))
+    }
+
+    #[test]
+    fn wildcards_match_file_names() {
+        assert!(matches("*.png", "shot.png"));
+        assert!(matches("shot-?.png", "shot-1.png"));
+        assert!(matches("*-*-*.png", "a-b-c-d.png"));
+        assert!(matches("*", ""));
+        assert!(!matches("*.png", "shot.jpg"));
+        assert!(!matches("shot-?.png", "shot-10.png"));
+        assert!(!matches("?", ""));
+    }
+
+    #[test]
+    fn many_stars_do_not_backtrack_exponentially() {
+        let name = "a".repeat(200);
+        assert!(!matches(&format!("{}b", "*a".repeat(30)), &name));
+    }
+
+    #[test]
+    fn unsupported_globs_are_rejected() {
+        assert!(unsupported_glob("docs/*.png").is_none());
+        assert!(unsupported_glob("plain.png").is_none());
+        assert!(unsupported_glob("*/shot.png").is_some());
+        assert!(unsupported_glob("docs/**.png").is_some());
+        assert!(unsupported_glob("shot-[0-9]*.png").is_some());
     }
 }
@@ -1,20 +1,84 @@
-use anyhow::{Context, Result};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
 use gdk_pixbuf::{gio, Pixbuf};
 use gio::prelude::*;
 use relm4::ComponentSender;
 
-use crate::ipc::{IpcMessage, IpcResponse, IpcServer};
+use crate::annotations;
+use crate::configuration::{SessionOptions, APP_CONFIG};
+use crate::ipc::{IpcMessage, IpcResponse, IpcServer, METHODS, PROTOCOL_VERSION};
+use crate::recent_images::RecentImages;
+use crate::sketch_board::EditorState;
+use crate::tray::{self, TrayAction};
+use crate::ui::toolbars::ToolbarEvent;
 use crate::{App, AppInput};
 
+// how often the daemon checks whether it has been idle for too long
+const IDLE_CHECK_INTERVAL: u32 = 5;
+
 pub struct DaemonServer {
     server: IpcServer,
+    recent_images: Rc<RefCell<RecentImages>>,
+    state: Rc<RefCell<DaemonState>>,
+    started: Instant,
+}
+
+/// What the app tells the daemon about the editor, reported by `GetState`.
+#[derive(Default)]
+pub struct DaemonState {
+    /// Dimensions of the image being edited, `None` while no editor is shown.
+    pub image_size: Option<(i32, i32)>,
+    pub editor: Option<EditorState>,
+    /// Set from the tray icon, new images are rejected while paused.
+    pub paused: bool,
+    /// Ids of the editor windows shown, `0` is the daemon's own window.
+    pub windows: BTreeSet<usize>,
+    last_window: usize,
+}
+
+impl DaemonState {
+    fn to_json(&self, uptime: u64) -> String {
+        serde_json::json!({
+            "image_loaded": self.image_size.is_some(),
+            "width": self.image_size.map(|(width, _)| width),
+            "height": self.image_size.map(|(_, height)| height),
+            "active_tool": self.editor.map(|editor| editor.active_tool.to_string()),
+            "unsaved_changes": self.editor.is_some_and(|editor| editor.unsaved_changes),
+            "paused": self.paused,
+            "windows": self.windows,
+            "uptime_seconds": uptime,
+        })
+        .to_string()
+    }
+
+    /// Id of the window the next image is shown in, the daemon's own window is reused unless
+    /// every image gets its own.
+    pub fn window_for_image(&mut self) -> usize {
+        if !APP_CONFIG.read().daemon_multiple_windows() {
+            return 0;
+        }
+        self.last_window += 1;
+        self.last_window
+    }
 }
 
 impl DaemonServer {
-    pub async fn new() -> Result<Self> {
+    pub async fn new(
+        recent_images: Rc<RefCell<RecentImages>>,
+        state: Rc<RefCell<DaemonState>>,
+    ) -> Result<Self> {
         let server = IpcServer::new();
-        eprintln!("Daemon server initialized");
-        Ok(Self { server })
+        log::info!("Daemon server initialized");
+        Ok(Self {
+            server,
+            recent_images,
+            state,
+            started: Instant::now(),
+        })
     }
 
     pub async fn run(self, sender: ComponentSender<App>) -> Result<()> {
@@ -22,16 +86,92 @@ impl DaemonServer {
             .await
             .context("Failed to connect to session bus")?;
 
+        let sender_clone = sender.clone();
+        let recent_images = self.recent_images.clone();
+        let state = self.state.clone();
+        let started = self.started;
+        let last_activity = Rc::new(Cell::new(Instant::now()));
+        Self::watch_idle_timeout(self.state.clone(), last_activity.clone());
+        self.server
+            .register_object(&connection, move |message| {
+                last_activity.set(Instant::now());
+                let sender = sender_clone.clone();
+                let recent_images = recent_images.clone();
+                let response = match &message {
+                    IpcMessage::Ping => {
+                        IpcResponse::Text(Self::ping_info(started.elapsed().as_secs()))
+                    }
+                    IpcMessage::GetCapabilities => IpcResponse::Text(Self::capabilities()),
+                    IpcMessage::ListRecent => IpcResponse::Text(recent_images.borrow().describe()),
+                    IpcMessage::GetState => {
+                        IpcResponse::Text(state.borrow().to_json(started.elapsed().as_secs()))
+                    }
+                    IpcMessage::LoadRecent { index } => {
+                        match recent_images.borrow().get(*index as usize) {
+                            Some(recent) => {
+                                let window = state.borrow_mut().window_for_image();
+                                sender.input(AppInput::ReceiveImage(
+                                    recent.image.clone(),
+                                    SessionOptions::default(),
+                                    window,
+                                ));
+                                sender.input(AppInput::ShowWindow);
+                                IpcResponse::Text(Self::window_info(window))
+                            }
+                            None => {
+                                IpcResponse::Error(format!("No recent image with index {index}"))
+                            }
+                        }
+                    }
+                    IpcMessage::Save
+                    | IpcMessage::CopyToClipboard
+                    | IpcMessage::Undo
+                    | IpcMessage::Redo
+                    | IpcMessage::SwitchTool { .. }
+                    | IpcMessage::AddAnnotations { .. } => {
+                        Self::control_window(&sender, None, &message)
+                    }
+                    IpcMessage::ControlWindow { window, message } => {
+                        if state.borrow().windows.contains(window) {
+                            Self::control_window(&sender, Some(*window), message)
+                        } else {
+                            IpcResponse::Error(format!("No window with id {window}"))
+                        }
+                    }
+                    IpcMessage::LoadImage { .. } if state.borrow().paused => {
+                        IpcResponse::Error("The daemon is paused".to_string())
+                    }
+                    IpcMessage::Shutdown => {
+                        Self::shutdown();
+                        IpcResponse::Ok
+                    }
+                    IpcMessage::LoadImage {
+                        filename,
+                        options,
+                        source,
+                    } => {
+                        return Box::pin(Self::load_image(
+                            filename.clone(),
+                            source.clone(),
+                            options.clone(),
+                            sender,
+                            recent_images,
+                            state.clone(),
+                        ));
+                    }
+                };
+                Box::pin(std::future::ready(response))
+            })
+            .context("Failed to register DBus object")?;
+
+        // the name is only requested once the object is there, clients may call it right away
         let request_result = connection
             .call_future(
                 Some("org.freedesktop.DBus"),
                 "/org/freedesktop/DBus",
                 "org.freedesktop.DBus",
                 "RequestName",
-                Some(&(
-                    "com.gabm.satty",
-                    1u32 | 4u32,
-                ).to_variant()),
+                Some(&("com.gabm.satty", 1u32 | 4u32).to_variant()),
                 Some(&glib::VariantTy::new("(u)").unwrap()),
                 gio::DBusCallFlags::NONE,
                 -1,
@@ -39,69 +179,198 @@ impl DaemonServer {
             .await
             .context("Failed to request DBus name")?;
 
-        let (name_result,): (u32,) = request_result.get().context("Failed to parse name request result")?;
+        let (name_result,): (u32,) = request_result
+            .get()
+            .context("Failed to parse name request result")?;
 
         match name_result {
-            1 => eprintln!("Daemon listening on DBus: com.gabm.satty"),
+            1 => log::info!("Daemon listening on DBus: com.gabm.satty"),
             2 => return Err(anyhow::anyhow!("Daemon already running on DBus")),
             3 => return Err(anyhow::anyhow!("Name already exists on DBus")),
-            4 => eprintln!("Daemon became primary owner on DBus"),
-            _ => return Err(anyhow::anyhow!("Unknown name request result: {}", name_result)),
+            4 => log::info!("Daemon became primary owner on DBus"),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unknown name request result: {}",
+                    name_result
+                ))
+            }
         }
 
-        let sender_clone = sender.clone();
-        self.server
-            .register_object(&connection, move |message| {
-                let sender = sender_clone.clone();
-                match &message {
-                    IpcMessage::Ping => {
-                        IpcResponse::Pong
-                    }
-                    IpcMessage::Shutdown => {
-                        glib::spawn_future_local(async move {
-                            std::process::exit(0);
-                        });
-                        IpcResponse::Ok
-                    }
-                    _ => {
-                        glib::spawn_future_local(glib::clone!(
-                            #[strong]
-                            sender,
-                            async move {
-                                if let Err(e) = Self::handle_message(message, &sender).await {
-                                    eprintln!("Error handling message: {}", e);
-                                }
-                            }
-                        ));
-                        IpcResponse::Ok
-                    }
-                }
+        if APP_CONFIG.read().daemon_tray_icon() {
+            let recent_images = self.recent_images.clone();
+            let state = self.state.clone();
+            let result = tray::register(&connection, move |action| {
+                Self::handle_tray_action(action, &sender, &recent_images, &state)
             })
-            .context("Failed to register DBus object")?;
+            .await;
+            if let Err(e) = result {
+                log::warn!("{e:#}");
+            }
+        }
+
+        if APP_CONFIG.read().systemd() {
+            Self::notify_systemd("READY=1").context("Failed to notify systemd")?;
+        }
         Ok(())
     }
 
-    async fn handle_message(message: IpcMessage, sender: &ComponentSender<App>) -> Result<()> {
-        match message {
-            IpcMessage::LoadImage { filename } => {
-                let sender = sender.clone();
-                glib::spawn_future_local(async move {
-                    match Self::load_pixbuf_from_file(&filename) {
-                        Ok(pixbuf) => {
-                            sender.input(AppInput::LoadNewImage(pixbuf));
-                            sender.input(AppInput::ShowWindow);
+    /// Sends `state` to the service manager as described in sd_notify(3), does nothing when
+    /// not started by systemd.
+    #[cfg(target_os = "linux")]
+    fn notify_systemd(state: &str) -> Result<()> {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::net::{SocketAddr, UnixDatagram};
 
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to load image: {}", e);
-                            sender.input(AppInput::HideWindow);
-                        }
-                    }
-                });
+        let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+            return Ok(());
+        };
+        let socket = UnixDatagram::unbound()?;
+        let address = match path.as_bytes().strip_prefix(b"@") {
+            Some(name) => SocketAddr::from_abstract_name(name)?,
+            None => SocketAddr::from_pathname(&path)?,
+        };
+        socket.send_to_addr(state.as_bytes(), &address)?;
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn notify_systemd(_state: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Loads the image before answering, so the client may remove the file afterwards. It is
+    /// decoded on a worker thread, so the daemon keeps handling other messages meanwhile. The
+    /// answer tells the id of the window showing the image.
+    async fn load_image(
+        filename: String,
+        source: Option<String>,
+        options: SessionOptions,
+        sender: ComponentSender<App>,
+        recent_images: Rc<RefCell<RecentImages>>,
+        state: Rc<RefCell<DaemonState>>,
+    ) -> IpcResponse {
+        let path = filename.clone();
+        let decoded = gio::spawn_blocking(move || Self::load_pixbuf_from_file(&path))
+            .await
+            .unwrap_or_else(|_| Err(anyhow!("Decoding {filename} panicked")));
+        match decoded {
+            Ok(pixbuf) => {
+                // temporary copies are gone once the client got the answer
+                recent_images
+                    .borrow_mut()
+                    .push(source.unwrap_or(filename), pixbuf.clone());
+                let window = state.borrow_mut().window_for_image();
+                sender.input(AppInput::ReceiveImage(pixbuf, options, window));
+                sender.input(AppInput::ShowWindow);
+                IpcResponse::Text(Self::window_info(window))
+            }
+            Err(e) => {
+                log::error!("Failed to load image: {}", e);
+                sender.input(AppInput::HideWindow);
+                IpcResponse::Error(format!("{e:#}"))
             }
-            _ => {}
         }
-        Ok(())
+    }
+
+    fn window_info(window: usize) -> String {
+        serde_json::json!({ "window": window }).to_string()
+    }
+
+    fn capabilities() -> String {
+        serde_json::json!({
+            "protocol_version": PROTOCOL_VERSION,
+            "methods": METHODS,
+        })
+        .to_string()
+    }
+
+    fn ping_info(uptime: u64) -> String {
+        serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "protocol_version": PROTOCOL_VERSION,
+            "methods": METHODS,
+            "uptime_seconds": uptime,
+        })
+        .to_string()
+    }
+
+    /// Exits the daemon once no image was shown and no request arrived for the configured time.
+    fn watch_idle_timeout(state: Rc<RefCell<DaemonState>>, last_activity: Rc<Cell<Instant>>) {
+        let timeout = APP_CONFIG.read().daemon_idle_timeout();
+        if timeout == 0 {
+            return;
+        }
+        let timeout = Duration::from_secs(timeout);
+
+        glib::timeout_add_seconds_local(IDLE_CHECK_INTERVAL, move || {
+            if state.borrow().image_size.is_some() {
+                // the idle time starts once the image is closed
+                last_activity.set(Instant::now());
+            } else if last_activity.get().elapsed() >= timeout {
+                log::info!("Daemon was idle for {} seconds, exiting", timeout.as_secs());
+                std::process::exit(0);
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+
+    fn handle_tray_action(
+        action: TrayAction,
+        sender: &ComponentSender<App>,
+        recent_images: &RefCell<RecentImages>,
+        state: &RefCell<DaemonState>,
+    ) {
+        match action {
+            TrayAction::OpenLastImage => match recent_images.borrow().get(0) {
+                Some(recent) => {
+                    let window = state.borrow_mut().window_for_image();
+                    sender.input(AppInput::ReceiveImage(
+                        recent.image.clone(),
+                        SessionOptions::default(),
+                        window,
+                    ));
+                    sender.input(AppInput::ShowWindow);
+                }
+                None => log::warn!("The daemon has not received any images yet"),
+            },
+            TrayAction::Pause(paused) => state.borrow_mut().paused = paused,
+            TrayAction::Shutdown => Self::shutdown(),
+        }
+    }
+
+    fn shutdown() {
+        glib::spawn_future_local(async move {
+            std::process::exit(0);
+        });
+    }
+
+    /// Passes a message controlling the editor to the window with id `window`, or to the most
+    /// recently opened one without an id.
+    fn control_window(
+        sender: &ComponentSender<App>,
+        window: Option<usize>,
+        message: &IpcMessage,
+    ) -> IpcResponse {
+        let event = match message {
+            IpcMessage::Save => ToolbarEvent::SaveFile,
+            IpcMessage::CopyToClipboard => ToolbarEvent::CopyClipboard,
+            IpcMessage::Undo => ToolbarEvent::Undo,
+            IpcMessage::Redo => ToolbarEvent::Redo,
+            IpcMessage::SwitchTool { tool } => ToolbarEvent::ToolSelected(*tool),
+            IpcMessage::AddAnnotations { json } => {
+                return match annotations::parse(json) {
+                    Ok(annotations) => {
+                        sender.input(AppInput::AddAnnotations(window, annotations));
+                        IpcResponse::Ok
+                    }
+                    Err(e) => IpcResponse::Error(format!("{e:#}")),
+                };
+            }
+            _ => return IpcResponse::Error("The method does not control a window".to_string()),
+        };
+        sender.input(AppInput::RemoteControl(window, event));
+        IpcResponse::Ok
     }
 
     fn load_pixbuf_from_file(filename: &str) -> Result<Pixbuf> {
@@ -0,0 +1,331 @@
+use std::cell::OnceCell;
+
+use gdk_pixbuf::Pixbuf;
+
+use crate::math::Vec2D;
+
+/// Minimum difference in luminance between two neighbouring pixels to count as an edge.
+const EDGE_THRESHOLD: u8 = 24;
+
+/// Half the length of the window (in pixels) along which an edge has to run
+/// to be considered for snapping. This filters out text and noise while
+/// keeping window borders and UI element outlines.
+const SEGMENT_HALF_LENGTH: i32 = 24;
+
+/// Minimum width and height of a region to be suggested for cropping.
+const MIN_REGION_SIZE: i32 = 64;
+
+/// How far (in pixels) the borders of a region may be misaligned.
+const ALIGN_TOLERANCE: i32 = 3;
+
+/// Gaps up to this length don't interrupt a border, e.g. for anti-aliased corners.
+const MAX_SEGMENT_GAP: i32 = 2;
+
+/// Only the longest segments are considered when looking for regions.
+const MAX_SEGMENTS: usize = 200;
+
+struct Segment {
+    line: i32,
+    from: i32,
+    to: i32,
+}
+
+/// Lightweight edge map of the background image, used to snap shapes to
+/// window borders and UI element edges.
+pub struct EdgeMap {
+    width: i32,
+    height: i32,
+    // edge between pixel (x-1, y) and (x, y)
+    vertical: Vec<bool>,
+    // edge between pixel (x, y-1) and (x, y)
+    horizontal: Vec<bool>,
+    crop_suggestions: OnceCell<Vec<(Vec2D, Vec2D)>>,
+}
+
+impl EdgeMap {
+    pub fn from_pixbuf(image: &Pixbuf) -> Self {
+        let luminance = Self::luminance(image);
+        let width = image.width();
+        let height = image.height();
+
+        let mut vertical = vec![false; (width * height) as usize];
+        let mut horizontal = vec![false; (width * height) as usize];
+
+        for y in 0..height {
+            for x in 0..width {
+                let i = (y * width + x) as usize;
+                if x > 0 {
+                    vertical[i] = luminance[i].abs_diff(luminance[i - 1]) > EDGE_THRESHOLD;
+                }
+                if y > 0 {
+                    horizontal[i] =
+                        luminance[i].abs_diff(luminance[i - width as usize]) > EDGE_THRESHOLD;
+                }
+            }
+        }
+
+        Self {
+            width,
+            height,
+            vertical,
+            horizontal,
+            crop_suggestions: OnceCell::new(),
+        }
+    }
+
+    fn luminance(image: &Pixbuf) -> Vec<u8> {
+        let bytes = image.read_pixel_bytes();
+        let stride = image.rowstride() as usize;
+        let channels = image.n_channels() as usize;
+        let width = image.width() as usize;
+        let height = image.height() as usize;
+
+        let mut result = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let p = y * stride + x * channels;
+                let (r, g, b) = (bytes[p] as u32, bytes[p + 1] as u32, bytes[p + 2] as u32);
+                // integer approximation of https://en.wikipedia.org/wiki/Luma_(video)
+                result.push(((r * 54 + g * 183 + b * 19) >> 8) as u8);
+            }
+        }
+        result
+    }
+
+    /// Snap `pos` to the closest edge within `distance` pixels, per axis.
+    pub fn snap(&self, pos: Vec2D, distance: f32) -> Vec2D {
+        let x = pos.x.round() as i32;
+        let y = pos.y.round() as i32;
+        let distance = distance.round() as i32;
+
+        Vec2D::new(
+            self.find_edge(x, y, distance, true)
+                .map(|x| x as f32)
+                .unwrap_or(pos.x),
+            self.find_edge(y, x, distance, false)
+                .map(|y| y as f32)
+                .unwrap_or(pos.y),
+        )
+    }
+
+    // `along` is the coordinate to snap, `across` the other one
+    fn find_edge(&self, along: i32, across: i32, distance: i32, vertical: bool) -> Option<i32> {
+        let (limit_along, limit_across) = if vertical {
+            (self.width, self.height)
+        } else {
+            (self.height, self.width)
+        };
+
+        let mut best: Option<(i32, i32)> = None;
+        for offset in 0..=distance {
+            for candidate in [along - offset, along + offset] {
+                if candidate <= 0 || candidate >= limit_along {
+                    continue;
+                }
+
+                let from = (across - SEGMENT_HALF_LENGTH).max(0);
+                let to = (across + SEGMENT_HALF_LENGTH).min(limit_across - 1);
+                let score = (from..=to)
+                    .filter(|&a| self.edge(vertical, candidate, a))
+                    .count() as i32;
+
+                // require at least half the window to be an edge, prefer closer candidates on ties
+                if score > SEGMENT_HALF_LENGTH && best.is_none_or(|(_, s)| score > s) {
+                    best = Some((candidate, score));
+                }
+            }
+        }
+        best.map(|(candidate, _)| candidate)
+    }
+
+    // whether there is an edge on the `line`-th column (vertical) or row at position `t`
+    fn edge(&self, vertical: bool, line: i32, t: i32) -> bool {
+        if vertical {
+            self.vertical[(t * self.width + line) as usize]
+        } else {
+            self.horizontal[(line * self.width + t) as usize]
+        }
+    }
+
+    /// Regions that look like windows or other framed content, largest first.
+    /// The analysis runs on first use and is cached afterwards.
+    pub fn crop_suggestions(&self) -> &[(Vec2D, Vec2D)] {
+        self.crop_suggestions.get_or_init(|| self.detect_regions())
+    }
+
+    fn detect_regions(&self) -> Vec<(Vec2D, Vec2D)> {
+        let mut horizontal = self.segments(false);
+        horizontal.sort_by_key(|s| s.from - s.to);
+        horizontal.truncate(MAX_SEGMENTS);
+
+        let mut regions: Vec<(Vec2D, Vec2D)> = Vec::new();
+        let mut push_region = |left: i32, top: i32, right: i32, bottom: i32| {
+            let region = (
+                Vec2D::new(left as f32, top as f32),
+                Vec2D::new((right - left) as f32, (bottom - top) as f32),
+            );
+            let duplicate = regions.iter().any(|(pos, size)| {
+                (pos.x - region.0.x).abs() <= ALIGN_TOLERANCE as f32
+                    && (pos.y - region.0.y).abs() <= ALIGN_TOLERANCE as f32
+                    && (size.x - region.1.x).abs() <= ALIGN_TOLERANCE as f32
+                    && (size.y - region.1.y).abs() <= ALIGN_TOLERANCE as f32
+            });
+            if !duplicate {
+                regions.push(region);
+            }
+        };
+
+        // windows: a top and bottom border of the same extent, connected by left and right borders
+        for top in &horizontal {
+            for bottom in &horizontal {
+                if bottom.line - top.line < MIN_REGION_SIZE
+                    || (top.from - bottom.from).abs() > ALIGN_TOLERANCE
+                    || (top.to - bottom.to).abs() > ALIGN_TOLERANCE
+                {
+                    continue;
+                }
+
+                let left = top.from.min(bottom.from);
+                let right = top.to.max(bottom.to);
+                if self.coverage(true, left, top.line, bottom.line) < 0.8
+                    || self.coverage(true, right, top.line, bottom.line) < 0.8
+                {
+                    continue;
+                }
+
+                push_region(left, top.line, right, bottom.line);
+            }
+        }
+
+        // content: everything that is not a uniform border
+        if let Some((left, top, right, bottom)) = self.edge_bounds() {
+            if (right - left >= MIN_REGION_SIZE && bottom - top >= MIN_REGION_SIZE)
+                && (left > 0 || top > 0 || right < self.width || bottom < self.height)
+            {
+                push_region(left, top, right, bottom);
+            }
+        }
+
+        regions.sort_by(|a, b| (b.1.x * b.1.y).total_cmp(&(a.1.x * a.1.y)));
+        regions
+    }
+
+    fn segments(&self, vertical: bool) -> Vec<Segment> {
+        let (lines, length) = if vertical {
+            (self.width, self.height)
+        } else {
+            (self.height, self.width)
+        };
+
+        let mut result = Vec::new();
+        for line in 1..lines {
+            let mut start: Option<i32> = None;
+            let mut last = 0;
+            for t in 0..length {
+                if !self.edge(vertical, line, t) {
+                    continue;
+                }
+                match start {
+                    Some(from) if t - last > MAX_SEGMENT_GAP + 1 => {
+                        if last + 1 - from >= MIN_REGION_SIZE {
+                            result.push(Segment {
+                                line,
+                                from,
+                                to: last + 1,
+                            });
+                        }
+                        start = Some(t);
+                    }
+                    None => start = Some(t),
+                    _ => {}
+                }
+                last = t;
+            }
+            if let Some(from) = start {
+                if last + 1 - from >= MIN_REGION_SIZE {
+                    result.push(Segment {
+                        line,
+                        from,
+                        to: last + 1,
+                    });
+                }
+            }
+        }
+        result
+    }
+
+    // share of positions in [from, to) covered by an edge on a line close to `line`
+    fn coverage(&self, vertical: bool, line: i32, from: i32, to: i32) -> f32 {
+        let lines = if vertical { self.width } else { self.height };
+        ((line - ALIGN_TOLERANCE).max(1)..=(line + ALIGN_TOLERANCE).min(lines - 1))
+            .map(|l| (from..to).filter(|&t| self.edge(vertical, l, t)).count())
+            .max()
+            .map(|count| count as f32 / (to - from).max(1) as f32)
+            .unwrap_or(0.0)
+    }
+
+    // bounding box (left, top, right, bottom) of all detected edges
+    fn edge_bounds(&self) -> Option<(i32, i32, i32, i32)> {
+        let mut bounds: Option<(i32, i32, i32, i32)> = None;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = (y * self.width + x) as usize;
+                if !self.vertical[i] && !self.horizontal[i] {
+                    continue;
+                }
+                bounds = Some(match bounds {
+                    None => (x, y, x + 1, y + 1),
+                    Some((l, t, r, b)) => (l.min(x), t.min(y), r.max(x + 1), b.max(y + 1)),
+                });
+            }
+        }
+        bounds
+    }
+}
+
+/// Area left after cutting away margins of a single color, like letterboxing or the desktop
+/// around a window. Colors may differ from the corner pixel by `tolerance` per channel.
+/// Returns `None` if there are no such margins or the whole image is one color.
+pub fn trim_uniform_borders(image: &Pixbuf, tolerance: u8) -> Option<(Vec2D, Vec2D)> {
+    let bytes = image.read_pixel_bytes();
+    let stride = image.rowstride() as usize;
+    let channels = image.n_channels() as usize;
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let pixel = |x: usize, y: usize| {
+        let p = y * stride + x * channels;
+        [bytes[p], bytes[p + 1], bytes[p + 2]]
+    };
+    let matches = |x: usize, y: usize, reference: [u8; 3]| {
+        pixel(x, y)
+            .iter()
+            .zip(reference)
+            .all(|(a, b)| a.abs_diff(b) <= tolerance)
+    };
+
+    // top and left margins are compared to the top left corner, the others to the bottom right
+    let first = pixel(0, 0);
+    let last = pixel(width - 1, height - 1);
+
+    let top = (0..height).find(|&y| !(0..width).all(|x| matches(x, y, first)))?;
+    let bottom = (top..height)
+        .rev()
+        .find(|&y| !(0..width).all(|x| matches(x, y, last)))?
+        + 1;
+    let left = (0..width).find(|&x| !(top..bottom).all(|y| matches(x, y, first)))?;
+    let right = (left..width)
+        .rev()
+        .find(|&x| !(top..bottom).all(|y| matches(x, y, last)))?
+        + 1;
+
+    if left == 0 && top == 0 && right == width && bottom == height {
+        return None;
+    }
+    Some((
+        Vec2D::new(left as f32, top as f32),
+        Vec2D::new((right - left) as f32, (bottom - top) as f32),
+    ))
+}
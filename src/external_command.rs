@@ -0,0 +1,69 @@
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use anyhow::{anyhow, Context, Result};
+
+/// A temporary file holding the image for a command, which is removed once this is dropped.
+pub struct TempFile {
+    pub path: PathBuf,
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Runs `command` by `sh -c` with the `png` image and waits for it to exit. `{path}` in the
+/// command is replaced by a temporary file holding the image, which is returned to read
+/// what the command changed, without it the image is piped to stdin.
+pub fn run(command: &str, png: &[u8]) -> Result<Option<TempFile>> {
+    let mut command = command.to_string();
+    let mut temp_file = None;
+    if command.contains("{path}") {
+        let path = env::temp_dir().join(format!(
+            "satty-{}.png",
+            chrono::Local::now().format("%Y%m%d-%H%M%S%.3f")
+        ));
+        fs::write(&path, png).with_context(|| format!("Failed to write {path:?}"))?;
+        command = command.replace("{path}", &shell_quote(&path.to_string_lossy()));
+        temp_file = Some(TempFile { path });
+    }
+    let pipe = temp_file.is_none();
+
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .stdin(if pipe { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run '{command}'"))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // the command may exit without reading the image
+        match stdin.write_all(png) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e.into()),
+            _ => {}
+        }
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "'{command}' failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(temp_file)
+}
+
+/// Quotes `text` to pass it as a single argument to `sh -c`.
+pub fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
@@ -0,0 +1,195 @@
+use std::fmt::Display;
+
+use anyhow::{anyhow, Result};
+use glow::HasContext;
+
+/// Color vision deficiencies the canvas can be previewed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorVision {
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+impl Display for ColorVision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Deuteranopia => write!(f, "Deuteranopia"),
+            Self::Protanopia => write!(f, "Protanopia"),
+            Self::Tritanopia => write!(f, "Tritanopia"),
+        }
+    }
+}
+
+impl ColorVision {
+    /// The simulation following `current`, `None` after the last one turns it off.
+    pub fn cycle(current: Option<Self>) -> Option<Self> {
+        match current {
+            None => Some(Self::Deuteranopia),
+            Some(Self::Deuteranopia) => Some(Self::Protanopia),
+            Some(Self::Protanopia) => Some(Self::Tritanopia),
+            Some(Self::Tritanopia) => None,
+        }
+    }
+
+    // Machado, Oliveira and Fernandes (2009) at full severity, row by row, for linear RGB
+    fn matrix(self) -> [f32; 9] {
+        match self {
+            Self::Deuteranopia => [
+                0.367_322, 0.860_646, -0.227_968, //
+                0.280_085, 0.672_501, 0.047_413, //
+                -0.011_820, 0.042_940, 0.968_881,
+            ],
+            Self::Protanopia => [
+                0.152_286, 1.052_583, -0.204_868, //
+                0.114_503, 0.786_281, 0.099_216, //
+                -0.003_882, -0.048_116, 1.051_998,
+            ],
+            Self::Tritanopia => [
+                1.255_528, -0.076_749, -0.178_779, //
+                -0.078_411, 0.930_809, 0.147_602, //
+                0.004_733, 0.691_367, 0.303_900,
+            ],
+        }
+    }
+}
+
+// a single triangle covering the screen, without any vertex buffer
+const VERTEX_SHADER: &str = r#"
+out vec2 uv;
+void main() {
+    vec2 pos = vec2(float((gl_VertexID & 1) << 2) - 1.0, float((gl_VertexID & 2) << 1) - 1.0);
+    // images rendered by femtovg have their top row first
+    uv = vec2(pos.x, -pos.y) * 0.5 + 0.5;
+    gl_Position = vec4(pos, 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+in vec2 uv;
+out vec4 color;
+uniform sampler2D frame;
+// uploaded row by row, so multiplying from the left applies the rows
+uniform mat3 simulation;
+
+vec3 to_linear(vec3 c) {
+    return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+}
+
+vec3 to_srgb(vec3 c) {
+    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+}
+
+void main() {
+    vec4 texel = texture(frame, uv);
+    // the frame has premultiplied alpha
+    vec3 rgb = texel.a > 0.0 ? texel.rgb / texel.a : vec3(0.0);
+    rgb = clamp(to_linear(rgb) * simulation, 0.0, 1.0);
+    color = vec4(to_srgb(rgb) * texel.a, texel.a);
+}
+"#;
+
+/// Draws a frame rendered into a texture onto the screen, as seen with a color vision
+/// deficiency.
+pub struct ColorVisionPass {
+    gl: glow::Context,
+    framebuffer: glow::NativeFramebuffer,
+    program: glow::NativeProgram,
+    vertex_array: glow::NativeVertexArray,
+}
+
+impl ColorVisionPass {
+    /// # Safety
+    /// The GL context of the canvas has to be current.
+    pub unsafe fn new(gl: glow::Context, framebuffer: glow::NativeFramebuffer) -> Result<Self> {
+        let header = if gl.version().is_embedded {
+            "#version 300 es\nprecision mediump float;\n"
+        } else {
+            "#version 150\n"
+        };
+
+        let program = gl.create_program().map_err(|e| anyhow!(e))?;
+        let mut shaders = Vec::new();
+        for (kind, source) in [
+            (glow::VERTEX_SHADER, VERTEX_SHADER),
+            (glow::FRAGMENT_SHADER, FRAGMENT_SHADER),
+        ] {
+            let shader = gl.create_shader(kind).map_err(|e| anyhow!(e))?;
+            gl.shader_source(shader, &format!("{header}{source}"));
+            gl.compile_shader(shader);
+            if !gl.get_shader_compile_status(shader) {
+                let log = gl.get_shader_info_log(shader);
+                gl.delete_shader(shader);
+                gl.delete_program(program);
+                return Err(anyhow!("Cannot compile color vision shader: {log}"));
+            }
+            gl.attach_shader(program, shader);
+            shaders.push(shader);
+        }
+        gl.link_program(program);
+        for shader in shaders {
+            gl.detach_shader(program, shader);
+            gl.delete_shader(shader);
+        }
+        if !gl.get_program_link_status(program) {
+            let log = gl.get_program_info_log(program);
+            gl.delete_program(program);
+            return Err(anyhow!("Cannot link color vision shader: {log}"));
+        }
+
+        // core profiles can't draw without a vertex array, even an empty one
+        let vertex_array = gl.create_vertex_array().map_err(|e| anyhow!(e))?;
+
+        Ok(Self {
+            gl,
+            framebuffer,
+            program,
+            vertex_array,
+        })
+    }
+
+    /// # Safety
+    /// The GL context of the canvas has to be current and `frame` a texture of it.
+    pub unsafe fn draw(
+        &self,
+        frame: glow::NativeTexture,
+        vision: ColorVision,
+        width: u32,
+        height: u32,
+    ) {
+        let gl = &self.gl;
+        gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.framebuffer));
+        gl.viewport(0, 0, width as i32, height as i32);
+        gl.disable(glow::BLEND);
+        gl.disable(glow::SCISSOR_TEST);
+        gl.disable(glow::STENCIL_TEST);
+        gl.disable(glow::CULL_FACE);
+        gl.color_mask(true, true, true, true);
+
+        gl.use_program(Some(self.program));
+        gl.active_texture(glow::TEXTURE0);
+        gl.bind_texture(glow::TEXTURE_2D, Some(frame));
+        gl.uniform_1_i32(gl.get_uniform_location(self.program, "frame").as_ref(), 0);
+        gl.uniform_matrix_3_f32_slice(
+            gl.get_uniform_location(self.program, "simulation").as_ref(),
+            false,
+            &vision.matrix(),
+        );
+        gl.bind_vertex_array(Some(self.vertex_array));
+        gl.draw_arrays(glow::TRIANGLES, 0, 3);
+
+        gl.bind_vertex_array(None);
+        gl.bind_texture(glow::TEXTURE_2D, None);
+        gl.use_program(None);
+    }
+}
+
+impl Drop for ColorVisionPass {
+    // only dropped together with the canvas, while its context is current
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.delete_vertex_array(self.vertex_array);
+            self.gl.delete_program(self.program);
+        }
+    }
+}
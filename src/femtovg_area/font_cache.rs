@@ -0,0 +1,50 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use fontconfig::Fontconfig;
+use serde_derive::{Deserialize, Serialize};
+use xdg::BaseDirectories;
+
+const CACHE_FILE: &str = "font.json";
+
+/// The file fontconfig found for the configured font. Initializing fontconfig scans every
+/// installed font, which is a noticeable part of the startup.
+#[derive(Serialize, Deserialize)]
+struct CachedFont {
+    family: String,
+    style: Option<String>,
+    path: PathBuf,
+}
+
+/// Path of the font file for `family` and `style`, from the cache if it is still there.
+pub fn find(family: &str, style: Option<&str>) -> Result<PathBuf> {
+    let dirs = BaseDirectories::with_prefix(env!("CARGO_PKG_NAME"));
+    let cached = dirs
+        .find_cache_file(CACHE_FILE)
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str::<CachedFont>(&json).ok())
+        .filter(|c| c.family == family && c.style.as_deref() == style && c.path.exists());
+    if let Some(cached) = cached {
+        return Ok(cached.path);
+    }
+
+    let font = CachedFont {
+        family: family.to_string(),
+        style: style.map(String::from),
+        path: Fontconfig::new()
+            .ok_or_else(|| anyhow!("Error while initializing fontconfig"))?
+            .find(family, style)
+            .ok_or_else(|| anyhow!("Can not find font"))?
+            .path,
+    };
+    if let Err(e) = store(&dirs, &font) {
+        log::debug!("Cannot cache the font path: {e}");
+    }
+    Ok(font.path)
+}
+
+fn store(dirs: &BaseDirectories, font: &CachedFont) -> Result<()> {
+    let path = dirs.place_cache_file(CACHE_FILE)?;
+    fs::write(path, serde_json::to_string(font)?)?;
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use std::ffi::c_void;
+
+use anyhow::{anyhow, Result};
+use femtovg::{
+    imgref::ImgVec, renderer::OpenGl, rgb::RGBA8, Canvas, Color, ImageFlags, Paint, Path,
+    PixelFormat, RenderTarget,
+};
+use gdk_pixbuf::Pixbuf;
+use khronos_egl as egl;
+
+use crate::{adjustments::Adjustments, math::Vec2D, tools::Drawable};
+
+use super::imp::{load_font, FemtoVgAreaMut};
+
+type Egl = egl::DynamicInstance<egl::EGL1_4>;
+
+/// Composites `annotations` onto `image` without a window, in a surfaceless EGL context.
+pub fn render_annotations(
+    image: &Pixbuf,
+    annotations: &[Box<dyn Drawable>],
+) -> Result<ImgVec<RGBA8>> {
+    // SAFETY: libEGL is only used through the loaded instance
+    let egl = unsafe { Egl::load_required() }.map_err(|e| anyhow!("Cannot load EGL: {e}"))?;
+    // SAFETY: the default display doesn't refer to any native display
+    let display =
+        unsafe { egl.get_display(egl::DEFAULT_DISPLAY) }.ok_or(anyhow!("No EGL display"))?;
+    egl.initialize(display)?;
+
+    let result = render_in_context(&egl, display, image, annotations);
+    let _ = egl.terminate(display);
+    result
+}
+
+fn render_in_context(
+    egl: &Egl,
+    display: egl::Display,
+    image: &Pixbuf,
+    annotations: &[Box<dyn Drawable>],
+) -> Result<ImgVec<RGBA8>> {
+    egl.bind_api(egl::OPENGL_API)?;
+    let config = egl
+        .choose_first_config(display, &[egl::RENDERABLE_TYPE, egl::OPENGL_BIT, egl::NONE])?
+        .ok_or(anyhow!("No EGL config for OpenGL"))?;
+    let context = egl.create_context(display, config, None, &[egl::NONE])?;
+    // without a surface, everything is rendered into images of the canvas
+    egl.make_current(display, None, None, Some(context))?;
+
+    let result = composite(egl, image, annotations);
+    egl.make_current(display, None, None, None)?;
+    egl.destroy_context(display, context)?;
+    result
+}
+
+// the canvas is dropped before the context is released
+fn composite(
+    egl: &Egl,
+    image: &Pixbuf,
+    annotations: &[Box<dyn Drawable>],
+) -> Result<ImgVec<RGBA8>> {
+    // SAFETY: the context was made current before
+    let renderer = unsafe {
+        OpenGl::new_from_function(|name| {
+            egl.get_proc_address(name)
+                .map_or(std::ptr::null(), |f| f as *const c_void)
+        })
+    }?;
+    let mut canvas = Canvas::new(renderer)?;
+    let (width, height) = (image.width() as u32, image.height() as u32);
+    canvas.set_size(width, height, 1.0);
+    let font = load_font(&mut canvas);
+
+    let background =
+        FemtoVgAreaMut::upload_background_image(&mut canvas, image, Adjustments::default())?;
+    let target = canvas.create_image_empty(
+        width as usize,
+        height as usize,
+        PixelFormat::Rgba8,
+        ImageFlags::empty(),
+    )?;
+    canvas.set_render_target(RenderTarget::Image(target));
+    canvas.clear_rect(0, 0, width, height, Color::rgba(0, 0, 0, 0));
+
+    let bounds = (Vec2D::zero(), Vec2D::new(width as f32, height as f32));
+    let mut path = Path::new();
+    path.rect(0.0, 0.0, bounds.1.x, bounds.1.y);
+    canvas.fill_path(
+        &path,
+        &Paint::image(background, 0.0, 0.0, bounds.1.x, bounds.1.y, 0.0, 1.0),
+    );
+    for annotation in annotations {
+        annotation.draw(&mut canvas, font, bounds)?;
+    }
+    canvas.flush();
+    Ok(canvas.screenshot()?)
+}
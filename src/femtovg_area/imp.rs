@@ -1,9 +1,12 @@
-use anyhow::{anyhow, Error, Result};
+use anyhow::{Error, Result};
 use glow::HasContext;
 use std::{
-    cell::{RefCell, RefMut},
+    cell::{Cell, RefCell, RefMut},
+    collections::{HashMap, VecDeque},
     num::NonZeroU32,
     rc::Rc,
+    sync::mpsc::{SyncSender, TrySendError},
+    time::{Duration, Instant},
 };
 
 use femtovg::{
@@ -12,38 +15,129 @@ use femtovg::{
     rgb::{RGB, RGBA, RGBA8},
     Canvas, FontId, ImageFlags, ImageId, ImageSource, Paint, Path, PixelFormat, Transform2D,
 };
-use fontconfig::Fontconfig;
-use gdk_pixbuf::Pixbuf;
+use gdk_pixbuf::{InterpType, Pixbuf};
 use gtk::{glib, prelude::*, subclass::prelude::*};
 use relm4::{gtk, Sender};
 use resource::resource;
 
 use crate::{
+    adjustments::Adjustments,
+    annotations::{self, AnnotationSpec},
     configuration::Action,
-    math::{rect_ensure_in_bounds, rect_round, Vec2D},
+    layers::{LayerInfo, Layers},
+    math::{
+        rect_around_points, rect_contains, rect_ensure_in_bounds, rect_ensure_positive_size,
+        rect_intersects, rect_round, Vec2D,
+    },
+    profiling,
     sketch_board::SketchBoardInput,
-    tools::{CropTool, Drawable, Tool},
+    tools::{self, CropTool, Drawable, Tool, Tools},
+    undo_tree::UndoTree,
     APP_CONFIG,
 };
 
+use super::{
+    color_vision::{ColorVision, ColorVisionPass},
+    font_cache,
+};
+
+/// Length of the longer side of the minimap, in physical pixels.
+const MINIMAP_SIZE: f32 = 200.0;
+const MINIMAP_MARGIN: f32 = 10.0;
+
+/// Edge length of a checkerboard square behind transparent images, in physical pixels.
+const CHECKERBOARD_SIZE: f32 = 8.0;
+
+/// How far next to an annotation a click still selects it, in physical pixels.
+const SELECTION_MARGIN: f32 = 6.0;
+/// Offset of a duplicated annotation from the original, in image pixels.
+const DUPLICATE_OFFSET: Vec2D = Vec2D { x: 10.0, y: 10.0 };
+/// Nudges or resizes following each other within this time are undone together.
+const CHANGE_BURST_TIMEOUT: Duration = Duration::from_millis(1000);
+/// Image pixels along each side of the pixel inspector, odd so that one is in the center.
+const INSPECTOR_PIXELS: i32 = 11;
+/// Size of a magnified pixel and distance of the inspector from the cursor, in physical pixels.
+const INSPECTOR_CELL: f32 = 12.0;
+const INSPECTOR_OFFSET: f32 = 24.0;
+const INSPECTOR_FONT_SIZE: f32 = 14.0;
+/// Share of the canvas a rectangle fills when zooming to it, leaving a margin around it.
+const ZOOM_TO_RECT_FILL: f32 = 0.9;
+/// Maximum size of the thumbnails in the layers panel, in pixels.
+const THUMBNAIL_SIZE: Vec2D = Vec2D { x: 48.0, y: 32.0 };
+/// How far strokes, arrow heads and text may reach past the bounds of a drawable, in image
+/// pixels at an annotation size factor of 1.
+const DAMAGE_MARGIN: f32 = 64.0;
+
+/// Part of the frame that changed since it was last rendered.
+#[derive(Debug, Clone, Copy)]
+enum Damage {
+    Nothing,
+    // position and size in image coordinates
+    Area((Vec2D, Vec2D)),
+    Full,
+}
+
+impl Damage {
+    fn union(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Full, _) | (_, Self::Full) => Self::Full,
+            (Self::Nothing, d) | (d, Self::Nothing) => d,
+            (Self::Area(a), Self::Area(b)) => {
+                Self::Area(rect_around_points(&[a.0, a.0 + a.1, b.0, b.0 + b.1]))
+            }
+        }
+    }
+}
+
+// the last frame, kept offscreen so that only its damaged part has to be rendered again
+struct Frame {
+    image_id: ImageId,
+    size: (u32, u32),
+    // scale and offset it was rendered with
+    transform: (f32, Vec2D),
+}
+
+// how long exporting the steps waits for room in the queue of the encoder
+const STEPS_EXPORT_RETRY: Duration = Duration::from_millis(20);
+
+// steps of the history still to be exported
+struct StepsExportJob {
+    steps: VecDeque<Option<usize>>,
+    // rendered, waiting for room in the queue of the encoder
+    pending: Option<ImgVec<RGBA8>>,
+    frames: SyncSender<ImgVec<RGBA8>>,
+}
+
 #[derive(Default)]
 pub struct FemtoVGArea {
     canvas: RefCell<Option<femtovg::Canvas<femtovg::renderer::OpenGl>>>,
     font: RefCell<Option<FontId>>,
     inner: RefCell<Option<FemtoVgAreaMut>>,
     request_render: RefCell<Option<Vec<Action>>>,
+    steps_export: RefCell<Option<StepsExportJob>>,
+    request_drag_image: Cell<bool>,
+    // exported instead of the crop by the requested render
+    render_region: RefCell<Option<(Vec2D, Vec2D)>>,
     sender: RefCell<Option<Sender<SketchBoardInput>>>,
+    // created with the canvas, `None` if the shader is not supported
+    color_vision_pass: RefCell<Option<ColorVisionPass>>,
 }
 
 pub struct FemtoVgAreaMut {
+    // as loaded, the undo history may replace it with the one shown
+    loaded_image: Pixbuf,
     background_image: Pixbuf,
+    // read once, reading copies the pixels of pixbufs that aren't immutable
+    background_pixels: glib::Bytes,
     background_image_id: Option<femtovg::ImageId>,
+    // applied when uploading the background image, which itself stays unchanged
+    adjustments: Adjustments,
+    checkerboard_image_id: Option<femtovg::ImageId>,
     active_tool: Rc<RefCell<dyn Tool>>,
     crop_tool: Rc<RefCell<CropTool>>,
     scale_factor: f32,
     offset: Vec2D,
-    drawables: Vec<Box<dyn Drawable>>,
-    redo_stack: Vec<Box<dyn Drawable>>,
+    history: UndoTree,
     zoom_scale: f32,
     last_scale: f32,
     pointer_offset: Vec2D,
@@ -51,6 +145,30 @@ pub struct FemtoVgAreaMut {
     drag_offset: Vec2D,
     is_drag: bool,
     is_reset: bool,
+    canvas_size: Vec2D,
+    beautify: bool,
+    // id of the selected annotation in the history
+    selection: Option<usize>,
+    // annotation id and history node of the last nudge or resize, to merge bursts of them
+    last_change: Option<(usize, usize, Instant)>,
+    layers: Rc<RefCell<Layers>>,
+    // annotations are hidden on screen while peeking at the original image
+    peek: bool,
+    // erased versions of annotations, shown instead of them until the eraser is released
+    erased: HashMap<usize, Box<dyn Drawable>>,
+    // simulated on screen only, exports keep the original colors
+    color_vision: Option<ColorVision>,
+    // image position magnified by the pixel inspector, if shown
+    inspector: Option<Vec2D>,
+    // region being chosen for exporting only that part
+    export_region: Option<(Vec2D, Vec2D)>,
+    frame: Option<Frame>,
+    // rendered again with the next frame, at least where the active tool drew
+    damage: Damage,
+    // the image with the visible annotations at its resolution, drawn onto the frame
+    // instead of every annotation until they change
+    composite: Option<ImageId>,
+    composite_valid: bool,
 }
 
 #[glib::object_subclass]
@@ -65,6 +183,8 @@ impl ObjectImpl for FemtoVGArea {
         self.parent_constructed();
         let area = self.obj();
         area.set_has_stencil_buffer(true);
+        // a translucent scrim shows what is behind the window
+        area.set_has_alpha(APP_CONFIG.read().scrim_color().is_some());
         area.queue_render();
     }
 }
@@ -75,6 +195,7 @@ impl WidgetImpl for FemtoVGArea {
     }
     fn unrealize(&self) {
         self.obj().make_current();
+        self.color_vision_pass.borrow_mut().take();
         self.canvas.borrow_mut().take();
         self.parent_unrealize();
     }
@@ -108,20 +229,23 @@ impl GLAreaImpl for FemtoVGArea {
         let mut bc = self.canvas.borrow_mut();
         let canvas = bc.as_mut().unwrap(); // this unwrap is safe as long as we call "ensure_canvas" before
         let font = self.font.borrow().unwrap(); // this unwrap is safe as long as we call "ensure_canvas" before
+        tools::delete_released_images(canvas);
         let mut actions = self.request_render.borrow_mut();
 
         // if we got requested to render a frame
         if let Some(a) = actions.take() {
             // render image
+            let annotations_only = a.contains(&Action::SaveAnnotationsToFileAs);
+            let region = self.render_region.borrow_mut().take();
             let image = match self
                 .inner()
                 .as_mut()
                 .expect("Did you call init before using FemtoVgArea?")
-                .render_native_resolution(canvas, font)
+                .render_native_resolution(canvas, font, annotations_only, region)
             {
                 Ok(t) => t,
                 Err(e) => {
-                    println!("Error while rendering image: {e}");
+                    log::error!("Error while rendering image: {e}");
                     return glib::Propagation::Stop;
                 }
             };
@@ -136,34 +260,81 @@ impl GLAreaImpl for FemtoVGArea {
             // reset request
             *actions = None;
         }
+        if let Some(job) = self.steps_export.take() {
+            self.export_next_step(job, canvas, font);
+        }
+        if self.request_drag_image.take() {
+            match self
+                .inner()
+                .as_mut()
+                .expect("Did you call init before using FemtoVgArea?")
+                .render_native_resolution(canvas, font, false, None)
+            {
+                Ok(image) => self
+                    .sender
+                    .borrow()
+                    .as_ref()
+                    .expect("Did you call init before using FemtoVgArea?")
+                    .emit(SketchBoardInput::DragImageRendered(image)),
+                Err(e) => log::error!("Error while rendering image: {e}"),
+            }
+        }
         if let Err(e) = self
             .inner()
             .as_mut()
             .expect("Did you call init before using FemtoVgArea?")
-            .render_framebuffer(canvas, font)
+            .render_framebuffer(canvas, font, self.color_vision_pass.borrow().as_ref())
         {
-            println!("Error rendering to framebuffer: {e}");
+            log::error!("Error rendering to framebuffer: {e}");
         }
+        profiling::first_frame_rendered();
         glib::Propagation::Stop
     }
 }
+
+/// Adds the configured font to `canvas`, or the bundled one if it can't be loaded.
+pub(super) fn load_font(canvas: &mut Canvas<renderer::OpenGl>) -> FontId {
+    let app_config = APP_CONFIG.read();
+    app_config
+        .font()
+        .family()
+        .map(|font| {
+            let path = font_cache::find(font, app_config.font().style())?;
+            Ok(canvas.add_font(path)?)
+        })
+        .transpose()
+        .unwrap_or_else(|e: Error| {
+            log::warn!("Error while loading font. Using default font: {e}");
+            None
+        })
+        .unwrap_or_else(|| {
+            canvas
+                .add_font_mem(&resource!("src/assets/Roboto-Regular.ttf"))
+                .expect("Cannot add font")
+        })
+}
+
 impl FemtoVGArea {
     pub fn init(
         &self,
         sender: Sender<SketchBoardInput>,
         crop_tool: Rc<RefCell<CropTool>>,
         active_tool: Rc<RefCell<dyn Tool>>,
+        layers: Rc<RefCell<Layers>>,
         background_image: Pixbuf,
     ) {
         self.inner().replace(FemtoVgAreaMut {
+            loaded_image: background_image.clone(),
+            background_pixels: background_image.read_pixel_bytes(),
             background_image,
             background_image_id: None,
+            adjustments: Adjustments::default(),
+            checkerboard_image_id: None,
             active_tool,
             crop_tool,
             scale_factor: 1.0,
             offset: Vec2D::zero(),
-            drawables: Vec::new(),
-            redo_stack: Vec::new(),
+            history: UndoTree::default(),
             zoom_scale: 0.0,
             pointer_offset: Vec2D::zero(),
             last_offset: Vec2D::zero(),
@@ -171,6 +342,20 @@ impl FemtoVGArea {
             last_scale: 0.0,
             is_drag: false,
             is_reset: false,
+            canvas_size: Vec2D::zero(),
+            beautify: APP_CONFIG.read().beautify(),
+            selection: None,
+            last_change: None,
+            erased: HashMap::new(),
+            layers,
+            peek: false,
+            color_vision: None,
+            inspector: None,
+            export_region: None,
+            frame: None,
+            damage: Damage::Full,
+            composite: None,
+            composite_valid: false,
         });
         self.sender.borrow_mut().replace(sender);
     }
@@ -182,40 +367,9 @@ impl FemtoVGArea {
             self.canvas.borrow_mut().replace(c);
         }
 
-        let app_config = APP_CONFIG.read();
-        let font = app_config
-            .font()
-            .family()
-            .map(|font| {
-                let font = Fontconfig::new()
-                    .ok_or_else(|| anyhow!("Error while initializing fontconfig"))?
-                    .find(font, app_config.font().style())
-                    .ok_or_else(|| anyhow!("Can not find font"))?;
-                let font_id = self
-                    .canvas
-                    .borrow_mut()
-                    .as_mut()
-                    .unwrap() // this unwrap is safe because it gets placed above
-                    .add_font(font.path)?;
-                Ok(font_id)
-            })
-            .transpose()
-            .unwrap_or_else(|e: Error| {
-                println!("Error while loading font. Using default font: {e}");
-                None
-            });
-        if let Some(font) = font {
-            self.font.borrow_mut().replace(font);
-        } else {
-            self.font.borrow_mut().replace(
-                self.canvas
-                    .borrow_mut()
-                    .as_mut()
-                    .unwrap() // this unwrap is safe because it gets placed above
-                    .add_font_mem(&resource!("src/assets/Roboto-Regular.ttf"))
-                    .expect("Cannot add font"),
-            );
-        }
+        // this unwrap is safe because it gets placed above
+        let font = load_font(self.canvas.borrow_mut().as_mut().unwrap());
+        self.font.borrow_mut().replace(font);
     }
 
     fn setup_canvas(&self) -> Result<femtovg::Canvas<femtovg::renderer::OpenGl>> {
@@ -238,6 +392,16 @@ impl FemtoVGArea {
             (renderer, glow::NativeFramebuffer(id))
         };
         renderer.set_screen_target(Some(fbo));
+
+        // SAFETY: the context is current, as for the renderer above
+        let pass =
+            unsafe { ColorVisionPass::new(glow::Context::from_loader_function(LOAD_FN), fbo) };
+        match pass {
+            Ok(pass) => {
+                self.color_vision_pass.borrow_mut().replace(pass);
+            }
+            Err(e) => log::warn!("Color vision simulation not available: {e}"),
+        }
         Ok(Canvas::new(renderer)?)
     }
 
@@ -245,9 +409,84 @@ impl FemtoVGArea {
         self.inner.borrow_mut()
     }
     pub fn request_render(&self, actions: &[Action]) {
+        self.render_region.borrow_mut().take();
         self.request_render.borrow_mut().replace(actions.into());
         self.obj().queue_render();
     }
+    pub fn request_drag_image(&self) {
+        self.request_drag_image.set(true);
+        self.obj().queue_render();
+    }
+    /// Renders only `region` of the image for `actions`, regardless of the crop.
+    pub fn request_render_region(&self, actions: &[Action], region: (Vec2D, Vec2D)) {
+        self.request_render(actions);
+        self.render_region.borrow_mut().replace(region);
+    }
+    /// Renders every step of the history into `frames`, to show how the image was annotated.
+    /// One step is rendered per frame, so the window stays responsive while they are encoded.
+    /// Returns the number of steps.
+    pub fn export_steps(&self, frames: SyncSender<ImgVec<RGBA8>>) -> usize {
+        let steps: VecDeque<_> = self
+            .inner()
+            .as_ref()
+            .expect("Did you call init before using FemtoVgArea?")
+            .steps()
+            .into();
+        let count = steps.len();
+        self.steps_export.replace(Some(StepsExportJob {
+            steps,
+            pending: None,
+            frames,
+        }));
+        self.obj().queue_render();
+        count
+    }
+    // renders the next step unless the last one is still waiting for the encoder, and
+    // schedules the following one
+    fn export_next_step(
+        &self,
+        mut job: StepsExportJob,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        font: FontId,
+    ) {
+        if job.pending.is_none() {
+            if let Some(step) = job.steps.pop_front() {
+                match self
+                    .inner()
+                    .as_mut()
+                    .expect("Did you call init before using FemtoVgArea?")
+                    .render_step(canvas, font, step)
+                {
+                    Ok(frame) => job.pending = Some(frame),
+                    // the encoder notices the missing frames once the job is dropped
+                    Err(e) => {
+                        log::error!("Error while rendering the steps: {e}");
+                        return;
+                    }
+                }
+            }
+        }
+        let delay = match job.pending.take().map(|frame| job.frames.try_send(frame)) {
+            Some(Err(TrySendError::Full(frame))) => {
+                job.pending = Some(frame);
+                STEPS_EXPORT_RETRY
+            }
+            // the encoder failed and reports why
+            Some(Err(TrySendError::Disconnected(_))) => return,
+            _ => Duration::ZERO,
+        };
+        if job.pending.is_none() && job.steps.is_empty() {
+            return;
+        }
+        self.steps_export.replace(Some(job));
+        let area = self.obj().downgrade();
+        glib::timeout_add_local_once(delay, move || {
+            if let Some(area) = area.upgrade() {
+                area.queue_render();
+            }
+        });
+    }
+
     pub fn set_parent_sender(&self, sender: Sender<SketchBoardInput>) {
         self.sender.borrow_mut().replace(sender);
     }
@@ -255,66 +494,412 @@ impl FemtoVGArea {
 
 impl FemtoVgAreaMut {
     pub fn commit(&mut self, drawable: Box<dyn Drawable>) {
-        self.drawables.push(drawable);
-        self.redo_stack.clear();
+        self.history.commit(drawable);
+        self.composite_valid = false;
+    }
+
+    /// Commits `drawable` and selects it.
+    pub fn commit_selected(&mut self, drawable: Box<dyn Drawable>) {
+        self.commit(drawable);
+        self.selection = self.history.current();
     }
 
     pub fn undo(&mut self) -> bool {
-        match self.drawables.pop() {
-            Some(mut d) => {
-                // notify of the undo action
-                d.handle_undo();
+        let undone = self.history.undo();
+        self.restore_crop();
+        self.restore_image();
+        self.composite_valid = false;
+        undone
+    }
+    pub fn redo(&mut self) -> bool {
+        let redone = self.history.redo();
+        self.restore_crop();
+        self.restore_image();
+        self.composite_valid = false;
+        redone
+    }
+    pub fn switch_undo_branch(&mut self) -> bool {
+        let switched = self.history.switch_branch();
+        self.restore_crop();
+        self.restore_image();
+        self.composite_valid = false;
+        switched
+    }
+    /// Current node in the undo history, `None` if nothing is drawn.
+    pub fn history_position(&self) -> Option<usize> {
+        self.history.current()
+    }
+    /// Goes back to the loaded image, locked annotations are kept.
+    pub fn reset(&mut self) -> bool {
+        let layers = self.layers.borrow();
+        let any_undone = self
+            .history
+            .reset_keeping(|id| layers.is_locked(id), &self.loaded_image);
+        drop(layers);
+        self.restore_crop();
+        self.restore_image();
+        self.composite_valid = false;
+        any_undone
+    }
 
-                // push to redo stack
-                self.redo_stack.push(d);
-                true
-            }
+    /// Records the crop in the undo history once the crop tool finished changing it.
+    pub fn record_crop(&mut self) {
+        let crop_tool = self.crop_tool.borrow();
+        if crop_tool.is_changing() {
+            return;
+        }
+        let crop = crop_tool.get_crop();
+        let unchanged = match (crop, self.history.crop()) {
+            (Some(crop), Some(recorded)) => crop.same_area(recorded),
+            (crop, recorded) => crop.is_none() && recorded.is_none(),
+        };
+        if unchanged {
+            return;
+        }
+        let crop = crop.cloned();
+        drop(crop_tool);
+        self.history.commit_crop(crop);
+    }
+
+    // shows the crop of the current point in the undo history
+    fn restore_crop(&mut self) {
+        self.crop_tool
+            .borrow_mut()
+            .restore_crop(self.history.crop().cloned());
+    }
+
+    pub fn set_active_tool(&mut self, active_tool: Rc<RefCell<dyn Tool>>) {
+        self.active_tool = active_tool;
+    }
+
+    pub fn load_image(&mut self, pixbuf: &Pixbuf) {
+        self.history.clear();
+        self.set_base_image(pixbuf);
+        self.selection = None;
+        self.erased.clear();
+        self.composite_valid = false;
+    }
+
+    /// Replaces the image under the annotations, keeping them.
+    pub fn set_base_image(&mut self, pixbuf: &Pixbuf) {
+        self.loaded_image = pixbuf.clone();
+        self.restore_image();
+    }
+
+    /// Replaces the image with `pixbuf` as an undoable step, removing the annotations and
+    /// the crop of the previous one.
+    pub fn replace_image(&mut self, pixbuf: &Pixbuf) {
+        self.finish_erasing();
+        self.history.replace_image(pixbuf.clone());
+        self.selection = None;
+        self.restore_crop();
+        self.restore_image();
+    }
+
+    // shows the image of the current point in the undo history
+    fn restore_image(&mut self) {
+        let image = self.history.image().unwrap_or(&self.loaded_image);
+        if *image != self.background_image {
+            self.background_image = image.clone();
+            self.background_pixels = image.read_pixel_bytes();
+            self.invalidate_background();
+        }
+    }
+
+    // the background is uploaded again with the next frame, and what the annotations cached
+    // of it is captured again
+    fn invalidate_background(&mut self) {
+        if let Some(image_id) = self.background_image_id.take() {
+            tools::release_image(image_id);
+        }
+        self.history.clear_caches();
+        self.composite_valid = false;
+    }
+
+    /// The image as loaded, without adjustments.
+    pub fn background_image(&self) -> Pixbuf {
+        self.background_image.clone()
+    }
+
+    // `d` placed against the annotations it is attached to, if any
+    fn attached(&self, d: &dyn Drawable) -> Option<Box<dyn Drawable>> {
+        d.attach(&|id| self.history.get(id).and_then(|a| a.bounds()))
+    }
+
+    // topmost visible and unlocked annotation at `pos` that matches `filter`
+    fn hit_test(&self, pos: Vec2D, filter: impl Fn(&dyn Drawable) -> bool) -> Option<usize> {
+        let margin = SELECTION_MARGIN / self.scale_factor;
+        let layers = self.layers.borrow();
+        layers
+            .arrange(self.history.annotations())
+            .into_iter()
+            .filter(|&(id, _)| !layers.is_hidden(id) && !layers.is_locked(id))
+            .filter(|&(_, d)| filter(d))
+            .filter(|&(_, d)| {
+                self.attached(d)
+                    .as_deref()
+                    .unwrap_or(d)
+                    .bounds()
+                    .is_some_and(|b| rect_contains(b, pos, margin))
+            })
+            .map(|(id, _)| id)
+            .last()
+    }
+
+    /// The topmost annotation at `pos` that connectors can attach to, in image coordinates.
+    pub fn annotation_at(&self, pos: Vec2D) -> Option<usize> {
+        self.hit_test(pos, |d| d.get_tool_type() != Tools::Connector)
+    }
+
+    /// Selects the topmost annotation at `pos`, in image coordinates.
+    /// Returns whether the selection changed.
+    pub fn select_at(&mut self, pos: Vec2D) -> bool {
+        let hit = self.hit_test(pos, |_| true);
+        let changed = hit != self.selected();
+        self.selection = hit;
+        changed
+    }
+
+    /// Selects the annotation `id`, unless it is hidden or locked.
+    pub fn select(&mut self, id: usize) -> bool {
+        let layers = self.layers.borrow();
+        if layers.is_hidden(id) || layers.is_locked(id) || self.history.get(id).is_none() {
+            return false;
+        }
+        self.selection = Some(id);
+        true
+    }
+
+    pub fn clear_selection(&mut self) -> bool {
+        self.selection.take().is_some()
+    }
+
+    /// Copies the selected annotation and selects the copy.
+    pub fn duplicate_selection(&mut self) -> bool {
+        let Some(id) = self.selected() else {
+            return false;
+        };
+        self.selection = self.history.duplicate(id, DUPLICATE_OFFSET);
+        self.composite_valid = false;
+        true
+    }
+
+    fn delete(&mut self, id: usize) -> bool {
+        if self.selection == Some(id) {
+            self.selection = None;
+        }
+        self.composite_valid = false;
+        self.history.remove(id)
+    }
+
+    /// Removes the selected annotation as an undo step.
+    pub fn delete_selection(&mut self) -> bool {
+        match self.selected() {
+            Some(id) => self.delete(id),
             None => false,
         }
     }
-    pub fn redo(&mut self) -> bool {
-        match self.redo_stack.pop() {
-            Some(mut d) => {
-                // notify of the redo action
-                d.handle_redo();
 
-                // push to drawable stack
-                self.drawables.push(d);
+    /// Removes the topmost annotation at `pos`, in image coordinates, as an undo step.
+    pub fn delete_at(&mut self, pos: Vec2D) -> bool {
+        match self.hit_test(pos, |_| true) {
+            Some(id) => self.delete(id),
+            None => false,
+        }
+    }
 
+    /// Locks the selected annotation, which also deselects it.
+    pub fn lock_selection(&mut self) -> bool {
+        match self.selected() {
+            Some(id) => {
+                self.layers.borrow_mut().set_locked(id, true);
+                self.selection = None;
                 true
             }
             None => false,
         }
     }
-    pub fn reset(&mut self) -> bool {
-        let mut any_undone = false;
-        while let Some(mut d) = self.drawables.pop() {
-            // notify of the undo action
-            d.handle_undo();
 
-            // push to redo stack
-            self.redo_stack.push(d);
+    /// Moves the selected annotation by `offset`, in image coordinates.
+    pub fn nudge_selection(&mut self, offset: Vec2D) -> bool {
+        self.change_selection(|d| {
+            let mut moved = d.clone_box();
+            moved.translate(offset);
+            Some(moved)
+        })
+    }
+
+    /// Scales the selected annotation by `factor` around its center, if it can be resized.
+    pub fn scale_selection(&mut self, factor: f32) -> bool {
+        self.change_selection(|d| d.scale(factor))
+    }
 
-            any_undone = true;
+    // replaces the selected annotation with its `change`, amending the undo step of the
+    // previous change if they follow each other quickly
+    fn change_selection(
+        &mut self,
+        change: impl FnOnce(&dyn Drawable) -> Option<Box<dyn Drawable>>,
+    ) -> bool {
+        let Some(id) = self.selected() else {
+            return false;
+        };
+        let Some(changed) = self.history.get(id).and_then(change) else {
+            return false;
+        };
+
+        let burst = self.last_change.is_some_and(|(last, node, time)| {
+            last == id
+                && self.history.current() == Some(node)
+                && time.elapsed() < CHANGE_BURST_TIMEOUT
+        });
+        if burst {
+            self.history.amend(changed);
+        } else {
+            self.history.update(id, changed);
         }
-        any_undone
+        self.last_change = self
+            .history
+            .current()
+            .map(|node| (id, node, Instant::now()));
+        self.composite_valid = false;
+        true
     }
 
-    pub fn set_active_tool(&mut self, active_tool: Rc<RefCell<dyn Tool>>) {
-        self.active_tool = active_tool;
+    /// Erases the parts of the visible, unlocked annotations within `radius` of `center`.
+    /// The changes are only shown until `finish_erasing` commits them.
+    pub fn erase_at(&mut self, center: Vec2D, radius: f32) -> bool {
+        let layers = self.layers.borrow();
+        let mut changed = false;
+        for (id, d) in layers.arrange(self.history.annotations()) {
+            if layers.is_hidden(id) || layers.is_locked(id) {
+                continue;
+            }
+            let current = self.erased.get(&id).map_or(d, |e| e.as_ref());
+            if let Some(erased) = current.erase(center, radius) {
+                self.erased.insert(id, erased);
+                self.composite_valid = false;
+                changed = true;
+            }
+        }
+        changed
     }
 
-    pub fn load_image(&mut self, pixbuf: &Pixbuf) {
-        self.background_image = pixbuf.clone();
-        self.background_image_id = None;
-        self.drawables.clear();
-        self.redo_stack.clear();
+    /// Commits the annotations changed by the eraser as a single undo step, strokes erased
+    /// completely are removed.
+    pub fn finish_erasing(&mut self) -> bool {
+        let mut erased: Vec<_> = self
+            .erased
+            .drain()
+            .map(|(id, drawable)| (id, drawable.bounds().is_some().then_some(drawable)))
+            .collect();
+        erased.sort_by_key(|(id, _)| *id);
+        let changed = !erased.is_empty();
+        self.history.update_all(erased);
+        self.composite_valid &= !changed;
+        changed
+    }
+
+    // the selection, unless its annotation has been undone, hidden or locked
+    fn selected(&self) -> Option<usize> {
+        let layers = self.layers.borrow();
+        self.selection.filter(|&id| {
+            self.history.get(id).is_some() && !layers.is_hidden(id) && !layers.is_locked(id)
+        })
+    }
+
+    /// The annotations as shown in the layers panel, from bottom to top.
+    pub fn layer_infos(&self) -> Vec<LayerInfo> {
+        let layers = self.layers.borrow();
+        let selected = self.selected();
+        layers
+            .arrange(self.history.annotations())
+            .into_iter()
+            .map(|(id, d)| LayerInfo {
+                id,
+                name: d.display_name(),
+                color: d.color(),
+                bounds: d.bounds(),
+                visible: !layers.is_hidden(id),
+                locked: layers.is_locked(id),
+                selected: selected == Some(id),
+            })
+            .collect()
+    }
+
+    /// The selected annotation if `selection` is set and there is one, or else all visible
+    /// ones, to save as a snippet or template, and how many of them cannot be saved.
+    pub fn annotation_specs(&self, selection: bool) -> (Vec<AnnotationSpec>, usize) {
+        let layers = self.layers.borrow();
+        let selected = self.selected().filter(|_| selection);
+        let drawables: Vec<&dyn Drawable> = match selected {
+            Some(id) => self.history.get(id).into_iter().collect(),
+            None => layers
+                .arrange(self.history.annotations())
+                .into_iter()
+                .filter(|(id, _)| !layers.is_hidden(*id))
+                .map(|(_, d)| d)
+                .collect(),
+        };
+        let specs: Vec<_> = drawables.iter().filter_map(|d| d.to_spec()).collect();
+        let skipped = drawables.len() - specs.len();
+        (specs, skipped)
+    }
+
+    /// Commits the annotations of a snippet, centered on the crop or else the image.
+    pub fn insert_snippet(&mut self, specs: Vec<AnnotationSpec>) {
+        let Some((pos, size)) = annotations::bounds(&specs) else {
+            return;
+        };
+        let (area_pos, area_size) = match self.crop_tool.borrow().get_crop() {
+            Some(crop) => crop.get_rectangle(),
+            None => (
+                Vec2D::zero(),
+                Vec2D::new(
+                    self.background_image.width() as f32,
+                    self.background_image.height() as f32,
+                ),
+            ),
+        };
+        let offset = area_pos + (area_size - size) * 0.5 - pos;
+        for mut spec in specs {
+            spec.translate(offset);
+            self.commit(spec.into_drawable());
+        }
     }
 
+    /// The part of the image below `bounds`, scaled down to fit a thumbnail.
+    pub fn thumbnail(&self, bounds: (Vec2D, Vec2D)) -> Option<Pixbuf> {
+        let image_size = Vec2D::new(
+            self.background_image.width() as f32,
+            self.background_image.height() as f32,
+        );
+        let (pos, size) = rect_round(rect_ensure_in_bounds(
+            rect_ensure_positive_size(bounds.0, bounds.1),
+            (Vec2D::zero(), image_size),
+        ));
+        if size.x < 1.0 || size.y < 1.0 {
+            return None;
+        }
+
+        let scale = (THUMBNAIL_SIZE.x / size.x).min(THUMBNAIL_SIZE.y / size.y);
+        self.background_image
+            .new_subpixbuf(pos.x as i32, pos.y as i32, size.x as i32, size.y as i32)
+            .scale_simple(
+                ((size.x * scale) as i32).max(1),
+                ((size.y * scale) as i32).max(1),
+                InterpType::Bilinear,
+            )
+    }
+
+    /// Renders the image as exported. With `annotations_only`, the background image is
+    /// left out and the annotations are drawn onto a transparent canvas instead. A
+    /// `region` is exported instead of the crop.
     pub fn render_native_resolution(
         &mut self,
         canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
         font: FontId,
+        annotations_only: bool,
+        region: Option<(Vec2D, Vec2D)>,
     ) -> anyhow::Result<ImgVec<RGBA8>> {
         let bounds = (
             Vec2D::zero(),
@@ -324,12 +909,16 @@ impl FemtoVgAreaMut {
             ),
         );
         // get offset and size of the area in question
-        let (pos, size) = self
-            .crop_tool
-            .borrow()
-            .get_crop()
-            .map(|c| c.get_rectangle())
-            .map(|rect| rect_ensure_in_bounds(rect, bounds))
+        let crop = self.crop_tool.borrow().get_crop().map(|c| {
+            if c.extends_canvas() {
+                c.get_rectangle()
+            } else {
+                rect_ensure_in_bounds(c.get_rectangle(), bounds)
+            }
+        });
+        let (pos, size) = region
+            .map(|r| rect_ensure_in_bounds(rect_ensure_positive_size(r.0, r.1), bounds))
+            .or(crop)
             .map(rect_round)
             .filter(|(_, size)| !size.is_zero())
             .unwrap_or(bounds);
@@ -350,7 +939,7 @@ impl FemtoVgAreaMut {
         canvas.set_transform(&transform);
 
         // render
-        self.render(canvas, font, false)?;
+        self.render(canvas, font, false, !annotations_only, None)?;
 
         // return screenshot
         let result = canvas.screenshot();
@@ -359,16 +948,364 @@ impl FemtoVgAreaMut {
         canvas.set_render_target(femtovg::RenderTarget::Screen);
         canvas.delete_image(image_id);
 
+        // a beautified overlay would no longer line up with the screenshot
+        if self.beautify && !annotations_only {
+            Self::beautify(canvas, result?)
+        } else {
+            Ok(result?)
+        }
+    }
+
+    /// The steps of the history up to the current one.
+    pub fn steps(&self) -> Vec<Option<usize>> {
+        self.history.steps()
+    }
+
+    /// The exported image after `step` of the history.
+    pub fn render_step(
+        &mut self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        font: FontId,
+        step: Option<usize>,
+    ) -> Result<ImgVec<RGBA8>> {
+        let current = self.history.current();
+        self.history.show_step(step);
+        let frame = self.render_native_resolution(canvas, font, false, None);
+        self.history.show_step(current);
+        frame
+    }
+
+    /// Place the image on a padded background with rounded corners and a drop shadow.
+    fn beautify(
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        image: ImgVec<RGBA8>,
+    ) -> Result<ImgVec<RGBA8>> {
+        let config = APP_CONFIG.read();
+        let padding = config.beautify_padding().max(0.0).round();
+        let radius = config.beautify_corner_radius();
+        let width = image.width() as f32;
+        let height = image.height() as f32;
+        let size = Vec2D::new(width + 2.0 * padding, height + 2.0 * padding);
+
+        let content_id =
+            canvas.create_image(ImageSource::Rgba(image.as_ref()), ImageFlags::empty())?;
+        let target_id = canvas.create_image_empty(
+            size.x as usize,
+            size.y as usize,
+            PixelFormat::Rgba8,
+            ImageFlags::empty(),
+        )?;
+        canvas.set_render_target(femtovg::RenderTarget::Image(target_id));
+        canvas.reset_transform();
+        canvas.clear_rect(
+            0,
+            0,
+            size.x as u32,
+            size.y as u32,
+            femtovg::Color::rgba(0, 0, 0, 0),
+        );
+
+        // background
+        let mut background = Path::new();
+        background.rect(0.0, 0.0, size.x, size.y);
+        match config.beautify_background() {
+            [] => {}
+            [color] => canvas.fill_path(&background, &Paint::color((*color).into())),
+            [from, to, ..] => canvas.fill_path(
+                &background,
+                &Paint::linear_gradient(0.0, 0.0, size.x, size.y, (*from).into(), (*to).into()),
+            ),
+        }
+
+        // shadow, slightly offset downwards
+        let feather = padding / 2.0;
+        let shadow_offset = feather / 4.0;
+        let mut shadow = Path::new();
+        shadow.rect(
+            padding - feather,
+            padding - feather + shadow_offset,
+            width + 2.0 * feather,
+            height + 2.0 * feather,
+        );
+        canvas.fill_path(
+            &shadow,
+            &Paint::box_gradient(
+                padding,
+                padding + shadow_offset,
+                width,
+                height,
+                radius,
+                feather,
+                femtovg::Color::rgba(0, 0, 0, 128),
+                femtovg::Color::rgba(0, 0, 0, 0),
+            ),
+        );
+
+        // content
+        let mut content = Path::new();
+        content.rounded_rect(padding, padding, width, height, radius);
+        canvas.fill_path(
+            &content,
+            &Paint::image(content_id, padding, padding, width, height, 0f32, 1f32),
+        );
+        canvas.flush();
+
+        let result = canvas.screenshot();
+
+        // clean up
+        canvas.set_render_target(femtovg::RenderTarget::Screen);
+        canvas.delete_image(content_id);
+        canvas.delete_image(target_id);
+
         Ok(result?)
     }
 
+    pub fn set_beautify(&mut self, beautify: bool) {
+        self.beautify = beautify;
+    }
+
+    pub fn set_adjustments(&mut self, adjustments: Adjustments) {
+        if self.adjustments != adjustments {
+            self.adjustments = adjustments;
+            self.invalidate_background();
+        }
+    }
+
+    /// Returns whether peeking changed.
+    pub fn set_peek(&mut self, peek: bool) -> bool {
+        let changed = self.peek != peek;
+        self.peek = peek;
+        changed
+    }
+
+    /// Returns whether the simulation changed.
+    pub fn set_color_vision(&mut self, color_vision: Option<ColorVision>) -> bool {
+        let changed = self.color_vision != color_vision;
+        self.color_vision = color_vision;
+        changed
+    }
+
+    pub fn color_vision(&self) -> Option<ColorVision> {
+        self.color_vision
+    }
+
+    pub fn set_export_region(&mut self, region: Option<(Vec2D, Vec2D)>) {
+        self.export_region = region;
+    }
+
+    pub fn set_inspector(&mut self, pos: Option<Vec2D>) {
+        self.inspector = pos;
+    }
+
+    /// Renders the annotations into the composite again, after they changed outside of the
+    /// history, e.g. in the layers panel.
+    pub fn invalidate_annotations(&mut self) {
+        self.composite_valid = false;
+    }
+
+    /// Renders the whole next frame, not only where the drawable of the active tool changed.
+    pub fn invalidate(&mut self) {
+        self.damage = Damage::Full;
+    }
+
+    // how far drawables may reach past their bounds, in image coordinates
+    fn damage_margin(&self) -> f32 {
+        DAMAGE_MARGIN * APP_CONFIG.read().annotation_size_factor() + 2.0 / self.scale_factor
+    }
+
+    // area covered by the drawable of the active tool
+    fn tool_damage(&self) -> Damage {
+        let tool = self.active_tool.borrow();
+        let Some(d) = tool.get_drawable() else {
+            return Damage::Nothing;
+        };
+        let Some((pos, size)) = self.attached(d).as_deref().unwrap_or(d).bounds() else {
+            return Damage::Full;
+        };
+        let (pos, size) = rect_ensure_positive_size(pos, size);
+        let margin = Vec2D::new(self.damage_margin(), self.damage_margin());
+        Damage::Area((pos - margin, size + margin * 2.0))
+    }
+
+    /// Color of the background image at `pos`, `None` outside of it.
+    pub fn pixel_at(&self, pos: Vec2D) -> Option<crate::style::Color> {
+        let image = &self.background_image;
+        let (x, y) = (pos.x.floor(), pos.y.floor());
+        if x < 0.0 || y < 0.0 || x >= image.width() as f32 || y >= image.height() as f32 {
+            return None;
+        }
+        let channels = image.n_channels() as usize;
+        let offset = y as usize * image.rowstride() as usize + x as usize * channels;
+        let [r, g, b, rest @ ..] = self.background_pixels.get(offset..offset + channels)? else {
+            return None;
+        };
+        let a = if image.has_alpha() {
+            *rest.first()?
+        } else {
+            255
+        };
+        Some(crate::style::Color::new(*r, *g, *b, a))
+    }
+
     pub fn render_framebuffer(
         &mut self,
         canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
         font: FontId,
+        color_vision_pass: Option<&ColorVisionPass>,
     ) -> Result<()> {
+        let (width, height) = (canvas.width(), canvas.height());
+        let transform = (self.scale_factor, self.offset);
+        let frame = match self.frame.take() {
+            Some(frame)
+                if frame.size == (width, height) && canvas.image_info(frame.image_id).is_ok() =>
+            {
+                frame
+            }
+            outdated => {
+                if let Some(frame) = outdated {
+                    canvas.delete_image(frame.image_id);
+                }
+                self.damage = Damage::Full;
+                Frame {
+                    image_id: canvas.create_image_empty(
+                        width as usize,
+                        height as usize,
+                        PixelFormat::Rgba8,
+                        ImageFlags::NEAREST | ImageFlags::PREMULTIPLIED,
+                    )?,
+                    size: (width, height),
+                    transform,
+                }
+            }
+        };
+        if frame.transform != transform {
+            self.damage = Damage::Full;
+        }
+        let frame_id = frame.image_id;
+        self.frame = Some(Frame { transform, ..frame });
+
+        // the drawable of the active tool may have moved away from where it was last frame
+        let tool_damage = self.tool_damage();
+        let damage = std::mem::replace(&mut self.damage, tool_damage).union(tool_damage);
+
+        if !matches!(damage, Damage::Nothing) && self.use_composite() {
+            self.update_composite(canvas, font)?;
+        }
+
+        canvas.set_render_target(femtovg::RenderTarget::Image(frame_id));
+        let result = match damage {
+            Damage::Nothing => Ok(()),
+            Damage::Area(area) => self.render_frame(canvas, font, Some(area)),
+            Damage::Full => self.render_frame(canvas, font, None),
+        };
         canvas.set_render_target(femtovg::RenderTarget::Screen);
+        if result.is_err() {
+            self.damage = Damage::Full;
+        }
+        result?;
+
+        match (self.color_vision, color_vision_pass) {
+            (Some(vision), Some(pass)) => {
+                let frame = canvas.get_native_texture(frame_id)?;
+                // SAFETY: GTK made the context current for rendering and the frame is a
+                // texture of it
+                unsafe { pass.draw(frame, vision, width, height) };
+            }
+            _ => {
+                canvas.reset_transform();
+                canvas.clear_rect(0, 0, width, height, femtovg::Color::rgba(0, 0, 0, 0));
+                let mut path = Path::new();
+                path.rect(0.0, 0.0, width as f32, height as f32);
+                canvas.fill_path(
+                    &path,
+                    &Paint::image(frame_id, 0.0, 0.0, width as f32, height as f32, 0.0, 1.0),
+                );
+            }
+        }
+
+        // follow the pointer, so drawn onto the screen instead of damaging the frame
+        self.render_minimap(canvas)?;
+        self.render_inspector(canvas, font)?;
+        canvas.flush();
+        Ok(())
+    }
 
+    // the composite is only as sharp as the image, and a crop extending the canvas may show
+    // annotations outside of it
+    fn use_composite(&self) -> bool {
+        self.scale_factor <= 1.0
+            && !self.peek
+            && !self
+                .crop_tool
+                .borrow()
+                .get_crop()
+                .is_some_and(|c| c.extends_canvas())
+    }
+
+    // renders the image and the visible annotations into the composite, unless they didn't
+    // change since
+    fn update_composite(
+        &mut self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        font: FontId,
+    ) -> Result<()> {
+        let (width, height) = (
+            self.background_image.width() as usize,
+            self.background_image.height() as usize,
+        );
+        let image_id = match self.composite.take() {
+            Some(id)
+                if canvas
+                    .image_info(id)
+                    .is_ok_and(|info| (info.width(), info.height()) == (width, height)) =>
+            {
+                if self.composite_valid {
+                    self.composite = Some(id);
+                    return Ok(());
+                }
+                id
+            }
+            outdated => {
+                if let Some(id) = outdated {
+                    canvas.delete_image(id);
+                }
+                canvas.create_image_empty(
+                    width,
+                    height,
+                    PixelFormat::Rgba8,
+                    ImageFlags::PREMULTIPLIED,
+                )?
+            }
+        };
+
+        canvas.set_render_target(femtovg::RenderTarget::Image(image_id));
+        canvas.reset_transform();
+        canvas.clear_rect(
+            0,
+            0,
+            width as u32,
+            height as u32,
+            femtovg::Color::rgba(0, 0, 0, 0),
+        );
+        let bounds = (Vec2D::zero(), Vec2D::new(width as f32, height as f32));
+        let result = self
+            .render_background_image(canvas)
+            .and_then(|()| self.render_annotations(canvas, font, bounds, None));
+        canvas.flush();
+        canvas.set_render_target(femtovg::RenderTarget::Screen);
+        result?;
+
+        self.composite = Some(image_id);
+        self.composite_valid = true;
+        Ok(())
+    }
+
+    fn render_frame(
+        &mut self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        font: FontId,
+        damage: Option<(Vec2D, Vec2D)>,
+    ) -> Result<()> {
         // setup transform to image coordinates
         let mut transform = Transform2D::identity();
         transform.scale(self.scale_factor, self.scale_factor);
@@ -377,28 +1314,323 @@ impl FemtoVgAreaMut {
         canvas.reset_transform();
         canvas.set_transform(&transform);
 
-        self.render(canvas, font, true)?;
+        self.render(canvas, font, true, true, damage)
+    }
 
+    fn render_minimap(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+    ) -> Result<()> {
+        let (Some((pos, size)), Some(background_image_id)) =
+            (self.minimap_rect(), self.background_image_id)
+        else {
+            return Ok(());
+        };
+
+        canvas.reset_transform();
+
+        let mut path = Path::new();
+        path.rect(pos.x, pos.y, size.x, size.y);
+        canvas.fill_path(
+            &path,
+            &Paint::image(
+                background_image_id,
+                pos.x,
+                pos.y,
+                size.x,
+                size.y,
+                0f32,
+                1f32,
+            ),
+        );
+        canvas.stroke_path(
+            &path,
+            &Paint::color(femtovg::Color::rgba(0, 0, 0, 153)).with_line_width(2.0),
+        );
+
+        // visible part of the image, clamped to the image
+        let scale = size.x / self.background_image.width() as f32;
+        let (view_pos, view_size) = self.viewport();
+        let top_left = Vec2D::new(view_pos.x.max(0.0), view_pos.y.max(0.0)) * scale;
+        let bottom_right = Vec2D::new(
+            (view_pos.x + view_size.x).min(self.background_image.width() as f32),
+            (view_pos.y + view_size.y).min(self.background_image.height() as f32),
+        ) * scale;
+
+        let mut viewport = Path::new();
+        viewport.rect(
+            pos.x + top_left.x,
+            pos.y + top_left.y,
+            bottom_right.x - top_left.x,
+            bottom_right.y - top_left.y,
+        );
+        canvas.stroke_path(
+            &viewport,
+            &Paint::color(femtovg::Color::rgba(249, 249, 249, 255)).with_line_width(2.0),
+        );
+
+        canvas.flush();
+        Ok(())
+    }
+
+    // magnified pixels around the inspected position next to the cursor, with the color
+    // and coordinates of the center one
+    fn render_inspector(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        font: FontId,
+    ) -> Result<()> {
+        let Some(pos) = self.inspector else {
+            return Ok(());
+        };
+        let center = Vec2D::new(pos.x.floor(), pos.y.floor());
+        let radius = INSPECTOR_PIXELS / 2;
+        let side = INSPECTOR_PIXELS as f32 * INSPECTOR_CELL;
+
+        let mut text = Paint::color(femtovg::Color::white());
+        text.set_font(&[font]);
+        text.set_font_size(INSPECTOR_FONT_SIZE);
+        text.set_text_baseline(femtovg::Baseline::Top);
+        let label = match self.pixel_at(center) {
+            Some(c) => format!(
+                "{:.0}, {:.0}  {}  rgba({}, {}, {}, {})",
+                center.x,
+                center.y,
+                c.to_hex(),
+                c.r,
+                c.g,
+                c.b,
+                c.a
+            ),
+            None => format!("{:.0}, {:.0}", center.x, center.y),
+        };
+        let metrics = canvas.measure_text(0.0, 0.0, &label, &text)?;
+        let (label_width, label_height) = (metrics.width() + 8.0, metrics.height() + 8.0);
+
+        // next to the cursor, on the other side if it would leave the canvas
+        canvas.reset_transform();
+        let cursor = pos * self.scale_factor + self.offset;
+        let width = side.max(label_width);
+        let height = side + label_height;
+        let x = if cursor.x + INSPECTOR_OFFSET + width > canvas.width() as f32 {
+            cursor.x - INSPECTOR_OFFSET - width
+        } else {
+            cursor.x + INSPECTOR_OFFSET
+        };
+        let y = if cursor.y + INSPECTOR_OFFSET + height > canvas.height() as f32 {
+            cursor.y - INSPECTOR_OFFSET - height
+        } else {
+            cursor.y + INSPECTOR_OFFSET
+        };
+
+        let mut background = Path::new();
+        background.rect(x, y, width, height);
+        canvas.fill_path(
+            &background,
+            &Paint::color(femtovg::Color::rgba(0, 0, 0, 204)),
+        );
+
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let Some(color) = self.pixel_at(center + Vec2D::new(dx as f32, dy as f32)) else {
+                    continue;
+                };
+                let mut cell = Path::new();
+                cell.rect(
+                    x + (dx + radius) as f32 * INSPECTOR_CELL,
+                    y + (dy + radius) as f32 * INSPECTOR_CELL,
+                    INSPECTOR_CELL,
+                    INSPECTOR_CELL,
+                );
+                canvas.fill_path(&cell, &Paint::color(color.into()).with_anti_alias(false));
+            }
+        }
+
+        // the inspected pixel, visible on light and dark colors
+        let mut marker = Path::new();
+        marker.rect(
+            x + radius as f32 * INSPECTOR_CELL,
+            y + radius as f32 * INSPECTOR_CELL,
+            INSPECTOR_CELL,
+            INSPECTOR_CELL,
+        );
+        canvas.stroke_path(
+            &marker,
+            &Paint::color(femtovg::Color::black()).with_line_width(3.0),
+        );
+        canvas.stroke_path(
+            &marker,
+            &Paint::color(femtovg::Color::white()).with_line_width(1.0),
+        );
+        canvas.stroke_path(
+            &background,
+            &Paint::color(femtovg::Color::rgba(249, 249, 249, 255)).with_line_width(1.0),
+        );
+
+        canvas.fill_text(x + 4.0, y + side + 4.0, &label, &text)?;
+        canvas.flush();
         Ok(())
     }
 
+    // position and size of the visible area, in image coordinates
+    fn viewport(&self) -> (Vec2D, Vec2D) {
+        (
+            self.offset * (-1.0 / self.scale_factor),
+            self.canvas_size * (1.0 / self.scale_factor),
+        )
+    }
+
+    // position and size of the minimap in physical pixels, only shown if part of the image is hidden
+    fn minimap_rect(&self) -> Option<(Vec2D, Vec2D)> {
+        if APP_CONFIG.read().disable_minimap() {
+            return None;
+        }
+
+        let image = Vec2D::new(
+            self.background_image.width() as f32,
+            self.background_image.height() as f32,
+        );
+        let (view_pos, view_size) = self.viewport();
+        // allow for rounding errors when the image fills the window exactly
+        if view_pos.x <= 0.5
+            && view_pos.y <= 0.5
+            && view_pos.x + view_size.x >= image.x - 0.5
+            && view_pos.y + view_size.y >= image.y - 0.5
+        {
+            return None;
+        }
+
+        let length = MINIMAP_SIZE.min(self.canvas_size.x.min(self.canvas_size.y) / 4.0);
+        let size = image * (length / image.x.max(image.y));
+        let pos = self.canvas_size - size - Vec2D::new(MINIMAP_MARGIN, MINIMAP_MARGIN);
+        Some((pos, size))
+    }
+
+    pub fn is_in_minimap(&self, input: Vec2D, dpi_scale_factor: f32) -> bool {
+        let input = input * dpi_scale_factor;
+        self.minimap_rect().is_some_and(|(pos, size)| {
+            input.x >= pos.x
+                && input.y >= pos.y
+                && input.x <= pos.x + size.x
+                && input.y <= pos.y + size.y
+        })
+    }
+
+    /// Pan so that the image position under `input` (in the minimap) ends up in the center.
+    pub fn center_on_minimap_position(&mut self, input: Vec2D, dpi_scale_factor: f32) {
+        let Some((pos, size)) = self.minimap_rect() else {
+            return;
+        };
+
+        let input = input * dpi_scale_factor - pos;
+        let scale = self.background_image.width() as f32 / size.x;
+        let target = Vec2D::new(
+            input.x.clamp(0.0, size.x) * scale,
+            input.y.clamp(0.0, size.y) * scale,
+        );
+
+        let image = Vec2D::new(
+            self.background_image.width() as f32,
+            self.background_image.height() as f32,
+        );
+        let center_offset = (self.canvas_size - image * self.scale_factor) * 0.5;
+        self.drag_offset = self.canvas_size * 0.5 - target * self.scale_factor - center_offset;
+        self.store_last_offset();
+    }
+
+    /// Zoom so that the image fills the width of the canvas, showing its top.
+    pub fn zoom_to_width(&mut self) {
+        let width = self.background_image.width() as f32;
+        let scale = self.canvas_size.x / width;
+        self.show_at(scale, Vec2D::new(width, self.canvas_size.y / scale) * 0.5);
+    }
+
+    /// Zoom so that `rect` in image coordinates fills the canvas, centered on it.
+    pub fn zoom_to_rect(&mut self, rect: (Vec2D, Vec2D)) {
+        let (pos, size) = rect_ensure_positive_size(rect.0, rect.1);
+        if size.x <= 0.0 || size.y <= 0.0 {
+            return;
+        }
+        let scale =
+            (self.canvas_size.x / size.x).min(self.canvas_size.y / size.y) * ZOOM_TO_RECT_FILL;
+        self.show_at(scale, pos + size * 0.5);
+    }
+
+    // zoom to `scale` with `target` in image coordinates in the center of the canvas
+    fn show_at(&mut self, scale: f32, target: Vec2D) {
+        if self.is_drag || !scale.is_finite() || scale <= 0.0 {
+            return;
+        }
+        // the zoom does not follow the pointer, as it would for a changed scale
+        self.zoom_scale = scale;
+        self.last_scale = scale;
+
+        let image = Vec2D::new(
+            self.background_image.width() as f32,
+            self.background_image.height() as f32,
+        );
+        let center_offset = (self.canvas_size - image * scale) * 0.5;
+        self.drag_offset = self.canvas_size * 0.5 - target * scale - center_offset;
+        self.store_last_offset();
+    }
+
+    /// Bounds of the selected annotation, in image coordinates.
+    pub fn selection_bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.selected()
+            .and_then(|id| self.history.get(id))
+            .and_then(|d| self.attached(d).as_deref().unwrap_or(d).bounds())
+    }
+
     fn render(
         &mut self,
         canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
         font: FontId,
         render_crop: bool,
+        render_background: bool,
+        damage: Option<(Vec2D, Vec2D)>,
     ) -> Result<()> {
-        // clear canvas
-        canvas.clear_rect(
-            0,
-            0,
-            canvas.width(),
-            canvas.height(),
-            femtovg::Color::black(),
-        );
-
-        // render background
-        self.render_background_image(canvas)?;
+        // clear canvas, exports keep transparency unless a background is configured
+        let clear_color = if render_crop {
+            APP_CONFIG
+                .read()
+                .scrim_color()
+                .map(Into::into)
+                .unwrap_or(femtovg::Color::black())
+        } else if !render_background {
+            femtovg::Color::rgba(0, 0, 0, 0)
+        } else {
+            APP_CONFIG
+                .read()
+                .export_background()
+                .map(Into::into)
+                .unwrap_or(femtovg::Color::rgba(0, 0, 0, 0))
+        };
+        match damage {
+            Some((pos, size)) => {
+                // whole pixels, so that the scissor leaves none partly rendered
+                let to_canvas = |v: f32, offset: f32, max: u32| {
+                    (v * self.scale_factor + offset).clamp(0.0, max as f32)
+                };
+                let left = to_canvas(pos.x, self.offset.x, canvas.width()).floor();
+                let top = to_canvas(pos.y, self.offset.y, canvas.height()).floor();
+                let right = to_canvas(pos.x + size.x, self.offset.x, canvas.width()).ceil();
+                let bottom = to_canvas(pos.y + size.y, self.offset.y, canvas.height()).ceil();
+                canvas.clear_rect(
+                    left as u32,
+                    top as u32,
+                    (right - left) as u32,
+                    (bottom - top) as u32,
+                    clear_color,
+                );
+                canvas.scissor(
+                    (left - self.offset.x) / self.scale_factor,
+                    (top - self.offset.y) / self.scale_factor,
+                    (right - left) / self.scale_factor,
+                    (bottom - top) / self.scale_factor,
+                );
+            }
+            None => canvas.clear_rect(0, 0, canvas.width(), canvas.height(), clear_color),
+        }
 
         let bounds = (
             Vec2D::zero(),
@@ -407,27 +1639,194 @@ impl FemtoVgAreaMut {
                 self.background_image.height() as f32,
             ),
         );
-        // render the whole stack
-        for d in &mut self.drawables {
-            d.draw(canvas, font, bounds)?;
+        // exports always contain the annotations, peeking only affects the screen
+        let show_annotations = !render_crop || !self.peek;
+        // kept up to date by `render_framebuffer` while it is used
+        let composite = self
+            .composite
+            .filter(|_| render_crop && self.use_composite());
+
+        // render background
+        if render_background {
+            self.render_canvas_extension(canvas);
+            if render_crop && self.background_image.has_alpha() {
+                self.render_transparency_backdrop(canvas)?;
+            }
+            if composite.is_none() {
+                self.render_background_image(canvas)?;
+            }
         }
 
-        // render active tool
-        if let Some(d) = self.active_tool.borrow().get_drawable() {
-            d.draw(canvas, font, bounds)?;
+        if let Some(composite) = composite {
+            let mut path = Path::new();
+            path.rect(0.0, 0.0, bounds.1.x, bounds.1.y);
+            canvas.fill_path(
+                &path,
+                &Paint::image(composite, 0.0, 0.0, bounds.1.x, bounds.1.y, 0.0, 1.0),
+            );
+        } else if show_annotations {
+            self.render_annotations(canvas, font, bounds, damage)?;
+        }
+
+        if show_annotations {
+            // render active tool
+            if let Some(d) = self.active_tool.borrow().get_drawable() {
+                self.attached(d)
+                    .as_deref()
+                    .unwrap_or(d)
+                    .draw(canvas, font, bounds)?;
+            }
         }
 
-        // render crop tool
+        // render crop tool and selection
         if render_crop {
             if let Some(c) = self.crop_tool.borrow().get_crop() {
                 c.draw(canvas, font, bounds)?;
             }
+            if show_annotations {
+                self.render_selection(canvas);
+            }
+            self.render_export_region(canvas);
         }
 
+        canvas.reset_scissor();
         canvas.flush();
         Ok(())
     }
 
+    // the visible annotations from bottom to top, or only those that may reach into `damage`
+    fn render_annotations(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        font: FontId,
+        bounds: (Vec2D, Vec2D),
+        damage: Option<(Vec2D, Vec2D)>,
+    ) -> Result<()> {
+        let margin = self.damage_margin();
+        let layers = self.layers.borrow();
+        for (id, d) in layers.arrange(self.history.annotations()) {
+            if layers.is_hidden(id) {
+                continue;
+            }
+            let d = self.erased.get(&id).map_or(d, |e| e.as_ref());
+            let attached = self.attached(d);
+            let d = attached.as_deref().unwrap_or(d);
+            let damaged = damage
+                .zip(d.bounds())
+                .is_none_or(|(damage, b)| rect_intersects(damage, b, margin));
+            if damaged {
+                d.draw(canvas, font, bounds)?;
+            }
+        }
+        Ok(())
+    }
+
+    // outline around the selected annotation
+    fn render_selection(&self, canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>) {
+        let Some(bounds) = self.selection_bounds() else {
+            return;
+        };
+
+        let (pos, size) = rect_ensure_positive_size(bounds.0, bounds.1);
+        let margin = SELECTION_MARGIN / self.scale_factor;
+        let mut path = Path::new();
+        path.rect(
+            pos.x - margin,
+            pos.y - margin,
+            size.x + 2.0 * margin,
+            size.y + 2.0 * margin,
+        );
+        canvas.stroke_path(
+            &path,
+            &Paint::color(femtovg::Color::rgb(53, 132, 228))
+                .with_line_width(1.5 / self.scale_factor),
+        );
+    }
+
+    // dims everything but the region being chosen for export
+    fn render_export_region(&self, canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>) {
+        let Some((pos, size)) = self
+            .export_region
+            .map(|(pos, size)| rect_ensure_positive_size(pos, size))
+        else {
+            return;
+        };
+
+        let (view_pos, view_size) = self.viewport();
+        let mut outside = Path::new();
+        outside.rect(view_pos.x, view_pos.y, view_size.x, view_size.y);
+        outside.rect(pos.x, pos.y, size.x, size.y);
+        canvas.fill_path(
+            &outside,
+            &Paint::color(femtovg::Color::rgba(0, 0, 0, 128))
+                .with_fill_rule(femtovg::FillRule::EvenOdd),
+        );
+
+        let mut outline = Path::new();
+        outline.rect(pos.x, pos.y, size.x, size.y);
+        canvas.stroke_path(
+            &outline,
+            &Paint::color(femtovg::Color::rgb(53, 132, 228))
+                .with_line_width(1.5 / self.scale_factor),
+        );
+    }
+
+    // fill the space a crop adds around the image
+    fn render_canvas_extension(&self, canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>) {
+        let crop_tool = self.crop_tool.borrow();
+        let Some(crop) = crop_tool.get_crop().filter(|c| c.extends_canvas()) else {
+            return;
+        };
+
+        let (pos, size) = crop.get_rectangle();
+        let mut path = Path::new();
+        path.rect(pos.x, pos.y, size.x, size.y);
+        canvas.fill_path(
+            &path,
+            &Paint::color(APP_CONFIG.read().canvas_extension_color().into()),
+        );
+    }
+
+    fn render_transparency_backdrop(
+        &mut self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+    ) -> Result<()> {
+        let paint = match APP_CONFIG.read().transparency_backdrop() {
+            Some(color) => Paint::color(color.into()),
+            None => {
+                let checkerboard_image_id = match self.checkerboard_image_id {
+                    Some(id) => id,
+                    None => {
+                        let light = RGB::new(255, 255, 255);
+                        let dark = RGB::new(204, 204, 204);
+                        let image = Img::new(vec![light, dark, dark, light], 2, 2);
+                        let id = canvas.create_image(
+                            ImageSource::Rgb(image.as_ref()),
+                            ImageFlags::REPEAT_X | ImageFlags::REPEAT_Y | ImageFlags::NEAREST,
+                        )?;
+                        self.checkerboard_image_id.replace(id);
+                        id
+                    }
+                };
+
+                // keep the squares the same size on screen regardless of zoom
+                let size = 2.0 * CHECKERBOARD_SIZE / self.scale_factor;
+                Paint::image(checkerboard_image_id, 0f32, 0f32, size, size, 0f32, 1f32)
+            }
+        };
+
+        let mut path = Path::new();
+        path.rect(
+            0.0,
+            0.0,
+            self.background_image.width() as f32,
+            self.background_image.height() as f32,
+        );
+        canvas.fill_path(&path, &paint);
+
+        Ok(())
+    }
+
     fn render_background_image(
         &mut self,
         canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
@@ -435,7 +1834,11 @@ impl FemtoVgAreaMut {
         let background_image_id = match self.background_image_id {
             Some(id) => id,
             None => {
-                let id = Self::upload_background_image(canvas, &self.background_image)?;
+                let id = Self::upload_background_image(
+                    canvas,
+                    &self.background_image,
+                    self.adjustments,
+                )?;
                 self.background_image_id.replace(id);
                 id
             }
@@ -466,9 +1869,10 @@ impl FemtoVgAreaMut {
         Ok(())
     }
 
-    fn upload_background_image(
+    pub(super) fn upload_background_image(
         canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
         image: &Pixbuf,
+        adjustments: Adjustments,
     ) -> Result<ImageId> {
         let format = if image.has_alpha() {
             PixelFormat::Rgba8
@@ -512,6 +1916,10 @@ impl FemtoVgAreaMut {
             // end of the buffer, e.g. after width * height * bytes_per_pixel
             dst_buffer.truncate(width * height * bytes_per_pixel);
 
+            if !adjustments.is_identity() {
+                adjustments.apply(&mut dst_buffer, bytes_per_pixel);
+            }
+
             if image.has_alpha() {
                 let img = Img::new_stride(
                     dst_buffer.align_to::<RGBA<u8>>().1.to_vec(),
@@ -546,6 +1954,7 @@ impl FemtoVgAreaMut {
 
         let canvas_width = canvas.width() as f32;
         let canvas_height = canvas.height() as f32;
+        self.canvas_size = Vec2D::new(canvas_width, canvas_height);
 
         let prev_scale = self.scale_factor;
         let mut center_offset = Vec2D::zero();
@@ -617,6 +2026,10 @@ impl FemtoVgAreaMut {
         )
     }
 
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
     pub fn set_zoom_scale(&mut self, factor: f32, abs: bool) {
         if self.is_drag {
             return;
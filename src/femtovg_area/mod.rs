@@ -1,7 +1,11 @@
+mod color_vision;
+mod font_cache;
+mod headless;
 mod imp;
 
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, rc::Rc, sync::mpsc::SyncSender};
 
+use femtovg::{imgref::ImgVec, rgb::RGBA8};
 use gdk_pixbuf::{glib::subclass::types::ObjectSubclassIsExt, Pixbuf};
 use gtk::glib;
 use relm4::{
@@ -10,12 +14,18 @@ use relm4::{
 };
 
 use crate::{
+    adjustments::Adjustments,
+    annotations::AnnotationSpec,
     configuration::Action,
+    layers::{LayerInfo, Layers},
     math::Vec2D,
     sketch_board::SketchBoardInput,
     tools::{CropTool, Drawable, Tool},
 };
 
+pub use color_vision::ColorVision;
+pub use headless::render_annotations;
+
 glib::wrapper! {
     pub struct FemtoVGArea(ObjectSubclass<imp::FemtoVGArea>)
         @extends gtk::Widget, gtk::GLArea,
@@ -44,6 +54,13 @@ impl FemtoVGArea {
             .expect("Did you call init before using FemtoVgArea?")
             .commit(drawable);
     }
+    pub fn commit_selected(&mut self, drawable: Box<dyn Drawable>) {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .commit_selected(drawable);
+    }
     pub fn undo(&mut self) -> bool {
         self.imp()
             .inner()
@@ -58,9 +75,30 @@ impl FemtoVGArea {
             .expect("Did you call init before using FemtoVgArea?")
             .redo()
     }
+    pub fn switch_undo_branch(&mut self) -> bool {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .switch_undo_branch()
+    }
+    pub fn history_position(&self) -> Option<usize> {
+        self.imp()
+            .inner()
+            .as_ref()
+            .expect("Did you call init before using FemtoVgArea?")
+            .history_position()
+    }
     pub fn request_render(&self, actions: &[Action]) {
         self.imp().request_render(actions);
     }
+    /// Renders the image as exported with the next frame, to drag it out of the window.
+    pub fn request_drag_image(&self) {
+        self.imp().request_drag_image();
+    }
+    pub fn export_steps(&self, frames: SyncSender<ImgVec<RGBA8>>) -> usize {
+        self.imp().export_steps(frames)
+    }
     pub fn reset(&mut self) -> bool {
         self.imp()
             .inner()
@@ -77,6 +115,30 @@ impl FemtoVGArea {
             .load_image(pixbuf);
     }
 
+    pub fn set_base_image(&mut self, pixbuf: &Pixbuf) {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .set_base_image(pixbuf);
+    }
+
+    pub fn replace_image(&mut self, pixbuf: &Pixbuf) {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .replace_image(pixbuf);
+    }
+
+    pub fn background_image(&self) -> Pixbuf {
+        self.imp()
+            .inner()
+            .as_ref()
+            .expect("Did you call init before using FemtoVgArea?")
+            .background_image()
+    }
+
     pub fn abs_canvas_to_image_coordinates(&self, input: Vec2D) -> Vec2D {
         self.imp()
             .inner()
@@ -92,15 +154,259 @@ impl FemtoVGArea {
             .expect("Did you call init before using FemtoVgArea?")
             .rel_canvas_to_image_coordinates(input, self.scale_factor() as f32)
     }
+    pub fn annotation_at(&self, pos: Vec2D) -> Option<usize> {
+        self.imp()
+            .inner()
+            .as_ref()
+            .expect("Did you call init before using FemtoVgArea?")
+            .annotation_at(pos)
+    }
+
+    pub fn select_at(&self, pos: Vec2D) -> bool {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .select_at(pos)
+    }
+
+    pub fn clear_selection(&self) -> bool {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .clear_selection()
+    }
+
+    pub fn erase_at(&self, center: Vec2D, radius: f32) -> bool {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .erase_at(center, radius)
+    }
+
+    pub fn finish_erasing(&self) -> bool {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .finish_erasing()
+    }
+
+    pub fn duplicate_selection(&self) -> bool {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .duplicate_selection()
+    }
+
+    pub fn delete_selection(&self) -> bool {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .delete_selection()
+    }
+
+    pub fn delete_at(&self, pos: Vec2D) -> bool {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .delete_at(pos)
+    }
+
+    pub fn lock_selection(&self) -> bool {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .lock_selection()
+    }
+
+    pub fn select(&self, id: usize) -> bool {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .select(id)
+    }
+
+    pub fn layer_infos(&self) -> Vec<LayerInfo> {
+        self.imp()
+            .inner()
+            .as_ref()
+            .expect("Did you call init before using FemtoVgArea?")
+            .layer_infos()
+    }
+
+    pub fn annotation_specs(&self, selection: bool) -> (Vec<AnnotationSpec>, usize) {
+        self.imp()
+            .inner()
+            .as_ref()
+            .expect("Did you call init before using FemtoVgArea?")
+            .annotation_specs(selection)
+    }
+
+    pub fn insert_snippet(&mut self, specs: Vec<AnnotationSpec>) {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .insert_snippet(specs);
+    }
+
+    pub fn thumbnail(&self, bounds: (Vec2D, Vec2D)) -> Option<Pixbuf> {
+        self.imp()
+            .inner()
+            .as_ref()
+            .expect("Did you call init before using FemtoVgArea?")
+            .thumbnail(bounds)
+    }
+
+    pub fn nudge_selection(&self, offset: Vec2D) -> bool {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .nudge_selection(offset)
+    }
+
+    pub fn scale_selection(&self, factor: f32) -> bool {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .scale_selection(factor)
+    }
+
+    pub fn set_beautify(&self, beautify: bool) {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .set_beautify(beautify);
+    }
+
+    pub fn record_crop(&self) {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .record_crop()
+    }
+
+    pub fn set_adjustments(&self, adjustments: Adjustments) {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .set_adjustments(adjustments);
+    }
+
+    pub fn set_peek(&self, peek: bool) -> bool {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .set_peek(peek)
+    }
+
+    pub fn set_color_vision(&self, color_vision: Option<ColorVision>) -> bool {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .set_color_vision(color_vision)
+    }
+
+    pub fn color_vision(&self) -> Option<ColorVision> {
+        self.imp()
+            .inner()
+            .as_ref()
+            .expect("Did you call init before using FemtoVgArea?")
+            .color_vision()
+    }
+
+    /// Highlights the region being chosen for export, or hides it.
+    pub fn set_export_region(&self, region: Option<(Vec2D, Vec2D)>) {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .set_export_region(region);
+    }
+
+    pub fn request_render_region(&self, actions: &[Action], region: (Vec2D, Vec2D)) {
+        self.imp().request_render_region(actions, region);
+    }
+
+    /// Shows the pixel inspector for `pos` in image coordinates, or hides it.
+    pub fn set_inspector(&self, pos: Option<Vec2D>) {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .set_inspector(pos);
+    }
+
+    /// Renders the annotations again, after their visibility or order changed.
+    pub fn invalidate_annotations(&self) {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .invalidate_annotations();
+    }
+
+    /// Renders the whole canvas with the next frame, instead of only where the drawable of
+    /// the active tool changed.
+    pub fn invalidate(&self) {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .invalidate();
+    }
+
+    pub fn pixel_at(&self, pos: Vec2D) -> Option<crate::style::Color> {
+        self.imp()
+            .inner()
+            .as_ref()
+            .expect("Did you call init before using FemtoVgArea?")
+            .pixel_at(pos)
+    }
+
+    pub fn is_in_minimap(&self, input: Vec2D) -> bool {
+        self.imp()
+            .inner()
+            .as_ref()
+            .expect("Did you call init before using FemtoVgArea?")
+            .is_in_minimap(input, self.scale_factor() as f32)
+    }
+
+    pub fn center_on_minimap_position(&self, input: Vec2D) {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .center_on_minimap_position(input, self.scale_factor() as f32);
+        //trigger resize to recalculate offset
+        self.imp().resize(0, 0);
+    }
+
     pub fn init(
         &mut self,
         sender: Sender<SketchBoardInput>,
         crop_tool: Rc<RefCell<CropTool>>,
         active_tool: Rc<RefCell<dyn Tool>>,
+        layers: Rc<RefCell<Layers>>,
         background_image: Pixbuf,
     ) {
         self.imp()
-            .init(sender, crop_tool, active_tool, background_image);
+            .init(sender, crop_tool, active_tool, layers, background_image);
     }
 
     pub fn set_zoom_scale(&self, factor: f32) {
@@ -113,6 +419,43 @@ impl FemtoVGArea {
         self.imp().resize(0, 0);
     }
 
+    pub fn zoom_to_width(&self) {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .zoom_to_width();
+        //trigger resize to recalculate offset
+        self.imp().resize(0, 0);
+    }
+
+    pub fn zoom_to_rect(&self, rect: (Vec2D, Vec2D)) {
+        self.imp()
+            .inner()
+            .as_mut()
+            .expect("Did you call init before using FemtoVgArea?")
+            .zoom_to_rect(rect);
+        //trigger resize to recalculate offset
+        self.imp().resize(0, 0);
+    }
+
+    pub fn selection_bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.imp()
+            .inner()
+            .as_ref()
+            .expect("Did you call init before using FemtoVgArea?")
+            .selection_bounds()
+    }
+
+    /// Physical screen pixels per image pixel, 1.0 means 100%.
+    pub fn zoom_level(&self) -> f32 {
+        self.imp()
+            .inner()
+            .as_ref()
+            .expect("Did you call init before using FemtoVgArea?")
+            .scale_factor()
+    }
+
     pub fn set_pointer_offset(&self, offset: Vec2D) {
         self.imp()
             .inner()
@@ -0,0 +1,89 @@
+use gdk_pixbuf::Pixbuf;
+
+use crate::math::Vec2D;
+
+/// Number of pixels per value for each channel and the luma, of an image or a region of it.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    pub red: [u32; 256],
+    pub green: [u32; 256],
+    pub blue: [u32; 256],
+    pub luma: [u32; 256],
+    /// Size of the region counted, in pixels.
+    pub size: (usize, usize),
+}
+
+impl Histogram {
+    /// Counts the pixels of `image` within `region`, or all of them. Transparent pixels
+    /// are skipped.
+    pub fn new(image: &Pixbuf, region: Option<(Vec2D, Vec2D)>) -> Self {
+        let bytes = image.read_pixel_bytes();
+        let stride = image.rowstride() as usize;
+        let channels = image.n_channels() as usize;
+        let (width, height) = (image.width() as usize, image.height() as usize);
+
+        let clamp = |v: f32, max: usize| (v.round().max(0.0) as usize).min(max);
+        let (left, top, right, bottom) = match region {
+            Some((pos, size)) => (
+                clamp(pos.x, width),
+                clamp(pos.y, height),
+                clamp(pos.x + size.x, width),
+                clamp(pos.y + size.y, height),
+            ),
+            None => (0, 0, width, height),
+        };
+
+        let mut histogram = Self {
+            red: [0; 256],
+            green: [0; 256],
+            blue: [0; 256],
+            luma: [0; 256],
+            size: (right.saturating_sub(left), bottom.saturating_sub(top)),
+        };
+        for y in top..bottom {
+            for x in left..right {
+                let p = y * stride + x * channels;
+                if channels == 4 && bytes[p + 3] == 0 {
+                    continue;
+                }
+                let (r, g, b) = (bytes[p], bytes[p + 1], bytes[p + 2]);
+                histogram.red[r as usize] += 1;
+                histogram.green[g as usize] += 1;
+                histogram.blue[b as usize] += 1;
+                // integer approximation of https://en.wikipedia.org/wiki/Luma_(video)
+                let luma = (r as u32 * 54 + g as u32 * 183 + b as u32 * 19) >> 8;
+                histogram.luma[luma as usize] += 1;
+            }
+        }
+        histogram
+    }
+
+    /// Share of the counted pixels at 0 and at 255, in the channel where it is highest. A
+    /// lot of either is a sign of under- or overexposure.
+    pub fn clipped(&self) -> (f32, f32) {
+        let total = self.luma.iter().sum::<u32>().max(1) as f32;
+        let count = |v: usize| {
+            [&self.red, &self.green, &self.blue]
+                .iter()
+                .map(|c| c[v])
+                .max()
+                .unwrap_or_default()
+        };
+        (count(0) as f32 / total, count(255) as f32 / total)
+    }
+
+    /// Average luma from 0 to 255.
+    pub fn mean_luma(&self) -> f32 {
+        let total = self.luma.iter().sum::<u32>();
+        if total == 0 {
+            return 0.0;
+        }
+        let sum: u64 = self
+            .luma
+            .iter()
+            .enumerate()
+            .map(|(v, n)| v as u64 * *n as u64)
+            .sum();
+        sum as f32 / total as f32
+    }
+}
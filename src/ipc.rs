@@ -1,22 +1,58 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
 use gdk_pixbuf::gio;
 use gio::prelude::*;
 use gio::DBusConnection;
-use glib::Variant;
+use glib::{Variant, VariantDict};
 use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::command_line;
+use crate::configuration::SessionOptions;
+use crate::tools::Tools;
+
+/// The response to a message, which may need to wait for e.g. an image to be decoded.
+pub type IpcReply = Pin<Box<dyn Future<Output = IpcResponse>>>;
 
 #[derive(Debug, Clone)]
 pub enum IpcMessage {
-    LoadImage { filename: String },
+    LoadImage {
+        filename: String,
+        options: SessionOptions,
+        /// What the image was read from if `filename` is a temporary copy, e.g. `stdin`.
+        source: Option<String>,
+    },
+    ListRecent,
+    LoadRecent {
+        index: u32,
+    },
+    GetState,
+    Save,
+    CopyToClipboard,
+    Undo,
+    Redo,
+    SwitchTool {
+        tool: Tools,
+    },
+    AddAnnotations {
+        json: String,
+    },
+    /// One of the messages above that control the editor, for the window with id `window`.
+    ControlWindow {
+        window: usize,
+        message: Box<IpcMessage>,
+    },
     Shutdown,
     Ping,
+    GetCapabilities,
 }
 
 #[derive(Debug, Clone)]
 pub enum IpcResponse {
     Ok,
     Error(String),
-    Pong,
+    Text(String),
 }
 
 pub const DBUS_INTERFACE_XML: &str = r#"
@@ -26,19 +62,89 @@ pub const DBUS_INTERFACE_XML: &str = r#"
       <arg type='s' name='filename' direction='in'/>
       <arg type='s' name='response' direction='out'/>
     </method>
+    <method name='LoadImageWithOptions'>
+      <arg type='s' name='filename' direction='in'/>
+      <arg type='a{sv}' name='options' direction='in'/>
+      <arg type='s' name='response' direction='out'/>
+    </method>
+    <method name='ListRecent'>
+      <arg type='s' name='response' direction='out'/>
+    </method>
+    <method name='LoadRecent'>
+      <arg type='u' name='index' direction='in'/>
+      <arg type='s' name='response' direction='out'/>
+    </method>
+    <method name='GetState'>
+      <arg type='s' name='response' direction='out'/>
+    </method>
+    <method name='Save'>
+      <arg type='s' name='response' direction='out'/>
+    </method>
+    <method name='CopyToClipboard'>
+      <arg type='s' name='response' direction='out'/>
+    </method>
+    <method name='Undo'>
+      <arg type='s' name='response' direction='out'/>
+    </method>
+    <method name='Redo'>
+      <arg type='s' name='response' direction='out'/>
+    </method>
+    <method name='SwitchTool'>
+      <arg type='s' name='tool' direction='in'/>
+      <arg type='s' name='response' direction='out'/>
+    </method>
+    <method name='AddAnnotations'>
+      <arg type='s' name='annotations' direction='in'/>
+      <arg type='s' name='response' direction='out'/>
+    </method>
+    <method name='ControlWindow'>
+      <arg type='u' name='window' direction='in'/>
+      <arg type='s' name='method' direction='in'/>
+      <arg type='s' name='argument' direction='in'/>
+      <arg type='s' name='response' direction='out'/>
+    </method>
     <method name='Shutdown'>
       <arg type='s' name='response' direction='out'/>
     </method>
     <method name='Ping'>
       <arg type='s' name='response' direction='out'/>
     </method>
+    <method name='GetCapabilities'>
+      <arg type='s' name='response' direction='out'/>
+    </method>
   </interface>
 </node>
 "#;
 
 pub const DBUS_INTERFACE_NAME: &str = "com.gabm.satty.IPC";
+/// Raised whenever methods of the interface change incompatibly. Since 2, `LoadImage` only
+/// answers once the image is loaded. Since 3, `LoadImage` and `LoadRecent` answer with the id
+/// of the window showing the image.
+pub const PROTOCOL_VERSION: u32 = 3;
+/// Methods of `DBUS_INTERFACE_XML`, reported by `Ping` and `GetCapabilities`.
+pub const METHODS: &[&str] = &[
+    "LoadImage",
+    "LoadImageWithOptions",
+    "ListRecent",
+    "LoadRecent",
+    "GetState",
+    "Save",
+    "CopyToClipboard",
+    "Undo",
+    "Redo",
+    "SwitchTool",
+    "AddAnnotations",
+    "ControlWindow",
+    "Shutdown",
+    "Ping",
+    "GetCapabilities",
+];
 pub const DBUS_OBJECT_PATH: &str = "/com/gabm/satty/IPC";
 
+// milliseconds to wait for an answer, `LoadImage` waits as long as decoding the image takes
+const CALL_TIMEOUT: i32 = 5000;
+const LOAD_IMAGE_TIMEOUT: i32 = i32::MAX;
+
 impl IpcMessage {
     pub fn from_method_call(
         method: &str,
@@ -52,16 +158,215 @@ impl IpcMessage {
                         "Invalid filename parameter",
                     )
                 })?;
-                Ok(IpcMessage::LoadImage { filename })
+                Ok(IpcMessage::LoadImage {
+                    filename,
+                    options: SessionOptions::default(),
+                    source: None,
+                })
+            }
+            "LoadImageWithOptions" => {
+                let (filename, options): (String, VariantDict) = params.get().ok_or_else(|| {
+                    glib::Error::new(
+                        gio::DBusError::InvalidArgs,
+                        "Invalid filename or options parameter",
+                    )
+                })?;
+                let source = options.lookup::<String>("source").map_err(|_| {
+                    glib::Error::new(
+                        gio::DBusError::InvalidArgs,
+                        "Invalid value for option 'source'",
+                    )
+                })?;
+                Ok(IpcMessage::LoadImage {
+                    filename,
+                    options: session_options_from_dict(&options)?,
+                    source,
+                })
+            }
+            "ListRecent" => Ok(IpcMessage::ListRecent),
+            "LoadRecent" => {
+                let (index,): (u32,) = params.get().ok_or_else(|| {
+                    glib::Error::new(gio::DBusError::InvalidArgs, "Invalid index parameter")
+                })?;
+                Ok(IpcMessage::LoadRecent { index })
+            }
+            "GetState" => Ok(IpcMessage::GetState),
+            "Save" => Ok(IpcMessage::Save),
+            "CopyToClipboard" => Ok(IpcMessage::CopyToClipboard),
+            "Undo" => Ok(IpcMessage::Undo),
+            "Redo" => Ok(IpcMessage::Redo),
+            "SwitchTool" => {
+                let (name,): (String,) = params.get().ok_or_else(|| {
+                    glib::Error::new(gio::DBusError::InvalidArgs, "Invalid tool parameter")
+                })?;
+                let tool = command_line::Tools::from_str(&name, true).map_err(|_| {
+                    glib::Error::new(
+                        gio::DBusError::InvalidArgs,
+                        &format!("Unknown tool '{name}'"),
+                    )
+                })?;
+                Ok(IpcMessage::SwitchTool { tool: tool.into() })
+            }
+            "AddAnnotations" => {
+                let (json,): (String,) = params.get().ok_or_else(|| {
+                    glib::Error::new(gio::DBusError::InvalidArgs, "Invalid annotations parameter")
+                })?;
+                Ok(IpcMessage::AddAnnotations { json })
+            }
+            "ControlWindow" => {
+                let (window, method, argument): (u32, String, String) =
+                    params.get().ok_or_else(|| {
+                        glib::Error::new(
+                            gio::DBusError::InvalidArgs,
+                            "Invalid window, method or argument parameter",
+                        )
+                    })?;
+                let params = match method.as_str() {
+                    "SwitchTool" | "AddAnnotations" => (argument,).to_variant(),
+                    _ => ().to_variant(),
+                };
+                let message = Self::from_method_call(&method, params)?;
+                if !message.controls_window() {
+                    return Err(glib::Error::new(
+                        gio::DBusError::InvalidArgs,
+                        &format!("'{method}' does not control a window"),
+                    ));
+                }
+                Ok(IpcMessage::ControlWindow {
+                    window: window as usize,
+                    message: Box::new(message),
+                })
             }
             "Shutdown" => Ok(IpcMessage::Shutdown),
             "Ping" => Ok(IpcMessage::Ping),
+            "GetCapabilities" => Ok(IpcMessage::GetCapabilities),
             _ => Err(glib::Error::new(
                 gio::DBusError::UnknownMethod,
                 "Unknown method",
             )),
         }
     }
+
+    /// Whether the message acts on the image being edited, and may be sent to a single window.
+    pub fn controls_window(&self) -> bool {
+        matches!(
+            self,
+            IpcMessage::Save
+                | IpcMessage::CopyToClipboard
+                | IpcMessage::Undo
+                | IpcMessage::Redo
+                | IpcMessage::SwitchTool { .. }
+                | IpcMessage::AddAnnotations { .. }
+        )
+    }
+
+    fn to_method_call(&self) -> (&'static str, Variant) {
+        match self {
+            IpcMessage::LoadImage {
+                filename,
+                options,
+                source,
+            } => (
+                "LoadImageWithOptions",
+                Variant::tuple_from_iter([
+                    filename.to_variant(),
+                    session_options_to_dict(options, source.as_deref()),
+                ]),
+            ),
+            IpcMessage::ListRecent => ("ListRecent", ().to_variant()),
+            IpcMessage::LoadRecent { index } => ("LoadRecent", (index,).to_variant()),
+            IpcMessage::GetState => ("GetState", ().to_variant()),
+            IpcMessage::Save => ("Save", ().to_variant()),
+            IpcMessage::CopyToClipboard => ("CopyToClipboard", ().to_variant()),
+            IpcMessage::Undo => ("Undo", ().to_variant()),
+            IpcMessage::Redo => ("Redo", ().to_variant()),
+            IpcMessage::SwitchTool { tool } => ("SwitchTool", (tool.to_string(),).to_variant()),
+            IpcMessage::AddAnnotations { json } => ("AddAnnotations", (json,).to_variant()),
+            IpcMessage::ControlWindow { window, message } => {
+                let (method_name, params) = message.to_method_call();
+                // the argument of the method, if it takes one
+                let argument = params
+                    .try_child_value(0)
+                    .and_then(|argument| argument.get::<String>())
+                    .unwrap_or_default();
+                (
+                    "ControlWindow",
+                    (*window as u32, method_name.to_string(), argument).to_variant(),
+                )
+            }
+            IpcMessage::Shutdown => ("Shutdown", ().to_variant()),
+            IpcMessage::Ping => ("Ping", ().to_variant()),
+            IpcMessage::GetCapabilities => ("GetCapabilities", ().to_variant()),
+        }
+    }
+}
+
+/// Known keys are `output-filename` (s or as), `early-exit` (b), `initial-tool` (s) and
+/// `fullscreen` (b), unknown keys are ignored. `source` (s) is read along with them.
+fn session_options_from_dict(dict: &VariantDict) -> Result<SessionOptions, glib::Error> {
+    let invalid = |key: &str| {
+        glib::Error::new(
+            gio::DBusError::InvalidArgs,
+            &format!("Invalid value for option '{key}'"),
+        )
+    };
+
+    let initial_tool = match dict
+        .lookup::<String>("initial-tool")
+        .map_err(|_| invalid("initial-tool"))?
+    {
+        Some(name) => Some(
+            command_line::Tools::from_str(&name, true)
+                .map_err(|_| invalid("initial-tool"))?
+                .into(),
+        ),
+        None => None,
+    };
+
+    let output_filenames = match dict.lookup_value("output-filename", None) {
+        Some(value) => Some(
+            value
+                .get::<String>()
+                .map(|filename| vec![filename])
+                .or_else(|| value.get::<Vec<String>>())
+                .ok_or_else(|| invalid("output-filename"))?,
+        ),
+        None => None,
+    };
+
+    Ok(SessionOptions {
+        output_filenames,
+        early_exit: dict
+            .lookup("early-exit")
+            .map_err(|_| invalid("early-exit"))?,
+        initial_tool,
+        fullscreen: dict
+            .lookup("fullscreen")
+            .map_err(|_| invalid("fullscreen"))?,
+    })
+}
+
+fn session_options_to_dict(options: &SessionOptions, source: Option<&str>) -> Variant {
+    let dict = VariantDict::new(None);
+    // a single filename is sent as string, which daemons before multiple outputs understand
+    match options.output_filenames.as_deref() {
+        Some([filename]) => dict.insert_value("output-filename", &filename.to_variant()),
+        Some(filenames) => dict.insert_value("output-filename", &filenames.to_variant()),
+        None => {}
+    }
+    if let Some(v) = options.early_exit {
+        dict.insert_value("early-exit", &v.to_variant());
+    }
+    if let Some(v) = options.initial_tool {
+        dict.insert_value("initial-tool", &v.to_string().to_variant());
+    }
+    if let Some(v) = options.fullscreen {
+        dict.insert_value("fullscreen", &v.to_variant());
+    }
+    if let Some(v) = source {
+        dict.insert_value("source", &v.to_variant());
+    }
+    dict.end()
 }
 
 impl IpcResponse {
@@ -69,7 +374,7 @@ impl IpcResponse {
         match self {
             IpcResponse::Ok => ("Ok",).to_variant(),
             IpcResponse::Error(msg) => (format!("Error: {}", msg),).to_variant(),
-            IpcResponse::Pong => ("Pong",).to_variant(),
+            IpcResponse::Text(text) => (text,).to_variant(),
         }
     }
 }
@@ -91,7 +396,7 @@ impl IpcServer {
         callback: F,
     ) -> Result<()>
     where
-        F: Fn(IpcMessage) -> IpcResponse + 'static,
+        F: Fn(IpcMessage) -> IpcReply + 'static,
     {
         let interface_info = gio::DBusNodeInfo::for_xml(DBUS_INTERFACE_XML)
             .ok()
@@ -104,8 +409,10 @@ impl IpcServer {
                 let callback = &callback;
                 match IpcMessage::from_method_call(method, params) {
                     Ok(message) => {
-                        let response = callback(message);
-                        invocation.return_value(Some(&response.to_variant()));
+                        let reply = callback(message);
+                        glib::spawn_future_local(async move {
+                            invocation.return_value(Some(&reply.await.to_variant()));
+                        });
                     }
                     Err(e) => {
                         invocation.return_gerror(e);
@@ -122,43 +429,143 @@ impl IpcServer {
 pub struct IpcClient;
 
 impl IpcClient {
+    /// Whether `error` was returned by `send_message` because no daemon owns the bus name.
+    pub fn is_daemon_missing(error: &anyhow::Error) -> bool {
+        error.downcast_ref::<glib::Error>().is_some_and(|e| {
+            e.matches(gio::DBusError::ServiceUnknown) || e.matches(gio::DBusError::NameHasNoOwner)
+        })
+    }
+
     pub async fn send_message(message: &IpcMessage) -> Result<IpcResponse> {
         let connection = gio::bus_get_future(gio::BusType::Session)
             .await
             .context("Failed to connect to session bus")?;
 
-        let (method_name, params): (&str, Variant) = match message {
-            IpcMessage::LoadImage { filename } => ("LoadImage", (filename,).to_variant()),
-            IpcMessage::Shutdown => ("Shutdown", ().to_variant()),
-            IpcMessage::Ping => ("Ping", ().to_variant()),
+        let (method_name, params) = message.to_method_call();
+        let timeout = match message {
+            IpcMessage::LoadImage { .. } => LOAD_IMAGE_TIMEOUT,
+            _ => CALL_TIMEOUT,
+        };
+
+        let response_str = match Self::call(&connection, method_name, &params, timeout).await {
+            Ok(response) => response,
+            Err(e) if e.matches(gio::DBusError::UnknownMethod) => match message {
+                // daemons from before LoadImageWithOptions
+                IpcMessage::LoadImage { filename, .. } => {
+                    eprintln!("Warning: the daemon is too old to take options, they are ignored");
+                    Self::call(&connection, "LoadImage", &(filename,).to_variant(), timeout)
+                        .await
+                        .context("Failed to call DBus method")?
+                }
+                _ => return Err(Self::unsupported_method(&connection, method_name).await),
+            },
+            Err(e) => {
+                return Err(anyhow::Error::new(e)
+                    .context("Failed to call DBus method (is the daemon running?)"))
+            }
         };
 
+        if response_str.starts_with("Error:") {
+            Ok(IpcResponse::Error(
+                response_str
+                    .strip_prefix("Error: ")
+                    .unwrap_or(&response_str)
+                    .to_string(),
+            ))
+        } else if matches!(
+            message,
+            IpcMessage::LoadImage { .. }
+                | IpcMessage::LoadRecent { .. }
+                | IpcMessage::ListRecent
+                | IpcMessage::GetState
+                | IpcMessage::Ping
+                | IpcMessage::GetCapabilities
+        ) {
+            Ok(IpcResponse::Text(response_str))
+        } else {
+            Ok(IpcResponse::Ok)
+        }
+    }
+
+    async fn call(
+        connection: &DBusConnection,
+        method_name: &str,
+        params: &Variant,
+        timeout: i32,
+    ) -> Result<String, glib::Error> {
         let result = connection
             .call_future(
                 Some("com.gabm.satty"),
                 DBUS_OBJECT_PATH,
                 DBUS_INTERFACE_NAME,
                 method_name,
-                Some(&params),
+                Some(params),
                 None,
                 gio::DBusCallFlags::NONE,
-                5000,
+                timeout,
             )
+            .await?;
+
+        let (response_str,): (String,) = result.get().ok_or_else(|| {
+            glib::Error::new(
+                gio::DBusError::InvalidSignature,
+                "Failed to parse DBus response",
+            )
+        })?;
+        Ok(response_str)
+    }
+
+    /// The protocol version of the running daemon, 1 for daemons from before
+    /// `GetCapabilities`.
+    pub async fn protocol_version() -> Result<u64> {
+        let connection = gio::bus_get_future(gio::BusType::Session)
             .await
-            .context("Failed to call DBus method (is the daemon running?)")?;
+            .context("Failed to connect to session bus")?;
+        match Self::call(
+            &connection,
+            "GetCapabilities",
+            &().to_variant(),
+            CALL_TIMEOUT,
+        )
+        .await
+        {
+            Ok(capabilities) => Ok(Self::parse_protocol_version(&capabilities).unwrap_or(1)),
+            Err(e) if e.matches(gio::DBusError::UnknownMethod) => Ok(1),
+            Err(e) => Err(anyhow::Error::new(e)
+                .context("Failed to call DBus method (is the daemon running?)")),
+        }
+    }
 
-        let (response_str,): (String,) = result
-            .get()
-            .context("Failed to parse DBus response")?;
+    fn parse_protocol_version(capabilities: &str) -> Option<u64> {
+        serde_json::from_str::<serde_json::Value>(capabilities)
+            .ok()?
+            .get("protocol_version")?
+            .as_u64()
+    }
 
-        if response_str.starts_with("Error:") {
-            Ok(IpcResponse::Error(
-                response_str.strip_prefix("Error: ").unwrap_or(&response_str).to_string()
-            ))
-        } else if response_str == "Pong" {
-            Ok(IpcResponse::Pong)
-        } else {
-            Ok(IpcResponse::Ok)
+    /// Explains why the daemon doesn't know `method_name`, based on its protocol version.
+    async fn unsupported_method(connection: &DBusConnection, method_name: &str) -> anyhow::Error {
+        let capabilities = Self::call(
+            connection,
+            "GetCapabilities",
+            &().to_variant(),
+            CALL_TIMEOUT,
+        )
+        .await;
+        let daemon_version = capabilities
+            .ok()
+            .and_then(|capabilities| Self::parse_protocol_version(&capabilities));
+
+        match daemon_version {
+            Some(version) if version > u64::from(PROTOCOL_VERSION) => anyhow!(
+                "The daemon does not support {method_name}, it uses the newer protocol version {version} (this client uses {PROTOCOL_VERSION}). Please update this client"
+            ),
+            Some(version) => anyhow!(
+                "The daemon does not support {method_name}, it uses protocol version {version} (this client uses {PROTOCOL_VERSION}). Please restart the daemon"
+            ),
+            None => anyhow!(
+                "The daemon is older than this client and does not support {method_name}. Please restart the daemon"
+            ),
         }
     }
 }
@@ -0,0 +1,89 @@
+use std::collections::HashSet;
+
+use crate::{math::Vec2D, style::Color, tools::Drawable};
+
+/// An annotation as listed in the layers panel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerInfo {
+    pub id: usize,
+    pub name: &'static str,
+    pub color: Option<Color>,
+    pub bounds: Option<(Vec2D, Vec2D)>,
+    pub visible: bool,
+    pub locked: bool,
+    pub selected: bool,
+}
+
+/// Stacking order, visibility and locks of the annotations, which are
+/// identified by their id in the undo history.
+///
+/// None of this is part of the undo history: undoing an annotation and
+/// redoing it again keeps its place in the stack, visibility and lock.
+#[derive(Default)]
+pub struct Layers {
+    // annotation ids from bottom to top, annotations missing here are stacked on
+    // top in the order they were drawn
+    order: Vec<usize>,
+    hidden: HashSet<usize>,
+    locked: HashSet<usize>,
+}
+
+impl Layers {
+    /// Sorts the annotations by their stacking order, from bottom to top.
+    pub fn arrange<'a>(
+        &self,
+        annotations: impl Iterator<Item = (usize, &'a dyn Drawable)>,
+    ) -> Vec<(usize, &'a dyn Drawable)> {
+        let mut annotations: Vec<_> = annotations.collect();
+        annotations.sort_by_key(|(id, _)| {
+            self.order
+                .iter()
+                .position(|o| o == id)
+                .unwrap_or(usize::MAX)
+        });
+        annotations
+    }
+
+    /// Moves annotation `id` to `index` within `ids`, which are the annotations
+    /// as currently stacked from bottom to top.
+    pub fn move_to(&mut self, mut ids: Vec<usize>, id: usize, index: usize) {
+        let Some(current) = ids.iter().position(|&i| i == id) else {
+            return;
+        };
+        ids.remove(current);
+        ids.insert(index.min(ids.len()), id);
+        self.order = ids;
+    }
+
+    pub fn is_hidden(&self, id: usize) -> bool {
+        self.hidden.contains(&id)
+    }
+
+    pub fn set_hidden(&mut self, id: usize, hidden: bool) {
+        if hidden {
+            self.hidden.insert(id);
+        } else {
+            self.hidden.remove(&id);
+        }
+    }
+
+    pub fn is_locked(&self, id: usize) -> bool {
+        self.locked.contains(&id)
+    }
+
+    pub fn set_locked(&mut self, id: usize, locked: bool) {
+        if locked {
+            self.locked.insert(id);
+        } else {
+            self.locked.remove(&id);
+        }
+    }
+
+    pub fn unlock_all(&mut self) {
+        self.locked.clear();
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
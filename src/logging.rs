@@ -0,0 +1,186 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{Context, Result};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::configuration::{LogLevel, APP_CONFIG};
+
+#[cfg(target_os = "linux")]
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+enum Target {
+    Stderr,
+    File(Mutex<File>),
+    /// The native protocol of the systemd journal, which keeps the level of each message.
+    #[cfg(target_os = "linux")]
+    Journal(std::os::unix::net::UnixDatagram),
+}
+
+struct Logger {
+    // set by `init` once the configuration is read
+    target: OnceLock<Target>,
+    // logged before that, e.g. while reading the configuration
+    early: Mutex<Vec<(Level, String)>>,
+}
+
+static LOGGER: Logger = Logger {
+    target: OnceLock::new(),
+    early: Mutex::new(Vec::new()),
+};
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => Self::Error,
+            LogLevel::Warn => Self::Warn,
+            LogLevel::Info => Self::Info,
+            LogLevel::Debug => Self::Debug,
+            LogLevel::Trace => Self::Trace,
+        }
+    }
+}
+
+/// Keeps the messages logged from now on until `init` knows where to send them, so that
+/// reading the configuration can log as well.
+pub fn start() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Trace);
+    }
+}
+
+/// Sends the log messages to the configured file, to the journal when the daemon runs as
+/// a systemd service, or else to stderr.
+pub fn init(daemon: bool) {
+    let (level, file) = {
+        let config = APP_CONFIG.read();
+        (config.log_level(), config.log_file().map(String::from))
+    };
+
+    let mut error = None;
+    let target = match file {
+        Some(path) => match open_file(&path) {
+            Ok(file) => Target::File(Mutex::new(file)),
+            Err(e) => {
+                error = Some(e);
+                Target::Stderr
+            }
+        },
+        None if daemon && under_systemd() => journal_target(),
+        None => Target::Stderr,
+    };
+
+    start();
+    if LOGGER.target.set(target).is_err() {
+        return;
+    }
+    log::set_max_level(level.into());
+    let early = std::mem::take(&mut *LOGGER.early.lock().unwrap());
+    for (level, message) in early {
+        log::log!(level, "{message}");
+    }
+    if let Some(e) = error {
+        log::warn!("{e:#}, logging to stderr");
+    }
+}
+
+fn open_file(path: &str) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Cannot open log file {path}"))
+}
+
+// systemd connects stderr to the journal and announces it in JOURNAL_STREAM
+fn under_systemd() -> bool {
+    APP_CONFIG.read().systemd() || std::env::var_os("JOURNAL_STREAM").is_some()
+}
+
+#[cfg(target_os = "linux")]
+fn journal_target() -> Target {
+    match std::os::unix::net::UnixDatagram::unbound() {
+        Ok(socket) => Target::Journal(socket),
+        Err(_) => Target::Stderr,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn journal_target() -> Target {
+    Target::Stderr
+}
+
+impl Logger {
+    #[cfg(target_os = "linux")]
+    fn send_to_journal(
+        socket: &std::os::unix::net::UnixDatagram,
+        record: &Record,
+    ) -> std::io::Result<()> {
+        // syslog priorities
+        let priority = match record.level() {
+            Level::Error => 3,
+            Level::Warn => 4,
+            Level::Info => 6,
+            Level::Debug | Level::Trace => 7,
+        };
+        let message = record.args().to_string();
+        let mut data = format!("PRIORITY={priority}\nSYSLOG_IDENTIFIER=satty\n").into_bytes();
+        // the length prefixed form allows newlines in the message
+        data.extend_from_slice(b"MESSAGE\n");
+        data.extend_from_slice(&(message.len() as u64).to_le_bytes());
+        data.extend_from_slice(message.as_bytes());
+        data.push(b'\n');
+        socket.send_to(&data, JOURNAL_SOCKET)?;
+        Ok(())
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let Some(target) = self.target.get() else {
+            if let Ok(mut early) = self.early.lock() {
+                early.push((record.level(), record.args().to_string()));
+            }
+            return;
+        };
+        match target {
+            Target::Stderr => eprintln!("{:5} {}", record.level(), record.args()),
+            Target::File(file) => {
+                if let Ok(mut file) = file.lock() {
+                    let _ = writeln!(
+                        file,
+                        "{} {:5} {}",
+                        chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                        record.level(),
+                        record.args()
+                    );
+                }
+            }
+            #[cfg(target_os = "linux")]
+            Target::Journal(socket) => {
+                // the journal may be gone while shutting down
+                if Self::send_to_journal(socket, record).is_err() {
+                    eprintln!("{:5} {}", record.level(), record.args());
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(Target::File(file)) = self.target.get() {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
@@ -1,16 +1,22 @@
-use std::io::Read;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::rc::Rc;
 use std::sync::LazyLock;
 use std::{fs, ptr};
 use std::{io, time::Duration};
 
 use clap::Parser;
-use configuration::{Configuration, APP_CONFIG};
+use command_line::Command;
+use configuration::{Configuration, KeyboardMode, SessionOptions, WindowMode, APP_CONFIG};
 use gdk_pixbuf::gio::ApplicationFlags;
-use gdk_pixbuf::{Pixbuf, PixbufLoader};
+use gdk_pixbuf::{Pixbuf, PixbufAnimation, PixbufLoader};
 use glib::MainContext;
 use gtk::prelude::*;
 
-use relm4::gtk::gdk::Rectangle;
+use relm4::gtk::cairo::{RectangleInt, Region};
+use relm4::gtk::gdk::{Monitor, Rectangle};
 
 use relm4::{
     gtk::{self, gdk::DisplayManager, CssProvider, Window},
@@ -20,24 +26,54 @@ use gtk4_layer_shell::{Edge, Layer, LayerShell};
 
 use anyhow::{anyhow, Context, Result};
 
-use sketch_board::SketchBoardOutput;
-use ui::toolbars::{StyleToolbar, StyleToolbarInput, ToolsToolbar, ToolsToolbarInput};
+use annotations::AnnotationSpec;
+use daemon::DaemonState;
+use histogram::Histogram;
+use recent_images::RecentImages;
+use sketch_board::{EditorState, SketchBoardOutput};
+use ui::adjustments_panel::{AdjustmentsPanel, AdjustmentsPanelInput};
+use ui::frame_scrubber::{FrameScrubber, FrameScrubberInput};
+use ui::histogram_panel::{HistogramPanel, HistogramPanelInput};
+use ui::layers_panel::{LayerRow, LayersPanel, LayersPanelInput};
+use ui::recent_gallery::{RecentEntry, RecentGallery, RecentGalleryInput, RecentGalleryOutput};
+use ui::snippets_panel::{SnippetsPanel, SnippetsPanelInput};
+use ui::toasts::Severity;
+use ui::toolbars::{
+    StyleToolbar, StyleToolbarInput, ToolbarEvent, ToolsToolbar, ToolsToolbarInput,
+};
 use xdg::BaseDirectories;
 
+mod adjustments;
+mod annotations;
+mod capture;
 mod client;
+mod clipboard;
 mod command_line;
 mod configuration;
 mod daemon;
+mod edge_detection;
+mod external_command;
 mod femtovg_area;
+mod histogram;
 mod icons;
 mod ime;
 mod ipc;
+mod layers;
+mod logging;
 mod math;
 mod notification;
+mod ocr;
+mod profiling;
+mod progress;
+mod recent_images;
+mod recording;
 mod sketch_board;
+mod snippets;
 mod style;
 mod tools;
+mod tray;
 mod ui;
+mod undo_tree;
 
 use crate::sketch_board::{SketchBoard, SketchBoardInput};
 use crate::tools::Tools;
@@ -45,24 +81,46 @@ use crate::tools::Tools;
 pub static START_TIME: LazyLock<chrono::DateTime<chrono::Local>> =
     LazyLock::new(chrono::Local::now);
 
-macro_rules! generate_profile_output {
-    ($e: expr) => {
-        if (APP_CONFIG.read().profile_startup()) {
-            eprintln!(
-                "{:5} ms time elapsed: {}",
-                (chrono::Local::now() - *START_TIME).num_milliseconds(),
-                $e
-            );
-        }
-    };
-}
-
 struct App {
     image_dimensions: (i32, i32),
+    // false when running as a regular window, by choice or because layer-shell is not available
+    layer_shell: bool,
+    toolbars_visible: bool,
     sketch_board: Controller<SketchBoard>,
     tools_toolbar: Controller<ToolsToolbar>,
     style_toolbar: Controller<StyleToolbar>,
-    is_daemon: bool,
+    layers_panel: Controller<LayersPanel>,
+    snippets_panel: Controller<SnippetsPanel>,
+    adjustments_panel: Controller<AdjustmentsPanel>,
+    histogram_panel: Controller<HistogramPanel>,
+    frame_scrubber: Controller<FrameScrubber>,
+    recent_gallery: Controller<RecentGallery>,
+    recent_images: Rc<RefCell<RecentImages>>,
+    daemon_state: Rc<RefCell<DaemonState>>,
+    mode: AppMode,
+    // additional editor windows opened by the daemon, by id
+    windows: HashMap<usize, Controller<App>>,
+    // overrides of the daemon request the image came with
+    session: SessionOptions,
+    // the images given on the command line and the one being annotated
+    input_files: Vec<String>,
+    input_index: usize,
+    // frames of the current input file if it is animated
+    frames: Vec<Pixbuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AppMode {
+    Standalone,
+    Daemon,
+    /// A window opened by the daemon for a single image, closed on exit.
+    DaemonWindow,
+}
+
+#[derive(Debug)]
+enum AppOutput {
+    WindowClosed,
+    EditorStateChanged(EditorState),
 }
 
 #[derive(Debug)]
@@ -72,7 +130,28 @@ enum AppInput {
     ToggleToolbarsDisplay,
     ToolSwitchShortcut(Tools),
     ColorSwitchShortcut(u64),
+    AnnotationSizeChanged(f32),
+    CornerRadiusChanged(f32),
+    LayersChanged(Vec<LayerRow>),
+    ToggleLayersPanel,
+    SnippetsChanged(Vec<String>),
+    ToggleSnippetsPanel,
+    ToggleAdjustmentsPanel,
+    ToggleHistogramPanel,
+    HistogramChanged(Box<Histogram>, bool),
+    SelectFrame(usize),
+    ToggleRecentImages,
+    LoadRecentImage(usize),
     LoadNewImage(Pixbuf),
+    /// An image received by the daemon with the overrides of the request, for the window with
+    /// the id.
+    ReceiveImage(Pixbuf, SessionOptions, usize),
+    NextImage,
+    WindowClosed(usize),
+    /// For the window with the id, or the most recently opened one.
+    RemoteControl(Option<usize>, ToolbarEvent),
+    AddAnnotations(Option<usize>, Vec<AnnotationSpec>),
+    EditorStateChanged(EditorState),
     ShowWindow,
     HideWindow,
     RequestExit,
@@ -81,9 +160,123 @@ enum AppInput {
 #[derive(Debug)]
 enum AppCommandOutput {
     ResetResizable,
+    /// The frames of the current input file, or why it couldn't be loaded.
+    ImageDecoded(Result<Vec<Pixbuf>, String>),
 }
 
 impl App {
+    /// Opens an additional editor window for `image`, used by the daemon.
+    fn open_window(
+        &mut self,
+        id: usize,
+        image: Pixbuf,
+        session: SessionOptions,
+        sender: &ComponentSender<Self>,
+    ) {
+        let (image_width, image_height) = (image.width(), image.height());
+
+        let window = App::builder()
+            .launch((image, AppMode::DaemonWindow, session))
+            .forward(sender.input_sender(), move |output| match output {
+                AppOutput::WindowClosed => AppInput::WindowClosed(id),
+                AppOutput::EditorStateChanged(state) => AppInput::EditorStateChanged(state),
+            });
+        window.widget().present();
+        self.windows.insert(id, window);
+        let mut daemon_state = self.daemon_state.borrow_mut();
+        daemon_state.image_size = Some((image_width, image_height));
+        daemon_state.windows.insert(id);
+    }
+
+    /// The window opened by the daemon with id `window`, or the most recently opened one
+    /// without an id. `None` stands for this window.
+    fn target_window(&self, window: Option<usize>) -> Option<&Controller<App>> {
+        match window {
+            Some(id) => self.windows.get(&id),
+            None => self
+                .windows
+                .iter()
+                .max_by_key(|(id, _)| **id)
+                .map(|(_, window)| window),
+        }
+    }
+
+    /// Passes the overrides of the daemon request on to the editor, e.g. the tool to start
+    /// with.
+    fn apply_session(&self) {
+        let (tool, can_save) = {
+            let config = APP_CONFIG.read();
+            (
+                self.session.initial_tool(&config),
+                !self.session.outputs(&config).is_empty(),
+            )
+        };
+        self.sketch_board
+            .emit(SketchBoardInput::SetSession(self.session.clone()));
+        self.sketch_board
+            .emit(SketchBoardInput::ToolbarEvent(ToolbarEvent::ToolSelected(
+                tool,
+            )));
+        self.tools_toolbar
+            .emit(ToolsToolbarInput::SwitchSelectedTool(tool));
+        self.tools_toolbar
+            .emit(ToolsToolbarInput::SetCanSave(can_save));
+    }
+
+    /// Adds the annotations of the `template` file to the image that was just loaded, like the
+    /// screenshot the template was saved with.
+    fn apply_template(&self, sender: &ComponentSender<Self>) {
+        let Some(path) = APP_CONFIG.read().template().map(str::to_string) else {
+            return;
+        };
+        match annotations::load_template(&path) {
+            Ok(annotations) => sender.input(AppInput::AddAnnotations(None, annotations)),
+            Err(e) => self.sketch_board.emit(SketchBoardInput::Report(
+                Severity::Warning,
+                format!("{e:#}"),
+            )),
+        }
+    }
+
+    /// Loads the current input file on a worker thread, the window shows that it is loading
+    /// meanwhile.
+    fn decode_input_file(&self, sender: &ComponentSender<Self>) {
+        let Some(filename) = self.input_files.get(self.input_index).cloned() else {
+            return;
+        };
+        self.sketch_board.emit(SketchBoardInput::ShowLoading);
+        profiling::begin("image decode");
+        sender.spawn_oneshot_command(move || {
+            AppCommandOutput::ImageDecoded(load_frames(&filename).map_err(|e| format!("{e:#}")))
+        });
+    }
+
+    /// Tells the sketch board which file is being annotated and shows the progress of the
+    /// batch in the title.
+    fn announce_input_file(&self, root: &Window) {
+        let Some(filename) = self.input_files.get(self.input_index) else {
+            return;
+        };
+        if filename == "-" {
+            return;
+        }
+        let name = Path::new(filename)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let batch_pending = self.input_index + 1 < self.input_files.len();
+        self.sketch_board
+            .emit(SketchBoardInput::SetInputFile(name, batch_pending));
+
+        if self.input_files.len() > 1 {
+            root.set_title(Some(&format!(
+                "Satty - {filename} ({}/{})",
+                self.input_index + 1,
+                self.input_files.len()
+            )));
+        }
+    }
+
     fn get_monitor_size(root: &Window) -> Option<Rectangle> {
         root.surface().and_then(|surface| {
             DisplayManager::get()
@@ -93,6 +286,29 @@ impl App {
         })
     }
 
+    /// Looks up a monitor by its connector name, e.g. `DP-1`.
+    fn find_monitor(name: &str) -> Option<Monitor> {
+        let monitors = DisplayManager::get().default_display()?.monitors();
+        (0..monitors.n_items())
+            .filter_map(|i| monitors.item(i).and_downcast::<Monitor>())
+            .find(|monitor| monitor.connector().as_deref() == Some(name))
+    }
+
+    /// Lets clicks pass through the overlay while the toolbars are hidden, if configured.
+    fn update_input_region(&self, root: &Window) {
+        if !self.layer_shell || !APP_CONFIG.read().click_through() {
+            return;
+        }
+        if let Some(surface) = root.surface() {
+            let region = if self.toolbars_visible {
+                Region::create_rectangle(&RectangleInt::new(0, 0, root.width(), root.height()))
+            } else {
+                Region::create()
+            };
+            surface.set_input_region(&region);
+        }
+    }
+
     fn resize_window_initial(&self, root: &Window, sender: ComponentSender<Self>) {
         let monitor_size = match Self::get_monitor_size(root) {
             Some(s) => s,
@@ -132,7 +348,7 @@ impl App {
 
         root.set_resizable(false);
 
-        if APP_CONFIG.read().fullscreen() {
+        if self.session.fullscreen(&APP_CONFIG.read()) {
             root.fullscreen();
         }
 
@@ -156,12 +372,53 @@ impl App {
                 min-width: 50rem;
                 min-height: 10rem;
             }
+            .root.translucent {background: transparent;}
             .toolbar {color: #f9f9f9 ; background: #00000099;}
+            .toasts {margin: 50px 6px;}
             .toast {
                 color: #f9f9f9;
                 background: #00000099;
                 border-radius: 6px;
-                margin-top: 50px;
+            }
+            .toast-success {background: #26a269cc;}
+            .toast-warning {background: #c64600cc;}
+            .toast-error {background: #c01c28cc;}
+            .status-bar {
+                color: #f9f9f9;
+                background: #00000099;
+                border-radius: 0px 6px 0px 0px;
+                padding: 2px 8px;
+            }
+            .layers-panel {
+                border-radius: 6px 0px 0px 6px;
+                padding: 6px;
+            }
+            .layers-panel list {background: transparent;}
+            .layer-selected {background: #3584e499;}
+            .snippets-panel {
+                border-radius: 6px 0px 0px 0px;
+                padding: 6px;
+            }
+            .snippets-panel list {background: transparent;}
+            .adjustments-panel {
+                border-radius: 0px 6px 6px 0px;
+                padding: 6px;
+            }
+            .histogram-panel {
+                border-radius: 0px 0px 6px 0px;
+                padding: 6px;
+            }
+            .drag-grip {
+                border-radius: 6px 0px 0px 0px;
+                padding: 6px;
+            }
+            .frame-scrubber {
+                border-radius: 0px 0px 0px 6px;
+                padding: 6px;
+            }
+            .recent-gallery {
+                border-radius: 6px;
+                padding: 6px;
             }
             .toolbar-bottom {border-radius: 6px 6px 0px 0px;}
             .toolbar-top {border-radius: 0px 0px 6px 6px;}
@@ -174,16 +431,16 @@ impl App {
             Some(display) => {
                 gtk::style_context_add_provider_for_display(&display, &css_provider, 1)
             }
-            None => println!("Cannot apply style"),
+            None => log::warn!("Cannot apply style"),
         }
     }
 }
 
 #[relm4::component]
 impl Component for App {
-    type Init = (Pixbuf, bool);
+    type Init = (Pixbuf, AppMode, SessionOptions);
     type Input = AppInput;
-    type Output = ();
+    type Output = AppOutput;
     type CommandOutput = AppCommandOutput;
 
     view! {
@@ -193,7 +450,7 @@ impl Component for App {
             add_css_class: "root",
 
             connect_show[sender] => move |_| {
-                generate_profile_output!("gui show event");
+                profiling::mark("gui show event");
                 sender.input(AppInput::Realized);
             },
 
@@ -202,6 +459,18 @@ impl Component for App {
 
                 add_overlay = model.style_toolbar.widget(),
 
+                add_overlay = model.layers_panel.widget(),
+
+                add_overlay = model.snippets_panel.widget(),
+
+                add_overlay = model.adjustments_panel.widget(),
+
+                add_overlay = model.histogram_panel.widget(),
+
+                add_overlay = model.frame_scrubber.widget(),
+
+                add_overlay = model.recent_gallery.widget(),
+
                 model.sketch_board.widget(),
             }
         }
@@ -209,9 +478,16 @@ impl Component for App {
 
     fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, root: &Self::Root) {
         match message {
-            // AppInput::Realized => self.resize_window_initial(root, sender),
-            AppInput::Realized => {}, // TODO: Call resize_window_initial conditionally if layer shell mode is enabled
+            AppInput::Realized => {
+                // the layer-shell surface always covers the whole output
+                if !self.layer_shell {
+                    self.resize_window_initial(root, sender);
+                }
+                self.update_input_region(root);
+            }
             AppInput::SetToolbarsDisplay(visible) => {
+                self.toolbars_visible = visible;
+                self.update_input_region(root);
                 self.tools_toolbar
                     .sender()
                     .emit(ToolsToolbarInput::SetVisibility(visible));
@@ -220,6 +496,8 @@ impl Component for App {
                     .emit(StyleToolbarInput::SetVisibility(visible));
             }
             AppInput::ToggleToolbarsDisplay => {
+                self.toolbars_visible = !self.toolbars_visible;
+                self.update_input_region(root);
                 self.tools_toolbar
                     .sender()
                     .emit(ToolsToolbarInput::ToggleVisibility);
@@ -239,42 +517,228 @@ impl Component for App {
                         ui::toolbars::ColorButtons::Palette(index),
                     ));
             }
+            AppInput::AnnotationSizeChanged(value) => {
+                self.style_toolbar
+                    .sender()
+                    .emit(StyleToolbarInput::SetAnnotationSize(value));
+            }
+            AppInput::CornerRadiusChanged(value) => {
+                self.style_toolbar
+                    .sender()
+                    .emit(StyleToolbarInput::SetCornerRadius(value));
+            }
+            AppInput::LayersChanged(rows) => {
+                self.layers_panel
+                    .sender()
+                    .emit(LayersPanelInput::SetLayers(rows));
+            }
+            AppInput::ToggleLayersPanel => {
+                self.layers_panel
+                    .sender()
+                    .emit(LayersPanelInput::ToggleVisibility);
+            }
+            AppInput::SnippetsChanged(names) => {
+                self.snippets_panel
+                    .sender()
+                    .emit(SnippetsPanelInput::SetSnippets(names));
+            }
+            AppInput::ToggleSnippetsPanel => {
+                self.snippets_panel
+                    .sender()
+                    .emit(SnippetsPanelInput::ToggleVisibility);
+            }
+            AppInput::ToggleAdjustmentsPanel => {
+                self.adjustments_panel
+                    .sender()
+                    .emit(AdjustmentsPanelInput::ToggleVisibility);
+            }
+            AppInput::ToggleHistogramPanel => {
+                self.histogram_panel
+                    .sender()
+                    .emit(HistogramPanelInput::ToggleVisibility);
+            }
+            AppInput::HistogramChanged(histogram, cropped) => {
+                self.histogram_panel
+                    .sender()
+                    .emit(HistogramPanelInput::SetHistogram(histogram, cropped));
+            }
+            AppInput::SelectFrame(index) => {
+                if let Some(frame) = self.frames.get(index) {
+                    self.sketch_board
+                        .emit(SketchBoardInput::SetBaseImage(frame.clone()));
+                }
+            }
+            AppInput::ToggleRecentImages => {
+                // only the daemon keeps a history
+                if self.mode == AppMode::Daemon {
+                    let entries = self
+                        .recent_images
+                        .borrow()
+                        .iter()
+                        .map(|recent| RecentEntry {
+                            thumbnail: recent.thumbnail.clone(),
+                            label: recent.received.format("%H:%M:%S").to_string(),
+                        })
+                        .collect();
+                    self.recent_gallery
+                        .sender()
+                        .emit(RecentGalleryInput::Toggle(entries));
+                }
+            }
+            AppInput::LoadRecentImage(index) => {
+                let image = self
+                    .recent_images
+                    .borrow()
+                    .get(index)
+                    .map(|recent| recent.image.clone());
+                if let Some(image) = image {
+                    let window = self.daemon_state.borrow_mut().window_for_image();
+                    sender.input(AppInput::ReceiveImage(
+                        image,
+                        SessionOptions::default(),
+                        window,
+                    ));
+                }
+            }
+            AppInput::ReceiveImage(pixbuf, session, 0) => {
+                self.session = session;
+                self.apply_session();
+                sender.input(AppInput::LoadNewImage(pixbuf));
+                self.apply_template(&sender);
+            }
+            AppInput::ReceiveImage(pixbuf, session, window) => {
+                self.open_window(window, pixbuf, session, &sender);
+            }
             AppInput::LoadNewImage(pixbuf) => {
                 self.image_dimensions = (pixbuf.width(), pixbuf.height());
                 self.sketch_board
                     .sender()
                     .emit(SketchBoardInput::LoadNewImage(pixbuf));
 
-                // Trigger resize if needed after loading new image
-                if self.is_daemon {
+                if self.mode == AppMode::Daemon {
+                    let mut daemon_state = self.daemon_state.borrow_mut();
+                    daemon_state.image_size = Some(self.image_dimensions);
+                    daemon_state.windows.insert(0);
+
+                    // Trigger resize if needed after loading new image
                     sender.input(AppInput::Realized);
                 }
             }
+            AppInput::NextImage => {
+                if self.input_index + 1 < self.input_files.len() {
+                    self.input_index += 1;
+                    self.decode_input_file(&sender);
+                } else {
+                    relm4::main_application().quit();
+                }
+            }
+            AppInput::WindowClosed(id) => {
+                self.windows.remove(&id);
+                let mut daemon_state = self.daemon_state.borrow_mut();
+                daemon_state.windows.remove(&id);
+                if self.windows.is_empty() {
+                    daemon_state.image_size = None;
+                }
+            }
+            AppInput::EditorStateChanged(state) => {
+                if self.mode == AppMode::DaemonWindow {
+                    let _ = sender.output(AppOutput::EditorStateChanged(state));
+                } else {
+                    self.daemon_state.borrow_mut().editor = Some(state);
+                }
+            }
+            AppInput::RemoteControl(window, event) => {
+                if let Some(window) = self.target_window(window) {
+                    window.emit(AppInput::RemoteControl(None, event));
+                } else {
+                    if let ToolbarEvent::ToolSelected(tool) = event {
+                        self.tools_toolbar
+                            .sender()
+                            .emit(ToolsToolbarInput::SwitchSelectedTool(tool));
+                    }
+                    self.sketch_board
+                        .sender()
+                        .emit(SketchBoardInput::ToolbarEvent(event));
+                }
+            }
+            AppInput::AddAnnotations(window, annotations) => {
+                if let Some(window) = self.target_window(window) {
+                    window.emit(AppInput::AddAnnotations(None, annotations));
+                } else {
+                    self.sketch_board
+                        .sender()
+                        .emit(SketchBoardInput::AddAnnotations(annotations));
+                }
+            }
             AppInput::ShowWindow => {
-                root.set_visible(true);
-                root.present();
+                // every image gets its own window instead
+                if !APP_CONFIG.read().daemon_multiple_windows() {
+                    if self.session.fullscreen(&APP_CONFIG.read()) {
+                        root.fullscreen();
+                    } else {
+                        root.unfullscreen();
+                    }
+                    root.set_visible(true);
+                    root.present();
+                }
             }
             AppInput::HideWindow => {
                 root.set_visible(false);
+                let mut daemon_state = self.daemon_state.borrow_mut();
+                daemon_state.image_size = None;
+                daemon_state.windows.remove(&0);
             }
-            AppInput::RequestExit => {
-                if self.is_daemon {
+            AppInput::RequestExit => match self.mode {
+                AppMode::Standalone => relm4::main_application().quit(),
+                AppMode::Daemon => {
                     root.set_visible(false);
-                } else {
-                    relm4::main_application().quit();
+                    let mut daemon_state = self.daemon_state.borrow_mut();
+                    daemon_state.image_size = None;
+                    daemon_state.windows.remove(&0);
                 }
-            }
+                AppMode::DaemonWindow => {
+                    root.close();
+                    let _ = sender.output(AppOutput::WindowClosed);
+                }
+            },
         }
     }
 
     fn update_cmd(
         &mut self,
         command: AppCommandOutput,
-        _: ComponentSender<Self>,
+        sender: ComponentSender<Self>,
         root: &Self::Root,
     ) {
         match command {
             AppCommandOutput::ResetResizable => root.set_resizable(true),
+            AppCommandOutput::ImageDecoded(Ok(frames)) => {
+                profiling::end("image decode");
+                let image = frames[0].clone();
+                self.frame_scrubber
+                    .emit(FrameScrubberInput::SetFrameCount(frames.len()));
+                self.frames = frames;
+                let resize = (image.width(), image.height()) != self.image_dimensions;
+                sender.input(AppInput::LoadNewImage(image));
+                self.apply_template(&sender);
+                if resize {
+                    sender.input(AppInput::Realized);
+                }
+                self.announce_input_file(root);
+            }
+            AppCommandOutput::ImageDecoded(Err(e)) => {
+                // a single image is all there is to annotate
+                if self.input_files.len() == 1 {
+                    log::error!("{e}");
+                    std::process::exit(1);
+                }
+                // unreadable files of a batch are skipped
+                self.sketch_board.emit(SketchBoardInput::Report(
+                    Severity::Warning,
+                    format!("Skipping {}: {e}", self.input_files[self.input_index]),
+                ));
+                sender.input(AppInput::NextImage);
+            }
         }
     }
 
@@ -283,41 +747,82 @@ impl Component for App {
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        let (image, is_daemon) = init_data;
+        let (image, mode, session) = init_data;
 
         Self::apply_style();
 
-        root.init_layer_shell();
+        // X11 and compositors without wlr-layer-shell get a regular window instead
+        let layer_shell = APP_CONFIG.read().window_mode() == WindowMode::LayerShell
+            && gtk4_layer_shell::is_supported();
+        if layer_shell {
+            root.init_layer_shell();
 
-        root.set_anchor(Edge::Top, true);
-        root.set_anchor(Edge::Bottom, true);
-        root.set_anchor(Edge::Left, true);
-        root.set_anchor(Edge::Right, true);
+            root.set_anchor(Edge::Top, true);
+            root.set_anchor(Edge::Bottom, true);
+            root.set_anchor(Edge::Left, true);
+            root.set_anchor(Edge::Right, true);
 
-        root.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::Exclusive);
-        root.set_layer(Layer::Overlay);
-        root.set_exclusive_zone(-1);
+            root.set_keyboard_mode(match APP_CONFIG.read().keyboard_mode() {
+                KeyboardMode::Exclusive => gtk4_layer_shell::KeyboardMode::Exclusive,
+                KeyboardMode::OnDemand => gtk4_layer_shell::KeyboardMode::OnDemand,
+            });
+            root.set_layer(Layer::Overlay);
+            root.set_exclusive_zone(-1);
+
+            // without an output the compositor picks one, usually the focused one
+            if let Some(output) = APP_CONFIG.read().output() {
+                let name = if output == "cursor" {
+                    capture::output_under_cursor()
+                        .inspect_err(|e| log::warn!("{e}, using the default output"))
+                        .ok()
+                } else {
+                    Some(output.clone())
+                };
+                if let Some(name) = name {
+                    match Self::find_monitor(&name) {
+                        Some(monitor) => root.set_monitor(Some(&monitor)),
+                        None => log::warn!("Output '{name}' not found, using the default output"),
+                    }
+                }
+            }
+        }
 
-        if is_daemon {
+        if mode == AppMode::Daemon {
             root.set_visible(false);
         }
 
         let image_dimensions = (image.width(), image.height());
 
-        // SketchBoard
-        let sketch_board =
-            SketchBoard::builder()
-                .launch(image)
-                .forward(sender.input_sender(), |t| match t {
-                    SketchBoardOutput::ToggleToolbarsDisplay => AppInput::ToggleToolbarsDisplay,
-                    SketchBoardOutput::ToolSwitchShortcut(tool) => {
-                        AppInput::ToolSwitchShortcut(tool)
-                    }
-                    SketchBoardOutput::ColorSwitchShortcut(index) => {
-                        AppInput::ColorSwitchShortcut(index)
-                    }
-                    SketchBoardOutput::RequestExit => AppInput::RequestExit,
-                });
+        // SketchBoard, windows of the daemon are only opened with their image
+        let sketch_board = SketchBoard::builder()
+            .launch((image, mode != AppMode::DaemonWindow))
+            .forward(sender.input_sender(), |t| match t {
+                SketchBoardOutput::ToggleToolbarsDisplay => AppInput::ToggleToolbarsDisplay,
+                SketchBoardOutput::ToolSwitchShortcut(tool) => AppInput::ToolSwitchShortcut(tool),
+                SketchBoardOutput::ColorSwitchShortcut(index) => {
+                    AppInput::ColorSwitchShortcut(index)
+                }
+                SketchBoardOutput::AnnotationSizeChanged(value) => {
+                    AppInput::AnnotationSizeChanged(value)
+                }
+                SketchBoardOutput::CornerRadiusChanged(value) => {
+                    AppInput::CornerRadiusChanged(value)
+                }
+                SketchBoardOutput::LayersChanged(rows) => AppInput::LayersChanged(rows),
+                SketchBoardOutput::ToggleLayersPanel => AppInput::ToggleLayersPanel,
+                SketchBoardOutput::ToggleAdjustmentsPanel => AppInput::ToggleAdjustmentsPanel,
+                SketchBoardOutput::ToggleHistogramPanel => AppInput::ToggleHistogramPanel,
+                SketchBoardOutput::ToggleSnippetsPanel => AppInput::ToggleSnippetsPanel,
+                SketchBoardOutput::SnippetsChanged(names) => AppInput::SnippetsChanged(names),
+                SketchBoardOutput::HistogramChanged(histogram, cropped) => {
+                    AppInput::HistogramChanged(histogram, cropped)
+                }
+                SketchBoardOutput::ToggleRecentImages => AppInput::ToggleRecentImages,
+                SketchBoardOutput::EditorStateChanged(state) => AppInput::EditorStateChanged(state),
+                SketchBoardOutput::NextImage => AppInput::NextImage,
+                SketchBoardOutput::RequestExit => AppInput::RequestExit,
+                SketchBoardOutput::OpenImage(pixbuf) => AppInput::LoadNewImage(pixbuf),
+            });
 
         // Toolbars
         let tools_toolbar = ToolsToolbar::builder()
@@ -328,17 +833,78 @@ impl Component for App {
             .launch(())
             .forward(sketch_board.sender(), SketchBoardInput::ToolbarEvent);
 
+        let layers_panel = LayersPanel::builder()
+            .launch(())
+            .forward(sketch_board.sender(), SketchBoardInput::LayerEvent);
+
+        let snippet_names = snippets::names().unwrap_or_else(|e| {
+            log::warn!("Cannot load snippets: {e}");
+            Vec::new()
+        });
+        let snippets_panel = SnippetsPanel::builder()
+            .launch(snippet_names)
+            .forward(sketch_board.sender(), SketchBoardInput::SnippetEvent);
+
+        let adjustments_panel = AdjustmentsPanel::builder()
+            .launch(())
+            .forward(sketch_board.sender(), SketchBoardInput::SetAdjustments);
+
+        let histogram_panel = HistogramPanel::builder().launch(()).detach();
+
+        let frame_scrubber = FrameScrubber::builder()
+            .launch(())
+            .forward(sender.input_sender(), AppInput::SelectFrame);
+
+        let recent_gallery =
+            RecentGallery::builder()
+                .launch(())
+                .forward(sender.input_sender(), |t| match t {
+                    RecentGalleryOutput::Load(index) => AppInput::LoadRecentImage(index),
+                });
+        let recent_images = Rc::new(RefCell::new(RecentImages::default()));
+        let daemon_state = Rc::new(RefCell::new(DaemonState::default()));
+
         // Model
         let model = App {
             sketch_board,
             tools_toolbar,
             style_toolbar,
+            layers_panel,
+            snippets_panel,
+            adjustments_panel,
+            histogram_panel,
+            frame_scrubber,
+            recent_gallery,
+            recent_images: recent_images.clone(),
+            daemon_state: daemon_state.clone(),
             image_dimensions,
-            is_daemon,
+            layer_shell,
+            toolbars_visible: !APP_CONFIG.read().default_hide_toolbars(),
+            mode,
+            windows: HashMap::new(),
+            session,
+            input_files: if mode == AppMode::Standalone {
+                APP_CONFIG.read().input_filenames().to_vec()
+            } else {
+                Vec::new()
+            },
+            input_index: 0,
+            frames: Vec::new(),
         };
 
         let widgets = view_output!();
 
+        model.decode_input_file(&sender);
+
+        if mode == AppMode::DaemonWindow {
+            model.apply_session();
+            model.apply_template(&sender);
+        }
+
+        if APP_CONFIG.read().scrim_color().is_some() {
+            root.add_css_class("translucent");
+        }
+
         if APP_CONFIG.read().focus_toggles_toolbars() {
             let motion_controller = gtk::EventControllerMotion::builder().build();
             let sender_clone = sender.clone();
@@ -354,23 +920,24 @@ impl Component for App {
             root.add_controller(motion_controller);
         }
 
-        generate_profile_output!("app init end");
+        profiling::end("gtk init");
+        profiling::begin(profiling::FIRST_FRAME);
 
-        if is_daemon {
+        if mode == AppMode::Daemon {
             root.hide();
             glib::spawn_future_local(glib::clone!(
                 #[strong]
                 sender,
                 async move {
-                    match daemon::DaemonServer::new().await {
+                    match daemon::DaemonServer::new(recent_images, daemon_state).await {
                         Ok(server) => {
                             if let Err(e) = server.run(sender).await {
-                                eprintln!("Daemon server error: {}", e);
+                                log::error!("Daemon server error: {}", e);
                                 std::process::exit(1);
                             }
                         }
                         Err(e) => {
-                            eprintln!("Failed to start daemon: {}", e);
+                            log::error!("Failed to start daemon: {}", e);
                             std::process::exit(1);
                         }
                     }
@@ -379,7 +946,7 @@ impl Component for App {
         }
 
         glib::idle_add_local_once(move || {
-            generate_profile_output!("main loop idle");
+            profiling::mark("main loop idle");
         });
 
         ComponentParts { model, widgets }
@@ -391,7 +958,7 @@ fn read_css_overrides() -> Option<String> {
     let path = dirs.get_config_file("overrides.css")?;
 
     if !path.exists() {
-        eprintln!(
+        log::debug!(
             "CSS overrides file {} does not exist, using builtin CSS only.",
             &path.display()
         );
@@ -401,7 +968,7 @@ fn read_css_overrides() -> Option<String> {
     match fs::read_to_string(&path) {
         Ok(content) => Some(content),
         Err(e) => {
-            eprintln!(
+            log::warn!(
                 "failed to read CSS overrides from {} with error: {}",
                 &path.display(),
                 e
@@ -431,16 +998,74 @@ fn load_gl() -> Result<()> {
 }
 
 fn run_satty() -> Result<()> {
+    // the capture is annotated instead of the given images, its file is removed on return
+    let _capture = if APP_CONFIG.read().capture_region() {
+        let Some(capture) = capture::capture_region()? else {
+            // cancelling the selection isn't an error
+            return Ok(());
+        };
+        APP_CONFIG
+            .write()
+            .set_input_filenames(vec![capture.path.to_string_lossy().into_owned()]);
+        Some(capture)
+    } else if APP_CONFIG.read().capture_window() {
+        let (capture, title) = capture::capture_window()?;
+        let mut config = APP_CONFIG.write();
+        config.set_input_filenames(vec![capture.path.to_string_lossy().into_owned()]);
+        config.set_window_title(title);
+        Some(capture)
+    } else {
+        None
+    };
+
+    // the images of a batch would be saved over each other
+    {
+        let config = APP_CONFIG.read();
+        if config.input_filenames().len() > 1 {
+            if let Some(output) = config
+                .outputs()
+                .iter()
+                .find(|output| !output.filename.contains("{input}"))
+            {
+                return Err(anyhow!(
+                    "'{}' has to contain {{input}} to save several images",
+                    output.filename
+                ));
+            }
+        }
+    }
+
     // load OpenGL
-    load_gl()?;
-    generate_profile_output!("loaded gl");
+    profiling::span("gl load", load_gl)?;
+
+    // the window shows an empty image of the same size until the first input image is
+    // decoded, the others of a batch are loaded once it is saved
+    let filename = APP_CONFIG
+        .read()
+        .input_filenames()
+        .first()
+        .cloned()
+        .unwrap_or_default();
+    let placeholder = match Pixbuf::file_info(&filename) {
+        Some((_, width, height)) => empty_image(width, height)?,
+        // the window is resized once the size is known
+        None if filename == "-" => empty_image(500, 500)?,
+        // fails right away, without showing the window
+        None => load_image(&filename)?,
+    };
 
-    // load app config
-    let config = APP_CONFIG.read();
+    start_gui(placeholder, AppMode::Standalone)
+}
 
-    generate_profile_output!("loading image");
-    // load input image
-    let image = if config.input_filename() == "-" {
+fn empty_image(width: i32, height: i32) -> Result<Pixbuf> {
+    let image = Pixbuf::new(gdk_pixbuf::Colorspace::Rgb, true, 8, width, height)
+        .ok_or(anyhow!("Failed to create placeholder pixbuf"))?;
+    image.fill(0);
+    Ok(image)
+}
+
+fn load_image(filename: &str) -> Result<Pixbuf> {
+    if filename == "-" {
         let mut buf = Vec::<u8>::new();
         io::stdin().lock().read_to_end(&mut buf)?;
         let pb_loader = PixbufLoader::new();
@@ -448,39 +1073,116 @@ fn run_satty() -> Result<()> {
         pb_loader.close()?;
         pb_loader
             .pixbuf()
+            .ok_or(anyhow!("Conversion to Pixbuf failed"))
+    } else {
+        Pixbuf::from_file(filename).context("couldn't load image")
+    }
+}
+
+// memory the frames of an animated input may take up, later frames are dropped
+const MAX_ANIMATION_BYTES: usize = 512 * 1024 * 1024;
+
+/// All frames of `filename`, a single one unless it is animated.
+fn load_frames(filename: &str) -> Result<Vec<Pixbuf>> {
+    let animation = if filename == "-" {
+        let mut buf = Vec::<u8>::new();
+        io::stdin().lock().read_to_end(&mut buf)?;
+        let pb_loader = PixbufLoader::new();
+        pb_loader.write(&buf)?;
+        pb_loader.close()?;
+        pb_loader
+            .animation()
             .ok_or(anyhow!("Conversion to Pixbuf failed"))?
     } else {
-        Pixbuf::from_file(config.input_filename()).context("couldn't load image")?
+        PixbufAnimation::from_file(filename).context("couldn't load image")?
     };
+    if animation.is_static_image() {
+        return animation
+            .static_image()
+            .map(|image| vec![image])
+            .ok_or(anyhow!("Conversion to Pixbuf failed"));
+    }
 
-    generate_profile_output!("image loaded, starting gui");
+    // the animation is played back with a made up clock, frames are kept until they take
+    // up too much memory
+    let mut time = std::time::SystemTime::now();
+    let iter = animation.iter(Some(time));
+    let first = iter.pixbuf();
+    let mut frames: Vec<Pixbuf> = Vec::new();
+    let mut size = 0;
+    loop {
+        let shown = iter.pixbuf();
+        // looping animations start over with the first frame, which loaders keep as its own
+        // pixbuf, so frames repeating its pixels are still kept. Loaders drawing every frame
+        // into the same pixbuf end once the pixels repeat as well
+        if !frames.is_empty()
+            && shown == first
+            && shown.read_pixel_bytes() == frames[0].read_pixel_bytes()
+        {
+            break;
+        }
+        let frame = shown
+            .copy()
+            .ok_or(anyhow!("Failed to copy animation frame"))?;
+        size += frame.byte_length();
+        frames.push(frame);
+
+        let Some(delay) = iter.delay_time() else {
+            break;
+        };
+        // frames without a delay would never be left
+        time += delay.max(Duration::from_millis(1));
+        if size >= MAX_ANIMATION_BYTES || !iter.advance(time) {
+            break;
+        }
+    }
+    Ok(frames)
+}
 
-    start_gui(image, false)
+/// Composites the annotations of `project` onto `input` and writes the result to `output`,
+/// without opening a window.
+fn run_render(project: &str, input: &str, output: &str) -> Result<()> {
+    let json = fs::read_to_string(project)
+        .with_context(|| format!("Failed to read annotations from {project}"))?;
+    let annotations: Vec<_> = annotations::parse(&json)?
+        .into_iter()
+        .map(AnnotationSpec::into_drawable)
+        .collect();
+    let image = load_image(input)?;
+
+    let rendered = femtovg_area::render_annotations(&image, &annotations)?;
+    let data = SketchBoard::image_to_pixbuf(rendered).save_to_bufferv("png", &[])?;
+    if output == "-" {
+        io::stdout().lock().write_all(&data)?;
+    } else {
+        fs::write(output, data).with_context(|| format!("Failed to write {output}"))?;
+    }
+    Ok(())
 }
 
 fn run_satty_daemon() -> Result<()> {
-    load_gl()?;
-    generate_profile_output!("loaded gl (daemon mode)");
+    profiling::span("gl load", load_gl)?;
 
     let dummy_image = Pixbuf::new(gdk_pixbuf::Colorspace::Rgb, true, 8, 100, 100)
         .ok_or(anyhow!("Failed to create dummy pixbuf"))?;
 
-    generate_profile_output!("starting gui in daemon mode");
-    start_gui(dummy_image, true)
+    start_gui(dummy_image, AppMode::Daemon)
 }
 
-fn start_gui(image: Pixbuf, is_daemon: bool) -> Result<()> {
+fn start_gui(image: Pixbuf, mode: AppMode) -> Result<()> {
+    profiling::begin("gtk init");
     let app = relm4::main_application();
     app.set_application_id(Some("com.gabm.satty"));
     app.set_flags(ApplicationFlags::NON_UNIQUE);
     let app = RelmApp::from_app(app)
         .with_args(vec![])
-        .visible_on_activate(!is_daemon);
+        .visible_on_activate(mode != AppMode::Daemon);
     relm4_icons::initialize_icons(
         icons::icon_names::GRESOURCE_BYTES,
         icons::icon_names::RESOURCE_PREFIX,
     );
-    app.run::<App>((image, is_daemon));
+    app.run::<App>((image, mode, SessionOptions::default()));
+    clipboard::remove_drag_files();
     Ok(())
 }
 
@@ -496,6 +1198,24 @@ fn main() -> Result<()> {
         });
     }
 
+    if command_line.list_recent {
+        return MainContext::default().block_on(async { client::Client::list_recent().await });
+    }
+
+    if command_line.daemon_state {
+        return MainContext::default().block_on(async { client::Client::print_state().await });
+    }
+
+    if let Some(filename) = command_line.add_annotations {
+        let window = command_line.window;
+        return MainContext::default()
+            .block_on(async { client::Client::add_annotations(&filename, window).await });
+    }
+
+    if let Some(index) = command_line.load_recent {
+        return MainContext::default().block_on(async { client::Client::load_recent(index).await });
+    }
+
     if command_line.shutdown_daemon {
         return MainContext::default().block_on(async {
             client::Client::shutdown().await
@@ -503,28 +1223,66 @@ fn main() -> Result<()> {
     }
 
     if command_line.send_to_daemon {
-        let filename = command_line.filename
-            .ok_or(anyhow!("--filename is required when using --send-to-daemon"))?;
+        let filename = match command_line.filename.as_slice() {
+            [filename] => filename.clone(),
+            [] => {
+                return Err(anyhow!(
+                    "--filename is required when using --send-to-daemon"
+                ))
+            }
+            _ => {
+                return Err(anyhow!(
+                    "only a single --filename can be sent to the daemon"
+                ))
+            }
+        };
+        // these only apply to the sent image
+        let options = SessionOptions {
+            output_filenames: (!command_line.output_filename.is_empty())
+                .then_some(command_line.output_filename),
+            early_exit: command_line.early_exit.then_some(true),
+            initial_tool: command_line.initial_tool.map(Into::into),
+            fullscreen: command_line.fullscreen.then_some(true),
+        };
+
+        // the config decides whether a missing daemon is started
+        logging::start();
+        Configuration::load();
+        logging::init(false);
+        let spawn_daemon = APP_CONFIG.read().daemon_auto_spawn();
 
         return MainContext::default().block_on(async {
-            client::Client::send_image(&filename).await
+            client::Client::send_image(&filename, options, spawn_daemon).await
         });
     }
 
+    // ended once logging is set up, so the duration can be printed
+    profiling::begin("config parse");
+    logging::start();
     Configuration::load();
+    logging::init(command_line.daemon || command_line.systemd);
     if APP_CONFIG.read().profile_startup() {
-        eprintln!(
+        log::info!(
             "startup timestamp was {}",
             START_TIME.format("%s.%f %Y-%m-%d %H:%M:%S")
         );
     }
-    generate_profile_output!("configuration loaded");
+    profiling::end("config parse");
+
+    if let Some(Command::Render {
+        project,
+        input,
+        output,
+    }) = command_line.command
+    {
+        return run_render(&project, &input, &output);
+    }
 
-    if command_line.daemon {
-        eprintln!("Starting in daemon mode...");
+    if command_line.daemon || command_line.systemd {
+        log::info!("Starting in daemon mode...");
         match run_satty_daemon() {
             Err(e) => {
-                eprintln!("Error: {e}");
+                log::error!("{e}");
                 Err(e)
             }
             Ok(v) => Ok(v),
@@ -532,7 +1290,7 @@ fn main() -> Result<()> {
     } else {
         match run_satty() {
             Err(e) => {
-                eprintln!("Error: {e}");
+                log::error!("{e}");
                 Err(e)
             }
             Ok(v) => Ok(v),
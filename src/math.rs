@@ -164,6 +164,29 @@ impl Display for Vec2D {
     }
 }
 
+/// Computes the top left corner and size of a shape spanned by dragging from `origin` by `offset`.
+/// With `from_center`, the origin is the center of the shape and the offset points to a corner.
+/// With `proportional`, both sides get the length of the longer one.
+pub fn rect_from_drag(
+    origin: Vec2D,
+    offset: Vec2D,
+    from_center: bool,
+    proportional: bool,
+) -> (Vec2D, Vec2D) {
+    let offset = if proportional {
+        let max_size = offset.x.abs().max(offset.y.abs());
+        Vec2D::new(max_size * offset.x.signum(), max_size * offset.y.signum())
+    } else {
+        offset
+    };
+
+    if from_center {
+        (origin - offset, offset * 2.0)
+    } else {
+        (origin, offset)
+    }
+}
+
 pub fn rect_ensure_positive_size(pos: Vec2D, size: Vec2D) -> (Vec2D, Vec2D) {
     let (pos_x, size_x) = if size.x > 0.0 {
         (pos.x, size.x)
@@ -204,6 +227,41 @@ pub fn rect_ensure_in_bounds(rect: (Vec2D, Vec2D), bounds: (Vec2D, Vec2D)) -> (V
     (pos, size)
 }
 
+/// The smallest rectangle containing all `points`, as position and size.
+pub fn rect_around_points(points: &[Vec2D]) -> (Vec2D, Vec2D) {
+    let (min, max) = points.iter().fold(
+        (
+            Vec2D::new(f32::MAX, f32::MAX),
+            Vec2D::new(f32::MIN, f32::MIN),
+        ),
+        |(min, max), p| {
+            (
+                Vec2D::new(min.x.min(p.x), min.y.min(p.y)),
+                Vec2D::new(max.x.max(p.x), max.y.max(p.y)),
+            )
+        },
+    );
+    (min, max - min)
+}
+
+pub fn rect_contains(rect: (Vec2D, Vec2D), point: Vec2D, margin: f32) -> bool {
+    let (pos, size) = rect_ensure_positive_size(rect.0, rect.1);
+    point.x >= pos.x - margin
+        && point.y >= pos.y - margin
+        && point.x <= pos.x + size.x + margin
+        && point.y <= pos.y + size.y + margin
+}
+
+/// Whether `a` and `b` overlap, or are at most `margin` apart.
+pub fn rect_intersects(a: (Vec2D, Vec2D), b: (Vec2D, Vec2D), margin: f32) -> bool {
+    let (a_pos, a_size) = rect_ensure_positive_size(a.0, a.1);
+    let (b_pos, b_size) = rect_ensure_positive_size(b.0, b.1);
+    a_pos.x <= b_pos.x + b_size.x + margin
+        && b_pos.x <= a_pos.x + a_size.x + margin
+        && a_pos.y <= b_pos.y + b_size.y + margin
+        && b_pos.y <= a_pos.y + a_size.y + margin
+}
+
 pub fn rect_round(rect: (Vec2D, Vec2D)) -> (Vec2D, Vec2D) {
     let (mut pos, mut size) = rect;
 
@@ -3,8 +3,10 @@ use relm4::gtk::gio::{prelude::ApplicationExt, Notification};
 
 use relm4::gtk::{IconLookupFlags, IconTheme, TextDirection};
 
+/// Logs `msg`, which goes to stderr unless configured otherwise, stdout is left to the
+/// output of `print-saved-uri` and saving to `-`.
 pub fn log_result(msg: &str, notify: bool) {
-    println!("{msg}");
+    log::info!("{msg}");
     if notify {
         show_notification(msg);
     }
@@ -0,0 +1,176 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+
+use crate::math::{self, Vec2D};
+
+/// A recognized word and the area it covers, in image coordinates.
+#[derive(Debug, Clone)]
+pub struct Word {
+    pub text: String,
+    pub pos: Vec2D,
+    pub size: Vec2D,
+    // words with the same line id are on the same line
+    line: (u32, u32, u32),
+}
+
+/// Runs `command` with the PNG encoded image on stdin and parses the words from the TSV
+/// it prints, in the format of `tesseract stdin stdout tsv`.
+pub fn recognize(command: &str, png: &[u8]) -> Result<Vec<Word>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run '{command}'"))?;
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("No stdin for '{command}'"))?
+        .write_all(png)?;
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(anyhow!("'{command}' failed with {}", output.status));
+    }
+    Ok(parse_tsv(&String::from_utf8_lossy(&output.stdout)))
+}
+
+// columns: level page block paragraph line word left top width height confidence text
+fn parse_tsv(tsv: &str) -> Vec<Word> {
+    tsv.lines()
+        .skip(1)
+        .filter_map(|row| {
+            let columns: Vec<&str> = row.split('\t').collect();
+            let [level, _, block, paragraph, line, _, left, top, width, height, _, text] =
+                columns.as_slice()
+            else {
+                return None;
+            };
+            // level 5 are words
+            if *level != "5" || text.trim().is_empty() {
+                return None;
+            }
+            let number = |s: &str| s.parse::<f32>().ok();
+            Some(Word {
+                text: text.trim().to_string(),
+                pos: Vec2D::new(number(left)?, number(top)?),
+                size: Vec2D::new(number(width)?, number(height)?),
+                line: (
+                    block.parse().ok()?,
+                    paragraph.parse().ok()?,
+                    line.parse().ok()?,
+                ),
+            })
+        })
+        .collect()
+}
+
+impl Word {
+    pub fn same_line(&self, other: &Word) -> bool {
+        self.line == other.line
+    }
+}
+
+/// Compiles the redact patterns, invalid ones are left out and described in the errors.
+pub fn compile_patterns(patterns: &[String]) -> (Vec<Regex>, Vec<String>) {
+    let mut errors = Vec::new();
+    let regexes = patterns
+        .iter()
+        .filter_map(|pattern| match Regex::new(pattern) {
+            Ok(regex) => Some(regex),
+            Err(e) => {
+                errors.push(format!("Invalid redact pattern '{pattern}': {e}"));
+                None
+            }
+        })
+        .collect();
+    (regexes, errors)
+}
+
+/// Areas of the text matching any of `patterns`. Each line is matched as a whole, with its
+/// words separated by single spaces, so patterns can span several words.
+pub fn find_matches(words: &[Word], patterns: &[Regex]) -> Vec<(Vec2D, Vec2D)> {
+    let mut regions = Vec::new();
+    for line in words.chunk_by(|a, b| a.same_line(b)) {
+        let mut text = String::new();
+        let mut ranges = Vec::new();
+        for word in line {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            ranges.push(text.len()..text.len() + word.text.len());
+            text.push_str(&word.text);
+        }
+
+        for found in patterns.iter().flat_map(|p| p.find_iter(&text)) {
+            let corners: Vec<Vec2D> = line
+                .iter()
+                .zip(&ranges)
+                .filter(|(_, range)| range.start < found.end() && found.start() < range.end)
+                .flat_map(|(word, _)| [word.pos, word.pos + word.size])
+                .collect();
+            if !corners.is_empty() {
+                regions.push(math::rect_around_points(&corners));
+            }
+        }
+    }
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TSV: &str = "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext
+5\t1\t1\t1\t1\t1\t10\t10\t70\t12\t95\tpassword:
+5\t1\t1\t1\t1\t2\t90\t10\t60\t12\t95\thunter2
+5\t1\t1\t1\t2\t1\t10\t30\t40\t12\t95\tsee
+5\t1\t1\t1\t2\t2\t60\t30\t100\t12\t95\twww.example.org
+5\t1\t1\t1\t2\t3\t170\t30\t50\t12\t95\tor
+5\t1\t1\t1\t2\t4\t230\t30\t60\t12\t95\t10.0.0.1
+";
+
+    fn default_patterns() -> Vec<Regex> {
+        let (patterns, errors) =
+            compile_patterns(crate::configuration::Configuration::default().redact_patterns());
+        assert!(errors.is_empty(), "{errors:?}");
+        patterns
+    }
+
+    #[test]
+    fn parses_words_of_a_line() {
+        let words = parse_tsv(TSV);
+        assert_eq!(words.len(), 6);
+        assert_eq!(words[1].text, "hunter2");
+        assert!(words[0].same_line(&words[1]));
+        assert!(!words[1].same_line(&words[2]));
+    }
+
+    // the credential spans two words, the host name with dots is left alone
+    #[test]
+    fn default_patterns_match_sensitive_text() {
+        let words = parse_tsv(TSV);
+        let regions = find_matches(&words, &default_patterns());
+        assert_eq!(
+            regions,
+            vec![
+                (Vec2D::new(10.0, 10.0), Vec2D::new(140.0, 12.0)),
+                (Vec2D::new(230.0, 30.0), Vec2D::new(60.0, 12.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_patterns_are_reported() {
+        let (patterns, errors) = compile_patterns(&["(".to_string(), "a+".to_string()]);
+        assert_eq!(patterns.len(), 1);
+        assert_eq!(errors.len(), 1);
+    }
+}
@@ -0,0 +1,91 @@
+use std::{
+    collections::HashMap,
+    fs,
+    sync::{LazyLock, Mutex},
+};
+
+use serde_json::{json, Value};
+
+use crate::{configuration::APP_CONFIG, START_TIME};
+
+/// The phase ending when the window shows the image, after which the trace is written.
+pub const FIRST_FRAME: &str = "first frame";
+
+// phases that began and didn't end yet, by name with their start
+static OPEN: LazyLock<Mutex<HashMap<&'static str, i64>>> = LazyLock::new(Default::default);
+// everything recorded so far, as events of the Chrome trace format
+static EVENTS: LazyLock<Mutex<Vec<Value>>> = LazyLock::new(Default::default);
+
+// microseconds since startup
+fn elapsed() -> i64 {
+    (chrono::Local::now() - *START_TIME)
+        .num_microseconds()
+        .unwrap_or_default()
+}
+
+fn record(event: Value) {
+    if let Ok(mut events) = EVENTS.lock() {
+        events.push(event);
+    }
+}
+
+/// Marks a moment of the startup.
+pub fn mark(name: &'static str) {
+    let now = elapsed();
+    if APP_CONFIG.read().profile_startup() {
+        log::info!("{:5} ms time elapsed: {name}", now / 1000);
+    }
+    record(json!({ "name": name, "ph": "i", "s": "g", "ts": now, "pid": 1, "tid": 1 }));
+}
+
+/// Starts the phase `name` of the startup, until `end` is called with the same name.
+pub fn begin(name: &'static str) {
+    if let Ok(mut open) = OPEN.lock() {
+        open.insert(name, elapsed());
+    }
+}
+
+/// Ends the phase `name`, returns false if it wasn't started or already ended.
+pub fn end(name: &'static str) -> bool {
+    let Some(start) = OPEN.lock().ok().and_then(|mut open| open.remove(name)) else {
+        return false;
+    };
+    let now = elapsed();
+    if APP_CONFIG.read().profile_startup() {
+        log::info!(
+            "{:5} ms time elapsed: {name} took {} ms",
+            now / 1000,
+            (now - start) / 1000
+        );
+    }
+    record(json!({
+        "name": name, "ph": "X", "ts": start, "dur": now - start, "pid": 1, "tid": 1
+    }));
+    true
+}
+
+/// Runs `f` as the phase `name`.
+pub fn span<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    begin(name);
+    let result = f();
+    end(name);
+    result
+}
+
+/// Ends the startup with the first frame and writes the trace, if one was requested.
+pub fn first_frame_rendered() {
+    if !end(FIRST_FRAME) {
+        return;
+    }
+    let Some(path) = APP_CONFIG.read().profile_trace().map(String::from) else {
+        return;
+    };
+    let events = EVENTS
+        .lock()
+        .map(|events| events.clone())
+        .unwrap_or_default();
+    match fs::write(&path, json!({ "traceEvents": events }).to_string()) {
+        Ok(()) => log::info!("Startup trace written to {path}"),
+        Err(e) => log::warn!("Cannot write startup trace to {path}: {e}"),
+    }
+}
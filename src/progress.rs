@@ -0,0 +1,54 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::ui::toasts::ToastsInput;
+
+static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+
+/// Shows how far a slow operation got in the toasts of a window, also from the thread doing
+/// the work. The progress is hidden once this is dropped, so a failed operation can't leave
+/// it behind.
+pub struct Progress {
+    id: u32,
+    text: String,
+    toasts: relm4::Sender<ToastsInput>,
+    // last sent, whole percents are enough and keep the window from redrawing for each step
+    percent: Option<u32>,
+}
+
+impl Progress {
+    /// Shows `text` with a spinner until a fraction is set.
+    pub fn start(toasts: &relm4::Sender<ToastsInput>, text: &str) -> Self {
+        let progress = Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            text: text.to_string(),
+            toasts: toasts.clone(),
+            percent: None,
+        };
+        progress.send(None);
+        progress
+    }
+
+    /// Shows that `done` of `total` steps are done.
+    pub fn set_steps(&mut self, done: usize, total: usize) {
+        let fraction = done as f64 / total.max(1) as f64;
+        let percent = (fraction * 100.0) as u32;
+        if self.percent != Some(percent) {
+            self.percent = Some(percent);
+            self.send(Some(fraction));
+        }
+    }
+
+    fn send(&self, fraction: Option<f64>) {
+        self.toasts.emit(ToastsInput::SetProgress(
+            self.id,
+            self.text.clone(),
+            fraction,
+        ));
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.toasts.emit(ToastsInput::EndProgress(self.id));
+    }
+}
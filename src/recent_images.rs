@@ -0,0 +1,72 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Local};
+use gdk_pixbuf::{InterpType, Pixbuf};
+
+use crate::configuration::APP_CONFIG;
+
+const THUMBNAIL_SIZE: (f64, f64) = (160.0, 100.0);
+
+pub struct RecentImage {
+    /// File the image was loaded from, or e.g. `stdin` for an image the client had to copy.
+    pub source: String,
+    pub received: DateTime<Local>,
+    pub image: Pixbuf,
+    pub thumbnail: Option<Pixbuf>,
+}
+
+/// Images received by the daemon, newest first.
+#[derive(Default)]
+pub struct RecentImages {
+    images: VecDeque<RecentImage>,
+}
+
+impl RecentImages {
+    pub fn push(&mut self, source: String, image: Pixbuf) {
+        let capacity = APP_CONFIG.read().daemon_history_size();
+        if capacity == 0 {
+            return;
+        }
+
+        let scale = (THUMBNAIL_SIZE.0 / image.width() as f64)
+            .min(THUMBNAIL_SIZE.1 / image.height() as f64)
+            .min(1.0);
+        let thumbnail = image.scale_simple(
+            ((image.width() as f64 * scale) as i32).max(1),
+            ((image.height() as f64 * scale) as i32).max(1),
+            InterpType::Bilinear,
+        );
+
+        self.images.push_front(RecentImage {
+            source,
+            received: Local::now(),
+            image,
+            thumbnail,
+        });
+        self.images.truncate(capacity);
+    }
+
+    pub fn get(&self, index: usize) -> Option<&RecentImage> {
+        self.images.get(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RecentImage> {
+        self.images.iter()
+    }
+
+    /// One line per image: index, time received and source, separated by tabs.
+    pub fn describe(&self) -> String {
+        self.images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| {
+                format!(
+                    "{index}\t{}\t{}",
+                    image.received.format("%Y-%m-%d %H:%M:%S"),
+                    image.source
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    sync::mpsc::Receiver,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use femtovg::{imgref::ImgVec, rgb::RGBA8};
+
+use crate::progress::Progress;
+
+/// How many times longer than the others the last frame is shown, so the result can be seen
+/// before the animation starts over.
+const LAST_FRAME_REPEAT: u32 = 3;
+
+/// Writes the `count` frames of equal size received from `frames` as an animation to
+/// `path`, shown for `delay` each. WebM if the name ends in `.webm`, which needs ffmpeg,
+/// otherwise GIF. Frames are encoded as they arrive and counted in `progress`.
+pub fn save(
+    frames: Receiver<ImgVec<RGBA8>>,
+    count: usize,
+    path: &Path,
+    delay: Duration,
+    progress: &mut Progress,
+) -> Result<()> {
+    if count == 0 {
+        return Err(anyhow!("Nothing to record"));
+    }
+    let first = frames
+        .recv()
+        .map_err(|_| anyhow!("Rendering the steps failed"))?;
+    let size = (first.width(), first.height());
+    let frames = std::iter::once(first).chain(frames).map(|frame| {
+        if (frame.width(), frame.height()) == size {
+            Ok(frame)
+        } else {
+            Err(anyhow!("Frames of the recording differ in size"))
+        }
+    });
+
+    let written = if path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("webm"))
+    {
+        save_webm(frames, count, size, path, delay, progress)?
+    } else {
+        save_gif(frames, count, size, path, delay, progress)?
+    };
+    // the sender is dropped early when rendering fails
+    if written < count {
+        return Err(anyhow!("Rendering the steps failed"));
+    }
+    Ok(())
+}
+
+fn rgba_bytes(frame: &ImgVec<RGBA8>) -> Vec<u8> {
+    frame.pixels().flat_map(|p| [p.r, p.g, p.b, p.a]).collect()
+}
+
+// both return the number of frames written
+fn save_gif(
+    frames: impl Iterator<Item = Result<ImgVec<RGBA8>>>,
+    count: usize,
+    (width, height): (usize, usize),
+    path: &Path,
+    delay: Duration,
+    progress: &mut Progress,
+) -> Result<usize> {
+    let width = u16::try_from(width).context("Image too wide for a GIF")?;
+    let height = u16::try_from(height).context("Image too high for a GIF")?;
+    let file = File::create(path).with_context(|| format!("Failed to create {path:?}"))?;
+    let mut encoder = gif::Encoder::new(file, width, height, &[])?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+
+    // GIF delays are in hundredths of a second
+    let hundredths = (delay.as_millis() / 10).clamp(1, u16::MAX as u128 / 4) as u16;
+    let mut written = 0;
+    for frame in frames {
+        let mut pixels = rgba_bytes(&frame?);
+        let mut gif_frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, 10);
+        gif_frame.delay = if written + 1 == count {
+            hundredths * LAST_FRAME_REPEAT as u16
+        } else {
+            hundredths
+        };
+        encoder.write_frame(&gif_frame)?;
+        written += 1;
+        progress.set_steps(written, count);
+    }
+    Ok(written)
+}
+
+fn save_webm(
+    frames: impl Iterator<Item = Result<ImgVec<RGBA8>>>,
+    count: usize,
+    (width, height): (usize, usize),
+    path: &Path,
+    delay: Duration,
+    progress: &mut Progress,
+) -> Result<usize> {
+    let mut child = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error"])
+        .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+        .args(["-s", &format!("{width}x{height}")])
+        .args(["-framerate", &format!("1000/{}", delay.as_millis().max(1))])
+        .args(["-i", "-"])
+        // the chroma subsampling needs even dimensions
+        .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+        .args(["-c:v", "libvpx-vp9", "-pix_fmt", "yuv420p"])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run ffmpeg")?;
+
+    let mut stdin = child.stdin.take().ok_or(anyhow!("No stdin for ffmpeg"))?;
+    let mut written = 0;
+    for frame in frames {
+        let pixels = rgba_bytes(&frame?);
+        let repeat = if written + 1 == count {
+            LAST_FRAME_REPEAT
+        } else {
+            1
+        };
+        for _ in 0..repeat {
+            stdin.write_all(&pixels)?;
+        }
+        written += 1;
+        progress.set_steps(written, count);
+    }
+    drop(stdin);
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "ffmpeg failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(written)
+}
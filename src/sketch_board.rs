@@ -1,40 +1,203 @@
-use anyhow::anyhow;
-
 use femtovg::imgref::Img;
 use femtovg::rgb::{ComponentBytes, RGBA};
 use gdk_pixbuf::glib::Bytes;
-use gdk_pixbuf::Pixbuf;
+use gdk_pixbuf::{InterpType, Pixbuf};
 use keycode::{KeyMap, KeyMappingId};
-use std::cell::RefCell;
+use std::cell::{OnceCell, RefCell};
 use std::io::Write;
 use std::panic;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
 use std::{fs, io};
 
 use gtk::prelude::*;
 
-use relm4::gtk::gdk::{DisplayManager, Key, ModifierType, Texture};
-use relm4::{gtk, Component, ComponentParts, ComponentSender, RelmWidgetExt};
-
-use crate::configuration::{Action, APP_CONFIG};
-use crate::femtovg_area::FemtoVGArea;
+use relm4::gtk::gdk::{AxisUse, Key, ModifierType, Texture};
+use relm4::{
+    gtk, Component, ComponentController, ComponentParts, ComponentSender, Controller, RelmWidgetExt,
+};
+
+use crate::adjustments::Adjustments;
+use crate::annotations::{self, AnnotationSpec};
+use crate::clipboard;
+use crate::configuration::{
+    Action, ButtonAction, GestureAction, GestureDirection, HistoryStep, KeyCommand, KeyMatch,
+    OutputTarget, OverwritePolicy, ScrollAction, SessionOptions, APP_CONFIG,
+};
+use crate::edge_detection::{self, EdgeMap};
+use crate::external_command;
+use crate::femtovg_area::{ColorVision, FemtoVGArea};
+use crate::histogram::Histogram;
 use crate::ime::pango_adapter::spans_from_pango_attrs;
-use crate::math::Vec2D;
-use crate::notification::log_result;
+use crate::layers::{LayerInfo, Layers};
+use crate::math::{self, Vec2D};
+use crate::ocr;
+use crate::progress::Progress;
+use crate::recording;
+use crate::snippets;
 use crate::style::Style;
-use crate::tools::{Tool, ToolEvent, ToolUpdateResult, Tools, ToolsManager};
+use crate::tools::{Caption, Picture, Tool, ToolEvent, ToolUpdateResult, Tools, ToolsManager};
+use crate::ui::layers_panel::{LayerEvent, LayerRow};
+use crate::ui::snippets_panel::SnippetEvent;
+use crate::ui::toasts::{self, Severity, Toasts, ToastsInput};
 use crate::ui::toolbars::ToolbarEvent;
 
 type RenderedImage = Img<Vec<RGBA<u8>>>;
 
+/// A rendered image and its PNG encodings, made off the main thread.
+#[derive(Debug)]
+pub struct EncodedImage {
+    pixbuf: Pixbuf,
+    // by the scale of the image, or why encoding it failed
+    png: Vec<(f32, Result<Vec<u8>, String>)>,
+    // the point in the undo history it shows, which counts as saved once it is. `None` for
+    // an exported region, which leaves the changes unsaved
+    position: Option<Option<usize>>,
+}
+
+impl EncodedImage {
+    /// Encodes `image` at each of `scales` in parallel.
+    fn new(image: RenderedImage, scales: Vec<f32>, position: Option<Option<usize>>) -> Self {
+        let pixbuf = SketchBoard::image_to_pixbuf(image);
+        let workers: Vec<_> = scales
+            .into_iter()
+            .map(|scale| {
+                let pixbuf = pixbuf.clone();
+                (
+                    scale,
+                    std::thread::spawn(move || Self::encode(&pixbuf, scale)),
+                )
+            })
+            .collect();
+        let png = workers
+            .into_iter()
+            .map(|(scale, worker)| {
+                let png = worker
+                    .join()
+                    .unwrap_or_else(|_| Err("Encoding the image panicked".to_string()));
+                (scale, png)
+            })
+            .collect();
+        Self {
+            pixbuf,
+            png,
+            position,
+        }
+    }
+
+    fn encode(image: &Pixbuf, scale: f32) -> Result<Vec<u8>, String> {
+        let scaled;
+        let image = if scale == 1.0 {
+            image
+        } else {
+            scaled = image
+                .scale_simple(
+                    ((image.width() as f32 * scale).round() as i32).max(1),
+                    ((image.height() as f32 * scale).round() as i32).max(1),
+                    InterpType::Bilinear,
+                )
+                .ok_or(format!("Error scaling image by {scale}"))?;
+            &scaled
+        };
+        image
+            .save_to_bufferv("png", &Vec::new())
+            .map_err(|e| format!("Error serializing image: {e}"))
+    }
+
+    fn png(&self, scale: f32) -> Result<&[u8], String> {
+        match self.png.iter().find(|(s, _)| *s == scale) {
+            Some((_, Ok(data))) => Ok(data),
+            Some((_, Err(e))) => Err(e.clone()),
+            None => Err(format!("Image not encoded at scale {scale}")),
+        }
+    }
+}
+
+// multiplier applied to the annotation size per scroll step
+const SIZE_SCROLL_STEP: f32 = 1.1;
+const CORNER_RADIUS_SCROLL_STEP: f32 = 2.0;
+const SIZE_INDICATOR_TIMEOUT: Duration = Duration::from_millis(1000);
+const ESCAPE_CONFIRM_TIMEOUT: Duration = Duration::from_millis(2000);
+// how far the pointer has to move for a gesture, in pixels of the canvas
+const GESTURE_MIN_DISTANCE: f32 = 30.0;
+// rendered steps of the history waiting to be encoded, rendering pauses beyond
+const QUEUED_STEPS: usize = 2;
+
 #[derive(Debug, Clone)]
 pub enum SketchBoardInput {
     InputEvent(InputEvent),
     ToolbarEvent(ToolbarEvent),
     RenderResult(RenderedImage, Vec<Action>),
+    /// Where to export the images after each step of the history to.
+    SaveSteps(StepsExport, PathBuf),
+    /// The pointer is on the grip, the image is prepared for dragging it out of the window.
+    PrepareDrag,
+    /// The image to drag out of the window.
+    DragImageRendered(RenderedImage),
     CommitEvent(TextEventMsg),
     LoadNewImage(Pixbuf),
+    /// Replaces the image with another frame of the same animation, keeping the annotations.
+    SetBaseImage(Pixbuf),
+    /// Shows that the image is still being decoded, until it is loaded.
+    ShowLoading,
+    /// The image showing this point in the undo history was saved or copied.
+    Saved(Option<usize>),
+    /// A message of the app to show as a toast.
+    Report(Severity, String),
+    HideSizeIndicator(u32),
+    /// The keys typed for a keybind weren't continued in time.
+    KeySequenceTimeout(u32),
+    LayerEvent(LayerEvent),
+    SnippetEvent(SnippetEvent),
+    AddAnnotations(Vec<AnnotationSpec>),
+    SetAdjustments(Adjustments),
+    /// Name of the input file without extension, and whether more images of the batch
+    /// follow it.
+    SetInputFile(String, bool),
+    /// Overrides of the daemon request the image came with.
+    SetSession(SessionOptions),
+    /// An image pasted or dropped to insert, centered on the position in image coordinates
+    /// if given.
+    InsertImage(Pixbuf, Option<Vec2D>),
+    /// A file dropped onto the canvas at the position in canvas coordinates.
+    DropFile(PathBuf, Vec2D),
+}
+
+#[derive(Debug)]
+pub enum SketchBoardCommand {
+    /// Regions found for auto-redact, or why recognizing the text failed, with the
+    /// generation of the image they were found in.
+    RedactRegions(u32, Result<Vec<(Vec2D, Vec2D)>, String>),
+    /// Words recognized for the text selection tool, with the generation of the image.
+    Words(u32, Result<Vec<ocr::Word>, String>),
+    /// The rendered image encoded for the actions it was rendered for.
+    Encoded(EncodedImage, Vec<Action>),
+    /// Where the recording was saved, or why it failed.
+    RecordingSaved(Result<PathBuf, String>),
+    /// The images saved for each step, or why saving them failed.
+    StepImagesSaved(Result<Vec<PathBuf>, String>),
+    /// Name of a quick action whose command exited, and why it failed.
+    QuickActionFinished(String, Result<(), String>),
+    /// The image as saved by the external editor if it is reloaded, or why running the
+    /// editor failed.
+    EditorClosed(Result<Option<Pixbuf>, String>),
+    /// The image of a dropped file with where to center it, or why it couldn't be read.
+    DroppedFileLoaded(Result<Pixbuf, String>, Vec2D),
+    /// The file the save dialog saved the image to, with the actions left to run after it.
+    SavedAs(PathBuf, EncodedImage, Vec<Action>),
+    /// The file to drag out of the window, or why writing it failed, and its image.
+    DragFileWritten(Result<PathBuf, String>, Pixbuf),
+}
+
+/// How the images after each step of the history are exported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepsExport {
+    /// An animation replaying the steps.
+    Recording,
+    /// One numbered image per step.
+    Images,
 }
 
 #[derive(Debug, Clone)]
@@ -42,7 +205,52 @@ pub enum SketchBoardOutput {
     ToggleToolbarsDisplay,
     ToolSwitchShortcut(Tools),
     ColorSwitchShortcut(u64),
+    AnnotationSizeChanged(f32),
+    CornerRadiusChanged(f32),
+    LayersChanged(Vec<LayerRow>),
+    ToggleLayersPanel,
+    ToggleAdjustmentsPanel,
+    ToggleHistogramPanel,
+    ToggleSnippetsPanel,
+    /// Names of the saved snippets after one was saved or deleted.
+    SnippetsChanged(Vec<String>),
+    /// The histogram of the image, or of the crop if the flag is set.
+    HistogramChanged(Box<Histogram>, bool),
+    ToggleRecentImages,
+    EditorStateChanged(EditorState),
+    /// The image was saved and the next one of the batch can be loaded.
+    NextImage,
     RequestExit,
+    /// An image to annotate instead of the placeholder shown until one is loaded.
+    OpenImage(Pixbuf),
+}
+
+/// Mode of the vim-style `modal-editing`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EditMode {
+    /// Keys run keybinds and commands.
+    Normal,
+    /// Keys only go to the text of the active tool.
+    Insert,
+    /// Keys move and remove the selected annotation.
+    Visual,
+}
+
+impl EditMode {
+    fn label(self) -> &'static str {
+        match self {
+            EditMode::Normal => "NORMAL",
+            EditMode::Insert => "INSERT",
+            EditMode::Visual => "VISUAL",
+        }
+    }
+}
+
+/// State of the editor as reported to the daemon.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EditorState {
+    pub active_tool: Tools,
+    pub unsaved_changes: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -60,6 +268,8 @@ pub enum MouseButton {
     Primary,
     Secondary,
     Middle,
+    Back,
+    Forward,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -96,6 +306,25 @@ pub struct MouseEventMsg {
     pub button: MouseButton,
     pub modifier: ModifierType,
     pub pos: Vec2D,
+    /// Stylus pressure from 0 to 1, if the device reports it.
+    pub pressure: Option<f32>,
+}
+
+impl MouseEventMsg {
+    /// Shapes are drawn from their center outward while Ctrl is held.
+    pub fn from_center(&self) -> bool {
+        self.modifier.intersects(ModifierType::CONTROL_MASK)
+    }
+
+    /// Shapes keep 1:1 proportions while Shift is held.
+    pub fn proportional(&self) -> bool {
+        self.modifier.intersects(ModifierType::SHIFT_MASK)
+    }
+
+    /// Whether the button is set to pan the canvas in `[mouse]`.
+    pub fn pans(&self) -> bool {
+        APP_CONFIG.read().mouse().action(self.button) == ButtonAction::Pan
+    }
 }
 
 impl SketchBoardInput {
@@ -104,12 +333,14 @@ impl SketchBoardInput {
         button: u32,
         modifier: ModifierType,
         pos: Vec2D,
+        pressure: Option<f32>,
     ) -> SketchBoardInput {
         SketchBoardInput::InputEvent(InputEvent::Mouse(MouseEventMsg {
             type_: event_type,
             button: button.into(),
             modifier,
             pos,
+            pressure,
         }))
     }
     pub fn new_key_event(event: KeyEventMsg) -> SketchBoardInput {
@@ -128,22 +359,52 @@ impl SketchBoardInput {
         SketchBoardInput::CommitEvent(event)
     }
 
-    pub fn new_scroll_event(delta_y: f64) -> SketchBoardInput {
+    pub fn new_scroll_event(delta_y: f64, modifier: ModifierType) -> SketchBoardInput {
         SketchBoardInput::InputEvent(InputEvent::Mouse(MouseEventMsg {
             type_: MouseEventType::Scroll,
             button: MouseButton::Middle,
-            modifier: ModifierType::empty(),
+            modifier,
             pos: Vec2D::new(0.0, delta_y as f32),
+            pressure: None,
         }))
     }
 }
 
+/// Direction of a gesture that moved by `offset`, in canvas coordinates, if it moved far
+/// enough.
+fn gesture_direction(offset: Vec2D) -> Option<GestureDirection> {
+    if offset.x.abs().max(offset.y.abs()) < GESTURE_MIN_DISTANCE {
+        None
+    } else if offset.x.abs() > offset.y.abs() {
+        Some(if offset.x < 0.0 {
+            GestureDirection::Left
+        } else {
+            GestureDirection::Right
+        })
+    } else if offset.y < 0.0 {
+        Some(GestureDirection::Up)
+    } else {
+        Some(GestureDirection::Down)
+    }
+}
+
+/// Stylus pressure of the event `controller` is handling, if the device reports it.
+fn event_pressure(controller: &impl IsA<gtk::EventController>) -> Option<f32> {
+    controller
+        .current_event()?
+        .axis(AxisUse::Pressure)
+        .map(|pressure| pressure as f32)
+}
+
 impl From<u32> for MouseButton {
     fn from(value: u32) -> Self {
         match value {
             gtk::gdk::BUTTON_PRIMARY => MouseButton::Primary,
             gtk::gdk::BUTTON_MIDDLE => MouseButton::Middle,
             gtk::gdk::BUTTON_SECONDARY => MouseButton::Secondary,
+            // gdk has no constants for the side buttons
+            8 => MouseButton::Back,
+            9 => MouseButton::Forward,
             _ => MouseButton::Primary,
         }
     }
@@ -153,32 +414,12 @@ impl InputEvent {
     fn handle_event_mouse_input(&mut self, renderer: &FemtoVGArea) -> Option<ToolUpdateResult> {
         if let InputEvent::Mouse(me) = self {
             match me.type_ {
-                MouseEventType::Click => {
-                    if me.button == MouseButton::Secondary {
-                        renderer.request_render(&APP_CONFIG.read().actions_on_right_click());
-                        None
-                    } else {
-                        me.pos = renderer.abs_canvas_to_image_coordinates(me.pos);
-                        None
-                    }
-                }
-                MouseEventType::BeginDrag => {
+                MouseEventType::Click | MouseEventType::BeginDrag => {
                     me.pos = renderer.abs_canvas_to_image_coordinates(me.pos);
                     None
                 }
                 MouseEventType::EndDrag | MouseEventType::UpdateDrag => {
-                    if me.button == MouseButton::Middle {
-                        renderer.set_drag_offset(me.pos);
-                        renderer.set_is_drag(true);
-
-                        if me.type_ == MouseEventType::EndDrag {
-                            renderer.store_last_offset();
-                            renderer.set_is_drag(false);
-                        }
-                        renderer.request_render(&APP_CONFIG.read().actions_on_right_click());
-                    } else {
-                        me.pos = renderer.rel_canvas_to_image_coordinates(me.pos);
-                    }
+                    me.pos = renderer.rel_canvas_to_image_coordinates(me.pos);
                     None
                 }
                 MouseEventType::Scroll => {
@@ -208,14 +449,69 @@ pub struct SketchBoard {
     tools: ToolsManager,
     style: Style,
     im_context: gtk::IMMulticontext,
+    // detected on first use, snapping and crop suggestions may never be used
+    edge_map: OnceCell<EdgeMap>,
+    // counts replaced images, so that results of background work on an earlier one are dropped
+    image_generation: u32,
+    // raw and snapped start position of the current drag, in image coordinates
+    drag_start: Option<(Vec2D, Vec2D)>,
+    crop_suggestion: Option<usize>,
+    // shown in the status bar, in image coordinates
+    cursor_pos: Option<Vec2D>,
+    selection_size: Option<Vec2D>,
+    // canvas position where a drag inside the minimap started
+    minimap_drag: Option<Vec2D>,
+    // clicks copy the inspected color instead of reaching the tool
+    pixel_inspector: bool,
+    // replaces `{input}` in output filenames
+    input_name: Option<String>,
+    // overrides of the daemon request, e.g. other output filenames
+    session: SessionOptions,
+    // saving loads the next image of the batch instead of exiting
+    batch_pending: bool,
+    // action for the region being chosen for export and where its drag started
+    region_export: Option<(Action, Option<Vec2D>)>,
+    // the pending render is a region export, which doesn't count as saving
+    exporting_region: bool,
+    // the last event only changed the drawable of the active tool, which is redrawn alone
+    tool_redraw: bool,
+    // text of the size indicator shown while scrolling, and a counter so that
+    // only the most recent hide timeout takes effect
+    size_indicator: Option<String>,
+    size_indicator_generation: u32,
+    toasts: Controller<Toasts>,
+    // characters typed so far of a keybind of several keys
+    pending_keys: String,
+    pending_keys_generation: u32,
+    // mode of modal editing, if enabled
+    edit_mode: Option<EditMode>,
+    // start of the gesture being made and the direction shown for it
+    gesture: Option<(Vec2D, Option<GestureDirection>)>,
+    // indicator generation of the shown prompt to confirm exiting with Escape
+    escape_confirmation: Option<u32>,
+    layers: Rc<RefCell<Layers>>,
+    // last annotation list sent to the layers panel
+    layer_infos: Vec<LayerInfo>,
+    // history position at the last save or copy
+    saved_position: Option<usize>,
+    // last editor state sent
+    editor_state: Option<EditorState>,
+    histogram_visible: bool,
+    // crop of the last histogram sent, `None` if it has to be computed again
+    histogram_region: Option<Option<(Vec2D, Vec2D)>>,
+    // no image has been loaded yet, pasting opens the pasted one
+    placeholder: bool,
+    // image prepared for dragging out of the window, `None` while it is being prepared
+    drag_file: Rc<RefCell<Option<(PathBuf, Texture)>>>,
 }
 
 impl SketchBoard {
     fn refresh_screen(&mut self) {
+        self.renderer.invalidate();
         self.renderer.queue_render();
     }
 
-    fn image_to_pixbuf(image: RenderedImage) -> Pixbuf {
+    pub fn image_to_pixbuf(image: RenderedImage) -> Pixbuf {
         let (buf, w, h) = image.into_contiguous_buf();
 
         Pixbuf::from_bytes(
@@ -229,289 +525,1669 @@ impl SketchBoard {
         )
     }
 
-    fn deactivate_active_tool(&mut self) -> bool {
-        if self.active_tool.borrow().active() {
-            if let ToolUpdateResult::Commit(result) =
-                self.active_tool.borrow_mut().handle_deactivated()
-            {
-                self.renderer.commit(result);
-                return true;
-            }
-        }
-        false
-    }
-
-    fn handle_action(&mut self, actions: &[Action]) -> ToolUpdateResult {
-        let rv = if self.deactivate_active_tool() {
-            ToolUpdateResult::Redraw
-        } else {
-            ToolUpdateResult::Unmodified
+    /// Snap rectangle and crop drags to detected edges while Alt is held.
+    /// Tools receive drag updates relative to the start position, so the snapped
+    /// start has to be taken into account for every following update.
+    fn apply_edge_snapping(&mut self, ie: &mut InputEvent) {
+        let InputEvent::Mouse(me) = ie else {
+            return;
         };
-        self.renderer.request_render(actions);
-        rv
-    }
-
-    fn handle_render_result(
-        &self,
-        image: RenderedImage,
-        actions: Vec<Action>,
-        sender: &ComponentSender<Self>,
-    ) {
-        let needs_pixbuf = actions.iter().any(|action| {
-            matches!(
-                action,
-                Action::SaveToClipboard | Action::SaveToFile | Action::SaveToFileAs
-            )
-        });
+        if me.pans() || !matches!(self.active_tool_type(), Tools::Rectangle | Tools::Crop) {
+            return;
+        }
 
-        let pix_buf = if needs_pixbuf {
-            Some(Self::image_to_pixbuf(image))
-        } else {
-            None
-        };
+        let distance = APP_CONFIG.read().edge_snap_distance();
+        let snap = distance > 0.0 && me.modifier.contains(ModifierType::ALT_MASK);
 
-        for action in actions {
-            match action {
-                Action::SaveToClipboard => {
-                    if let Some(ref pix_buf) = pix_buf {
-                        self.handle_copy_clipboard(pix_buf);
-                    }
-                }
-                Action::SaveToFile => {
-                    if let Some(ref pix_buf) = pix_buf {
-                        self.handle_save(pix_buf);
-                    }
-                }
-                Action::SaveToFileAs => {
-                    if let Some(ref pix_buf) = pix_buf {
-                        self.handle_save_as(pix_buf);
-                    }
+        match me.type_ {
+            MouseEventType::BeginDrag => {
+                let raw = me.pos;
+                if snap {
+                    me.pos = self.edge_map().snap(raw, distance);
                 }
-                _ => (),
+                self.drag_start = Some((raw, me.pos));
             }
+            MouseEventType::UpdateDrag | MouseEventType::EndDrag => {
+                let Some((raw_start, snapped_start)) = self.drag_start else {
+                    return;
+                };
+                let current = raw_start + me.pos;
+                let target = if snap {
+                    self.edge_map().snap(current, distance)
+                } else {
+                    current
+                };
+                me.pos = target - snapped_start;
 
-            if APP_CONFIG.read().early_exit() || action == Action::Exit {
-                self.handle_exit(sender);
-                return;
+                if me.type_ == MouseEventType::EndDrag {
+                    self.drag_start = None;
+                }
             }
+            _ => {}
         }
     }
 
-    fn handle_exit(&self, sender: &ComponentSender<Self>) {
-        let _ = sender.output(SketchBoardOutput::RequestExit);
-    }
+    /// Pans to the position clicked or dragged to in the minimap.
+    /// Returns whether the event was consumed.
+    fn handle_minimap(&mut self, ie: &InputEvent) -> bool {
+        let InputEvent::Mouse(me) = ie else {
+            return false;
+        };
 
-    fn handle_save(&self, image: &Pixbuf) {
-        let mut output_filename = match APP_CONFIG.read().output_filename() {
-            None => {
-                println!("No Output filename specified!");
-                return;
+        let pos = match me.type_ {
+            MouseEventType::Click | MouseEventType::BeginDrag
+                if me.button == MouseButton::Primary && self.renderer.is_in_minimap(me.pos) =>
+            {
+                if me.type_ == MouseEventType::BeginDrag {
+                    self.minimap_drag = Some(me.pos);
+                }
+                me.pos
             }
-            Some(o) => o.clone(),
+            MouseEventType::UpdateDrag | MouseEventType::EndDrag => {
+                let Some(start) = self.minimap_drag else {
+                    return false;
+                };
+                if me.type_ == MouseEventType::EndDrag {
+                    self.minimap_drag = None;
+                }
+                start + me.pos
+            }
+            _ => return false,
         };
 
-        // run the output filename by "chrono date format"
-        let delayed_format = chrono::Local::now().format(&output_filename);
-        let result = panic::catch_unwind(|| {
-            delayed_format.to_string();
-        });
-
-        if result.is_err() {
-            println!(
-                "Warning: Could not format filename {output_filename} due to chrono format error, falling back to literal filename."
-            );
-        } else {
-            output_filename = format!("{delayed_format}");
-        }
+        self.renderer.center_on_minimap_position(pos);
+        true
+    }
 
-        // TODO: we could support more data types
-        if output_filename != "-" && !output_filename.ends_with(".png") {
-            log_result(
-                "The only supported format is png, but the filename does not end in png",
-                !APP_CONFIG.read().disable_notifications(),
-            );
-            return;
+    /// Applies the scroll action configured for the held modifiers, zooming is left to the
+    /// renderer. Returns whether the event was consumed.
+    fn handle_scroll(&mut self, ie: &InputEvent, sender: &ComponentSender<Self>) -> bool {
+        let InputEvent::Mouse(me) = ie else {
+            return false;
+        };
+        if me.type_ != MouseEventType::Scroll {
+            return false;
         }
+        let up = match me.pos.y {
+            v if v < 0.0 => true,
+            v if v > 0.0 => false,
+            _ => return true,
+        };
 
-        if let Some(tilde_stripped) =
-            output_filename.strip_prefix(&format!("~{}", std::path::MAIN_SEPARATOR_STR))
-        {
-            if let Some(h) = std::env::home_dir() {
-                let mut p = h;
-                p.push(tilde_stripped);
-                output_filename = p.to_string_lossy().into_owned();
-            } else {
-                log_result(
-                    "~ found but could not determine homedir",
-                    !APP_CONFIG.read().disable_notifications(),
-                );
-                return;
+        let action = APP_CONFIG.read().scroll().action(me.modifier);
+        match action {
+            ScrollAction::Zoom => false,
+            // the pointer and crop tools have no size
+            ScrollAction::Size
+                if matches!(self.active_tool_type(), Tools::Pointer | Tools::Crop) =>
+            {
+                false
             }
-        }
-
-        let data = match image.save_to_bufferv("png", &Vec::new()) {
-            Ok(d) => d,
-            Err(e) => {
-                println!("Error serializing image: {e}");
-                return;
+            ScrollAction::Size => {
+                self.scroll_size(up, sender);
+                true
             }
-        };
-
-        if output_filename == "-" {
-            // "-" means stdout
-            let stdout = io::stdout();
-            let mut handle = stdout.lock();
-            if let Err(e) = handle.write_all(&data) {
-                eprintln!("Error writing image to stdout: {e}");
+            ScrollAction::PanVertical | ScrollAction::PanHorizontal => {
+                let step = APP_CONFIG.read().pan_step_size() * if up { 1.0 } else { -1.0 };
+                self.renderer
+                    .set_drag_offset(if action == ScrollAction::PanVertical {
+                        Vec2D::new(0., step)
+                    } else {
+                        Vec2D::new(step, 0.)
+                    });
+                self.renderer.store_last_offset();
+                self.renderer
+                    .request_render(&APP_CONFIG.read().actions_on_right_click());
+                true
             }
-            return;
+            ScrollAction::None => true,
         }
-        match fs::write(&output_filename, data) {
-            Err(e) => log_result(
-                &format!("Error while saving file: {e}"),
-                !APP_CONFIG.read().disable_notifications(),
-            ),
-            Ok(_) => log_result(
-                &format!("File saved to '{}'.", &output_filename),
-                !APP_CONFIG.read().disable_notifications(),
-            ),
-        };
     }
 
-    fn handle_save_as(&self, image: &Pixbuf) {
-        let data = match image.save_to_bufferv("png", &Vec::new()) {
-            Ok(d) => d,
-            Err(e) => {
-                println!("Error serializing image: {e}");
-                return;
-            }
+    fn scroll_size(&mut self, up: bool, sender: &ComponentSender<Self>) {
+        let factor = if up {
+            SIZE_SCROLL_STEP
+        } else {
+            1.0 / SIZE_SCROLL_STEP
         };
+        let value = (self.style.annotation_size_factor * factor).clamp(0.1, 100.0);
+        self.style.annotation_size_factor = value;
+        self.active_tool
+            .borrow_mut()
+            .handle_event(ToolEvent::StyleChanged(self.style));
+        sender
+            .output_sender()
+            .emit(SketchBoardOutput::AnnotationSizeChanged(value));
 
-        let root = self.renderer.toplevel_window();
+        self.show_indicator(format!("Size: {value:.2}"), SIZE_INDICATOR_TIMEOUT, sender);
+    }
 
-        relm4::spawn_local(async move {
-            let builder = gtk::FileChooserDialog::builder()
-                .modal(false)
-                .title("Save Image As")
-                .action(gtk::FileChooserAction::Save);
+    /// Scrolling, also with Shift, goes to the active tool first while it is drawing
+    /// something.
+    fn handle_scroll_in_tool(&mut self, ie: &InputEvent) -> bool {
+        let InputEvent::Mouse(me) = ie else {
+            return false;
+        };
+        if me.type_ != MouseEventType::Scroll
+            || !(me.modifier - ModifierType::SHIFT_MASK).is_empty()
+            || !self.active_tool.borrow().active()
+        {
+            return false;
+        }
+        !matches!(
+            self.active_tool
+                .borrow_mut()
+                .handle_event(ToolEvent::Input(ie.clone())),
+            ToolUpdateResult::Unmodified
+        )
+    }
 
-            let dialog = match root {
-                Some(w) => builder.transient_for(&w),
-                None => builder,
-            }
-            .build();
+    /// Shift+scroll with the pointer tool resizes the selected annotation, if it can be
+    /// resized.
+    fn handle_scroll_selection(&mut self, ie: &InputEvent) -> bool {
+        let InputEvent::Mouse(me) = ie else {
+            return false;
+        };
+        if me.type_ != MouseEventType::Scroll
+            || me.modifier != ModifierType::SHIFT_MASK
+            || self.active_tool_type() != Tools::Pointer
+        {
+            return false;
+        }
+        let factor = match me.pos.y {
+            v if v < 0.0 => SIZE_SCROLL_STEP,
+            v if v > 0.0 => 1.0 / SIZE_SCROLL_STEP,
+            _ => return false,
+        };
+        self.renderer.scale_selection(factor)
+    }
 
-            dialog.add_buttons(&[
-                ("Cancel", gtk::ResponseType::Cancel),
-                ("Save", gtk::ResponseType::Accept),
-            ]);
+    /// Alt+scroll changes the corner radius of new rectangles.
+    fn handle_scroll_corner_radius(
+        &mut self,
+        ie: &InputEvent,
+        sender: &ComponentSender<Self>,
+    ) -> bool {
+        let InputEvent::Mouse(me) = ie else {
+            return false;
+        };
+        if me.type_ != MouseEventType::Scroll
+            || me.modifier != ModifierType::ALT_MASK
+            || self.active_tool_type() != Tools::Rectangle
+        {
+            return false;
+        }
 
-            dialog.connect_response(move |dialog, response| {
-                if response == gtk::ResponseType::Accept {
-                    if let Some(file) = dialog.file() {
-                        let output_filename = match file.path() {
-                            Some(path) => path.to_string_lossy().into_owned(),
-                            None => return,
-                        };
+        let step = match me.pos.y {
+            v if v < 0.0 => CORNER_RADIUS_SCROLL_STEP,
+            v if v > 0.0 => -CORNER_RADIUS_SCROLL_STEP,
+            _ => return true,
+        };
+        let value = (self.style.corner_radius + step).clamp(0.0, 100.0);
+        self.style.corner_radius = value;
+        self.active_tool
+            .borrow_mut()
+            .handle_event(ToolEvent::StyleChanged(self.style));
+        sender
+            .output_sender()
+            .emit(SketchBoardOutput::CornerRadiusChanged(value));
 
-                        match fs::write(&output_filename, &data) {
-                            Err(e) => log_result(
-                                &format!("Error while saving file: {e}"),
-                                !APP_CONFIG.read().disable_notifications(),
-                            ),
-                            Ok(_) => log_result(
-                                &format!("File saved to '{}'.", &output_filename),
-                                !APP_CONFIG.read().disable_notifications(),
-                            ),
-                        };
-                    }
-                }
-                dialog.close();
-            });
+        self.show_indicator(
+            format!("Corner radius: {value:.0}"),
+            SIZE_INDICATOR_TIMEOUT,
+            sender,
+        );
+        true
+    }
 
-            dialog.show();
+    /// Shows `text` in the indicator at the top for `timeout`.
+    fn show_indicator(&mut self, text: String, timeout: Duration, sender: &ComponentSender<Self>) {
+        self.size_indicator = Some(text);
+        self.size_indicator_generation = self.size_indicator_generation.wrapping_add(1);
+        let generation = self.size_indicator_generation;
+        let input = sender.input_sender().clone();
+        glib::timeout_add_local_once(timeout, move || {
+            input.emit(SketchBoardInput::HideSizeIndicator(generation));
         });
     }
 
-    fn save_to_clipboard(&self, texture: &impl IsA<Texture>) -> anyhow::Result<()> {
-        let display = DisplayManager::get()
-            .default_display()
-            .ok_or(anyhow!("Cannot open default display for clipboard."))?;
-        display.clipboard().set_texture(texture);
-
-        Ok(())
+    /// Shows `message` as a toast, prints it and sends it as a notification unless disabled.
+    fn report(&self, severity: Severity, message: &str) {
+        toasts::report(self.toasts.sender(), severity, message);
     }
 
-    fn save_to_external_process(
-        &self,
-        texture: &impl IsA<Texture>,
-        command: &str,
-    ) -> anyhow::Result<()> {
-        let mut child = Command::new("sh")
-            .arg("-c")
-            .arg(command)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::null())
-            .spawn()?;
+    // the indicator is shown above the toasts, unchanged text is ignored there
+    fn update_toasts(&self) {
+        self.toasts
+            .emit(ToastsInput::SetIndicator(self.size_indicator.clone()));
+    }
 
-        let child_stdin = child.stdin.as_mut().unwrap();
-        child_stdin.write_all(texture.save_to_png_bytes().as_ref())?;
+    /// Handles Escape once the active tool had nothing to cancel: deselects first if
+    /// configured, then runs the escape actions. With `confirm-escape`, exiting with unsaved
+    /// changes needs a second press while the prompt is shown.
+    fn handle_escape(&mut self, sender: &ComponentSender<Self>) -> ToolUpdateResult {
+        if APP_CONFIG.read().escape_deselects() && self.renderer.clear_selection() {
+            return ToolUpdateResult::Redraw;
+        }
 
-        if !child.wait()?.success() {
-            return Err(anyhow!("Writing to process '{command}' failed."));
+        let actions = APP_CONFIG.read().actions_on_escape();
+        let confirmed = self.size_indicator.is_some()
+            && self.escape_confirmation == Some(self.size_indicator_generation);
+        if APP_CONFIG.read().confirm_escape()
+            && actions.contains(&Action::Exit)
+            && self.renderer.history_position() != self.saved_position
+            && !confirmed
+        {
+            self.show_indicator(
+                "Unsaved changes, press Escape again to exit".to_string(),
+                ESCAPE_CONFIRM_TIMEOUT,
+                sender,
+            );
+            self.escape_confirmation = Some(self.size_indicator_generation);
+            return ToolUpdateResult::Unmodified;
         }
 
-        Ok(())
+        self.renderer.request_render(&actions);
+        ToolUpdateResult::Unmodified
     }
 
-    fn handle_copy_clipboard(&self, image: &Pixbuf) {
-        let texture = Texture::for_pixbuf(image);
-
-        let result = if let Some(command) = APP_CONFIG.read().copy_command() {
-            self.save_to_external_process(&texture, command)
-        } else {
-            self.save_to_clipboard(&texture)
+    /// Erases under the eraser while it is dragged, the result is committed once the
+    /// drag ends or another tool is selected.
+    fn handle_eraser(&mut self, ie: &InputEvent) {
+        let InputEvent::Mouse(me) = ie else {
+            return;
         };
+        if self.active_tool_type() != Tools::Eraser {
+            return;
+        }
+        let eraser = self.tools.get_eraser_tool();
+        let eraser = eraser.borrow();
+        if let Some(center) = eraser.position() {
+            self.renderer.erase_at(center, eraser.radius());
+        }
+        if me.type_ == MouseEventType::EndDrag {
+            self.renderer.finish_erasing();
+        }
+    }
 
-        match result {
-            Err(e) => println!("Error saving {e}"),
-            Ok(()) => {
-                log_result(
-                    "Copied to clipboard.",
-                    !APP_CONFIG.read().disable_notifications(),
-                );
-
-                // TODO: rethink order and messaging patterns
-                if APP_CONFIG.read().save_after_copy() {
-                    self.handle_save(image);
+    /// Tells the connector tool which annotation its ends would attach to.
+    fn handle_connector(&mut self, ie: &InputEvent) {
+        let InputEvent::Mouse(me) = ie else {
+            return;
+        };
+        if self.active_tool_type() != Tools::Connector {
+            return;
+        }
+        let connector = self.tools.get_connector_tool();
+        let pos = match me.type_ {
+            MouseEventType::BeginDrag => me.pos,
+            MouseEventType::UpdateDrag | MouseEventType::EndDrag => {
+                let Some(start) = connector.borrow().start() else {
+                    return;
                 };
+                start + me.pos
             }
-        }
+            _ => return,
+        };
+        connector
+            .borrow_mut()
+            .set_hovered(self.renderer.annotation_at(pos));
     }
 
-    fn handle_undo(&mut self) -> ToolUpdateResult {
-        if self.active_tool.borrow().active() {
-            self.active_tool.borrow_mut().handle_undo()
-        } else if self.renderer.undo() {
+    /// Runs the action of the button of `ie` from `[mouse]`, unless it is passed to the
+    /// active tool.
+    fn handle_mouse_button(
+        &mut self,
+        ie: &InputEvent,
+        sender: &ComponentSender<Self>,
+    ) -> Option<ToolUpdateResult> {
+        let InputEvent::Mouse(me) = ie else {
+            return None;
+        };
+        if let Some(result) = self.handle_gesture(me, sender) {
+            return Some(result);
+        }
+        let action = APP_CONFIG.read().mouse().action(me.button);
+        let result = match (action, me.type_) {
+            (ButtonAction::None, _) | (_, MouseEventType::Scroll | MouseEventType::PointerPos) => {
+                return None
+            }
+            (ButtonAction::Pan, MouseEventType::UpdateDrag | MouseEventType::EndDrag) => {
+                self.renderer.set_drag_offset(me.pos);
+                self.renderer.set_is_drag(true);
+
+                if me.type_ == MouseEventType::EndDrag {
+                    self.renderer.store_last_offset();
+                    self.renderer.set_is_drag(false);
+                }
+                self.renderer
+                    .request_render(&APP_CONFIG.read().actions_on_right_click());
+                ToolUpdateResult::Unmodified
+            }
+            (_, MouseEventType::Click) => self.run_button_action(action, me.pos, sender),
+            // clicks act once, the drags of their buttons are dropped
+            (
+                _,
+                MouseEventType::BeginDrag | MouseEventType::UpdateDrag | MouseEventType::EndDrag,
+            ) => ToolUpdateResult::Unmodified,
+        };
+        Some(result)
+    }
+
+    /// Runs the click `action` of a mouse button at `pos`, in canvas coordinates.
+    fn run_button_action(
+        &mut self,
+        action: ButtonAction,
+        pos: Vec2D,
+        sender: &ComponentSender<Self>,
+    ) -> ToolUpdateResult {
+        match action {
+            ButtonAction::Pan | ButtonAction::None => ToolUpdateResult::Unmodified,
+            ButtonAction::ActionsOnRightClick => {
+                let actions = APP_CONFIG.read().actions_on_right_click();
+                self.handle_action(&actions)
+            }
+            ButtonAction::Undo => self.handle_undo(),
+            ButtonAction::Redo => self.handle_redo(),
+            ButtonAction::DeleteUnderCursor => {
+                let pos = self.renderer.abs_canvas_to_image_coordinates(pos);
+                if self.renderer.delete_at(pos) {
+                    ToolUpdateResult::Redraw
+                } else {
+                    ToolUpdateResult::Unmodified
+                }
+            }
+            ButtonAction::NextColor | ButtonAction::PreviousColor => {
+                self.cycle_color(action == ButtonAction::NextColor, sender);
+                ToolUpdateResult::Unmodified
+            }
+        }
+    }
+
+    /// Drags with the right button are gestures while `[mouse.gestures]` is set. Releasing it
+    /// without moving runs the action of the button instead.
+    fn handle_gesture(
+        &mut self,
+        me: &MouseEventMsg,
+        sender: &ComponentSender<Self>,
+    ) -> Option<ToolUpdateResult> {
+        if me.button != MouseButton::Secondary || !APP_CONFIG.read().mouse().has_gestures() {
+            return None;
+        }
+        let result = match me.type_ {
+            MouseEventType::BeginDrag => {
+                self.gesture = Some((me.pos, None));
+                ToolUpdateResult::Unmodified
+            }
+            MouseEventType::UpdateDrag => {
+                if let (Some((_, shown)), Some(direction)) =
+                    (&mut self.gesture, gesture_direction(me.pos))
+                {
+                    if *shown != Some(direction) {
+                        *shown = Some(direction);
+                        let arrow = match direction {
+                            GestureDirection::Left => "←",
+                            GestureDirection::Right => "→",
+                            GestureDirection::Up => "↑",
+                            GestureDirection::Down => "↓",
+                        };
+                        self.show_indicator(arrow.to_string(), SIZE_INDICATOR_TIMEOUT, sender);
+                    }
+                }
+                ToolUpdateResult::Unmodified
+            }
+            MouseEventType::EndDrag => {
+                let (start, _) = self.gesture.take()?;
+                match gesture_direction(me.pos) {
+                    Some(direction) => self.run_gesture(direction, sender),
+                    None => {
+                        let action = APP_CONFIG.read().mouse().action(MouseButton::Secondary);
+                        self.run_button_action(action, start, sender)
+                    }
+                }
+            }
+            // the click is handled once the button is released
+            MouseEventType::Click => ToolUpdateResult::Unmodified,
+            MouseEventType::Scroll | MouseEventType::PointerPos => return None,
+        };
+        Some(result)
+    }
+
+    fn run_gesture(
+        &mut self,
+        direction: GestureDirection,
+        sender: &ComponentSender<Self>,
+    ) -> ToolUpdateResult {
+        let gesture = APP_CONFIG.read().mouse().gesture(direction);
+        match gesture {
+            Some(GestureAction::History(HistoryStep::Undo)) => self.handle_undo(),
+            Some(GestureAction::History(HistoryStep::Redo)) => self.handle_redo(),
+            Some(GestureAction::Tool(tool)) => {
+                self.run_key_command(KeyCommand::Tool(tool), sender);
+                ToolUpdateResult::Unmodified
+            }
+            Some(GestureAction::Action(action)) => self.handle_action(&[action]),
+            None => ToolUpdateResult::Unmodified,
+        }
+    }
+
+    /// Selects the palette color after the current one, or before it if not `forward`.
+    fn cycle_color(&self, forward: bool, sender: &ComponentSender<Self>) {
+        let len = APP_CONFIG.read().color_palette().palette().len();
+        if len == 0 {
+            return;
+        }
+        let current = APP_CONFIG
+            .read()
+            .color_palette()
+            .palette()
+            .iter()
+            .position(|&color| color == self.style.color);
+        let next = match current {
+            Some(index) if forward => (index + 1) % len,
+            Some(index) => (index + len - 1) % len,
+            None if forward => 0,
+            None => len - 1,
+        };
+        sender
+            .output_sender()
+            .emit(SketchBoardOutput::ColorSwitchShortcut(next as u64));
+    }
+
+    /// Selects the annotation under a click with the pointer tool.
+    /// Returns whether the selection changed.
+    fn handle_selection(&mut self, ie: &InputEvent) -> bool {
+        let InputEvent::Mouse(me) = ie else {
+            return false;
+        };
+        me.type_ == MouseEventType::Click
+            && me.button == MouseButton::Primary
+            && self.active_tool_type() == Tools::Pointer
+            && self.renderer.select_at(me.pos)
+    }
+
+    fn handle_nudge(&mut self, ke: KeyEventMsg) -> ToolUpdateResult {
+        let step = if ke.modifier == ModifierType::SHIFT_MASK {
+            10.0
+        } else {
+            1.0
+        };
+        let offset = match ke.key {
+            Key::Left => Vec2D::new(-step, 0.0),
+            Key::Right => Vec2D::new(step, 0.0),
+            Key::Up => Vec2D::new(0.0, -step),
+            Key::Down => Vec2D::new(0.0, step),
+            _ => return ToolUpdateResult::Unmodified,
+        };
+
+        if self.renderer.nudge_selection(offset) {
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        }
+    }
+
+    fn update_status(&mut self, ie: &InputEvent) {
+        let InputEvent::Mouse(me) = ie else {
+            return;
+        };
+        match me.type_ {
+            MouseEventType::PointerPos => {
+                self.cursor_pos = Some(self.renderer.abs_canvas_to_image_coordinates(me.pos));
+            }
+            MouseEventType::BeginDrag if !me.pans() => {
+                self.cursor_pos = Some(me.pos);
+                self.selection_size = Some(Vec2D::zero());
+            }
+            MouseEventType::UpdateDrag if !me.pans() => {
+                self.selection_size = Some(me.pos);
+            }
+            MouseEventType::EndDrag => self.selection_size = None,
+            _ => {}
+        }
+    }
+
+    /// The next drag chooses a region that is exported with `action` at full resolution,
+    /// leaving the crop as it is.
+    fn handle_start_region_export(
+        &mut self,
+        action: Action,
+        sender: &ComponentSender<Self>,
+    ) -> ToolUpdateResult {
+        let result = if self.deactivate_active_tool() {
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        };
+        self.region_export = Some((action, None));
+        let message = if action == Action::SaveToClipboard {
+            "Drag over the region to copy"
+        } else {
+            "Drag over the region to save"
+        };
+        self.show_indicator(message.to_string(), SIZE_INDICATOR_TIMEOUT, sender);
+        result
+    }
+
+    fn handle_region_export(&mut self, ie: &InputEvent) -> bool {
+        let (Some((action, start)), InputEvent::Mouse(me)) = (&mut self.region_export, ie) else {
+            return false;
+        };
+        // the pan button still pans
+        if me.pans() {
+            return true;
+        }
+        match (me.type_, *start) {
+            (MouseEventType::BeginDrag, _) => {
+                *start = Some(me.pos);
+                self.renderer
+                    .set_export_region(Some((me.pos, Vec2D::zero())));
+            }
+            (MouseEventType::UpdateDrag, Some(start)) => {
+                self.renderer.set_export_region(Some((start, me.pos)));
+            }
+            (MouseEventType::EndDrag, Some(start)) => {
+                let action = *action;
+                self.region_export = None;
+                self.renderer.set_export_region(None);
+                let region = math::rect_round(math::rect_ensure_positive_size(start, me.pos));
+                if !region.1.is_zero() {
+                    self.exporting_region = true;
+                    self.renderer.request_render_region(&[action], region);
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn handle_toggle_pixel_inspector(
+        &mut self,
+        sender: &ComponentSender<Self>,
+    ) -> ToolUpdateResult {
+        self.pixel_inspector = !self.pixel_inspector;
+        self.renderer
+            .set_inspector(self.cursor_pos.filter(|_| self.pixel_inspector));
+        let message = if self.pixel_inspector {
+            "Pixel inspector, click to copy the color"
+        } else {
+            "Pixel inspector off"
+        };
+        self.show_indicator(message.to_string(), SIZE_INDICATOR_TIMEOUT, sender);
+        ToolUpdateResult::Redraw
+    }
+
+    /// While the pixel inspector is shown it follows the cursor and takes all mouse input,
+    /// a click copies the color of the inspected pixel as hex.
+    fn handle_pixel_inspector(&mut self, ie: &InputEvent, sender: &ComponentSender<Self>) -> bool {
+        let (true, InputEvent::Mouse(me)) = (self.pixel_inspector, ie) else {
+            return false;
+        };
+        match me.type_ {
+            MouseEventType::PointerPos => self.renderer.set_inspector(self.cursor_pos),
+            MouseEventType::Click if me.button == MouseButton::Primary => {
+                let Some(color) = self.renderer.pixel_at(me.pos) else {
+                    return true;
+                };
+                let hex = color.to_hex();
+                match clipboard::from_config().copy_text(&hex) {
+                    Ok(()) => {
+                        self.show_indicator(format!("Copied {hex}"), SIZE_INDICATOR_TIMEOUT, sender)
+                    }
+                    Err(e) => self.report(Severity::Error, &format!("Error copying color: {e}")),
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Sends the histogram of the crop or the whole image to the panel while it is shown,
+    /// once the crop is no longer dragged.
+    fn update_histogram(&mut self, sender: &ComponentSender<Self>) {
+        if !self.histogram_visible || self.drag_start.is_some() {
+            return;
+        }
+        let region = self
+            .tools
+            .get_crop_tool()
+            .borrow()
+            .get_crop()
+            .map(|c| math::rect_round(c.get_rectangle()))
+            .filter(|(_, size)| !size.is_zero());
+        if self.histogram_region == Some(region) {
+            return;
+        }
+        self.histogram_region = Some(region);
+
+        let histogram = Histogram::new(&self.renderer.background_image(), region);
+        sender
+            .output_sender()
+            .emit(SketchBoardOutput::HistogramChanged(
+                Box::new(histogram),
+                region.is_some(),
+            ));
+    }
+
+    fn status_text(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(mode) = self.edit_mode {
+            parts.push(format!("-- {} --", mode.label()));
+        }
+        if let Some(pos) = self.cursor_pos {
+            parts.push(format!("{:.0}, {:.0}", pos.x, pos.y));
+        }
+        if let Some(size) = self.selection_size {
+            parts.push(format!("{:.0} × {:.0}", size.x.abs(), size.y.abs()));
+        }
+        parts.push(format!("{:.0}%", self.renderer.zoom_level() * 100.0));
+        parts.join("  |  ")
+    }
+
+    fn deactivate_active_tool(&mut self) -> bool {
+        if self.active_tool.borrow().active() {
+            if let ToolUpdateResult::Commit(result) =
+                self.active_tool.borrow_mut().handle_deactivated()
+            {
+                self.renderer.commit(result);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn handle_action(&mut self, actions: &[Action]) -> ToolUpdateResult {
+        let rv = if self.deactivate_active_tool() {
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        };
+        self.renderer.request_render(actions);
+        rv
+    }
+
+    fn handle_render_result(
+        &mut self,
+        image: RenderedImage,
+        actions: Vec<Action>,
+        position: Option<Option<usize>>,
+        sender: &ComponentSender<Self>,
+    ) {
+        let scales = self.encoding_scales(&actions);
+        if scales.is_empty() {
+            self.handle_encoded(None, actions, Vec::new(), sender);
+            return;
+        }
+
+        // encoding large images takes a while, which would freeze the window
+        let progress = Progress::start(self.toasts.sender(), "Encoding image");
+        sender.spawn_oneshot_command(move || {
+            let image = EncodedImage::new(image, scales, position);
+            drop(progress);
+            SketchBoardCommand::Encoded(image, actions)
+        });
+    }
+
+    // scales the image has to be encoded at for `actions`
+    fn encoding_scales(&self, actions: &[Action]) -> Vec<f32> {
+        let dialog = actions.contains(&Action::SaveToFile) && self.saves_with_dialog();
+        let config = APP_CONFIG.read();
+        let mut scales = Vec::new();
+        let copies = dialog
+            || actions.iter().any(|action| {
+                matches!(
+                    action,
+                    Action::SaveToClipboard
+                        | Action::SaveToFileAs
+                        | Action::SaveAnnotationsToFileAs
+                        | Action::OpenInEditor
+                        | Action::RunQuickAction(_)
+                )
+            });
+        if copies {
+            scales.push(1.0);
+        }
+
+        let saves = actions.iter().any(|action| match action {
+            Action::SaveToFile | Action::CopyFilePath => true,
+            Action::SaveToClipboard => config.save_after_copy(),
+            _ => false,
+        });
+        if saves {
+            for output in self.session.outputs(&config) {
+                if !scales.contains(&output.scale) {
+                    scales.push(output.scale);
+                }
+            }
+        }
+        scales
+    }
+
+    /// Runs `actions` with the encoded image, `saved_files` are the files it was already
+    /// saved to.
+    fn handle_encoded(
+        &self,
+        image: Option<EncodedImage>,
+        actions: Vec<Action>,
+        mut saved_files: Vec<PathBuf>,
+        sender: &ComponentSender<Self>,
+    ) {
+        // image the clipboard holds, kept there when file paths are copied as well
+        let mut copied_texture = None;
+
+        for (i, &action) in actions.iter().enumerate() {
+            match action {
+                Action::SaveToFile if self.saves_with_dialog() => {
+                    let Some(image) = image else {
+                        return;
+                    };
+                    // the remaining actions run once the dialog saved the image, the image
+                    // is handed on after the dialog took its data
+                    let pending = Rc::new(RefCell::new(None));
+                    let resume = pending.clone();
+                    let sender = sender.clone();
+                    self.handle_save_as(&image, self.suggested_filename(), move |path| {
+                        if let Some((image, actions)) = resume.take() {
+                            sender.spawn_oneshot_command(move || {
+                                SketchBoardCommand::SavedAs(path, image, actions)
+                            });
+                        }
+                    });
+                    pending.replace(Some((image, actions[i + 1..].to_vec())));
+                    return;
+                }
+                Action::SaveToClipboard => {
+                    if let Some(ref image) = image {
+                        copied_texture = self.handle_copy_clipboard(image);
+                        if copied_texture.is_some() {
+                            Self::mark_saved(image, sender);
+                        }
+                    }
+                }
+                Action::SaveToFile => {
+                    if let Some(ref image) = image {
+                        let saved = self.handle_save(image);
+                        if APP_CONFIG.read().copy_file_path() {
+                            self.handle_copy_file_paths(&saved, copied_texture.as_ref());
+                        }
+                        if !saved.is_empty() {
+                            Self::mark_saved(image, sender);
+                        }
+                        saved_files.extend(saved);
+                    }
+                }
+                Action::CopyFilePath => {
+                    if let Some(ref image) = image {
+                        // save first unless an earlier action did
+                        if saved_files.is_empty() {
+                            saved_files = self.handle_save(image);
+                        }
+                        self.handle_copy_file_paths(&saved_files, copied_texture.as_ref());
+                        if !saved_files.is_empty() {
+                            Self::mark_saved(image, sender);
+                        }
+                    }
+                }
+                Action::SaveToFileAs => {
+                    if let Some(ref image) = image {
+                        let position = image.position;
+                        let sender = sender.clone();
+                        self.handle_save_as(image, None, move |_| {
+                            if let Some(position) = position {
+                                sender.input(SketchBoardInput::Saved(position));
+                            }
+                        });
+                    }
+                }
+                Action::SaveAnnotationsToFileAs => {
+                    if let Some(ref image) = image {
+                        self.handle_save_as(image, None, |_| ());
+                    }
+                }
+                Action::RunQuickAction(index) => {
+                    if let Some(ref image) = image {
+                        self.handle_quick_action(image, index, sender);
+                    }
+                    // the image is neither saved nor copied, so this doesn't exit early
+                    continue;
+                }
+                Action::OpenInEditor => {
+                    if let Some(ref image) = image {
+                        self.handle_open_in_editor(image, sender);
+                    }
+                    continue;
+                }
+                _ => (),
+            }
+
+            if self.batch_pending && !saved_files.is_empty() {
+                let _ = sender.output(SketchBoardOutput::NextImage);
+                return;
+            }
+            if self.session.early_exit(&APP_CONFIG.read()) || action == Action::Exit {
+                self.handle_exit(sender);
+                return;
+            }
+        }
+    }
+
+    // continues after the save dialog as after saving to the output filename
+    fn handle_saved_as(
+        &self,
+        path: PathBuf,
+        image: EncodedImage,
+        actions: Vec<Action>,
+        sender: &ComponentSender<Self>,
+    ) {
+        Self::mark_saved(&image, sender);
+        let saved = vec![path];
+        if APP_CONFIG.read().copy_file_path() {
+            self.handle_copy_file_paths(&saved, None);
+        }
+        if self.batch_pending {
+            let _ = sender.output(SketchBoardOutput::NextImage);
+        } else if self.session.early_exit(&APP_CONFIG.read()) {
+            self.handle_exit(sender);
+        } else {
+            self.handle_encoded(Some(image), actions, saved, sender);
+        }
+    }
+
+    // changes are unsaved while the undo history is at another point than the one saved last
+    fn mark_saved(image: &EncodedImage, sender: &ComponentSender<Self>) {
+        if let Some(position) = image.position {
+            sender.input(SketchBoardInput::Saved(position));
+        }
+    }
+
+    // runs the command in the background, editors may stay open and uploads take a while
+    fn handle_quick_action(
+        &self,
+        image: &EncodedImage,
+        index: usize,
+        sender: &ComponentSender<Self>,
+    ) {
+        let Some(action) = APP_CONFIG.read().quick_actions().get(index).cloned() else {
+            return;
+        };
+        let png = match image.png(1.0) {
+            Ok(png) => png.to_vec(),
+            Err(e) => {
+                log::error!("{e}");
+                return;
+            }
+        };
+        let progress = Progress::start(self.toasts.sender(), &format!("Running {}", action.name));
+        sender.spawn_oneshot_command(move || {
+            let result = external_command::run(&action.command, &png)
+                .map(|_| ())
+                .map_err(|e| format!("{e:#}"));
+            drop(progress);
+            SketchBoardCommand::QuickActionFinished(action.name, result)
+        });
+    }
+
+    // the editor gets a temporary file, which replaces the image once the editor exits
+    fn handle_open_in_editor(&self, image: &EncodedImage, sender: &ComponentSender<Self>) {
+        let config = APP_CONFIG.read();
+        let Some(mut command) = config.external_editor().map(String::from) else {
+            drop(config);
+            self.report(Severity::Warning, "No external-editor is configured.");
+            return;
+        };
+        if !command.contains("{path}") {
+            command.push_str(" {path}");
+        }
+        let reload = config.external_editor_reload();
+        drop(config);
+
+        let png = match image.png(1.0) {
+            Ok(png) => png.to_vec(),
+            Err(e) => {
+                log::error!("{e}");
+                return;
+            }
+        };
+        sender.spawn_oneshot_command(move || {
+            let result = external_command::run(&command, &png)
+                .and_then(|file| match file {
+                    Some(file) if reload => Ok(Some(Pixbuf::from_file(&file.path)?)),
+                    _ => Ok(None),
+                })
+                .map_err(|e| format!("{e:#}"));
+            SketchBoardCommand::EditorClosed(result)
+        });
+    }
+
+    fn handle_exit(&self, sender: &ComponentSender<Self>) {
+        let _ = sender.output(SketchBoardOutput::RequestExit);
+    }
+
+    /// Returns the files written, if any.
+    fn handle_save(&self, image: &EncodedImage) -> Vec<PathBuf> {
+        let outputs = self.session.outputs(&APP_CONFIG.read());
+        if outputs.is_empty() {
+            log::warn!("No Output filename specified!");
+            return Vec::new();
+        }
+
+        outputs
+            .into_iter()
+            .filter_map(|output| self.save_to_output(image, output))
+            .collect()
+    }
+
+    // saving to file asks where with `save-dialog`, without an output filename, or when a
+    // file would be replaced with `overwrite-policy = "ask"`
+    fn saves_with_dialog(&self) -> bool {
+        let (save_dialog, policy) = {
+            let config = APP_CONFIG.read();
+            (
+                config.save_dialog() || self.session.outputs(&config).is_empty(),
+                config.overwrite_policy(),
+            )
+        };
+        save_dialog || (policy == OverwritePolicy::Ask && self.output_exists())
+    }
+
+    fn output_exists(&self) -> bool {
+        let outputs = self.session.outputs(&APP_CONFIG.read());
+        outputs
+            .into_iter()
+            .filter_map(|output| self.expand_output_filename(output.filename))
+            .any(|filename| filename != "-" && Path::new(&filename).exists())
+    }
+
+    // `name-1.png`, `name-2.png`, ... for an existing `name.png`, whichever is unused first
+    fn unused_filename(filename: String) -> String {
+        let path = Path::new(&filename);
+        if !path.exists() {
+            return filename;
+        }
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut i = 1;
+        loop {
+            let candidate = path.with_file_name(format!("{stem}-{i}.png"));
+            if !candidate.exists() {
+                return candidate.to_string_lossy().into_owned();
+            }
+            i += 1;
+        }
+    }
+
+    /// The `output-dir` with `~` expanded.
+    fn output_dir() -> Option<PathBuf> {
+        let dir = APP_CONFIG.read().output_dir()?.to_string();
+        match dir.strip_prefix(&format!("~{}", std::path::MAIN_SEPARATOR_STR)) {
+            Some(tilde_stripped) => std::env::home_dir().map(|home| home.join(tilde_stripped)),
+            None => Some(PathBuf::from(dir)),
+        }
+    }
+
+    // where the save dialog starts, from the first output filename
+    fn suggested_filename(&self) -> Option<PathBuf> {
+        let output = self
+            .session
+            .outputs(&APP_CONFIG.read())
+            .into_iter()
+            .next()?
+            .filename;
+        self.expand_output_filename(output)
+            .filter(|filename| filename != "-")
+            .map(PathBuf::from)
+    }
+
+    fn save_to_output(&self, image: &EncodedImage, output: OutputTarget) -> Option<PathBuf> {
+        let mut output_filename = self.expand_output_filename(output.filename)?;
+
+        // TODO: we could support more data types
+        if output_filename != "-" && !output_filename.ends_with(".png") {
+            self.report(
+                Severity::Error,
+                "The only supported format is png, but the filename does not end in png",
+            );
+            return None;
+        }
+
+        // asking is done before saving to file, copying the file path increments instead
+        if output_filename != "-"
+            && APP_CONFIG.read().overwrite_policy() != OverwritePolicy::Overwrite
+        {
+            output_filename = Self::unused_filename(output_filename);
+        }
+
+        let data = match image.png(output.scale) {
+            Ok(d) => d,
+            Err(e) => {
+                log::error!("{e} for {output_filename}");
+                return None;
+            }
+        };
+
+        if output_filename == "-" {
+            // "-" means stdout
+            let stdout = io::stdout();
+            let mut handle = stdout.lock();
+            if let Err(e) = handle.write_all(data) {
+                log::error!("Error writing image to stdout: {e}");
+            }
+            return None;
+        }
+        match fs::write(&output_filename, data) {
+            Err(e) => {
+                self.report(Severity::Error, &format!("Error while saving file: {e}"));
+                None
+            }
+            Ok(_) => {
+                self.report(
+                    Severity::Success,
+                    &format!("File saved to '{}'.", &output_filename),
+                );
+                Self::print_saved_uri(Path::new(&output_filename));
+                Some(PathBuf::from(output_filename))
+            }
+        }
+    }
+
+    // with `print-saved-uri`, the program that started satty learns where the result is
+    fn print_saved_uri(path: &Path) {
+        if APP_CONFIG.read().print_saved_uri() {
+            println!("{}", gtk::gio::File::for_path(path).uri());
+        }
+    }
+
+    /// Replaces `{input}`, `{title}` and the chrono format specifiers in `output_filename`,
+    /// places relative names in the `output-dir` and expands `~`.
+    fn expand_output_filename(&self, mut output_filename: String) -> Option<String> {
+        // run the output filename by "chrono date format"
+        let delayed_format = chrono::Local::now().format(&output_filename);
+        let result = panic::catch_unwind(|| {
+            delayed_format.to_string();
+        });
+
+        if result.is_err() {
+            log::warn!(
+                "Could not format filename {output_filename} due to chrono format error, falling back to literal filename."
+            );
+        } else {
+            output_filename = format!("{delayed_format}");
+        }
+
+        // the names are put in after the date formats, they may contain `%`
+        let window_title = APP_CONFIG.read().window_title().map(str::to_string);
+        output_filename = Self::replace_names(
+            output_filename,
+            self.input_name.as_deref(),
+            window_title.as_deref(),
+        );
+
+        if output_filename != "-"
+            && !output_filename.starts_with('~')
+            && Path::new(&output_filename).is_relative()
+        {
+            if let Some(dir) = Self::output_dir() {
+                output_filename = dir.join(&output_filename).to_string_lossy().into_owned();
+            }
+        }
+
+        if let Some(tilde_stripped) =
+            output_filename.strip_prefix(&format!("~{}", std::path::MAIN_SEPARATOR_STR))
+        {
+            if let Some(h) = std::env::home_dir() {
+                let mut p = h;
+                p.push(tilde_stripped);
+                output_filename = p.to_string_lossy().into_owned();
+            } else {
+                self.report(Severity::Error, "~ found but could not determine homedir");
+                return None;
+            }
+        }
+
+        Some(output_filename)
+    }
+
+    /// Replaces `{input}` with the name of the annotated file and `{title}` with the window
+    /// title, placeholders without a value are kept.
+    fn replace_names(
+        mut output_filename: String,
+        input: Option<&str>,
+        title: Option<&str>,
+    ) -> String {
+        if let Some(input) = input {
+            output_filename = output_filename.replace("{input}", input);
+        }
+        if let Some(title) = title {
+            // a title may contain anything, but must not add directories
+            output_filename = output_filename.replace("{title}", &title.replace('/', "_"));
+        }
+        output_filename
+    }
+
+    /// Writes the annotations to the `--template` file, to add them to the next screenshot.
+    fn save_template(&self) {
+        let Some(path) = APP_CONFIG.read().template().map(str::to_string) else {
+            return;
+        };
+        let (specs, skipped) = self.renderer.annotation_specs(false);
+        if let Err(e) = annotations::store_template(&path, &specs) {
+            self.report(Severity::Error, &format!("{e:#}"));
+        } else if skipped > 0 {
+            self.report(
+                Severity::Warning,
+                &format!("{skipped} annotations are not supported by templates"),
+            );
+        }
+    }
+
+    /// Asks where to save `image`, starting at `suggested` or the `output-dir`, and calls
+    /// `on_saved` once it is written.
+    fn handle_save_as(
+        &self,
+        image: &EncodedImage,
+        suggested: Option<PathBuf>,
+        on_saved: impl Fn(PathBuf) + 'static,
+    ) {
+        let data = match image.png(1.0) {
+            Ok(d) => d.to_vec(),
+            Err(e) => {
+                log::error!("{e}");
+                return;
+            }
+        };
+
+        let root = self.renderer.toplevel_window();
+        let toasts = self.toasts.sender().clone();
+
+        relm4::spawn_local(async move {
+            let builder = gtk::FileChooserDialog::builder()
+                .modal(false)
+                .title("Save Image As")
+                .action(gtk::FileChooserAction::Save);
+
+            let dialog = match root {
+                Some(w) => builder.transient_for(&w),
+                None => builder,
+            }
+            .build();
+
+            dialog.add_buttons(&[
+                ("Cancel", gtk::ResponseType::Cancel),
+                ("Save", gtk::ResponseType::Accept),
+            ]);
+            let folder = match &suggested {
+                Some(path) => path.parent().map(Path::to_path_buf),
+                None => Self::output_dir(),
+            };
+            if let Some(folder) = folder.filter(|folder| folder.is_dir()) {
+                let _ = dialog.set_current_folder(Some(&gtk::gio::File::for_path(folder)));
+            }
+            if let Some(name) = suggested.as_deref().and_then(Path::file_name) {
+                dialog.set_current_name(&name.to_string_lossy());
+            }
+
+            dialog.connect_response(move |dialog, response| {
+                if response == gtk::ResponseType::Accept {
+                    if let Some(file) = dialog.file() {
+                        let output_filename = match file.path() {
+                            Some(path) => path.to_string_lossy().into_owned(),
+                            None => return,
+                        };
+
+                        match fs::write(&output_filename, &data) {
+                            Err(e) => toasts::report(
+                                &toasts,
+                                Severity::Error,
+                                &format!("Error while saving file: {e}"),
+                            ),
+                            Ok(_) => {
+                                toasts::report(
+                                    &toasts,
+                                    Severity::Success,
+                                    &format!("File saved to '{}'.", &output_filename),
+                                );
+                                Self::print_saved_uri(Path::new(&output_filename));
+                                on_saved(PathBuf::from(output_filename));
+                            }
+                        };
+                    }
+                }
+                dialog.close();
+            });
+
+            dialog.show();
+        });
+    }
+
+    /// Asks where to save the images of the steps of the history.
+    fn handle_save_steps(&self, export: StepsExport, sender: &ComponentSender<Self>) {
+        let root = self.renderer.toplevel_window();
+        let sender = sender.clone();
+        let (title, name) = match export {
+            StepsExport::Recording => ("Save Recording As", "recording.gif"),
+            StepsExport::Images => ("Save Steps As", "step.png"),
+        };
+
+        relm4::spawn_local(async move {
+            let builder = gtk::FileChooserDialog::builder()
+                .modal(false)
+                .title(title)
+                .action(gtk::FileChooserAction::Save);
+
+            let dialog = match root {
+                Some(w) => builder.transient_for(&w),
+                None => builder,
+            }
+            .build();
+
+            dialog.add_buttons(&[
+                ("Cancel", gtk::ResponseType::Cancel),
+                ("Save", gtk::ResponseType::Accept),
+            ]);
+            dialog.set_current_name(name);
+
+            dialog.connect_response(move |dialog, response| {
+                if response == gtk::ResponseType::Accept {
+                    if let Some(path) = dialog.file().and_then(|f| f.path()) {
+                        sender.input(SketchBoardInput::SaveSteps(export, path));
+                    }
+                }
+                dialog.close();
+            });
+
+            dialog.show();
+        });
+    }
+
+    /// Renders the steps of the history one per frame and encodes them in the background as
+    /// they come in, at most `QUEUED_STEPS` wait for the encoder.
+    fn handle_export_steps(
+        &self,
+        export: StepsExport,
+        path: PathBuf,
+        sender: &ComponentSender<Self>,
+    ) {
+        let (frames, received) = mpsc::sync_channel(QUEUED_STEPS);
+        let count = self.renderer.export_steps(frames);
+        let delay = Duration::from_millis(APP_CONFIG.read().recording_step_duration());
+        let mut progress = Progress::start(
+            self.toasts.sender(),
+            match export {
+                StepsExport::Recording => "Saving recording",
+                StepsExport::Images => "Saving steps",
+            },
+        );
+        sender.spawn_oneshot_command(move || match export {
+            StepsExport::Recording => SketchBoardCommand::RecordingSaved(
+                recording::save(received, count, &path, delay, &mut progress)
+                    .map(|()| path)
+                    .map_err(|e| format!("{e:#}")),
+            ),
+            StepsExport::Images => SketchBoardCommand::StepImagesSaved(Self::save_step_images(
+                received,
+                count,
+                &path,
+                &mut progress,
+            )),
+        });
+    }
+
+    // one PNG per step, numbered after the stem of `path`, e.g. step-01.png
+    fn save_step_images(
+        frames: Receiver<RenderedImage>,
+        count: usize,
+        path: &Path,
+        progress: &mut Progress,
+    ) -> Result<Vec<PathBuf>, String> {
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let digits = count.to_string().len().max(2);
+        let saved = frames
+            .into_iter()
+            .enumerate()
+            .map(|(i, frame)| {
+                let target = path.with_file_name(format!("{stem}-{:0digits$}.png", i + 1));
+                let png = EncodedImage::encode(&Self::image_to_pixbuf(frame), 1.0)?;
+                fs::write(&target, png)
+                    .map_err(|e| format!("Error while saving '{}': {e}", target.display()))?;
+                progress.set_steps(i + 1, count);
+                Ok(target)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        // the sender is dropped early when rendering fails
+        if saved.len() < count {
+            return Err("Rendering the steps failed".to_string());
+        }
+        Ok(saved)
+    }
+
+    /// Returns the texture if it was placed on the clipboard.
+    fn handle_copy_clipboard(&self, image: &EncodedImage) -> Option<Texture> {
+        let png = match image.png(1.0) {
+            Ok(png) => png,
+            Err(e) => {
+                log::error!("{e}");
+                return None;
+            }
+        };
+        let texture = Texture::for_pixbuf(&image.pixbuf);
+
+        match clipboard::from_config().copy_image(&texture, png) {
+            Err(e) => {
+                log::error!("Error saving {e}");
+                None
+            }
+            Ok(()) => {
+                let texture = Some(texture);
+                self.report(Severity::Success, "Copied to clipboard.");
+
+                // TODO: rethink order and messaging patterns
+                if APP_CONFIG.read().save_after_copy() {
+                    let saved = self.handle_save(image);
+                    if APP_CONFIG.read().copy_file_path() {
+                        self.handle_copy_file_paths(&saved, texture.as_ref());
+                    }
+                };
+                texture
+            }
+        }
+    }
+
+    fn handle_copy_file_paths(&self, paths: &[PathBuf], texture: Option<&Texture>) {
+        if paths.is_empty() {
+            return;
+        }
+
+        match clipboard::from_config().copy_paths(paths, texture) {
+            Err(e) => log::error!("Error copying file path: {e}"),
+            Ok(()) => self.report(Severity::Success, "Copied file path to clipboard."),
+        }
+        if APP_CONFIG.read().primary_selection() {
+            if let Err(e) = clipboard::copy_primary_paths(paths) {
+                log::error!("Error setting the primary selection: {e}");
+            }
+        }
+    }
+
+    fn handle_undo(&mut self) -> ToolUpdateResult {
+        if self.active_tool.borrow().active() {
+            self.active_tool.borrow_mut().handle_undo()
+        } else if self.walk_history(FemtoVGArea::undo) {
             ToolUpdateResult::Redraw
         } else {
             ToolUpdateResult::Unmodified
         }
     }
 
-    fn handle_redo(&mut self) -> ToolUpdateResult {
-        if self.active_tool.borrow().active() {
-            self.active_tool.borrow_mut().handle_redo()
-        } else if self.renderer.redo() {
-            ToolUpdateResult::Redraw
-        } else {
-            ToolUpdateResult::Unmodified
+    fn handle_redo(&mut self) -> ToolUpdateResult {
+        if self.active_tool.borrow().active() {
+            self.active_tool.borrow_mut().handle_redo()
+        } else if self.walk_history(FemtoVGArea::redo) {
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        }
+    }
+
+    // undo and redo may return to another image, e.g. to the one before straightening it
+    fn walk_history(&mut self, walk: impl FnOnce(&mut FemtoVGArea) -> bool) -> bool {
+        let image = self.renderer.background_image();
+        let walked = walk(&mut self.renderer);
+        let shown = self.renderer.background_image();
+        if shown != image {
+            self.image_replaced();
+            if (shown.width(), shown.height()) != (image.width(), image.height()) {
+                self.handle_resize();
+            }
+        }
+        walked
+    }
+
+    // drops what was derived from the previous image
+    fn image_replaced(&mut self) {
+        self.edge_map = OnceCell::new();
+        self.image_generation = self.image_generation.wrapping_add(1);
+        self.crop_suggestion = None;
+        self.tools.get_ocr_tool().borrow_mut().clear_words();
+        self.histogram_region = None;
+    }
+
+    fn handle_switch_undo_branch(&mut self) -> ToolUpdateResult {
+        if self.active_tool.borrow().active() {
+            ToolUpdateResult::Unmodified
+        } else if self.walk_history(FemtoVGArea::switch_undo_branch) {
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        }
+    }
+
+    fn edge_map(&self) -> &EdgeMap {
+        self.edge_map
+            .get_or_init(|| EdgeMap::from_pixbuf(&self.renderer.background_image()))
+    }
+
+    fn handle_crop_suggestion(&mut self, forward: bool) -> ToolUpdateResult {
+        let suggestions = self.edge_map().crop_suggestions().to_vec();
+        if suggestions.is_empty() {
+            self.report(Severity::Info, "No crop suggestions found.");
+            return ToolUpdateResult::Unmodified;
+        }
+
+        let count = suggestions.len();
+        let index = match self.crop_suggestion {
+            None if forward => 0,
+            None => count - 1,
+            Some(i) if forward => (i + 1) % count,
+            Some(i) => (i + count - 1) % count,
+        };
+        self.crop_suggestion = Some(index);
+
+        let (pos, size) = suggestions[index];
+        self.tools.get_crop_tool().borrow_mut().set_crop(pos, size);
+        ToolUpdateResult::Redraw
+    }
+
+    fn handle_trim_borders(&mut self, sender: &ComponentSender<Self>) -> ToolUpdateResult {
+        let Some((pos, size)) = edge_detection::trim_uniform_borders(
+            &self.renderer.background_image(),
+            APP_CONFIG.read().trim_tolerance(),
+        ) else {
+            self.report(Severity::Info, "No uniform borders found.");
+            return ToolUpdateResult::Unmodified;
+        };
+        self.tools.get_crop_tool().borrow_mut().set_crop(pos, size);
+        // show the crop handles so the result can be adjusted right away
+        if self.active_tool_type() != Tools::Crop {
+            sender.input(SketchBoardInput::ToolbarEvent(ToolbarEvent::ToolSelected(
+                Tools::Crop,
+            )));
+            sender
+                .output_sender()
+                .emit(SketchBoardOutput::ToolSwitchShortcut(Tools::Crop));
+        }
+        ToolUpdateResult::Redraw
+    }
+
+    /// Switches the screen to the next color blindness simulation, or back to normal.
+    fn handle_cycle_color_vision(&mut self, sender: &ComponentSender<Self>) -> ToolUpdateResult {
+        let vision = ColorVision::cycle(self.renderer.color_vision());
+        self.renderer.set_color_vision(vision);
+        let message = match vision {
+            Some(v) => format!("{v} simulation"),
+            None => "Color simulation off".to_string(),
+        };
+        self.show_indicator(message, SIZE_INDICATOR_TIMEOUT, sender);
+        ToolUpdateResult::Redraw
+    }
+
+    /// The image as PNG for the OCR command.
+    fn encode_for_ocr(&self) -> Option<Vec<u8>> {
+        match self
+            .renderer
+            .background_image()
+            .save_to_bufferv("png", &Vec::new())
+        {
+            Ok(png) => Some(png),
+            Err(e) => {
+                self.report(Severity::Error, &format!("Error serializing image: {e}"));
+                None
+            }
+        }
+    }
+
+    /// Recognizes the text of the image in the background, the parts matching the redact
+    /// patterns are then proposed for blurring.
+    fn handle_auto_redact(&mut self, sender: &ComponentSender<Self>) -> ToolUpdateResult {
+        let Some(png) = self.encode_for_ocr() else {
+            return ToolUpdateResult::Unmodified;
+        };
+        let (command, (patterns, errors)) = {
+            let config = APP_CONFIG.read();
+            (
+                config.ocr_command().to_string(),
+                ocr::compile_patterns(config.redact_patterns()),
+            )
+        };
+        for error in errors {
+            self.report(Severity::Warning, &error);
+        }
+        let generation = self.image_generation;
+
+        let progress = Progress::start(self.toasts.sender(), "Looking for sensitive text");
+        sender.spawn_oneshot_command(move || {
+            let regions = ocr::recognize(&command, &png)
+                .map(|words| ocr::find_matches(&words, &patterns))
+                .map_err(|e| e.to_string());
+            drop(progress);
+            SketchBoardCommand::RedactRegions(generation, regions)
+        });
+        ToolUpdateResult::Unmodified
+    }
+
+    /// Recognizes the text of the image in the background for the text selection tool,
+    /// once per image.
+    fn recognize_words(&mut self, sender: &ComponentSender<Self>) {
+        if !self.tools.get_ocr_tool().borrow_mut().request_words() {
+            return;
+        }
+        let Some(png) = self.encode_for_ocr() else {
+            return;
+        };
+        let command = APP_CONFIG.read().ocr_command().to_string();
+        let generation = self.image_generation;
+
+        let progress = Progress::start(self.toasts.sender(), "Recognizing text");
+        sender.spawn_oneshot_command(move || {
+            let words = ocr::recognize(&command, &png).map_err(|e| e.to_string());
+            drop(progress);
+            SketchBoardCommand::Words(generation, words)
+        });
+    }
+
+    fn handle_words(&mut self, words: Result<Vec<ocr::Word>, String>) {
+        match words {
+            Ok(words) => {
+                if words.is_empty() {
+                    self.report(Severity::Info, "No text found.");
+                }
+                self.tools.get_ocr_tool().borrow_mut().set_words(words);
+                self.refresh_screen();
+            }
+            Err(e) => {
+                // allow another attempt, e.g. after installing tesseract
+                self.tools.get_ocr_tool().borrow_mut().clear_words();
+                self.report(Severity::Error, &format!("Recognizing text failed: {e}"));
+            }
+        }
+    }
+
+    /// Copies the text selected with the text selection tool once the drag ends.
+    fn handle_text_selection(&mut self, ie: &InputEvent, sender: &ComponentSender<Self>) {
+        if !matches!(ie, InputEvent::Mouse(_)) || self.active_tool_type() != Tools::Ocr {
+            return;
+        }
+        self.recognize_words(sender);
+        let Some(text) = self.tools.get_ocr_tool().borrow_mut().take_selected_text() else {
+            return;
+        };
+        match clipboard::from_config().copy_text(&text) {
+            Ok(()) => self.show_indicator(
+                format!("Copied {} characters", text.chars().count()),
+                SIZE_INDICATOR_TIMEOUT,
+                sender,
+            ),
+            Err(e) => self.report(Severity::Error, &format!("Error copying text: {e}")),
+        }
+        if APP_CONFIG.read().primary_selection() {
+            if let Err(e) = clipboard::copy_primary_text(&text) {
+                log::error!("Error setting the primary selection: {e}");
+            }
+        }
+    }
+
+    fn handle_redact_regions(
+        &mut self,
+        regions: Result<Vec<(Vec2D, Vec2D)>, String>,
+        sender: &ComponentSender<Self>,
+    ) {
+        let regions = match regions {
+            Ok(regions) if regions.is_empty() => {
+                self.report(Severity::Info, "No sensitive text found.");
+                return;
+            }
+            Ok(regions) => regions,
+            Err(e) => {
+                self.report(Severity::Error, &format!("Recognizing text failed: {e}"));
+                return;
+            }
+        };
+
+        let count = regions.len();
+        self.tools.get_blur_tool().borrow_mut().propose(regions);
+        if self.active_tool_type() != Tools::Blur {
+            sender.input(SketchBoardInput::ToolbarEvent(ToolbarEvent::ToolSelected(
+                Tools::Blur,
+            )));
+            sender
+                .output_sender()
+                .emit(SketchBoardOutput::ToolSwitchShortcut(Tools::Blur));
+        }
+        self.show_indicator(
+            format!("{count} regions to blur, Enter blurs and Tab skips"),
+            SIZE_INDICATOR_TIMEOUT * 3,
+            sender,
+        );
+        self.refresh_screen();
+    }
+
+    fn handle_straighten(&mut self) -> ToolUpdateResult {
+        let perspective_tool = self.tools.get_perspective_tool();
+        let Some(quad) = perspective_tool.borrow().get_quad() else {
+            return ToolUpdateResult::Unmodified;
+        };
+        match quad.straighten(&self.renderer.background_image()) {
+            Some(pixbuf) => {
+                perspective_tool.borrow_mut().clear_quad();
+                self.renderer.replace_image(&pixbuf);
+                self.image_replaced();
+                self.handle_resize();
+                ToolUpdateResult::Redraw
+            }
+            None => {
+                self.report(Severity::Warning, "Can't straighten a region without area.");
+                ToolUpdateResult::Unmodified
+            }
         }
     }
 
     fn handle_reset(&mut self) -> ToolUpdateResult {
         // can't use lazy || here
-        if self.deactivate_active_tool() | self.renderer.reset() {
+        if self.deactivate_active_tool() | self.walk_history(FemtoVGArea::reset) {
             ToolUpdateResult::Redraw
         } else {
             ToolUpdateResult::Unmodified
@@ -532,6 +2208,162 @@ impl SketchBoard {
         ToolUpdateResult::Unmodified
     }
 
+    fn handle_fit_width(&mut self) -> ToolUpdateResult {
+        self.renderer.zoom_to_width();
+        self.renderer
+            .request_render(&APP_CONFIG.read().actions_on_right_click());
+        ToolUpdateResult::Unmodified
+    }
+
+    /// Adds a caption from the configured template in a corner of the crop, or of the image
+    /// if it isn't cropped.
+    // the crop, or the whole image without one
+    fn visible_area(&self) -> (Vec2D, Vec2D) {
+        let image = self.renderer.background_image();
+        self.tools
+            .get_crop_tool()
+            .borrow()
+            .get_crop()
+            .map(|c| {
+                let (pos, size) = c.get_rectangle();
+                math::rect_ensure_positive_size(pos, size)
+            })
+            .filter(|(_, size)| !size.is_zero())
+            .unwrap_or((
+                Vec2D::zero(),
+                Vec2D::new(image.width() as f32, image.height() as f32),
+            ))
+    }
+
+    fn handle_add_caption(&mut self) -> ToolUpdateResult {
+        let area = self.visible_area();
+        let config = APP_CONFIG.read();
+        let caption = Caption::new(
+            Caption::expand_template(config.caption_template()),
+            config.caption_corner(),
+            area,
+            self.style,
+        );
+        drop(config);
+        self.renderer.commit(Box::new(caption));
+        ToolUpdateResult::Redraw
+    }
+
+    /// Saves the image as exported for dragging it out of the window, the drag can't start
+    /// before it is written.
+    fn handle_drag_image(&self, image: RenderedImage, sender: &ComponentSender<Self>) {
+        let progress = Progress::start(self.toasts.sender(), "Preparing drag");
+        sender.spawn_oneshot_command(move || {
+            let pixbuf = Self::image_to_pixbuf(image);
+            let path = pixbuf
+                .save_to_bufferv("png", &[])
+                .map_err(|e| format!("Error serializing image: {e}"))
+                .and_then(|png| clipboard::write_drag_file(&png).map_err(|e| format!("{e:#}")));
+            drop(progress);
+            SketchBoardCommand::DragFileWritten(path, pixbuf)
+        });
+    }
+
+    /// The prepared image, to drag it out of the window.
+    fn drag_content(
+        drag_file: &RefCell<Option<(PathBuf, Texture)>>,
+    ) -> Option<gtk::gdk::ContentProvider> {
+        let drag_file = drag_file.borrow();
+        let (path, texture) = drag_file.as_ref()?;
+        match clipboard::drag_content(path, texture) {
+            Ok(content) => Some(content),
+            Err(e) => {
+                log::error!("Error while preparing the drag: {e}");
+                None
+            }
+        }
+    }
+
+    /// Reads the image in the clipboard, which is pasted once it arrives.
+    fn handle_paste(&self, sender: &ComponentSender<Self>) -> ToolUpdateResult {
+        let sender = sender.clone();
+        relm4::spawn_local(async move {
+            match clipboard::read_image().await {
+                Ok(pixbuf) => sender.input(SketchBoardInput::InsertImage(pixbuf, None)),
+                Err(e) => self.report(Severity::Error, &format!("Cannot paste: {e}")),
+            }
+        });
+        ToolUpdateResult::Unmodified
+    }
+
+    /// Decodes the image file dropped at `pos`, in canvas coordinates, to insert it there.
+    fn handle_drop_file(
+        &self,
+        path: PathBuf,
+        pos: Vec2D,
+        sender: &ComponentSender<Self>,
+    ) -> ToolUpdateResult {
+        let center = self.renderer.abs_canvas_to_image_coordinates(pos);
+        sender.spawn_oneshot_command(move || {
+            SketchBoardCommand::DroppedFileLoaded(
+                Pixbuf::from_file(&path).map_err(|e| format!("{path:?}: {e}")),
+                center,
+            )
+        });
+        ToolUpdateResult::Unmodified
+    }
+
+    /// Places `pixbuf` on `center`, or else in the middle of the crop, scaled down to fit
+    /// and selected to be moved and resized. Opens it as the image instead while there is
+    /// none.
+    fn handle_insert_image(
+        &mut self,
+        pixbuf: Pixbuf,
+        center: Option<Vec2D>,
+        sender: &ComponentSender<Self>,
+    ) -> ToolUpdateResult {
+        if self.placeholder {
+            sender
+                .output_sender()
+                .emit(SketchBoardOutput::OpenImage(pixbuf));
+            return ToolUpdateResult::Unmodified;
+        }
+
+        let (pos, size) = self.visible_area();
+        let center = center.unwrap_or(pos + size * 0.5);
+        match Picture::new(&pixbuf, center, size) {
+            Ok(picture) => {
+                self.renderer.commit_selected(Box::new(picture));
+                self.run_key_command(KeyCommand::Tool(Tools::Pointer), sender);
+                ToolUpdateResult::Redraw
+            }
+            Err(e) => {
+                self.report(Severity::Error, &format!("Cannot insert the image: {e}"));
+                ToolUpdateResult::Unmodified
+            }
+        }
+    }
+
+    /// Zoom to the selected annotation, or else to the crop.
+    fn handle_zoom_to_selection(&mut self) -> ToolUpdateResult {
+        let Some(rect) = self.renderer.selection_bounds().or_else(|| {
+            self.tools
+                .get_crop_tool()
+                .borrow()
+                .get_crop()
+                .map(|c| c.get_rectangle())
+        }) else {
+            return ToolUpdateResult::Unmodified;
+        };
+        self.renderer.zoom_to_rect(rect);
+        self.renderer
+            .request_render(&APP_CONFIG.read().actions_on_right_click());
+        ToolUpdateResult::Unmodified
+    }
+
+    fn show_zoom_level(&mut self, sender: &ComponentSender<Self>) {
+        self.show_indicator(
+            format!("{:.0}%", self.renderer.zoom_level() * 100.0),
+            SIZE_INDICATOR_TIMEOUT,
+            sender,
+        );
+    }
+
     // Toolbars = Tools Toolbar + Style Toolbar
     fn handle_toggle_toolbars_display(
         &mut self,
@@ -543,7 +2375,11 @@ impl SketchBoard {
         ToolUpdateResult::Unmodified
     }
 
-    fn handle_toolbar_event(&mut self, toolbar_event: ToolbarEvent) -> ToolUpdateResult {
+    fn handle_toolbar_event(
+        &mut self,
+        toolbar_event: ToolbarEvent,
+        sender: &ComponentSender<Self>,
+    ) -> ToolUpdateResult {
         match toolbar_event {
             ToolbarEvent::ToolSelected(tool) => {
                 // deactivate old tool and save drawable, if any
@@ -559,6 +2395,16 @@ impl SketchBoard {
                     deactivate_result = ToolUpdateResult::Redraw;
                 }
 
+                // a drag of the eraser may not have ended
+                if self.renderer.finish_erasing() {
+                    deactivate_result = ToolUpdateResult::Redraw;
+                }
+
+                // selections are only shown and used by the pointer tool
+                if tool != Tools::Pointer && self.renderer.clear_selection() {
+                    deactivate_result = ToolUpdateResult::Redraw;
+                }
+
                 // change active tool
                 self.active_tool = self.tools.get(&tool);
                 self.renderer.set_active_tool(self.active_tool.clone());
@@ -581,6 +2427,10 @@ impl SketchBoard {
                     .borrow_mut()
                     .handle_event(ToolEvent::Activated);
 
+                if tool == Tools::Ocr {
+                    self.recognize_words(sender);
+                }
+
                 match activate_result {
                     ToolUpdateResult::Unmodified => deactivate_result,
                     _ => activate_result,
@@ -598,117 +2448,445 @@ impl SketchBoard {
                     .borrow_mut()
                     .handle_event(ToolEvent::StyleChanged(self.style))
             }
-            ToolbarEvent::SaveFile => self.handle_action(&[Action::SaveToFile]),
-            ToolbarEvent::CopyClipboard => self.handle_action(&[Action::SaveToClipboard]),
-            ToolbarEvent::Undo => self.handle_undo(),
-            ToolbarEvent::Redo => self.handle_redo(),
-            ToolbarEvent::Reset => self.handle_reset(),
-            ToolbarEvent::ToggleFill => {
-                self.style.fill = !self.style.fill;
-                self.active_tool
-                    .borrow_mut()
-                    .handle_event(ToolEvent::StyleChanged(self.style))
+            ToolbarEvent::SaveFile => self.handle_action(&[Action::SaveToFile]),
+            ToolbarEvent::CopyClipboard => self.handle_action(&[Action::SaveToClipboard]),
+            ToolbarEvent::Undo => self.handle_undo(),
+            ToolbarEvent::Redo => self.handle_redo(),
+            ToolbarEvent::Reset => self.handle_reset(),
+            ToolbarEvent::ToggleFill => {
+                self.style.fill = !self.style.fill;
+                self.active_tool
+                    .borrow_mut()
+                    .handle_event(ToolEvent::StyleChanged(self.style))
+            }
+            ToolbarEvent::AnnotationSizeChanged(value) => {
+                self.style.annotation_size_factor = value;
+                self.active_tool
+                    .borrow_mut()
+                    .handle_event(ToolEvent::StyleChanged(self.style))
+            }
+            ToolbarEvent::CornerRadiusChanged(value) => {
+                self.style.corner_radius = value;
+                self.active_tool
+                    .borrow_mut()
+                    .handle_event(ToolEvent::StyleChanged(self.style))
+            }
+            ToolbarEvent::SaveFileAs => self.handle_action(&[Action::SaveToFileAs]),
+            ToolbarEvent::ToggleLayersPanel => {
+                sender
+                    .output_sender()
+                    .emit(SketchBoardOutput::ToggleLayersPanel);
+                ToolUpdateResult::Unmodified
+            }
+            ToolbarEvent::ToggleAdjustmentsPanel => {
+                sender
+                    .output_sender()
+                    .emit(SketchBoardOutput::ToggleAdjustmentsPanel);
+                ToolUpdateResult::Unmodified
+            }
+            ToolbarEvent::AddCaption => self.handle_add_caption(),
+            ToolbarEvent::LockSelection => {
+                if self.renderer.lock_selection() {
+                    ToolUpdateResult::Redraw
+                } else {
+                    ToolUpdateResult::Unmodified
+                }
+            }
+            ToolbarEvent::RunQuickAction(index) => {
+                self.handle_action(&[Action::RunQuickAction(index)])
+            }
+            ToolbarEvent::ToggleHistogramPanel => {
+                self.histogram_visible = !self.histogram_visible;
+                sender
+                    .output_sender()
+                    .emit(SketchBoardOutput::ToggleHistogramPanel);
+                ToolUpdateResult::Unmodified
+            }
+            ToolbarEvent::Resize => {
+                let result = self.handle_resize();
+                self.show_zoom_level(sender);
+                result
+            }
+            ToolbarEvent::OriginalScale => {
+                let result = self.handle_original_scale();
+                self.show_zoom_level(sender);
+                result
+            }
+            ToolbarEvent::SetBeautify(beautify) => {
+                self.renderer.set_beautify(beautify);
+                ToolUpdateResult::Unmodified
+            }
+        }
+    }
+
+    fn handle_text_commit(
+        &mut self,
+        event: TextEventMsg,
+        sender: ComponentSender<Self>,
+    ) -> ToolUpdateResult {
+        match event {
+            TextEventMsg::Commit(txt) => {
+                // NOTE:
+                // If there's an IMContext binded to the controller, single letter-key events will
+                // always go through it first, denying a bypass, so the only way we can do single-key
+                // bindings is to act upon the IMMulticontext's commit event itself.
+                // NOTE:
+                // Here we're basically bypassing the IMMulticontext. If a tool that takes text
+                // is active and wants text inputs, we're interested in the single-letter keypress as a text character.
+                // If not, we parse it as a shortcut event.
+                if self.wants_text_input() {
+                    sender.input(SketchBoardInput::new_text_event(TextEventMsg::Commit(
+                        txt.to_string(),
+                    )));
+                } else if let Some(key) = txt.chars().next() {
+                    match self.edit_mode {
+                        Some(EditMode::Normal) if self.pending_keys.is_empty() => {
+                            return self.handle_normal_mode_key(key, &sender);
+                        }
+                        Some(EditMode::Insert) => (),
+                        Some(EditMode::Visual) => return self.handle_visual_mode_key(key),
+                        _ => self.handle_shortcut_key(key, &sender),
+                    }
+                }
+            }
+            TextEventMsg::Preedit {
+                text,
+                cursor_chars,
+                spans,
+            } => {
+                if self.wants_text_input() {
+                    sender.input(SketchBoardInput::new_text_event(TextEventMsg::Preedit {
+                        text,
+                        cursor_chars,
+                        spans,
+                    }));
+                }
+            }
+            TextEventMsg::PreeditEnd => {
+                if self.wants_text_input() {
+                    sender.input(SketchBoardInput::new_text_event(TextEventMsg::PreeditEnd));
+                }
+            }
+        }
+        ToolUpdateResult::Unmodified
+    }
+
+    /// Adds `key` to the keys typed so far and runs the keybind they complete. A pending
+    /// keybind is shown until the next key or the timeout.
+    fn handle_shortcut_key(&mut self, key: char, sender: &ComponentSender<Self>) {
+        let mut keys = std::mem::take(&mut self.pending_keys);
+        if !keys.is_empty() {
+            self.size_indicator = None;
+        }
+        keys.push(key);
+        let (lookup, timeout) = {
+            let config = APP_CONFIG.read();
+            (
+                config.keybinds().lookup(&keys),
+                Duration::from_millis(config.keybinds().sequence_timeout()),
+            )
+        };
+
+        match lookup {
+            KeyMatch::Command(command) => self.run_key_command(command, sender),
+            KeyMatch::Pending(_) => {
+                self.show_indicator(format!("{}…", keys.replace(' ', "␣")), timeout, sender);
+                self.pending_keys = keys;
+                self.pending_keys_generation = self.pending_keys_generation.wrapping_add(1);
+                let generation = self.pending_keys_generation;
+                let input = sender.input_sender().clone();
+                glib::timeout_add_local_once(timeout, move || {
+                    input.emit(SketchBoardInput::KeySequenceTimeout(generation));
+                });
+            }
+            // the key may start another keybind
+            KeyMatch::None if keys.chars().count() > 1 => self.handle_shortcut_key(key, sender),
+            KeyMatch::None => Self::handle_unbound_key(key, sender),
+        }
+    }
+
+    // runs a pending keybind whose longer keybinds weren't continued in time
+    fn handle_key_sequence_timeout(&mut self, generation: u32, sender: &ComponentSender<Self>) {
+        if generation != self.pending_keys_generation || self.pending_keys.is_empty() {
+            return;
+        }
+        let keys = std::mem::take(&mut self.pending_keys);
+        let lookup = APP_CONFIG.read().keybinds().lookup(&keys);
+        if let KeyMatch::Pending(Some(command)) = lookup {
+            self.run_key_command(command, sender);
+        }
+    }
+
+    /// Runs the commands of normal mode, other keys go to the keybinds. The commands are on
+    /// uppercase keys, the lowercase ones select tools by default.
+    fn handle_normal_mode_key(
+        &mut self,
+        key: char,
+        sender: &ComponentSender<Self>,
+    ) -> ToolUpdateResult {
+        match key {
+            'I' => {
+                self.edit_mode = Some(EditMode::Insert);
+                ToolUpdateResult::Redraw
+            }
+            'V' => {
+                self.run_key_command(KeyCommand::Tool(Tools::Pointer), sender);
+                ToolUpdateResult::Unmodified
+            }
+            'U' => self.handle_undo(),
+            'X' if self.renderer.delete_selection() => ToolUpdateResult::Redraw,
+            'X' => ToolUpdateResult::Unmodified,
+            _ => {
+                self.handle_shortcut_key(key, sender);
+                ToolUpdateResult::Unmodified
+            }
+        }
+    }
+
+    fn handle_visual_mode_key(&mut self, key: char) -> ToolUpdateResult {
+        let step = if key.is_ascii_uppercase() { 10.0 } else { 1.0 };
+        let changed = match key {
+            'h' | 'H' => self.renderer.nudge_selection(Vec2D::new(-step, 0.0)),
+            'j' | 'J' => self.renderer.nudge_selection(Vec2D::new(0.0, step)),
+            'k' | 'K' => self.renderer.nudge_selection(Vec2D::new(0.0, -step)),
+            'l' | 'L' => self.renderer.nudge_selection(Vec2D::new(step, 0.0)),
+            'n' => self.cycle_selection(true),
+            'N' => self.cycle_selection(false),
+            'y' => self.renderer.duplicate_selection(),
+            'd' | 'x' => self.renderer.delete_selection(),
+            'u' => return self.handle_undo(),
+            _ => false,
+        };
+        if changed {
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        }
+    }
+
+    /// Selects the annotation above the selected one, or below if not `forward`, wrapping
+    /// around. Starts at the topmost one.
+    fn cycle_selection(&mut self, forward: bool) -> bool {
+        let layers = self.renderer.layer_infos();
+        let selectable: Vec<usize> = layers
+            .iter()
+            .filter(|info| info.visible && !info.locked)
+            .map(|info| info.id)
+            .collect();
+        if selectable.is_empty() {
+            return false;
+        }
+        let next = match layers.iter().find(|info| info.selected) {
+            Some(selected) => {
+                let index = selectable
+                    .iter()
+                    .position(|&id| id == selected.id)
+                    .unwrap_or(0);
+                if forward {
+                    (index + 1) % selectable.len()
+                } else {
+                    (index + selectable.len() - 1) % selectable.len()
+                }
+            }
+            None => selectable.len() - 1,
+        };
+        self.renderer.select(selectable[next])
+    }
+
+    fn run_key_command(&mut self, command: KeyCommand, sender: &ComponentSender<Self>) {
+        match command {
+            KeyCommand::Tool(tool) => {
+                if self.edit_mode.is_some() {
+                    self.edit_mode = Some(if tool == Tools::Pointer {
+                        EditMode::Visual
+                    } else {
+                        EditMode::Insert
+                    });
+                    // visual mode always works on a selection
+                    if tool == Tools::Pointer
+                        && !self.renderer.layer_infos().iter().any(|info| info.selected)
+                        && self.cycle_selection(true)
+                    {
+                        self.refresh_screen();
+                    }
+                }
+                sender.input(SketchBoardInput::ToolbarEvent(ToolbarEvent::ToolSelected(
+                    tool,
+                )));
+                sender
+                    .output_sender()
+                    .emit(SketchBoardOutput::ToolSwitchShortcut(tool));
+            }
+            KeyCommand::Action(action) => {
+                if let ToolUpdateResult::Redraw = self.handle_action(&[action]) {
+                    self.refresh_screen();
+                }
+            }
+        }
+    }
+
+    // digits select palette colors and S, M and L the size, unless they are keybinds
+    fn handle_unbound_key(key: char, sender: &ComponentSender<Self>) {
+        if let Some(hotkey_digit) = key.to_digit(10) {
+            let index_digit = if hotkey_digit == 0 {
+                9
+            } else {
+                hotkey_digit - 1
+            };
+            if APP_CONFIG.read().color_palette().palette().len() >= (index_digit + 1) as usize {
+                sender
+                    .output_sender()
+                    .emit(SketchBoardOutput::ColorSwitchShortcut(index_digit as u64));
             }
-            ToolbarEvent::AnnotationSizeChanged(value) => {
-                self.style.annotation_size_factor = value;
-                self.active_tool
-                    .borrow_mut()
-                    .handle_event(ToolEvent::StyleChanged(self.style))
+        } else {
+            match key {
+                'S' => sender.input(SketchBoardInput::ToolbarEvent(ToolbarEvent::SizeSelected(
+                    crate::style::Size::Small,
+                ))),
+                'M' => sender.input(SketchBoardInput::ToolbarEvent(ToolbarEvent::SizeSelected(
+                    crate::style::Size::Medium,
+                ))),
+                'L' => sender.input(SketchBoardInput::ToolbarEvent(ToolbarEvent::SizeSelected(
+                    crate::style::Size::Large,
+                ))),
+                _ => (),
             }
-            ToolbarEvent::SaveFileAs => self.handle_action(&[Action::SaveToFileAs]),
-            ToolbarEvent::Resize => self.handle_resize(),
-            ToolbarEvent::OriginalScale => self.handle_original_scale(),
         }
     }
 
-    fn handle_text_commit(
-        &self,
-        event: TextEventMsg,
-        sender: ComponentSender<Self>,
+    /// Whether typed text goes to the active tool instead of triggering shortcuts.
+    fn wants_text_input(&self) -> bool {
+        matches!(self.active_tool_type(), Tools::Text | Tools::Qr)
+            && self.active_tool.borrow().input_enabled()
+    }
+
+    /// Stop peeking when H is released, regardless of whether Ctrl is still held.
+    fn handle_peek_release(&mut self, ie: &InputEvent) -> bool {
+        match ie {
+            InputEvent::KeyRelease(ke) if ke.is_one_of(Key::h, KeyMappingId::UsH) => {
+                self.renderer.set_peek(false)
+            }
+            _ => false,
+        }
+    }
+
+    fn handle_layer_event(
+        &mut self,
+        event: LayerEvent,
+        sender: &ComponentSender<Self>,
     ) -> ToolUpdateResult {
         match event {
-            TextEventMsg::Commit(txt) => {
-                // NOTE:
-                // If there's an IMContext binded to the controller, single letter-key events will
-                // always go through it first, denying a bypass, so the only way we can do single-key
-                // bindings is to act upon the IMMulticontext's commit event itself.
-                // NOTE:
-                // Here we're basically bypassing the IMMulticontext. If the text tool is active
-                // and wants text inputs, we're interested in the single-letter keypress as a text character.
-                // If not, we parse it as a shortcut event.
-                if self.active_tool_type() == Tools::Text
-                    && self.active_tool.borrow().input_enabled()
-                {
-                    sender.input(SketchBoardInput::new_text_event(TextEventMsg::Commit(
-                        txt.to_string(),
-                    )));
-                } else if let Some(tool) = txt
-                    .chars()
-                    .next()
-                    .and_then(|char| APP_CONFIG.read().keybinds().get_tool(char))
-                {
+            LayerEvent::Select(id) => {
+                if !self.renderer.select(id) {
+                    return ToolUpdateResult::Unmodified;
+                }
+                // selections are only shown and moved by the pointer tool
+                if self.active_tool_type() != Tools::Pointer {
                     sender.input(SketchBoardInput::ToolbarEvent(ToolbarEvent::ToolSelected(
-                        tool,
+                        Tools::Pointer,
                     )));
                     sender
                         .output_sender()
-                        .emit(SketchBoardOutput::ToolSwitchShortcut(tool));
-                } else if let Some(hotkey_digit) =
-                    txt.chars().next().and_then(|char| char.to_digit(10))
-                {
-                    let index_digit = if hotkey_digit == 0 {
-                        9
-                    } else {
-                        hotkey_digit - 1
-                    };
-                    if APP_CONFIG.read().color_palette().palette().len()
-                        >= (index_digit + 1) as usize
-                    {
-                        sender
-                            .output_sender()
-                            .emit(SketchBoardOutput::ColorSwitchShortcut(index_digit as u64));
-                    }
-                } else {
-                    match txt.chars().next() {
-                        Some('S') => sender.input(SketchBoardInput::ToolbarEvent(
-                            ToolbarEvent::SizeSelected(crate::style::Size::Small),
-                        )),
-                        Some('M') => sender.input(SketchBoardInput::ToolbarEvent(
-                            ToolbarEvent::SizeSelected(crate::style::Size::Medium),
-                        )),
-                        Some('L') => sender.input(SketchBoardInput::ToolbarEvent(
-                            ToolbarEvent::SizeSelected(crate::style::Size::Large),
-                        )),
-                        _ => (),
-                    }
+                        .emit(SketchBoardOutput::ToolSwitchShortcut(Tools::Pointer));
                 }
             }
-            TextEventMsg::Preedit {
-                text,
-                cursor_chars,
-                spans,
-            } => {
-                if self.active_tool_type() == Tools::Text
-                    && self.active_tool.borrow().input_enabled()
-                {
-                    sender.input(SketchBoardInput::new_text_event(TextEventMsg::Preedit {
-                        text,
-                        cursor_chars,
-                        spans,
-                    }));
-                }
+            LayerEvent::SetVisible(id, visible) => {
+                self.layers.borrow_mut().set_hidden(id, !visible);
+                self.renderer.invalidate_annotations();
             }
-            TextEventMsg::PreeditEnd => {
-                if self.active_tool_type() == Tools::Text
-                    && self.active_tool.borrow().input_enabled()
-                {
-                    sender.input(SketchBoardInput::new_text_event(TextEventMsg::PreeditEnd));
+            LayerEvent::SetLocked(id, locked) => {
+                self.layers.borrow_mut().set_locked(id, locked);
+            }
+            LayerEvent::Move { id, index } => {
+                let ids = self.layer_infos.iter().map(|info| info.id).collect();
+                self.layers.borrow_mut().move_to(ids, id, index);
+                self.renderer.invalidate_annotations();
+            }
+        }
+        ToolUpdateResult::Redraw
+    }
+
+    fn handle_snippet_event(
+        &mut self,
+        event: SnippetEvent,
+        sender: &ComponentSender<Self>,
+    ) -> ToolUpdateResult {
+        let result = match event {
+            SnippetEvent::Save(name) => {
+                let (specs, skipped) = self.renderer.annotation_specs(true);
+                if specs.is_empty() {
+                    self.show_indicator(
+                        "No annotations to save".to_string(),
+                        SIZE_INDICATOR_TIMEOUT,
+                        sender,
+                    );
+                    return ToolUpdateResult::Unmodified;
                 }
+                snippets::save(&name, specs).map(|()| {
+                    let mut text = format!("Saved snippet {name}");
+                    if skipped > 0 {
+                        text.push_str(&format!(", {skipped} annotations not supported"));
+                    }
+                    self.show_indicator(text, SIZE_INDICATOR_TIMEOUT, sender);
+                })
+            }
+            SnippetEvent::Insert(name) => {
+                return match snippets::get(&name) {
+                    Ok(annotations) => {
+                        self.renderer.insert_snippet(annotations);
+                        ToolUpdateResult::Redraw
+                    }
+                    Err(e) => {
+                        self.report(Severity::Error, &format!("Error inserting snippet: {e}"));
+                        ToolUpdateResult::Unmodified
+                    }
+                };
             }
+            SnippetEvent::Delete(name) => snippets::delete(&name),
+        };
+
+        match result.and_then(|()| snippets::names()) {
+            Ok(names) => sender
+                .output_sender()
+                .emit(SketchBoardOutput::SnippetsChanged(names)),
+            Err(e) => self.report(Severity::Error, &format!("Error saving snippets: {e}")),
         }
         ToolUpdateResult::Unmodified
     }
 
+    /// Sends the annotations to the layers panel if they changed since the last update.
+    fn update_layers(&mut self, sender: &ComponentSender<Self>) {
+        let layer_infos = self.renderer.layer_infos();
+        if layer_infos == self.layer_infos {
+            return;
+        }
+
+        let rows = layer_infos
+            .iter()
+            .map(|info| LayerRow {
+                info: info.clone(),
+                thumbnail: info.bounds.and_then(|b| self.renderer.thumbnail(b)),
+            })
+            .collect();
+        sender
+            .output_sender()
+            .emit(SketchBoardOutput::LayersChanged(rows));
+        self.layer_infos = layer_infos;
+    }
+
+    fn update_editor_state(&mut self, sender: &ComponentSender<Self>) {
+        let editor_state = EditorState {
+            active_tool: self.active_tool_type(),
+            unsaved_changes: self.renderer.history_position() != self.saved_position,
+        };
+        if self.editor_state != Some(editor_state) {
+            self.editor_state = Some(editor_state);
+            sender
+                .output_sender()
+                .emit(SketchBoardOutput::EditorStateChanged(editor_state));
+        }
+    }
+
     pub fn active_tool_type(&self) -> Tools {
         self.active_tool.borrow().get_tool_type()
     }
@@ -716,13 +2894,14 @@ impl SketchBoard {
 
 #[relm4::component(pub)]
 impl Component for SketchBoard {
-    type CommandOutput = ();
+    type CommandOutput = SketchBoardCommand;
     type Input = SketchBoardInput;
     type Output = SketchBoardOutput;
-    type Init = Pixbuf;
+    /// The image, and whether it is only a placeholder until one is loaded.
+    type Init = (Pixbuf, bool);
 
     view! {
-        gtk::Box {
+        gtk::Overlay {
             #[local_ref]
             area -> FemtoVGArea {
                 set_vexpand: true,
@@ -738,7 +2917,8 @@ impl Component for SketchBoard {
                                 MouseEventType::BeginDrag,
                                 controller.current_button(),
                                 controller.current_event_state(),
-                                Vec2D::new(x as f32, y as f32)));
+                                Vec2D::new(x as f32, y as f32),
+                                event_pressure(controller)));
 
                         },
                         connect_drag_update[sender] => move |controller, x, y| {
@@ -746,14 +2926,16 @@ impl Component for SketchBoard {
                                 MouseEventType::UpdateDrag,
                                 controller.current_button(),
                                 controller.current_event_state(),
-                                Vec2D::new(x as f32, y as f32)));
+                                Vec2D::new(x as f32, y as f32),
+                                event_pressure(controller)));
                         },
                         connect_drag_end[sender] => move |controller, x, y| {
                             sender.input(SketchBoardInput::new_mouse_event(
                                 MouseEventType::EndDrag,
                                 controller.current_button(),
                                 controller.current_event_state(),
-                                Vec2D::new(x as f32, y as f32)
+                                Vec2D::new(x as f32, y as f32),
+                                event_pressure(controller)
                             ));
                         }
                 },
@@ -765,14 +2947,15 @@ impl Component for SketchBoard {
                             MouseEventType::Click,
                             controller.current_button(),
                             controller.current_event_state(),
-                            Vec2D::new(x as f32, y as f32)));
+                            Vec2D::new(x as f32, y as f32),
+                            event_pressure(controller)));
                     }
                 },
 
                 add_controller = gtk::EventControllerScroll{
                     set_flags: gtk::EventControllerScrollFlags::VERTICAL,
-                    connect_scroll[sender] => move |_, _, dy| {
-                        sender.input(SketchBoardInput::new_scroll_event(dy));
+                    connect_scroll[sender] => move |controller, _, dy| {
+                        sender.input(SketchBoardInput::new_scroll_event(dy, controller.current_event_state()));
                         glib::Propagation::Stop
                     },
                 },
@@ -809,23 +2992,103 @@ impl Component for SketchBoard {
                             MouseEventType::PointerPos,
                             0,
                             controller.current_event_state(),
-                            Vec2D::new(x as f32, y as f32)
+                            Vec2D::new(x as f32, y as f32),
+                            None
                         ));
                     }
+                },
+
+                add_controller = gtk::DropTarget::new(gtk::gio::File::static_type(), gtk::gdk::DragAction::COPY) {
+                    connect_drop[sender] => move |_, value, x, y| {
+                        // only local files can be opened
+                        match value.get::<gtk::gio::File>().ok().and_then(|file| file.path()) {
+                            Some(path) => {
+                                sender.input(SketchBoardInput::DropFile(path, Vec2D::new(x as f32, y as f32)));
+                                true
+                            }
+                            None => false,
+                        }
+                    },
                 }
-            }
+            },
+
+            add_overlay = &gtk::Image {
+                set_halign: gtk::Align::End,
+                set_valign: gtk::Align::End,
+                set_icon_name: Some("drag-regular"),
+                set_tooltip_text: Some("Drag the image into another application"),
+                add_css_class: "toolbar",
+                add_css_class: "drag-grip",
+
+                #[watch]
+                set_visible: !model.placeholder,
+
+                // rendering and writing the image takes too long to wait for once dragging
+                add_controller = gtk::EventControllerMotion {
+                    connect_enter[sender] => move |_, _, _| {
+                        sender.input(SketchBoardInput::PrepareDrag);
+                    },
+                },
+
+                add_controller = gtk::DragSource {
+                    set_actions: gtk::gdk::DragAction::COPY,
+                    connect_prepare[drag_file = model.drag_file.clone()] => move |_, _, _| {
+                        Self::drag_content(&drag_file)
+                    },
+                },
+            },
+
+            add_overlay = &gtk::Label {
+                set_halign: gtk::Align::Start,
+                set_valign: gtk::Align::End,
+                set_can_target: false,
+                add_css_class: "status-bar",
+                set_visible: APP_CONFIG.read().show_status_bar()
+                    || APP_CONFIG.read().modal_editing(),
+
+                #[watch]
+                set_label: &model.status_text(),
+            },
+
+            add_overlay = model.toasts.widget(),
         },
     }
 
     fn update(&mut self, msg: SketchBoardInput, sender: ComponentSender<Self>, _root: &Self::Root) {
         let result = match msg {
             SketchBoardInput::LoadNewImage(pixbuf) => {
+                self.placeholder = false;
+                self.size_indicator = None;
                 self.renderer.load_image(&pixbuf);
+                self.image_replaced();
+                self.layers.borrow_mut().clear();
+                self.saved_position = None;
                 self.tools.get_crop_tool().borrow_mut().clear_crop();
                 self.handle_resize();
                 self.refresh_screen();
                 ToolUpdateResult::Unmodified
             }
+            SketchBoardInput::SetBaseImage(pixbuf) => {
+                self.placeholder = false;
+                self.renderer.set_base_image(&pixbuf);
+                self.image_replaced();
+                self.refresh_screen();
+                ToolUpdateResult::Unmodified
+            }
+            SketchBoardInput::ShowLoading => {
+                // no timeout, loading the image hides it
+                self.size_indicator = Some("Loading image…".to_string());
+                self.size_indicator_generation = self.size_indicator_generation.wrapping_add(1);
+                ToolUpdateResult::Unmodified
+            }
+            SketchBoardInput::Report(severity, message) => {
+                self.report(severity, &message);
+                ToolUpdateResult::Unmodified
+            }
+            // loading the image would wipe whatever was drawn on the placeholder
+            SketchBoardInput::InputEvent(InputEvent::Mouse(_)) if self.placeholder => {
+                ToolUpdateResult::Unmodified
+            }
             SketchBoardInput::InputEvent(mut ie) => {
                 if let InputEvent::Key(ke) = ie {
                     if ke.is_one_of(Key::z, KeyMappingId::UsZ)
@@ -836,10 +3099,18 @@ impl Component for SketchBoard {
                         && ke.modifier == ModifierType::CONTROL_MASK
                     {
                         self.handle_redo()
+                    } else if ke.is_one_of(Key::y, KeyMappingId::UsY)
+                        && ke.modifier == (ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK)
+                    {
+                        self.handle_switch_undo_branch()
                     } else if ke.is_one_of(Key::t, KeyMappingId::UsT)
                         && ke.modifier == ModifierType::CONTROL_MASK
                     {
-                        self.handle_toggle_toolbars_display(sender)
+                        self.handle_toggle_toolbars_display(sender.clone())
+                    } else if ke.is_one_of(Key::t, KeyMappingId::UsT)
+                        && ke.modifier == (ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK)
+                    {
+                        self.handle_add_caption()
                     } else if ke.is_one_of(Key::s, KeyMappingId::UsS)
                         && ke.modifier == ModifierType::CONTROL_MASK
                     {
@@ -850,11 +3121,124 @@ impl Component for SketchBoard {
                     {
                         self.renderer.request_render(&[Action::SaveToFileAs]);
                         ToolUpdateResult::Unmodified
+                    } else if ke.is_one_of(Key::s, KeyMappingId::UsS)
+                        && ke.modifier == (ModifierType::CONTROL_MASK | ModifierType::ALT_MASK)
+                    {
+                        self.handle_action(&[Action::SaveAnnotationsToFileAs])
+                    } else if ke.is_one_of(Key::g, KeyMappingId::UsG)
+                        && ke.modifier == ModifierType::CONTROL_MASK
+                    {
+                        self.handle_save_steps(StepsExport::Recording, &sender);
+                        ToolUpdateResult::Unmodified
+                    } else if ke.is_one_of(Key::g, KeyMappingId::UsG)
+                        && ke.modifier == (ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK)
+                    {
+                        self.handle_save_steps(StepsExport::Images, &sender);
+                        ToolUpdateResult::Unmodified
+                    } else if ke.is_one_of(Key::b, KeyMappingId::UsB)
+                        && ke.modifier == ModifierType::CONTROL_MASK
+                    {
+                        self.handle_trim_borders(&sender)
+                    } else if ke.is_one_of(Key::b, KeyMappingId::UsB)
+                        && ke.modifier == (ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK)
+                    {
+                        self.handle_cycle_color_vision(&sender)
+                    } else if ke.is_one_of(Key::r, KeyMappingId::UsR)
+                        && ke.modifier == (ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK)
+                    {
+                        self.handle_auto_redact(&sender)
                     } else if ke.is_one_of(Key::c, KeyMappingId::UsC)
                         && ke.modifier == ModifierType::CONTROL_MASK
                     {
                         self.renderer.request_render(&[Action::SaveToClipboard]);
                         ToolUpdateResult::Unmodified
+                    } else if ke.is_one_of(Key::v, KeyMappingId::UsV)
+                        && ke.modifier == ModifierType::CONTROL_MASK
+                    {
+                        self.handle_paste(&sender)
+                    } else if ke.is_one_of(Key::d, KeyMappingId::UsD)
+                        && ke.modifier == ModifierType::CONTROL_MASK
+                    {
+                        if self.renderer.duplicate_selection() {
+                            ToolUpdateResult::Redraw
+                        } else {
+                            ToolUpdateResult::Unmodified
+                        }
+                    } else if ke.is_one_of(Key::l, KeyMappingId::UsL)
+                        && ke.modifier == ModifierType::CONTROL_MASK
+                    {
+                        self.handle_toolbar_event(ToolbarEvent::LockSelection, &sender)
+                    } else if ke.is_one_of(Key::_0, KeyMappingId::Digit0)
+                        && ke.modifier == ModifierType::CONTROL_MASK
+                    {
+                        self.handle_toolbar_event(ToolbarEvent::Resize, &sender)
+                    } else if ke.is_one_of(Key::_1, KeyMappingId::Digit1)
+                        && ke.modifier == ModifierType::CONTROL_MASK
+                    {
+                        self.handle_toolbar_event(ToolbarEvent::OriginalScale, &sender)
+                    } else if ke.is_one_of(Key::_2, KeyMappingId::Digit2)
+                        && ke.modifier == ModifierType::CONTROL_MASK
+                    {
+                        let result = self.handle_fit_width();
+                        self.show_zoom_level(&sender);
+                        result
+                    } else if ke.is_one_of(Key::_3, KeyMappingId::Digit3)
+                        && ke.modifier == ModifierType::CONTROL_MASK
+                    {
+                        let result = self.handle_zoom_to_selection();
+                        self.show_zoom_level(&sender);
+                        result
+                    } else if ke.is_one_of(Key::e, KeyMappingId::UsE)
+                        && ke.modifier == ModifierType::CONTROL_MASK
+                    {
+                        self.handle_start_region_export(Action::SaveToClipboard, &sender)
+                    } else if ke.is_one_of(Key::e, KeyMappingId::UsE)
+                        && ke.modifier == (ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK)
+                    {
+                        self.handle_start_region_export(Action::SaveToFileAs, &sender)
+                    } else if self.region_export.is_some()
+                        && ke.modifier.is_empty()
+                        && ke.key == Key::Escape
+                    {
+                        self.region_export = None;
+                        self.renderer.set_export_region(None);
+                        ToolUpdateResult::Redraw
+                    } else if ke.is_one_of(Key::i, KeyMappingId::UsI)
+                        && ke.modifier == ModifierType::CONTROL_MASK
+                    {
+                        self.handle_toggle_pixel_inspector(&sender)
+                    } else if ke.is_one_of(Key::o, KeyMappingId::UsO)
+                        && ke.modifier == ModifierType::CONTROL_MASK
+                    {
+                        sender
+                            .output_sender()
+                            .emit(SketchBoardOutput::ToggleRecentImages);
+                        ToolUpdateResult::Unmodified
+                    } else if ke.is_one_of(Key::o, KeyMappingId::UsO)
+                        && ke.modifier == (ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK)
+                    {
+                        self.handle_action(&[Action::OpenInEditor])
+                    } else if ke.is_one_of(Key::p, KeyMappingId::UsP)
+                        && ke.modifier == ModifierType::CONTROL_MASK
+                    {
+                        sender
+                            .output_sender()
+                            .emit(SketchBoardOutput::ToggleSnippetsPanel);
+                        ToolUpdateResult::Unmodified
+                    } else if ke.is_one_of(Key::h, KeyMappingId::UsH)
+                        && ke.modifier == ModifierType::CONTROL_MASK
+                    {
+                        // key repeat keeps sending presses while held
+                        if self.renderer.set_peek(true) {
+                            ToolUpdateResult::Redraw
+                        } else {
+                            ToolUpdateResult::Unmodified
+                        }
+                    } else if ke.is_one_of(Key::l, KeyMappingId::UsL)
+                        && ke.modifier == (ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK)
+                    {
+                        self.layers.borrow_mut().unlock_all();
+                        ToolUpdateResult::Unmodified
                     } else if (ke.is_one_of(Key::leftarrow, KeyMappingId::ArrowLeft)
                         || ke.is_one_of(Key::rightarrow, KeyMappingId::ArrowRight)
                         || ke.is_one_of(Key::uparrow, KeyMappingId::ArrowUp)
@@ -882,6 +3266,50 @@ impl Component for SketchBoard {
                         self.renderer
                             .request_render(&APP_CONFIG.read().actions_on_right_click());
                         ToolUpdateResult::Unmodified
+                    } else if self.active_tool_type() == Tools::Pointer
+                        && (ke.modifier.is_empty() || ke.modifier == ModifierType::SHIFT_MASK)
+                        && (ke.is_one_of(Key::leftarrow, KeyMappingId::ArrowLeft)
+                            || ke.is_one_of(Key::rightarrow, KeyMappingId::ArrowRight)
+                            || ke.is_one_of(Key::uparrow, KeyMappingId::ArrowUp)
+                            || ke.is_one_of(Key::downarrow, KeyMappingId::ArrowDown))
+                    {
+                        self.handle_nudge(ke)
+                    } else if self.active_tool_type() == Tools::Crop
+                        && (ke.key == Key::Tab || ke.key == Key::ISO_Left_Tab)
+                    {
+                        // Shift+Tab arrives as ISO_Left_Tab
+                        self.handle_crop_suggestion(ke.key == Key::Tab)
+                    } else if self.active_tool_type() == Tools::Filter
+                        && (ke.key == Key::Tab || ke.key == Key::ISO_Left_Tab)
+                    {
+                        let kind = self
+                            .tools
+                            .get_filter_tool()
+                            .borrow_mut()
+                            .cycle_kind(ke.key == Key::Tab);
+                        self.show_indicator(kind.to_string(), SIZE_INDICATOR_TIMEOUT, &sender);
+                        ToolUpdateResult::Redraw
+                    } else if self.active_tool_type() == Tools::Flowchart
+                        && (ke.key == Key::Tab || ke.key == Key::ISO_Left_Tab)
+                    {
+                        let shape = self
+                            .tools
+                            .get_flowchart_tool()
+                            .borrow_mut()
+                            .cycle_shape(ke.key == Key::Tab);
+                        self.show_indicator(shape.to_string(), SIZE_INDICATOR_TIMEOUT, &sender);
+                        ToolUpdateResult::Redraw
+                    } else if self.active_tool_type() == Tools::Perspective
+                        && ke.modifier.is_empty()
+                        && (ke.key == Key::Return || ke.key == Key::KP_Enter)
+                        && self
+                            .tools
+                            .get_perspective_tool()
+                            .borrow()
+                            .get_quad()
+                            .is_some()
+                    {
+                        self.handle_straighten()
                     } else if ke.modifier.is_empty() && ke.key == Key::Delete {
                         self.handle_reset()
                     } else if ke.modifier.is_empty()
@@ -895,58 +3323,261 @@ impl Component for SketchBoard {
                             .active_tool
                             .borrow_mut()
                             .handle_event(ToolEvent::Input(ie));
-                        if let ToolUpdateResult::Unmodified = result {
-                            let actions = if ke.key == Key::Escape {
-                                APP_CONFIG.read().actions_on_escape()
-                            } else {
-                                APP_CONFIG.read().actions_on_enter()
-                            };
-                            self.renderer.request_render(&actions);
-                        };
-                        result
+                        match result {
+                            ToolUpdateResult::Unmodified
+                                if ke.key == Key::Escape
+                                    && matches!(
+                                        self.edit_mode,
+                                        Some(EditMode::Insert | EditMode::Visual)
+                                    ) =>
+                            {
+                                self.renderer.clear_selection();
+                                self.edit_mode = Some(EditMode::Normal);
+                                ToolUpdateResult::Redraw
+                            }
+                            ToolUpdateResult::Unmodified if ke.key == Key::Escape => {
+                                self.handle_escape(&sender)
+                            }
+                            ToolUpdateResult::Unmodified => {
+                                self.renderer
+                                    .request_render(&APP_CONFIG.read().actions_on_enter());
+                                result
+                            }
+                            _ => result,
+                        }
                     } else {
                         self.active_tool
                             .borrow_mut()
                             .handle_event(ToolEvent::Input(ie))
                     }
+                } else if self.handle_peek_release(&ie) {
+                    ToolUpdateResult::Redraw
+                } else if self.handle_minimap(&ie) {
+                    ToolUpdateResult::Redraw
+                } else if self.handle_scroll_in_tool(&ie) {
+                    ToolUpdateResult::Redraw
+                } else if self.handle_scroll_corner_radius(&ie, &sender) {
+                    ToolUpdateResult::Redraw
+                } else if self.handle_scroll_selection(&ie) {
+                    ToolUpdateResult::Redraw
+                } else if self.handle_scroll(&ie, &sender) {
+                    ToolUpdateResult::Redraw
+                } else if let Some(result) = self.handle_mouse_button(&ie, &sender) {
+                    result
                 } else {
                     ie.handle_event_mouse_input(&self.renderer);
-                    self.active_tool
-                        .borrow_mut()
-                        .handle_event(ToolEvent::Input(ie))
+                    self.apply_edge_snapping(&mut ie);
+                    self.update_status(&ie);
+                    if self.handle_region_export(&ie) {
+                        ToolUpdateResult::Redraw
+                    } else if self.handle_pixel_inspector(&ie, &sender) {
+                        ToolUpdateResult::Redraw
+                    } else if self.handle_selection(&ie) {
+                        ToolUpdateResult::Redraw
+                    } else {
+                        self.handle_connector(&ie);
+                        let result = self
+                            .active_tool
+                            .borrow_mut()
+                            .handle_event(ToolEvent::Input(ie.clone()));
+                        self.handle_eraser(&ie);
+                        self.handle_text_selection(&ie, &sender);
+                        // dragging only changes the drawable of the tool, unless it erases
+                        // annotations or moves the crop
+                        self.tool_redraw =
+                            matches!(
+                                &ie,
+                                InputEvent::Mouse(me) if me.type_ == MouseEventType::UpdateDrag
+                            ) && !matches!(self.active_tool_type(), Tools::Eraser | Tools::Crop);
+                        result
+                    }
                 }
             }
             SketchBoardInput::ToolbarEvent(toolbar_event) => {
-                self.handle_toolbar_event(toolbar_event)
+                self.handle_toolbar_event(toolbar_event, &sender)
+            }
+            SketchBoardInput::SaveSteps(export, path) => {
+                self.handle_export_steps(export, path, &sender);
+                ToolUpdateResult::Unmodified
+            }
+            SketchBoardInput::PrepareDrag => {
+                self.drag_file.replace(None);
+                self.renderer.request_drag_image();
+                ToolUpdateResult::Unmodified
+            }
+            SketchBoardInput::DragImageRendered(image) => {
+                self.handle_drag_image(image, &sender);
+                ToolUpdateResult::Unmodified
+            }
+            SketchBoardInput::RenderResult(..) if self.placeholder => {
+                self.report(Severity::Warning, "The image is still loading");
+                ToolUpdateResult::Unmodified
             }
             SketchBoardInput::RenderResult(img, action) => {
-                self.handle_render_result(img, action, &sender);
+                let position = (!std::mem::take(&mut self.exporting_region))
+                    .then(|| self.renderer.history_position());
+                self.handle_render_result(img, action, position, &sender);
+                ToolUpdateResult::Unmodified
+            }
+            SketchBoardInput::Saved(position) => {
+                self.saved_position = position;
+                self.save_template();
+                ToolUpdateResult::Unmodified
+            }
+            SketchBoardInput::CommitEvent(txt) => self.handle_text_commit(txt, sender.clone()),
+            SketchBoardInput::HideSizeIndicator(generation) => {
+                if generation == self.size_indicator_generation {
+                    self.size_indicator = None;
+                }
+                ToolUpdateResult::Unmodified
+            }
+            SketchBoardInput::KeySequenceTimeout(generation) => {
+                self.handle_key_sequence_timeout(generation, &sender);
+                ToolUpdateResult::Unmodified
+            }
+            SketchBoardInput::LayerEvent(event) => self.handle_layer_event(event, &sender),
+            SketchBoardInput::SnippetEvent(event) => self.handle_snippet_event(event, &sender),
+            SketchBoardInput::AddAnnotations(annotations) => {
+                for annotation in annotations {
+                    self.renderer.commit(annotation.into_drawable());
+                }
+                ToolUpdateResult::Redraw
+            }
+            SketchBoardInput::SetAdjustments(adjustments) => {
+                self.renderer.set_adjustments(adjustments);
+                ToolUpdateResult::Redraw
+            }
+            SketchBoardInput::SetInputFile(name, batch_pending) => {
+                self.input_name = Some(name);
+                self.batch_pending = batch_pending;
                 ToolUpdateResult::Unmodified
             }
-            SketchBoardInput::CommitEvent(txt) => {
-                self.handle_text_commit(txt, sender);
+            SketchBoardInput::SetSession(session) => {
+                self.session = session;
                 ToolUpdateResult::Unmodified
             }
+            SketchBoardInput::InsertImage(pixbuf, center) => {
+                self.handle_insert_image(pixbuf, center, &sender)
+            }
+            SketchBoardInput::DropFile(path, pos) => self.handle_drop_file(path, pos, &sender),
         };
 
         //println!("Event={:?} Result={:?}", msg, result);
+        let tool_redraw = std::mem::take(&mut self.tool_redraw);
+        // a drag only changes the drawable of the tool, the annotations stay as they are
+        let annotations_changed = !tool_redraw && !matches!(result, ToolUpdateResult::Unmodified);
         match result {
             ToolUpdateResult::Commit(drawable) => {
                 self.renderer.commit(drawable);
                 self.refresh_screen();
             }
             ToolUpdateResult::Unmodified => (),
+            ToolUpdateResult::Redraw if tool_redraw => self.renderer.queue_render(),
             ToolUpdateResult::Redraw => self.refresh_screen(),
         };
+
+        // crop changes are undone like annotations
+        self.renderer.record_crop();
+        if annotations_changed {
+            self.update_layers(&sender);
+        }
+        self.update_editor_state(&sender);
+        self.update_histogram(&sender);
+        self.update_toasts();
+    }
+
+    fn update_cmd(
+        &mut self,
+        command: SketchBoardCommand,
+        sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        match command {
+            // found in an image that was replaced since
+            SketchBoardCommand::RedactRegions(generation, _)
+            | SketchBoardCommand::Words(generation, _)
+                if generation != self.image_generation => {}
+            SketchBoardCommand::RedactRegions(_, regions) => {
+                self.handle_redact_regions(regions, &sender)
+            }
+            SketchBoardCommand::Words(_, words) => self.handle_words(words),
+            SketchBoardCommand::Encoded(image, actions) => {
+                self.handle_encoded(Some(image), actions, Vec::new(), &sender);
+            }
+            SketchBoardCommand::SavedAs(path, image, actions) => {
+                self.handle_saved_as(path, image, actions, &sender)
+            }
+            SketchBoardCommand::DragFileWritten(path, pixbuf) => match path {
+                Ok(path) => {
+                    self.drag_file
+                        .replace(Some((path, Texture::for_pixbuf(&pixbuf))));
+                }
+                Err(e) => self.report(
+                    Severity::Error,
+                    &format!("Error while preparing the drag: {e}"),
+                ),
+            },
+            SketchBoardCommand::RecordingSaved(result) => match result {
+                Ok(path) => self.report(
+                    Severity::Success,
+                    &format!("Recording saved to '{}'.", path.display()),
+                ),
+                Err(e) => self.report(
+                    Severity::Error,
+                    &format!("Error while saving the recording: {e}"),
+                ),
+            },
+            SketchBoardCommand::StepImagesSaved(result) => match result {
+                Ok(paths) => self.report(
+                    Severity::Success,
+                    &format!(
+                        "Saved {} images, the first to '{}'.",
+                        paths.len(),
+                        paths
+                            .first()
+                            .map(|p| p.display().to_string())
+                            .unwrap_or_default()
+                    ),
+                ),
+                Err(e) => self.report(
+                    Severity::Error,
+                    &format!("Error while saving the steps: {e}"),
+                ),
+            },
+            SketchBoardCommand::EditorClosed(result) => match result {
+                Ok(Some(pixbuf)) => sender.input(SketchBoardInput::LoadNewImage(pixbuf)),
+                Ok(None) => (),
+                Err(e) => self.report(
+                    Severity::Error,
+                    &format!("Error in the external editor: {e}"),
+                ),
+            },
+            SketchBoardCommand::DroppedFileLoaded(result, center) => match result {
+                Ok(pixbuf) => sender.input(SketchBoardInput::InsertImage(pixbuf, Some(center))),
+                Err(e) => self.report(
+                    Severity::Error,
+                    &format!("Cannot open the dropped file {e}"),
+                ),
+            },
+            SketchBoardCommand::QuickActionFinished(name, result) => match result {
+                Ok(()) => log::info!("Quick action '{name}' finished"),
+                Err(e) => self.report(
+                    Severity::Error,
+                    &format!("Quick action '{name}' failed: {e}"),
+                ),
+            },
+        }
+        self.update_toasts();
     }
 
     fn init(
-        image: Self::Init,
+        (image, placeholder): Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
+        let toasts = Toasts::builder().launch(()).detach();
         let config = APP_CONFIG.read();
-        let tools = ToolsManager::new();
+        let tools = ToolsManager::new(toasts.sender());
 
         let im_context = gtk::IMMulticontext::new();
 
@@ -956,6 +3587,36 @@ impl Component for SketchBoard {
             style: Style::default(),
             tools,
             im_context,
+            edge_map: OnceCell::new(),
+            image_generation: 0,
+            drag_start: None,
+            crop_suggestion: None,
+            cursor_pos: None,
+            selection_size: None,
+            minimap_drag: None,
+            pixel_inspector: false,
+            input_name: None,
+            session: SessionOptions::default(),
+            batch_pending: false,
+            region_export: None,
+            exporting_region: false,
+            tool_redraw: false,
+            size_indicator: None,
+            size_indicator_generation: 0,
+            toasts,
+            pending_keys: String::new(),
+            pending_keys_generation: 0,
+            edit_mode: config.modal_editing().then_some(EditMode::Normal),
+            gesture: None,
+            escape_confirmation: None,
+            layers: Rc::new(RefCell::new(Layers::default())),
+            layer_infos: Vec::new(),
+            saved_position: None,
+            editor_state: None,
+            histogram_visible: false,
+            histogram_region: None,
+            placeholder,
+            drag_file: Rc::new(RefCell::new(None)),
         };
 
         let area = &mut model.renderer;
@@ -963,6 +3624,7 @@ impl Component for SketchBoard {
             sender.input_sender().clone(),
             model.tools.get_crop_tool(),
             model.active_tool.clone(),
+            model.layers.clone(),
             image,
         );
 
@@ -1059,3 +3721,45 @@ impl KeyEventMsg {
         self.key == key || self.code as u16 - 8 == keymap.evdev
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_the_input_name() {
+        assert_eq!(
+            SketchBoard::replace_names("{input}-annotated.png".into(), Some("shot"), None),
+            "shot-annotated.png"
+        );
+        assert_eq!(
+            SketchBoard::replace_names("out/{input}/{input}.png".into(), Some("a"), None),
+            "out/a/a.png"
+        );
+    }
+
+    // the names are replaced after the date formats, a `%` in them is kept as it is
+    #[test]
+    fn keeps_format_specifiers_in_the_input_name() {
+        assert_eq!(
+            SketchBoard::replace_names("{input}.png".into(), Some("100%d"), None),
+            "100%d.png"
+        );
+    }
+
+    #[test]
+    fn keeps_placeholders_without_a_value() {
+        assert_eq!(
+            SketchBoard::replace_names("{input}-{title}.png".into(), None, None),
+            "{input}-{title}.png"
+        );
+    }
+
+    #[test]
+    fn title_does_not_add_directories() {
+        assert_eq!(
+            SketchBoard::replace_names("{title}.png".into(), Some("shot"), Some("a/b")),
+            "a_b.png"
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use std::{collections::BTreeMap, fs};
+
+use anyhow::{anyhow, Context, Result};
+use xdg::BaseDirectories;
+
+use crate::{
+    annotations::{self, AnnotationSpec},
+    math::Vec2D,
+};
+
+const SNIPPETS_FILE: &str = "snippets.json";
+
+/// Annotations saved under a name to be inserted again, sorted by name.
+type Snippets = BTreeMap<String, Vec<AnnotationSpec>>;
+
+fn load(dirs: &BaseDirectories) -> Result<Snippets> {
+    let Some(path) = dirs.find_data_file(SNIPPETS_FILE) else {
+        return Ok(Snippets::new());
+    };
+    let json = fs::read_to_string(&path).with_context(|| format!("Cannot read {path:?}"))?;
+    serde_json::from_str(&json).with_context(|| format!("Invalid snippets in {path:?}"))
+}
+
+fn store(dirs: &BaseDirectories, snippets: &Snippets) -> Result<()> {
+    let path = dirs.place_data_file(SNIPPETS_FILE)?;
+    fs::write(path, serde_json::to_string_pretty(snippets)?)?;
+    Ok(())
+}
+
+/// Names of the saved snippets.
+pub fn names() -> Result<Vec<String>> {
+    let dirs = BaseDirectories::with_prefix(env!("CARGO_PKG_NAME"));
+    Ok(load(&dirs)?.into_keys().collect())
+}
+
+/// The annotations of the snippet `name`.
+pub fn get(name: &str) -> Result<Vec<AnnotationSpec>> {
+    let dirs = BaseDirectories::with_prefix(env!("CARGO_PKG_NAME"));
+    load(&dirs)?
+        .remove(name)
+        .ok_or_else(|| anyhow!("No snippet named {name}"))
+}
+
+/// Saves `annotations` as the snippet `name`, replacing one with the same name. They are
+/// stored relative to the top left corner of their bounds, to be inserted into any image.
+pub fn save(name: &str, mut annotations: Vec<AnnotationSpec>) -> Result<()> {
+    if let Some((top_left, _)) = annotations::bounds(&annotations) {
+        for annotation in &mut annotations {
+            annotation.translate(Vec2D::zero() - top_left);
+        }
+    }
+    let dirs = BaseDirectories::with_prefix(env!("CARGO_PKG_NAME"));
+    let mut snippets = load(&dirs)?;
+    snippets.insert(name.to_string(), annotations);
+    store(&dirs, &snippets)
+}
+
+pub fn delete(name: &str) -> Result<()> {
+    let dirs = BaseDirectories::with_prefix(env!("CARGO_PKG_NAME"));
+    let mut snippets = load(&dirs)?;
+    if snippets.remove(name).is_some() {
+        store(&dirs, &snippets)?;
+    }
+    Ok(())
+}
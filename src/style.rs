@@ -8,6 +8,7 @@ use gdk_pixbuf::{
 use glib::variant::FromVariant;
 use hex_color::HexColor;
 use relm4::gtk::gdk::RGBA;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::configuration::APP_CONFIG;
 
@@ -17,6 +18,8 @@ pub struct Style {
     pub size: Size,
     pub fill: bool,
     pub annotation_size_factor: f32,
+    // only used by rectangles
+    pub corner_radius: f32,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -27,7 +30,8 @@ pub struct Color {
     pub a: u8,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Size {
     Small = 0,
     #[default]
@@ -42,6 +46,7 @@ impl Default for Style {
             size: Size::default(),
             fill: APP_CONFIG.read().default_fill_shapes(),
             annotation_size_factor: APP_CONFIG.read().annotation_size_factor(),
+            corner_radius: APP_CONFIG.read().corner_roundness(),
         }
     }
 }
@@ -117,6 +122,16 @@ impl Color {
             (self.a as f64) / 255.0,
         )
     }
+    /// `#rrggbb`, with the alpha appended if not opaque.
+    pub fn to_hex(self) -> String {
+        let hex = format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b);
+        if self.a == 255 {
+            hex
+        } else {
+            format!("{hex}{:02x}", self.a)
+        }
+    }
+
     pub fn to_rgba_u32(self) -> u32 {
         ((self.r as u32) << 24) | ((self.g as u32) << 16) | ((self.b as u32) << 8) | (self.a as u32)
     }
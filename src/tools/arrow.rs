@@ -3,8 +3,9 @@ use femtovg::{FontId, Path};
 use relm4::gtk::gdk::{Key, ModifierType};
 
 use crate::{
-    math::{Angle, Vec2D},
-    sketch_board::{MouseButton, MouseEventMsg, MouseEventType},
+    annotations::AnnotationSpec,
+    math::{self, Angle, Vec2D},
+    sketch_board::{MouseEventMsg, MouseEventType},
     style::Style,
 };
 
@@ -17,6 +18,16 @@ pub struct Arrow {
     style: Style,
 }
 
+impl Arrow {
+    pub fn new(start: Vec2D, end: Vec2D, style: Style) -> Self {
+        Self {
+            start,
+            end: Some(end),
+            style,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct ArrowTool {
     arrow: Option<Arrow>,
@@ -40,7 +51,7 @@ impl Tool for ArrowTool {
     fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
         match event.type_ {
             MouseEventType::BeginDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
@@ -54,7 +65,7 @@ impl Tool for ArrowTool {
                 ToolUpdateResult::Redraw
             }
             MouseEventType::EndDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
@@ -79,7 +90,7 @@ impl Tool for ArrowTool {
                 }
             }
             MouseEventType::UpdateDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
@@ -234,4 +245,33 @@ impl Drawable for Arrow {
         canvas.restore();
         Ok(())
     }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.end
+            .map(|end| math::rect_around_points(&[self.start, end]))
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.start += offset;
+        if let Some(end) = &mut self.end {
+            *end += offset;
+        }
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Arrow
+    }
+
+    fn color(&self) -> Option<crate::style::Color> {
+        Some(self.style.color)
+    }
+
+    fn to_spec(&self) -> Option<AnnotationSpec> {
+        let end = self.end?;
+        Some(AnnotationSpec::Arrow {
+            start: [self.start.x, self.start.y],
+            end: [end.x, end.y],
+            style: self.style.into(),
+        })
+    }
 }
@@ -1,49 +1,66 @@
 use std::cell::RefCell;
 
 use anyhow::Result;
-use femtovg::{imgref::Img, Color, ImageFilter, ImageFlags, ImageId, Paint, Path};
+use femtovg::{
+    imgref::{Img, ImgVec},
+    rgb::RGBA8,
+    Color, ImageFilter, ImageFlags, ImageId, Paint, Path, PixelFormat,
+};
 
 use relm4::gtk::gdk::Key;
 
 use crate::{
     configuration::APP_CONFIG,
     math::{self, Vec2D},
-    sketch_board::{MouseButton, MouseEventMsg, MouseEventType},
+    sketch_board::{MouseEventMsg, MouseEventType},
     style::Style,
 };
 
-use super::{Drawable, DrawableClone, Tool, ToolUpdateResult, Tools};
+use super::{CachedImage, Drawable, DrawableClone, Tool, ToolUpdateResult, Tools};
+
+/// How much smaller than the canvas the preview shown while dragging is blurred.
+const PREVIEW_DOWNSCALE: usize = 4;
+
+// area of the canvas blurred once at a reduced resolution when a drag starts, in image
+// coordinates. The dragged part of it is shown until the blur is committed and rendered at
+// full resolution
+#[derive(Clone, Copy, Debug)]
+struct Preview {
+    pos: Vec2D,
+    size: Vec2D,
+}
 
 #[derive(Clone, Debug)]
 pub struct Blur {
+    origin: Vec2D,
     top_left: Vec2D,
     size: Option<Vec2D>,
     style: Style,
     editing: bool,
-    cached_image: RefCell<Option<ImageId>>,
+    cached_image: CachedImage,
+    preview_image: CachedImage,
+    preview: RefCell<Option<Preview>>,
 }
 
 impl Blur {
+    fn update(&mut self, event: &MouseEventMsg) {
+        let (top_left, size) = math::rect_from_drag(
+            self.origin,
+            event.pos,
+            event.from_center(),
+            event.proportional(),
+        );
+        self.top_left = top_left;
+        self.size = Some(size);
+    }
+
     fn blur(
         canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
         pos: Vec2D,
         size: Vec2D,
         sigma: f32,
     ) -> Result<ImageId> {
-        let img = canvas.screenshot()?;
-
-        let transformed_pos = canvas.transform().transform_point(pos.x, pos.y);
-        let transformed_size = size * canvas.transform().average_scale();
-
-        let (buf, width, height) = img
-            .sub_image(
-                transformed_pos.0 as usize,
-                transformed_pos.1 as usize,
-                (transformed_size.x as usize).max(1),
-                (transformed_size.y as usize).max(1),
-            )
-            .to_contiguous_buf();
-        let sub = Img::new(buf.into_owned(), width, height);
+        let sub = capture(canvas, pos, size)?;
 
         let src_image_id = canvas.create_image(sub.as_ref(), ImageFlags::empty())?;
         let dst_image_id = canvas.create_image_empty(
@@ -58,10 +75,74 @@ impl Blur {
             ImageFilter::GaussianBlur { sigma },
             src_image_id,
         );
-        //canvas.delete_image(src_image_id);
+        canvas.flush();
+        canvas.delete_image(src_image_id);
 
         Ok(dst_image_id)
     }
+
+    fn preview(
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        sigma: f32,
+    ) -> Result<(ImageId, Preview)> {
+        canvas.flush();
+        let screenshot = canvas.screenshot()?;
+        let width = (screenshot.width() / PREVIEW_DOWNSCALE).max(1);
+        let height = (screenshot.height() / PREVIEW_DOWNSCALE).max(1);
+        let (buf, stride) = (screenshot.buf(), screenshot.stride());
+        let pixels = (0..height)
+            .flat_map(|y| {
+                (0..width).map(move |x| buf[y * PREVIEW_DOWNSCALE * stride + x * PREVIEW_DOWNSCALE])
+            })
+            .collect();
+        let proxy = Img::new(pixels, width, height);
+
+        let src_image_id = canvas.create_image(proxy.as_ref(), ImageFlags::empty())?;
+        let dst_image_id =
+            canvas.create_image_empty(width, height, PixelFormat::Rgba8, ImageFlags::empty())?;
+        canvas.filter_image(
+            dst_image_id,
+            ImageFilter::GaussianBlur { sigma },
+            src_image_id,
+        );
+        canvas.flush();
+        canvas.delete_image(src_image_id);
+
+        // the whole canvas, in image coordinates
+        let mut inverse = canvas.transform();
+        inverse.inverse();
+        let top_left = inverse.transform_point(0.0, 0.0);
+        let bottom_right =
+            inverse.transform_point(screenshot.width() as f32, screenshot.height() as f32);
+        let preview = Preview {
+            pos: Vec2D::new(top_left.0, top_left.1),
+            size: Vec2D::new(bottom_right.0 - top_left.0, bottom_right.1 - top_left.1),
+        };
+        Ok((dst_image_id, preview))
+    }
+}
+
+/// Copies what has been drawn so far in the given area, `pos` and `size` are in image
+/// coordinates.
+pub(super) fn capture(
+    canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+    pos: Vec2D,
+    size: Vec2D,
+) -> Result<ImgVec<RGBA8>> {
+    let img = canvas.screenshot()?;
+
+    let transformed_pos = canvas.transform().transform_point(pos.x, pos.y);
+    let transformed_size = size * canvas.transform().average_scale();
+
+    let (buf, width, height) = img
+        .sub_image(
+            transformed_pos.0 as usize,
+            transformed_pos.1 as usize,
+            (transformed_size.x as usize).max(1),
+            (transformed_size.y as usize).max(1),
+        )
+        .to_contiguous_buf();
+    Ok(Img::new(buf.into_owned(), width, height))
 }
 
 impl Drawable for Blur {
@@ -80,12 +161,21 @@ impl Drawable for Blur {
             bounds,
         );
         if self.editing {
-            // set style
-            let mut color = Color::black();
-            color.set_alphaf(0.6);
-            let paint = Paint::color(color);
+            let image_id = self.preview_image.get_or_create(|| {
+                // as strong as the full resolution blur, relative to the image
+                let sigma = self
+                    .style
+                    .size
+                    .to_blur_factor(self.style.annotation_size_factor)
+                    * canvas.transform().average_scale()
+                    / PREVIEW_DOWNSCALE as f32;
+                let (image_id, preview) = Self::preview(canvas, sigma.max(0.5))?;
+                self.preview.borrow_mut().replace(preview);
+                Ok(image_id)
+            })?;
+            // this unwrap is safe because it is set along with the image
+            let preview = self.preview.borrow().unwrap();
 
-            // make rect
             let mut path = Path::new();
             path.rounded_rect(
                 pos.x,
@@ -94,10 +184,20 @@ impl Drawable for Blur {
                 size.y,
                 APP_CONFIG.read().corner_roundness(),
             );
-
-            // draw
-            canvas.fill_path(&path, &paint);
+            canvas.fill_path(
+                &path,
+                &Paint::image(
+                    image_id,
+                    preview.pos.x,
+                    preview.pos.y,
+                    preview.size.x,
+                    preview.size.y,
+                    0f32,
+                    1f32,
+                ),
+            );
         } else {
+            self.preview_image.clear();
             if size.x <= 0.0 || size.y <= 0.0 {
                 return Ok(());
             }
@@ -105,17 +205,16 @@ impl Drawable for Blur {
             canvas.save();
             canvas.flush();
 
-            // create new cached image
-            if self.cached_image.borrow().is_none() {
-                self.cached_image.borrow_mut().replace(Self::blur(
+            let image_id = self.cached_image.get_or_create(|| {
+                Self::blur(
                     canvas,
                     pos,
                     size,
                     self.style
                         .size
                         .to_blur_factor(self.style.annotation_size_factor),
-                )?);
-            }
+                )
+            })?;
 
             let mut path = Path::new();
             path.rounded_rect(
@@ -128,29 +227,90 @@ impl Drawable for Blur {
 
             canvas.fill_path(
                 &path,
-                &Paint::image(
-                    self.cached_image.borrow().unwrap(), // this unwrap is safe because we placed it above
-                    pos.x,
-                    pos.y,
-                    size.x,
-                    size.y,
-                    0f32,
-                    1f32,
-                ),
+                &Paint::image(image_id, pos.x, pos.y, size.x, size.y, 0f32, 1f32),
             );
             canvas.restore();
         }
         Ok(())
     }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.size.map(|size| (self.top_left, size))
+    }
+
+    fn memory_size(&self) -> usize {
+        std::mem::size_of_val(self) + self.cached_image.memory_size(self.bounds())
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.origin += offset;
+        self.top_left += offset;
+        // the cached image shows what was underneath the old position
+        self.cached_image.clear();
+    }
+
+    fn clear_cache(&self) {
+        self.cached_image.clear();
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Blur
+    }
+}
+
+/// Regions proposed for blurring, the first one is confirmed or skipped next.
+#[derive(Clone, Debug, Default)]
+pub struct Proposals {
+    regions: Vec<(Vec2D, Vec2D)>,
+}
+
+impl Drawable for Proposals {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        _font: femtovg::FontId,
+        _bounds: (Vec2D, Vec2D),
+    ) -> Result<()> {
+        let scale = canvas.transform().average_scale();
+        for (i, (pos, size)) in self.regions.iter().enumerate() {
+            let mut path = Path::new();
+            path.rect(pos.x, pos.y, size.x, size.y);
+            if i == 0 {
+                canvas.fill_path(&path, &Paint::color(Color::rgbaf(0.0, 0.0, 0.0, 0.6)));
+                canvas.stroke_path(
+                    &path,
+                    &Paint::color(Color::rgb(53, 132, 228)).with_line_width(2.0 / scale),
+                );
+            } else {
+                canvas.stroke_path(
+                    &path,
+                    &Paint::color(Color::rgbf(0.5, 0.5, 0.5)).with_line_width(1.0 / scale),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Blur
+    }
 }
 
 #[derive(Default)]
 pub struct BlurTool {
     blur: Option<Blur>,
+    proposals: Proposals,
     style: Style,
     input_enabled: bool,
 }
 
+impl BlurTool {
+    /// Shows `regions` to be blurred with Enter or skipped with Tab one by one.
+    pub fn propose(&mut self, regions: Vec<(Vec2D, Vec2D)>) {
+        self.proposals.regions = regions;
+    }
+}
+
 impl Tool for BlurTool {
     fn input_enabled(&self) -> bool {
         self.input_enabled
@@ -167,23 +327,26 @@ impl Tool for BlurTool {
     fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
         match event.type_ {
             MouseEventType::BeginDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
                 // start new
                 self.blur = Some(Blur {
+                    origin: event.pos,
                     top_left: event.pos,
                     size: None,
                     style: self.style,
                     editing: true,
-                    cached_image: RefCell::new(None),
+                    cached_image: CachedImage::default(),
+                    preview_image: CachedImage::default(),
+                    preview: RefCell::new(None),
                 });
 
                 ToolUpdateResult::Redraw
             }
             MouseEventType::EndDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
@@ -193,7 +356,7 @@ impl Tool for BlurTool {
 
                         ToolUpdateResult::Redraw
                     } else {
-                        a.size = Some(event.pos);
+                        a.update(&event);
                         a.editing = false;
 
                         let result = a.clone_box();
@@ -206,7 +369,7 @@ impl Tool for BlurTool {
                 }
             }
             MouseEventType::UpdateDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
@@ -214,7 +377,7 @@ impl Tool for BlurTool {
                     if event.pos == Vec2D::zero() {
                         return ToolUpdateResult::Unmodified;
                     }
-                    a.size = Some(event.pos);
+                    a.update(&event);
 
                     ToolUpdateResult::Redraw
                 } else {
@@ -229,6 +392,27 @@ impl Tool for BlurTool {
         if event.key == Key::Escape && self.blur.is_some() {
             self.blur = None;
             ToolUpdateResult::Redraw
+        } else if self.blur.is_some() || self.proposals.regions.is_empty() {
+            ToolUpdateResult::Unmodified
+        } else if event.key == Key::Return || event.key == Key::KP_Enter {
+            let (pos, size) = self.proposals.regions.remove(0);
+            let blur = Blur {
+                origin: pos,
+                top_left: pos,
+                size: Some(size),
+                style: self.style,
+                editing: false,
+                cached_image: CachedImage::default(),
+                preview_image: CachedImage::default(),
+                preview: RefCell::new(None),
+            };
+            ToolUpdateResult::Commit(blur.clone_box())
+        } else if event.key == Key::Tab {
+            self.proposals.regions.remove(0);
+            ToolUpdateResult::Redraw
+        } else if event.key == Key::Escape {
+            self.proposals.regions.clear();
+            ToolUpdateResult::Redraw
         } else {
             ToolUpdateResult::Unmodified
         }
@@ -242,6 +426,7 @@ impl Tool for BlurTool {
     fn get_drawable(&self) -> Option<&dyn Drawable> {
         match &self.blur {
             Some(d) => Some(d),
+            None if !self.proposals.regions.is_empty() => Some(&self.proposals),
             None => None,
         }
     }
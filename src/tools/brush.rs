@@ -1,21 +1,45 @@
 use std::time::Instant;
 
-use femtovg::{FontId, Path};
+use femtovg::{FontId, LineCap, LineJoin, Paint, Path};
+use relm4::gtk::gdk::{Key, ModifierType};
+use serde_derive::Deserialize;
 
 use crate::{
+    command_line,
     configuration::APP_CONFIG,
-    math::Vec2D,
-    sketch_board::{MouseButton, MouseEventMsg, MouseEventType},
+    math::{self, Vec2D},
+    sketch_board::{KeyEventMsg, MouseButton, MouseEventMsg, MouseEventType},
     style::Style,
+    ui::toasts::{self, Severity, ToastsInput},
 };
 
-use super::{Drawable, DrawableClone, Tool, ToolUpdateResult, Tools};
+use super::{eraser, shape_recognition, Drawable, DrawableClone, Tool, ToolUpdateResult, Tools};
+
+// steps the factors of dynamic strokes are rounded to, fine enough not to be seen
+const FACTOR_STEPS: f32 = 32.0;
 
-#[derive(Default)]
 pub struct BrushTool {
     drawable: Option<BrushDrawable>,
     style: Style,
     input_enabled: bool,
+    // overrides the configured shape recognition once toggled
+    shape_recognition: Option<bool>,
+    // overrides the configured smoothing strength once adjusted
+    smoothing_strength: Option<usize>,
+    toasts: relm4::Sender<ToastsInput>,
+}
+
+impl BrushTool {
+    pub fn new(toasts: &relm4::Sender<ToastsInput>) -> Self {
+        Self {
+            drawable: None,
+            style: Style::default(),
+            input_enabled: false,
+            shape_recognition: None,
+            smoothing_strength: None,
+            toasts: toasts.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -24,13 +48,118 @@ pub struct BrushDrawable {
     // after this the points are relative to the start point
     start_point: Option<Vec2D>,
     points: Vec<Vec2D>,
+    // indices of the points that start a new sub-path after erasing
+    breaks: Vec<usize>,
     smoother: Smoother,
+    // width or opacity factor of every point, only with configured dynamics
+    dynamics: Option<Dynamics>,
+    factors: Vec<f32>,
     style: Style,
 }
 
 impl BrushDrawable {
-    fn add_point(&mut self, point: Vec2D) {
-        self.points.push(self.smoother.update(point));
+    fn add_point(&mut self, point: Vec2D, pressure: Option<f32>) {
+        let point = self.smoother.update(point);
+        if let Some(dynamics) = &mut self.dynamics {
+            self.factors.push(dynamics.factor(point, pressure));
+        }
+        self.points.push(point);
+    }
+
+    /// Draws runs of segments whose averaged factor rounds to the same step as one path each,
+    /// so translucent strokes don't get darker where the segments overlap. Runs meet with butt
+    /// caps, only the ends of the stroke are round.
+    fn draw_dynamic(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        target: DynamicsTarget,
+    ) {
+        let Some(points) = self.absolute_points() else {
+            return;
+        };
+
+        let mut run: Vec<Vec2D> = Vec::new();
+        let mut run_factor = 0.0;
+        let mut round_start = true;
+        for (i, segment) in points.windows(2).enumerate() {
+            if self.breaks.contains(&(i + 1)) {
+                self.stroke_run(canvas, target, &run, run_factor, round_start, true);
+                run.clear();
+                round_start = true;
+                continue;
+            }
+            let factor = ((self.factors[i] + self.factors[i + 1]) / 2.0 * FACTOR_STEPS).round()
+                / FACTOR_STEPS;
+            if !run.is_empty() && factor != run_factor {
+                self.stroke_run(canvas, target, &run, run_factor, round_start, false);
+                run.clear();
+                round_start = false;
+            }
+            if run.is_empty() {
+                run.push(segment[0]);
+            }
+            run.push(segment[1]);
+            run_factor = factor;
+        }
+        self.stroke_run(canvas, target, &run, run_factor, round_start, true);
+    }
+
+    fn stroke_run(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        target: DynamicsTarget,
+        points: &[Vec2D],
+        factor: f32,
+        round_start: bool,
+        round_end: bool,
+    ) {
+        let [first, rest @ ..] = points else {
+            return;
+        };
+        if rest.is_empty() {
+            return;
+        }
+        let cap = |round| if round { LineCap::Round } else { LineCap::Butt };
+        let mut paint: Paint = self.style.into();
+        paint.set_line_cap_start(cap(round_start));
+        paint.set_line_cap_end(cap(round_end));
+        paint.set_line_join(LineJoin::Round);
+        if target != DynamicsTarget::Opacity {
+            paint.set_line_width(
+                self.style
+                    .size
+                    .to_line_width(self.style.annotation_size_factor)
+                    * factor,
+            );
+        }
+        if target != DynamicsTarget::Width {
+            let mut color: femtovg::Color = self.style.color.into();
+            color.a *= factor.clamp(0.0, 1.0);
+            paint.set_color(color);
+        }
+
+        let mut path = Path::new();
+        path.move_to(first.x, first.y);
+        for point in rest {
+            path.line_to(point.x, point.y);
+        }
+        canvas.stroke_path(&path, &paint);
+    }
+
+    // the stroke as drawn, in image coordinates
+    fn absolute_points(&self) -> Option<Vec<Vec2D>> {
+        let start_point = self.start_point?;
+        Some(
+            std::iter::once(start_point)
+                .chain(self.points.iter().skip(1).map(|p| start_point + *p))
+                .collect(),
+        )
+    }
+
+    fn recognize_shape(&self) -> Option<Box<dyn Drawable>> {
+        let points = self.absolute_points()?;
+        shape_recognition::recognize(&points, APP_CONFIG.read().shape_recognition_tolerance())
+            .map(|shape| shape.into_drawable(self.style))
     }
 }
 
@@ -50,17 +179,80 @@ impl Drawable for BrushDrawable {
         };
 
         canvas.save();
-        let mut path = Path::new();
+        if let Some(dynamics) = &self.dynamics {
+            if self.factors.len() == self.points.len() {
+                self.draw_dynamic(canvas, dynamics.target);
+                canvas.restore();
+                return Ok(());
+            }
+        }
 
+        let mut path = Path::new();
         path.move_to(start_point.x, start_point.y);
-        for p in self.points.iter().skip(1) {
-            path.line_to(start_point.x + p.x, start_point.y + p.y);
+        for (i, p) in self.points.iter().enumerate().skip(1) {
+            if self.breaks.contains(&i) {
+                path.move_to(start_point.x + p.x, start_point.y + p.y);
+            } else {
+                path.line_to(start_point.x + p.x, start_point.y + p.y);
+            }
         }
 
         canvas.stroke_path(&path, &self.style.into());
         canvas.restore();
         Ok(())
     }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        if self.points.is_empty() {
+            return None;
+        }
+        self.absolute_points()
+            .map(|points| math::rect_around_points(&points))
+    }
+
+    fn memory_size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.points.capacity() * std::mem::size_of::<Vec2D>()
+            + self.breaks.capacity() * std::mem::size_of::<usize>()
+            + self.factors.capacity() * std::mem::size_of::<f32>()
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        if let Some(start_point) = &mut self.start_point {
+            *start_point += offset;
+        }
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Brush
+    }
+
+    fn color(&self) -> Option<crate::style::Color> {
+        Some(self.style.color)
+    }
+
+    fn erase(&self, center: Vec2D, radius: f32) -> Option<Box<dyn Drawable>> {
+        let width = self
+            .style
+            .size
+            .to_line_width(self.style.annotation_size_factor);
+        let erased = eraser::erase_stroke(
+            &self.absolute_points()?,
+            &self.factors,
+            &self.breaks,
+            center,
+            radius + width / 2.0,
+        )?;
+
+        // the first point becomes the start, everything is relative to it
+        let mut brush = self.clone();
+        brush.start_point = erased.points.first().copied();
+        let start = brush.start_point.unwrap_or_default();
+        brush.points = erased.points.iter().map(|&p| p - start).collect();
+        brush.factors = erased.factors;
+        brush.breaks = erased.breaks;
+        Some(Box::new(brush))
+    }
 }
 
 impl Tool for BrushTool {
@@ -79,7 +271,7 @@ impl Tool for BrushTool {
     fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
         match event.type_ {
             MouseEventType::BeginDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
@@ -90,40 +282,57 @@ impl Tool for BrushTool {
                 ToolUpdateResult::Redraw
             }
             MouseEventType::EndDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
                 let Some(brush) = &mut self.drawable else {
                     return ToolUpdateResult::Unmodified;
                 };
-                brush.add_point(event.pos);
+                brush.add_point(event.pos, event.pressure);
 
                 // commit
-                let result = brush.clone_box();
+                let recognized = if self
+                    .shape_recognition
+                    .unwrap_or_else(|| APP_CONFIG.read().shape_recognition())
+                {
+                    brush.recognize_shape()
+                } else {
+                    None
+                };
+                let result = recognized.unwrap_or_else(|| brush.clone_box());
                 self.drawable = None;
 
                 ToolUpdateResult::Commit(result)
             }
             MouseEventType::UpdateDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
                 let Some(brush) = &mut self.drawable else {
                     return ToolUpdateResult::Unmodified;
                 };
-                brush.add_point(event.pos);
+                brush.add_point(event.pos, event.pressure);
                 ToolUpdateResult::Redraw
             }
             MouseEventType::Click => {
                 if event.button != MouseButton::Primary {
                     return ToolUpdateResult::Unmodified;
                 }
+                // drag positions are relative to the start, so the start is at zero
+                let mut dynamics = Dynamics::from_config();
+                let factors = dynamics
+                    .as_mut()
+                    .map(|d| vec![d.factor(Vec2D::zero(), event.pressure)])
+                    .unwrap_or_default();
                 self.drawable = Some(BrushDrawable {
                     start_point: None,
-                    smoother: Smoother::new(APP_CONFIG.read().brush_smooth_history_size()),
+                    smoother: Smoother::new(self.smoothing_strength),
                     points: vec![event.pos],
+                    breaks: Vec::new(),
+                    dynamics,
+                    factors,
                     style: self.style,
                 });
                 ToolUpdateResult::Unmodified
@@ -132,6 +341,27 @@ impl Tool for BrushTool {
         }
     }
 
+    fn handle_key_event(&mut self, event: KeyEventMsg) -> ToolUpdateResult {
+        if event.key == Key::r && event.modifier == ModifierType::CONTROL_MASK {
+            let enabled = !self
+                .shape_recognition
+                .unwrap_or_else(|| APP_CONFIG.read().shape_recognition());
+            self.shape_recognition = Some(enabled);
+            toasts::report(
+                &self.toasts,
+                Severity::Info,
+                if enabled {
+                    "Shape recognition enabled"
+                } else {
+                    "Shape recognition disabled"
+                },
+            );
+        } else {
+            adjust_smoothing_strength(&mut self.smoothing_strength, &event, &self.toasts);
+        }
+        ToolUpdateResult::Unmodified
+    }
+
     fn get_drawable(&self) -> Option<&dyn Drawable> {
         match &self.drawable {
             Some(d) => Some(d),
@@ -145,30 +375,187 @@ impl Tool for BrushTool {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmoothingMode {
+    /// Averages the last points, the strength is the number of points.
+    MovingAverage,
+    /// Follows the pointer on a virtual string, the strength is its length in pixels.
+    PullString,
+}
+
+impl From<command_line::SmoothingMode> for SmoothingMode {
+    fn from(mode: command_line::SmoothingMode) -> Self {
+        match mode {
+            command_line::SmoothingMode::MovingAverage => Self::MovingAverage,
+            command_line::SmoothingMode::PullString => Self::PullString,
+        }
+    }
+}
+
+/// What the pressure and velocity curves change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DynamicsTarget {
+    Width,
+    Opacity,
+    Both,
+}
+
+/// Piecewise linear mapping through the given points, constant beyond the first and the
+/// last one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve {
+    points: Vec<[f32; 2]>,
+}
+
+impl Curve {
+    pub fn new(mut points: Vec<[f32; 2]>) -> Self {
+        points.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        Self { points }
+    }
+
+    pub fn evaluate(&self, x: f32) -> f32 {
+        let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+            return 1.0;
+        };
+        if x <= first[0] {
+            return first[1];
+        }
+        for pair in self.points.windows(2) {
+            let ([x0, y0], [x1, y1]) = (pair[0], pair[1]);
+            if x <= x1 {
+                return if x1 > x0 {
+                    y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+                } else {
+                    y1
+                };
+            }
+        }
+        last[1]
+    }
+}
+
+/// Turns stylus pressure and stroke velocity into a factor for every point of a stroke.
+#[derive(Debug, Clone)]
+struct Dynamics {
+    pressure: Option<Curve>,
+    velocity: Option<Curve>,
+    target: DynamicsTarget,
+    last_point: Option<(Vec2D, Instant)>,
+}
+
+impl Dynamics {
+    /// Returns `None` unless a pressure or velocity curve is configured.
+    fn from_config() -> Option<Self> {
+        let config = APP_CONFIG.read();
+        let pressure = config.pressure_curve().cloned();
+        let velocity = config.velocity_curve().cloned();
+        (pressure.is_some() || velocity.is_some()).then_some(Self {
+            pressure,
+            velocity,
+            target: config.brush_dynamics(),
+            last_point: None,
+        })
+    }
+
+    /// Without a reported pressure, e.g. for a mouse, only the velocity counts.
+    fn factor(&mut self, point: Vec2D, pressure: Option<f32>) -> f32 {
+        let now = Instant::now();
+        let mut factor = 1.0;
+        if let (Some(curve), Some(pressure)) = (&self.pressure, pressure) {
+            factor *= curve.evaluate(pressure);
+        }
+        if let Some(curve) = &self.velocity {
+            // pixels per second
+            let speed = self.last_point.map_or(0.0, |(last, time)| {
+                point.distance_to(&last) / now.duration_since(time).as_secs_f32().max(0.001)
+            });
+            factor *= curve.evaluate(speed);
+        }
+        self.last_point = Some((point, now));
+        factor
+    }
+}
+
+/// Changes the smoothing strength of freehand tools with Ctrl+[ and Ctrl+].
+pub fn adjust_smoothing_strength(
+    strength: &mut Option<usize>,
+    event: &KeyEventMsg,
+    toasts: &relm4::Sender<ToastsInput>,
+) {
+    if event.modifier != ModifierType::CONTROL_MASK {
+        return;
+    }
+    let current = strength.unwrap_or_else(|| APP_CONFIG.read().brush_smooth_history_size());
+    let value = match event.key {
+        Key::bracketleft => current.saturating_sub(1),
+        Key::bracketright => current + 1,
+        _ => return,
+    };
+    *strength = Some(value);
+    toasts::report(
+        toasts,
+        Severity::Info,
+        &format!("Smoothing strength: {value}"),
+    );
+}
+
 #[derive(Debug, Clone)]
 pub struct Smoother {
     history: Vec<Vec2D>, // last N raw inputs
     smoothed_point: Option<Vec2D>,
-    max_history: usize,
+    mode: SmoothingMode,
+    strength: usize,
     last_update: Option<Instant>,
 }
 
 impl Smoother {
-    pub fn new(max_history: usize) -> Self {
+    /// Uses the configured strength unless `strength` overrides it.
+    pub fn new(strength: Option<usize>) -> Self {
+        let config = APP_CONFIG.read();
+        let strength = strength.unwrap_or(config.brush_smooth_history_size());
         Self {
-            history: Vec::with_capacity(max_history + 1),
+            history: Vec::with_capacity(strength + 1),
             smoothed_point: None,
-            max_history,
+            mode: config.brush_smoothing(),
+            strength,
             last_update: None,
         }
     }
 
     pub fn update(&mut self, raw: Vec2D) -> Vec2D {
-        if self.max_history == 0 {
+        if self.strength == 0 {
             return raw;
         }
+        match self.mode {
+            SmoothingMode::MovingAverage => self.moving_average(raw),
+            SmoothingMode::PullString => self.pull_string(raw),
+        }
+    }
+
+    /// Only moves once the pointer is further away than the string length, and then just
+    /// as far as needed to keep the string taut.
+    fn pull_string(&mut self, raw: Vec2D) -> Vec2D {
+        let length = self.strength as f32;
+        let smoothed = match self.smoothed_point {
+            Some(prev) => {
+                let distance = raw.distance_to(&prev);
+                if distance > length {
+                    prev + (raw - prev) * ((distance - length) / distance)
+                } else {
+                    prev
+                }
+            }
+            None => raw,
+        };
+        self.smoothed_point = Some(smoothed);
+        smoothed
+    }
+
+    fn moving_average(&mut self, raw: Vec2D) -> Vec2D {
         // Add to history
-        if self.history.len() >= self.max_history {
+        if self.history.len() >= self.strength {
             self.history.remove(0);
         }
         self.history.push(raw);
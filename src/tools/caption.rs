@@ -0,0 +1,144 @@
+use std::{cell::Cell, fmt::Write};
+
+use anyhow::Result;
+use femtovg::{Color, FontId, Paint, Path};
+
+use crate::{
+    configuration::CaptionCorner,
+    math::Vec2D,
+    style::{self, Style},
+};
+
+use super::{Drawable, Tools};
+
+/// Text such as the date or the user, kept in a corner of an area of the image.
+#[derive(Clone, Debug)]
+pub struct Caption {
+    text: String,
+    corner: CaptionCorner,
+    // the crop or the whole image when the caption was added, moves with the caption
+    area: (Vec2D, Vec2D),
+    style: Style,
+    measured_bounds: Cell<Option<(Vec2D, Vec2D)>>,
+}
+
+impl Caption {
+    pub fn new(text: String, corner: CaptionCorner, area: (Vec2D, Vec2D), style: Style) -> Self {
+        Self {
+            text,
+            corner,
+            area,
+            style,
+            measured_bounds: Cell::new(None),
+        }
+    }
+
+    /// Replaces `{user}` and `{hostname}` in `template` and formats the rest as a date
+    /// with chrono, e.g. `%Y-%m-%d %H:%M`. Invalid date formats are kept as they are.
+    pub fn expand_template(template: &str) -> String {
+        let user = std::env::var("USER").unwrap_or_default();
+        let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname")
+            .map(|name| name.trim().to_string())
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_default();
+        let template = template
+            .replace("{user}", &user)
+            .replace("{hostname}", &hostname);
+
+        let mut text = String::new();
+        match write!(text, "{}", chrono::Local::now().format(&template)) {
+            Ok(()) => text,
+            Err(_) => template,
+        }
+    }
+
+    // top left corner of text of `size`, away from the edges of the area by `margin`
+    fn position(&self, size: Vec2D, margin: f32) -> Vec2D {
+        let (pos, area) = self.area;
+        let x = match self.corner {
+            CaptionCorner::TopLeft | CaptionCorner::BottomLeft => pos.x + margin,
+            CaptionCorner::TopRight | CaptionCorner::BottomRight => {
+                pos.x + area.x - size.x - margin
+            }
+        };
+        let y = match self.corner {
+            CaptionCorner::TopLeft | CaptionCorner::TopRight => pos.y + margin,
+            CaptionCorner::BottomLeft | CaptionCorner::BottomRight => {
+                pos.y + area.y - size.y - margin
+            }
+        };
+        Vec2D::new(x, y)
+    }
+}
+
+impl Drawable for Caption {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        font: FontId,
+        _bounds: (Vec2D, Vec2D),
+    ) -> Result<()> {
+        let mut paint: Paint = self.style.into();
+        paint.set_font(&[font]);
+        paint.set_text_baseline(femtovg::Baseline::Top);
+
+        let lines: Vec<&str> = self.text.lines().collect();
+        let mut width: f32 = 0.0;
+        let mut line_height: f32 = 0.0;
+        for line in &lines {
+            let metrics = canvas.measure_text(0.0, 0.0, line, &paint)?;
+            width = width.max(metrics.width());
+            line_height = line_height.max(metrics.height());
+        }
+        let size = Vec2D::new(width, line_height * lines.len() as f32);
+        let padding = line_height * 0.3;
+        let pos = self.position(size, padding * 2.0);
+
+        // filled captions get a label behind them, as the ruler does
+        if self.style.fill {
+            let background: Color = self.style.color.into();
+            // https://en.wikipedia.org/wiki/Luma_(video)
+            let luminance = 0.2126 * background.r + 0.7152 * background.g + 0.0722 * background.b;
+            paint.set_color(if luminance > 0.5 {
+                Color::black()
+            } else {
+                Color::white()
+            });
+
+            let mut path = Path::new();
+            path.rounded_rect(
+                pos.x - padding,
+                pos.y - padding,
+                size.x + 2.0 * padding,
+                size.y + 2.0 * padding,
+                padding,
+            );
+            canvas.fill_path(&path, &Paint::color(background));
+        }
+
+        for (i, line) in lines.iter().enumerate() {
+            canvas.fill_text(pos.x, pos.y + i as f32 * line_height, line, &paint)?;
+        }
+        self.measured_bounds.set(Some((pos, size)));
+        Ok(())
+    }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.measured_bounds.get()
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.area.0 += offset;
+        if let Some((pos, size)) = self.measured_bounds.get() {
+            self.measured_bounds.set(Some((pos + offset, size)));
+        }
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Text
+    }
+
+    fn color(&self) -> Option<style::Color> {
+        Some(self.style.color)
+    }
+}
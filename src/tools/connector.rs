@@ -0,0 +1,205 @@
+use anyhow::Result;
+use femtovg::FontId;
+use relm4::gtk::gdk::Key;
+
+use crate::{
+    math::{self, Vec2D},
+    sketch_board::{KeyEventMsg, MouseEventMsg, MouseEventType},
+    style::Style,
+};
+
+use super::{Arrow, Drawable, DrawableClone, Tool, ToolUpdateResult, Tools};
+
+#[derive(Clone, Copy, Debug)]
+struct End {
+    // where the end was last drawn, kept in case the annotation goes away
+    pos: Vec2D,
+    // id of the annotation the end is attached to
+    anchor: Option<usize>,
+}
+
+/// An arrow between two annotations that follows them when they are moved.
+#[derive(Clone, Copy, Debug)]
+pub struct Connector {
+    start: End,
+    end: Option<End>,
+    style: Style,
+}
+
+// the point where the line from the center of `rect` towards `target` leaves it
+fn border_point(rect: (Vec2D, Vec2D), target: Vec2D) -> Vec2D {
+    let (pos, size) = math::rect_ensure_positive_size(rect.0, rect.1);
+    let half = size * 0.5;
+    let center = pos + half;
+    let direction = target - center;
+    let scale = [(half.x, direction.x), (half.y, direction.y)]
+        .into_iter()
+        .filter(|(_, d)| *d != 0.0)
+        .map(|(h, d)| h / d.abs())
+        .fold(1.0f32, f32::min);
+    center + direction * scale
+}
+
+impl Drawable for Connector {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        font: FontId,
+        bounds: (Vec2D, Vec2D),
+    ) -> Result<()> {
+        let Some(end) = self.end else {
+            return Ok(());
+        };
+        Arrow::new(self.start.pos, end.pos, self.style).draw(canvas, font, bounds)
+    }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.end
+            .map(|end| math::rect_around_points(&[self.start.pos, end.pos]))
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        // attached ends stay with their annotations
+        for end in [Some(&mut self.start), self.end.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            if end.anchor.is_none() {
+                end.pos += offset;
+            }
+        }
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Connector
+    }
+
+    fn color(&self) -> Option<crate::style::Color> {
+        Some(self.style.color)
+    }
+
+    fn attach(
+        &self,
+        bounds: &dyn Fn(usize) -> Option<(Vec2D, Vec2D)>,
+    ) -> Option<Box<dyn Drawable>> {
+        let end = self.end?;
+        let start_bounds = self.start.anchor.and_then(bounds);
+        let end_bounds = end.anchor.and_then(bounds);
+        if start_bounds.is_none() && end_bounds.is_none() {
+            return None;
+        }
+
+        let center = |rect: Option<(Vec2D, Vec2D)>, fallback: Vec2D| {
+            rect.map_or(fallback, |(pos, size)| pos + size * 0.5)
+        };
+        let start_center = center(start_bounds, self.start.pos);
+        let end_center = center(end_bounds, end.pos);
+
+        let mut attached = *self;
+        if let Some(rect) = start_bounds {
+            attached.start.pos = border_point(rect, end_center);
+        }
+        if let (Some(rect), Some(end)) = (end_bounds, &mut attached.end) {
+            end.pos = border_point(rect, start_center);
+        }
+        Some(attached.clone_box())
+    }
+}
+
+#[derive(Default)]
+pub struct ConnectorTool {
+    connector: Option<Connector>,
+    // annotation under the pointer, set by the sketch board before each event
+    hovered: Option<usize>,
+    style: Style,
+    input_enabled: bool,
+}
+
+impl ConnectorTool {
+    /// Where the connector being drawn starts.
+    pub fn start(&self) -> Option<Vec2D> {
+        self.connector.map(|c| c.start.pos)
+    }
+
+    pub fn set_hovered(&mut self, anchor: Option<usize>) {
+        self.hovered = anchor;
+    }
+}
+
+impl Tool for ConnectorTool {
+    fn input_enabled(&self) -> bool {
+        self.input_enabled
+    }
+
+    fn set_input_enabled(&mut self, value: bool) {
+        self.input_enabled = value;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Connector
+    }
+
+    fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
+        if event.pans() {
+            return ToolUpdateResult::Unmodified;
+        }
+        match event.type_ {
+            MouseEventType::BeginDrag => {
+                self.connector = Some(Connector {
+                    start: End {
+                        pos: event.pos,
+                        anchor: self.hovered,
+                    },
+                    end: None,
+                    style: self.style,
+                });
+
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::UpdateDrag | MouseEventType::EndDrag => {
+                let Some(c) = &mut self.connector else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                if event.pos == Vec2D::zero() {
+                    if event.type_ == MouseEventType::EndDrag {
+                        self.connector = None;
+                        return ToolUpdateResult::Redraw;
+                    }
+                    return ToolUpdateResult::Unmodified;
+                }
+                // an annotation can't be connected to itself
+                let anchor = self.hovered.filter(|&a| c.start.anchor != Some(a));
+                c.end = Some(End {
+                    pos: c.start.pos + event.pos,
+                    anchor,
+                });
+                if event.type_ == MouseEventType::UpdateDrag {
+                    return ToolUpdateResult::Redraw;
+                }
+
+                let result = c.clone_box();
+                self.connector = None;
+                ToolUpdateResult::Commit(result)
+            }
+            _ => ToolUpdateResult::Unmodified,
+        }
+    }
+
+    fn handle_key_event(&mut self, event: KeyEventMsg) -> ToolUpdateResult {
+        if event.key == Key::Escape && self.connector.is_some() {
+            self.connector = None;
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        }
+    }
+
+    fn handle_style_event(&mut self, style: Style) -> ToolUpdateResult {
+        self.style = style;
+        ToolUpdateResult::Unmodified
+    }
+
+    fn get_drawable(&self) -> Option<&dyn Drawable> {
+        self.connector.as_ref().map(|c| c as &dyn Drawable)
+    }
+}
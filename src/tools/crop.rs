@@ -2,11 +2,17 @@ use std::f32::consts::PI;
 
 use crate::{
     math::{self, Vec2D},
-    sketch_board::{KeyEventMsg, MouseButton, MouseEventMsg, MouseEventType},
+    sketch_board::{KeyEventMsg, MouseEventMsg, MouseEventType},
 };
 use anyhow::Result;
 use femtovg::{Color, Paint, Path};
-use relm4::{factory::Position, gtk::{gdk::Key, graphene::Vec2}};
+use relm4::{
+    factory::Position,
+    gtk::{
+        gdk::{Key, ModifierType},
+        graphene::Vec2,
+    },
+};
 
 use super::{Drawable, Tool, ToolUpdateResult, Tools};
 
@@ -15,6 +21,8 @@ pub struct Crop {
     pos: Vec2D,
     size: Vec2D,
     active: bool,
+    // whether the crop may extend beyond the image, enlarging the canvas
+    extend: bool,
 }
 
 #[derive(Default)]
@@ -33,6 +41,7 @@ impl Crop {
             pos,
             size: Vec2D::zero(),
             active: true,
+            extend: false,
         }
     }
 
@@ -63,6 +72,15 @@ impl Crop {
         math::rect_ensure_positive_size(self.pos, self.size)
     }
 
+    pub fn extends_canvas(&self) -> bool {
+        self.extend
+    }
+
+    /// Whether both crops cover the same area, regardless of their handles being shown.
+    pub fn same_area(&self, other: &Crop) -> bool {
+        self.pos == other.pos && self.size == other.size && self.extend == other.extend
+    }
+
     fn get_handle_pos(crop_pos: Vec2D, crop_size: Vec2D, handle: CropHandle) -> Vec2D {
         match handle {
             CropHandle::TopLeftCorner => crop_pos,
@@ -144,6 +162,10 @@ impl Drawable for Crop {
         canvas.restore();
         Ok(())
     }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Crop
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -182,10 +204,32 @@ impl CropTool {
         }
     }
 
+    pub fn set_crop(&mut self, pos: Vec2D, size: Vec2D) {
+        self.crop = Some(Crop {
+            pos,
+            size,
+            active: true,
+            extend: false,
+        });
+        self.action = None;
+    }
+
     pub fn clear_crop(&mut self) {
         self.crop = None;
         self.action = None;
     }
+
+    /// Whether the crop is being drawn, moved or resized.
+    pub fn is_changing(&self) -> bool {
+        self.action.is_some()
+    }
+
+    /// Replaces the crop with one from the undo history, keeping the handles as they are.
+    pub fn restore_crop(&mut self, crop: Option<Crop>) {
+        let active = self.crop.as_ref().is_some_and(|c| c.active);
+        self.crop = crop.map(|c| Crop { active, ..c });
+        self.action = None;
+    }
 }
 
 impl CropHandle {
@@ -273,6 +317,14 @@ impl CropTool {
         crop.size = br - tl;
     }
 
+    // holding ctrl while changing the crop allows it to grow beyond the image,
+    // alt is taken by edge snapping
+    fn update_extend(&mut self, modifier: ModifierType) {
+        if let Some(c) = &mut self.crop {
+            c.extend = modifier.contains(ModifierType::CONTROL_MASK);
+        }
+    }
+
     fn begin_drag(&mut self, pos: Vec2D) -> ToolUpdateResult {
         match &self.crop {
             None => {
@@ -389,6 +441,10 @@ impl Tool for CropTool {
                 }
             }
         }
+        if event.key == Key::BackSpace && self.crop.is_some() {
+            self.clear_crop();
+            return ToolUpdateResult::Redraw;
+        }
         // No crop exists or crop is inactive - let event bubble to global handler
         ToolUpdateResult::Unmodified
     }
@@ -396,21 +452,25 @@ impl Tool for CropTool {
     fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
         match event.type_ {
             MouseEventType::BeginDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
-                self.begin_drag(event.pos)
+                let result = self.begin_drag(event.pos);
+                self.update_extend(event.modifier);
+                result
             }
             MouseEventType::EndDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
+                self.update_extend(event.modifier);
                 self.end_drag(event.pos)
             }
             MouseEventType::UpdateDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
+                self.update_extend(event.modifier);
                 self.update_drag(event.pos)
             }
             _ => ToolUpdateResult::Unmodified,
@@ -1,10 +1,11 @@
 use anyhow::Result;
 use femtovg::{FontId, Path};
-use relm4::gtk::gdk::{Key, ModifierType};
+use relm4::gtk::gdk::Key;
 
 use crate::{
-    math::Vec2D,
-    sketch_board::{MouseButton, MouseEventMsg, MouseEventType},
+    annotations::AnnotationSpec,
+    math::{self, Vec2D},
+    sketch_board::{MouseEventMsg, MouseEventType},
     style::Style,
 };
 
@@ -59,43 +60,58 @@ impl Drawable for Ellipse {
 
         Ok(())
     }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.radii.map(|radii| (self.middle - radii, radii * 2.0))
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.origin += offset;
+        self.middle += offset;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Ellipse
+    }
+
+    fn color(&self) -> Option<crate::style::Color> {
+        Some(self.style.color)
+    }
+
+    fn to_spec(&self) -> Option<AnnotationSpec> {
+        let radii = self.radii?;
+        let (start, end) = (self.middle - radii, self.middle + radii);
+        Some(AnnotationSpec::Ellipse {
+            start: [start.x, start.y],
+            end: [end.x, end.y],
+            style: self.style.into(),
+        })
+    }
 }
 
 impl Ellipse {
-    fn calculate_shape(&mut self, event: &MouseEventMsg) {
-        self.centered = event.modifier & ModifierType::ALT_MASK == ModifierType::ALT_MASK;
-        match event.modifier & (ModifierType::ALT_MASK | ModifierType::SHIFT_MASK) {
-            v if v == ModifierType::ALT_MASK | ModifierType::SHIFT_MASK => {
-                self.middle = self.origin;
-                let max_size = event.pos.x.abs().max(event.pos.y.abs());
-                self.radii = Some(Vec2D {
-                    x: max_size * event.pos.x.signum(),
-                    y: max_size * event.pos.y.signum(),
-                });
-            }
-            ModifierType::ALT_MASK => {
-                self.middle = self.origin;
-                self.radii = Some(event.pos);
-            }
-            ModifierType::SHIFT_MASK => {
-                let max_size = (event.pos.x / 2.0).abs().max((event.pos.y / 2.0).abs());
-                self.radii = Some(Vec2D {
-                    x: max_size * event.pos.x.signum(),
-                    y: max_size * event.pos.y.signum(),
-                });
-                self.middle.x = self.origin.x + max_size * event.pos.x.signum();
-                self.middle.y = self.origin.y + max_size * event.pos.y.signum();
-            }
-            _ => {
-                self.radii = Some(Vec2D {
-                    x: event.pos.x / 2.0,
-                    y: event.pos.y / 2.0,
-                });
-                self.middle.x = self.origin.x + event.pos.x / 2.0;
-                self.middle.y = self.origin.y + event.pos.y / 2.0;
-            }
+    pub fn new(middle: Vec2D, radii: Vec2D, style: Style) -> Self {
+        Self {
+            origin: middle - radii,
+            middle,
+            radii: Some(radii),
+            style,
+            centered: false,
+            finishing: true,
         }
     }
+
+    fn calculate_shape(&mut self, event: &MouseEventMsg) {
+        self.centered = event.from_center();
+        let (top_left, size) = math::rect_from_drag(
+            self.origin,
+            event.pos,
+            event.from_center(),
+            event.proportional(),
+        );
+        self.radii = Some(size * 0.5);
+        self.middle = top_left + size * 0.5;
+    }
 }
 
 #[derive(Default)]
@@ -121,7 +137,7 @@ impl Tool for EllipseTool {
     fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
         match event.type_ {
             MouseEventType::BeginDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
@@ -138,7 +154,7 @@ impl Tool for EllipseTool {
                 ToolUpdateResult::Redraw
             }
             MouseEventType::EndDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
@@ -159,7 +175,7 @@ impl Tool for EllipseTool {
                 }
             }
             MouseEventType::UpdateDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
@@ -0,0 +1,206 @@
+use anyhow::Result;
+use femtovg::{FontId, Paint, Path};
+
+use crate::{
+    math::Vec2D,
+    sketch_board::{MouseButton, MouseEventMsg, MouseEventType},
+    style::Style,
+};
+
+use super::{Drawable, Tool, ToolUpdateResult, Tools};
+
+/// Erases parts of freehand strokes. The tool only tracks the pointer, the sketch board
+/// applies the erasure to the annotations under it.
+#[derive(Default)]
+pub struct EraserTool {
+    cursor: Option<EraserCursor>,
+    // start of the current drag, drag updates are relative to it
+    drag_start: Option<Vec2D>,
+    style: Style,
+    input_enabled: bool,
+}
+
+/// Outline of the eraser at its current position.
+#[derive(Clone, Copy, Debug)]
+struct EraserCursor {
+    center: Vec2D,
+    radius: f32,
+}
+
+impl EraserTool {
+    /// Center of the eraser while dragging, in image coordinates.
+    pub fn position(&self) -> Option<Vec2D> {
+        self.cursor.map(|c| c.center)
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.style
+            .size
+            .to_line_width(self.style.annotation_size_factor)
+            * 2.0
+    }
+}
+
+impl Drawable for EraserCursor {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        _font: FontId,
+        _bounds: (Vec2D, Vec2D),
+    ) -> Result<()> {
+        let mut path = Path::new();
+        path.circle(self.center.x, self.center.y, self.radius);
+        canvas.stroke_path(
+            &path,
+            &Paint::color(femtovg::Color::rgba(0, 0, 0, 153)).with_line_width(1.0),
+        );
+        Ok(())
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Eraser
+    }
+}
+
+impl Tool for EraserTool {
+    fn input_enabled(&self) -> bool {
+        self.input_enabled
+    }
+
+    fn set_input_enabled(&mut self, value: bool) {
+        self.input_enabled = value;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Eraser
+    }
+
+    fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
+        if event.button != MouseButton::Primary {
+            return ToolUpdateResult::Unmodified;
+        }
+        let radius = self.radius();
+        match event.type_ {
+            MouseEventType::BeginDrag => {
+                self.drag_start = Some(event.pos);
+                self.cursor = Some(EraserCursor {
+                    center: event.pos,
+                    radius,
+                });
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::UpdateDrag => {
+                let Some(start) = self.drag_start else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                self.cursor = Some(EraserCursor {
+                    center: start + event.pos,
+                    radius,
+                });
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::EndDrag => {
+                self.drag_start = None;
+                self.cursor = None;
+                ToolUpdateResult::Redraw
+            }
+            _ => ToolUpdateResult::Unmodified,
+        }
+    }
+
+    fn handle_deactivated(&mut self) -> ToolUpdateResult {
+        self.drag_start = None;
+        self.cursor = None;
+        ToolUpdateResult::Redraw
+    }
+
+    fn handle_style_event(&mut self, style: Style) -> ToolUpdateResult {
+        self.style = style;
+        ToolUpdateResult::Unmodified
+    }
+
+    fn get_drawable(&self) -> Option<&dyn Drawable> {
+        self.cursor.as_ref().map(|c| c as &dyn Drawable)
+    }
+}
+
+/// A stroke after erasing, in the same representation as [`erase_stroke`] takes.
+#[derive(Debug, Default)]
+pub struct ErasedStroke {
+    pub points: Vec<Vec2D>,
+    pub factors: Vec<f32>,
+    pub breaks: Vec<usize>,
+}
+
+/// Removes the parts of a stroke closer than `radius` to `center` and splits it where they
+/// were removed. `points` are absolute, `breaks` holds the indices that start a new
+/// sub-path and `factors` is either empty or holds a width factor for every point.
+/// Long segments are subdivided first, so erasing also works in between points.
+/// Returns `None` if nothing was removed.
+pub fn erase_stroke(
+    points: &[Vec2D],
+    factors: &[f32],
+    breaks: &[usize],
+    center: Vec2D,
+    radius: f32,
+) -> Option<ErasedStroke> {
+    let step = (radius / 2.0).max(1.0);
+    let factor = |i: usize| factors.get(i).copied();
+
+    let mut erased = false;
+    let mut sub_paths: Vec<Vec<(Vec2D, Option<f32>)>> = Vec::new();
+    let mut current: Vec<(Vec2D, Option<f32>)> = Vec::new();
+
+    for (i, &point) in points.iter().enumerate() {
+        let starts_sub_path = i == 0 || breaks.contains(&i);
+        if starts_sub_path && !current.is_empty() {
+            sub_paths.push(std::mem::take(&mut current));
+        }
+
+        let samples = if starts_sub_path {
+            vec![(point, factor(i))]
+        } else {
+            let previous = points[i - 1];
+            let count = (point.distance_to(&previous) / step).ceil().max(1.0) as usize;
+            (1..=count)
+                .map(|k| {
+                    let t = k as f32 / count as f32;
+                    let factor = factor(i - 1)
+                        .zip(factor(i))
+                        .map(|(from, to)| from + (to - from) * t);
+                    (previous + (point - previous) * t, factor)
+                })
+                .collect()
+        };
+
+        for sample in samples {
+            if sample.0.distance_to(&center) <= radius {
+                erased = true;
+                if !current.is_empty() {
+                    sub_paths.push(std::mem::take(&mut current));
+                }
+            } else {
+                current.push(sample);
+            }
+        }
+    }
+    if !erased {
+        return None;
+    }
+    if !current.is_empty() {
+        sub_paths.push(current);
+    }
+
+    // single points left over would be invisible
+    let mut result = ErasedStroke::default();
+    for sub_path in sub_paths.into_iter().filter(|s| s.len() >= 2) {
+        if !result.points.is_empty() {
+            result.breaks.push(result.points.len());
+        }
+        for (point, factor) in sub_path {
+            result.points.push(point);
+            result.factors.extend(factor);
+        }
+    }
+    Some(result)
+}
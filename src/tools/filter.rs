@@ -0,0 +1,262 @@
+use std::fmt::Display;
+
+use anyhow::Result;
+use femtovg::{imgref::ImgVec, rgb::RGBA8, Color, FontId, ImageFlags, Paint, Path};
+use relm4::gtk::gdk::Key;
+use serde_derive::Deserialize;
+
+use crate::{
+    command_line,
+    configuration::APP_CONFIG,
+    math::{self, Vec2D},
+    sketch_board::{KeyEventMsg, MouseEventMsg, MouseEventType},
+};
+
+use super::{blur, CachedImage, Drawable, DrawableClone, Tool, ToolUpdateResult, Tools};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterKind {
+    Grayscale,
+    Invert,
+    Sepia,
+}
+
+impl From<command_line::FilterKind> for FilterKind {
+    fn from(kind: command_line::FilterKind) -> Self {
+        match kind {
+            command_line::FilterKind::Grayscale => Self::Grayscale,
+            command_line::FilterKind::Invert => Self::Invert,
+            command_line::FilterKind::Sepia => Self::Sepia,
+        }
+    }
+}
+
+impl Display for FilterKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Grayscale => write!(f, "Grayscale"),
+            Self::Invert => write!(f, "Invert"),
+            Self::Sepia => write!(f, "Sepia"),
+        }
+    }
+}
+
+impl FilterKind {
+    const ALL: [FilterKind; 3] = [Self::Grayscale, Self::Invert, Self::Sepia];
+
+    /// The next filter in the list, or the previous one if not `forward`.
+    pub fn cycle(self, forward: bool) -> Self {
+        super::cycle(&Self::ALL, self, forward)
+    }
+
+    fn apply(self, image: &mut ImgVec<RGBA8>) {
+        for pixel in image.buf_mut().iter_mut() {
+            let [r, g, b] = [pixel.r, pixel.g, pixel.b].map(f32::from);
+            let [r, g, b] = match self {
+                Self::Grayscale => [0.2126 * r + 0.7152 * g + 0.0722 * b; 3],
+                Self::Invert => [255.0 - r, 255.0 - g, 255.0 - b],
+                Self::Sepia => [
+                    0.393 * r + 0.769 * g + 0.189 * b,
+                    0.349 * r + 0.686 * g + 0.168 * b,
+                    0.272 * r + 0.534 * g + 0.131 * b,
+                ],
+            };
+            pixel.r = r.min(255.0) as u8;
+            pixel.g = g.min(255.0) as u8;
+            pixel.b = b.min(255.0) as u8;
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Filter {
+    origin: Vec2D,
+    top_left: Vec2D,
+    size: Option<Vec2D>,
+    kind: FilterKind,
+    editing: bool,
+    cached_image: CachedImage,
+}
+
+impl Filter {
+    fn update(&mut self, event: &MouseEventMsg) {
+        let (top_left, size) = math::rect_from_drag(
+            self.origin,
+            event.pos,
+            event.from_center(),
+            event.proportional(),
+        );
+        self.top_left = top_left;
+        self.size = Some(size);
+    }
+}
+
+impl Drawable for Filter {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        _font: FontId,
+        bounds: (Vec2D, Vec2D),
+    ) -> Result<()> {
+        let Some(size) = self.size else {
+            return Ok(());
+        };
+        let (pos, size) = math::rect_ensure_in_bounds(
+            math::rect_ensure_positive_size(self.top_left, size),
+            bounds,
+        );
+        if self.editing {
+            let mut path = Path::new();
+            path.rect(pos.x, pos.y, size.x, size.y);
+            canvas.fill_path(&path, &Paint::color(Color::rgba(0, 0, 0, 153)));
+            return Ok(());
+        }
+        if size.x <= 0.0 || size.y <= 0.0 {
+            return Ok(());
+        }
+
+        canvas.save();
+        canvas.flush();
+
+        let image_id = self.cached_image.get_or_create(|| {
+            let mut image = blur::capture(canvas, pos, size)?;
+            self.kind.apply(&mut image);
+            Ok(canvas.create_image(image.as_ref(), ImageFlags::empty())?)
+        })?;
+
+        let mut path = Path::new();
+        path.rect(pos.x, pos.y, size.x, size.y);
+        canvas.fill_path(
+            &path,
+            &Paint::image(image_id, pos.x, pos.y, size.x, size.y, 0f32, 1f32),
+        );
+        canvas.restore();
+        Ok(())
+    }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.size.map(|size| (self.top_left, size))
+    }
+
+    fn memory_size(&self) -> usize {
+        std::mem::size_of_val(self) + self.cached_image.memory_size(self.bounds())
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.origin += offset;
+        self.top_left += offset;
+        // the cached image shows what was underneath the old position
+        self.cached_image.clear();
+    }
+
+    fn clear_cache(&self) {
+        self.cached_image.clear();
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Filter
+    }
+}
+
+pub struct FilterTool {
+    filter: Option<Filter>,
+    kind: FilterKind,
+    input_enabled: bool,
+}
+
+impl Default for FilterTool {
+    fn default() -> Self {
+        Self {
+            filter: None,
+            kind: APP_CONFIG.read().region_filter(),
+            input_enabled: false,
+        }
+    }
+}
+
+impl FilterTool {
+    /// Switches to the next or previous filter, which also applies to the region being drawn.
+    pub fn cycle_kind(&mut self, forward: bool) -> FilterKind {
+        self.kind = self.kind.cycle(forward);
+        if let Some(filter) = &mut self.filter {
+            filter.kind = self.kind;
+        }
+        self.kind
+    }
+}
+
+impl Tool for FilterTool {
+    fn input_enabled(&self) -> bool {
+        self.input_enabled
+    }
+
+    fn set_input_enabled(&mut self, value: bool) {
+        self.input_enabled = value;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Filter
+    }
+
+    fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
+        if event.pans() {
+            return ToolUpdateResult::Unmodified;
+        }
+        match event.type_ {
+            MouseEventType::BeginDrag => {
+                self.filter = Some(Filter {
+                    origin: event.pos,
+                    top_left: event.pos,
+                    size: None,
+                    kind: self.kind,
+                    editing: true,
+                    cached_image: CachedImage::default(),
+                });
+
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::UpdateDrag => {
+                let Some(f) = &mut self.filter else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                if event.pos == Vec2D::zero() {
+                    return ToolUpdateResult::Unmodified;
+                }
+                f.update(&event);
+
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::EndDrag => {
+                let Some(f) = &mut self.filter else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                if event.pos == Vec2D::zero() {
+                    self.filter = None;
+                    return ToolUpdateResult::Redraw;
+                }
+                f.update(&event);
+                f.editing = false;
+
+                let result = f.clone_box();
+                self.filter = None;
+
+                ToolUpdateResult::Commit(result)
+            }
+            _ => ToolUpdateResult::Unmodified,
+        }
+    }
+
+    fn handle_key_event(&mut self, event: KeyEventMsg) -> ToolUpdateResult {
+        if event.key == Key::Escape && self.filter.is_some() {
+            self.filter = None;
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        }
+    }
+
+    fn get_drawable(&self) -> Option<&dyn Drawable> {
+        self.filter.as_ref().map(|f| f as &dyn Drawable)
+    }
+}
@@ -0,0 +1,296 @@
+use std::fmt::Display;
+
+use anyhow::Result;
+use femtovg::{Color, FontId, Paint, Path};
+use relm4::gtk::gdk::Key;
+
+use crate::{
+    math::{self, Vec2D},
+    sketch_board::{KeyEventMsg, MouseEventMsg, MouseEventType},
+    style::Style,
+};
+
+use super::{Drawable, DrawableClone, Tool, ToolUpdateResult, Tools};
+
+// control point distance for approximating a quarter ellipse with a cubic bezier
+const KAPPA: f32 = 0.552_284_8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowchartShape {
+    Process,
+    Decision,
+    Data,
+    Database,
+}
+
+impl Display for FlowchartShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Process => write!(f, "Process"),
+            Self::Decision => write!(f, "Decision"),
+            Self::Data => write!(f, "Data"),
+            Self::Database => write!(f, "Database"),
+        }
+    }
+}
+
+impl FlowchartShape {
+    const ALL: [FlowchartShape; 4] = [Self::Process, Self::Decision, Self::Data, Self::Database];
+
+    /// The next shape in the list, or the previous one if not `forward`.
+    pub fn cycle(self, forward: bool) -> Self {
+        super::cycle(&Self::ALL, self, forward)
+    }
+
+    /// Outline of the shape filling the rectangle at `pos` with `size`, and for the
+    /// database the front rim of its top, which is only stroked. Processes get the corners
+    /// of rectangles.
+    fn paths(self, pos: Vec2D, size: Vec2D, corner_radius: f32) -> (Path, Option<Path>) {
+        let (x, y, w, h) = (pos.x, pos.y, size.x, size.y);
+        let mut path = Path::new();
+        match self {
+            Self::Process => {
+                path.rounded_rect(x, y, w, h, corner_radius);
+                (path, None)
+            }
+            Self::Decision => {
+                path.move_to(x + w * 0.5, y);
+                path.line_to(x + w, y + h * 0.5);
+                path.line_to(x + w * 0.5, y + h);
+                path.line_to(x, y + h * 0.5);
+                path.close();
+                (path, None)
+            }
+            Self::Data => {
+                let skew = (w * 0.2).min(h * 0.5);
+                path.move_to(x + skew, y);
+                path.line_to(x + w, y);
+                path.line_to(x + w - skew, y + h);
+                path.line_to(x, y + h);
+                path.close();
+                (path, None)
+            }
+            Self::Database => {
+                let (rx, ry) = (w * 0.5, (h * 0.15).min(w * 0.25));
+                let cx = x + rx;
+                let bottom = y + h - ry;
+
+                path.move_to(x, y + ry);
+                path.line_to(x, bottom);
+                path.bezier_to(x, bottom + ry * KAPPA, cx - rx * KAPPA, y + h, cx, y + h);
+                path.bezier_to(
+                    cx + rx * KAPPA,
+                    y + h,
+                    x + w,
+                    bottom + ry * KAPPA,
+                    x + w,
+                    bottom,
+                );
+                path.line_to(x + w, y + ry);
+                path.bezier_to(x + w, y + ry - ry * KAPPA, cx + rx * KAPPA, y, cx, y);
+                path.bezier_to(cx - rx * KAPPA, y, x, y + ry - ry * KAPPA, x, y + ry);
+                path.close();
+
+                let mut rim = Path::new();
+                rim.move_to(x, y + ry);
+                rim.bezier_to(
+                    x,
+                    y + ry + ry * KAPPA,
+                    cx - rx * KAPPA,
+                    y + 2.0 * ry,
+                    cx,
+                    y + 2.0 * ry,
+                );
+                rim.bezier_to(
+                    cx + rx * KAPPA,
+                    y + 2.0 * ry,
+                    x + w,
+                    y + ry + ry * KAPPA,
+                    x + w,
+                    y + ry,
+                );
+                (path, Some(rim))
+            }
+        }
+    }
+}
+
+/// A flowchart node, drawn into the rectangle spanned by the drag.
+#[derive(Clone, Copy, Debug)]
+pub struct FlowchartNode {
+    origin: Vec2D,
+    top_left: Vec2D,
+    size: Option<Vec2D>,
+    shape: FlowchartShape,
+    style: Style,
+}
+
+impl FlowchartNode {
+    fn update(&mut self, event: &MouseEventMsg) {
+        let (top_left, size) = math::rect_from_drag(
+            self.origin,
+            event.pos,
+            event.from_center(),
+            event.proportional(),
+        );
+        self.top_left = top_left;
+        self.size = Some(size);
+    }
+}
+
+impl Drawable for FlowchartNode {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        _font: FontId,
+        _bounds: (Vec2D, Vec2D),
+    ) -> Result<()> {
+        let Some(size) = self.size else {
+            return Ok(());
+        };
+        let (pos, size) = math::rect_ensure_positive_size(self.top_left, size);
+        let (path, rim) = self.shape.paths(pos, size, self.style.corner_radius);
+
+        canvas.save();
+        if self.style.fill {
+            canvas.fill_path(&path, &self.style.into());
+            if let Some(rim) = rim {
+                // the rim would vanish in the fill otherwise
+                let paint: Paint = self.style.into();
+                canvas.stroke_path(&rim, &paint.with_color(Color::rgbaf(1.0, 1.0, 1.0, 0.6)));
+            }
+        } else {
+            canvas.stroke_path(&path, &self.style.into());
+            if let Some(rim) = rim {
+                canvas.stroke_path(&rim, &self.style.into());
+            }
+        }
+        canvas.restore();
+
+        Ok(())
+    }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.size.map(|size| (self.top_left, size))
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.origin += offset;
+        self.top_left += offset;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Flowchart
+    }
+
+    fn color(&self) -> Option<crate::style::Color> {
+        Some(self.style.color)
+    }
+}
+
+pub struct FlowchartTool {
+    node: Option<FlowchartNode>,
+    shape: FlowchartShape,
+    style: Style,
+    input_enabled: bool,
+}
+
+impl Default for FlowchartTool {
+    fn default() -> Self {
+        Self {
+            node: None,
+            shape: FlowchartShape::Process,
+            style: Style::default(),
+            input_enabled: false,
+        }
+    }
+}
+
+impl FlowchartTool {
+    /// Switches to the next or previous shape, which also applies to the node being drawn.
+    pub fn cycle_shape(&mut self, forward: bool) -> FlowchartShape {
+        self.shape = self.shape.cycle(forward);
+        if let Some(node) = &mut self.node {
+            node.shape = self.shape;
+        }
+        self.shape
+    }
+}
+
+impl Tool for FlowchartTool {
+    fn input_enabled(&self) -> bool {
+        self.input_enabled
+    }
+
+    fn set_input_enabled(&mut self, value: bool) {
+        self.input_enabled = value;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Flowchart
+    }
+
+    fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
+        if event.pans() {
+            return ToolUpdateResult::Unmodified;
+        }
+        match event.type_ {
+            MouseEventType::BeginDrag => {
+                self.node = Some(FlowchartNode {
+                    origin: event.pos,
+                    top_left: event.pos,
+                    size: None,
+                    shape: self.shape,
+                    style: self.style,
+                });
+
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::UpdateDrag => {
+                let Some(n) = &mut self.node else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                if event.pos == Vec2D::zero() {
+                    return ToolUpdateResult::Unmodified;
+                }
+                n.update(&event);
+
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::EndDrag => {
+                let Some(n) = &mut self.node else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                if event.pos == Vec2D::zero() {
+                    self.node = None;
+                    return ToolUpdateResult::Redraw;
+                }
+                n.update(&event);
+
+                let result = n.clone_box();
+                self.node = None;
+
+                ToolUpdateResult::Commit(result)
+            }
+            _ => ToolUpdateResult::Unmodified,
+        }
+    }
+
+    fn handle_key_event(&mut self, event: KeyEventMsg) -> ToolUpdateResult {
+        if event.key == Key::Escape && self.node.is_some() {
+            self.node = None;
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        }
+    }
+
+    fn handle_style_event(&mut self, style: Style) -> ToolUpdateResult {
+        self.style = style;
+        ToolUpdateResult::Unmodified
+    }
+
+    fn get_drawable(&self) -> Option<&dyn Drawable> {
+        self.node.as_ref().map(|n| n as &dyn Drawable)
+    }
+}
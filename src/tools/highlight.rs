@@ -10,12 +10,16 @@ use crate::{
     command_line,
     configuration::APP_CONFIG,
     math::{self, Vec2D},
-    sketch_board::{MouseButton, MouseEventMsg, MouseEventType},
+    sketch_board::{MouseEventMsg, MouseEventType},
     style::Style,
     tools::DrawableClone,
+    ui::toasts::ToastsInput,
 };
 
-use super::{Drawable, Tool, ToolUpdateResult, Tools};
+use super::{
+    brush::{adjust_smoothing_strength, Smoother},
+    eraser, Drawable, Tool, ToolUpdateResult, Tools,
+};
 
 const HIGHLIGHT_OPACITY: f64 = 0.4;
 
@@ -37,6 +41,7 @@ impl From<command_line::Highlighters> for Highlighters {
 
 #[derive(Clone, Debug)]
 struct BlockHighlight {
+    origin: Vec2D,
     top_left: Vec2D,
     size: Option<Vec2D>,
 }
@@ -44,9 +49,24 @@ struct BlockHighlight {
 #[derive(Clone, Debug)]
 struct FreehandHighlight {
     points: Vec<Vec2D>,
+    // indices of the points that start a new sub-path after erasing
+    breaks: Vec<usize>,
+    smoother: Smoother,
     shift_pressed: bool,
 }
 
+impl FreehandHighlight {
+    // all points but the first are relative to the first one
+    fn absolute_points(&self) -> Option<Vec<Vec2D>> {
+        let first = *self.points.first()?;
+        Some(
+            std::iter::once(first)
+                .chain(self.points[1..].iter().map(|&p| first + p))
+                .collect(),
+        )
+    }
+}
+
 #[derive(Clone, Debug)]
 struct Highlighter<T> {
     data: T,
@@ -61,16 +81,20 @@ impl Highlight for Highlighter<FreehandHighlight> {
     fn highlight(&self, canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>) -> Result<()> {
         canvas.save();
 
-        let mut path = Path::new();
-        let first = self
-            .data
-            .points
-            .first()
-            .expect("should exist at least one point in highlight instance.");
+        // everything may have been erased
+        let Some(first) = self.data.points.first() else {
+            canvas.restore();
+            return Ok(());
+        };
 
+        let mut path = Path::new();
         path.move_to(first.x, first.y);
-        for p in self.data.points.iter().skip(1) {
-            path.line_to(first.x + p.x, first.y + p.y);
+        for (i, p) in self.data.points.iter().enumerate().skip(1) {
+            if self.data.breaks.contains(&i) {
+                path.move_to(first.x + p.x, first.y + p.y);
+            } else {
+                path.line_to(first.x + p.x, first.y + p.y);
+            }
         }
 
         let mut paint = Paint::color(femtovg::Color::rgba(
@@ -129,11 +153,26 @@ enum HighlightKind {
     Freehand(Highlighter<FreehandHighlight>),
 }
 
-#[derive(Default, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct HighlightTool {
     highlighter: Option<HighlightKind>,
     style: Style,
     input_enabled: bool,
+    // overrides the configured smoothing strength once adjusted
+    smoothing_strength: Option<usize>,
+    toasts: relm4::Sender<ToastsInput>,
+}
+
+impl HighlightTool {
+    pub fn new(toasts: &relm4::Sender<ToastsInput>) -> Self {
+        Self {
+            highlighter: None,
+            style: Style::default(),
+            input_enabled: false,
+            smoothing_strength: None,
+            toasts: toasts.clone(),
+        }
+    }
 }
 
 impl Drawable for HighlightKind {
@@ -148,6 +187,72 @@ impl Drawable for HighlightKind {
             HighlightKind::Freehand(highlighter) => highlighter.highlight(canvas),
         }
     }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        match self {
+            HighlightKind::Block(highlighter) => highlighter
+                .data
+                .size
+                .map(|size| (highlighter.data.top_left, size)),
+            HighlightKind::Freehand(highlighter) => highlighter
+                .data
+                .absolute_points()
+                .map(|points| math::rect_around_points(&points)),
+        }
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        match self {
+            HighlightKind::Block(highlighter) => {
+                highlighter.data.origin += offset;
+                highlighter.data.top_left += offset;
+            }
+            HighlightKind::Freehand(highlighter) => {
+                if let Some(first) = highlighter.data.points.first_mut() {
+                    *first += offset;
+                }
+            }
+        }
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Highlight
+    }
+
+    fn erase(&self, center: Vec2D, radius: f32) -> Option<Box<dyn Drawable>> {
+        let HighlightKind::Freehand(highlighter) = self else {
+            return None;
+        };
+        let width = highlighter
+            .style
+            .size
+            .to_highlight_width(highlighter.style.annotation_size_factor);
+        let erased = eraser::erase_stroke(
+            &highlighter.data.absolute_points()?,
+            &[],
+            &highlighter.data.breaks,
+            center,
+            radius + width / 2.0,
+        )?;
+
+        let mut highlighter = highlighter.clone();
+        let first = erased.points.first().copied().unwrap_or_default();
+        highlighter.data.points = erased
+            .points
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| if i == 0 { p } else { p - first })
+            .collect();
+        highlighter.data.breaks = erased.breaks;
+        Some(Box::new(HighlightKind::Freehand(highlighter)))
+    }
+
+    fn color(&self) -> Option<crate::style::Color> {
+        match self {
+            HighlightKind::Block(highlighter) => Some(highlighter.style.color),
+            HighlightKind::Freehand(highlighter) => Some(highlighter.style.color),
+        }
+    }
 }
 
 impl Tool for HighlightTool {
@@ -169,7 +274,7 @@ impl Tool for HighlightTool {
         let primary_highlighter = APP_CONFIG.read().primary_highlighter();
         match event.type_ {
             MouseEventType::BeginDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
                 // There exists two types of highlighting modes currently: freehand, block
@@ -183,6 +288,7 @@ impl Tool for HighlightTool {
                         self.highlighter =
                             Some(HighlightKind::Block(Highlighter::<BlockHighlight> {
                                 data: BlockHighlight {
+                                    origin: event.pos,
                                     top_left: event.pos,
                                     size: None,
                                 },
@@ -197,6 +303,8 @@ impl Tool for HighlightTool {
                             Some(HighlightKind::Freehand(Highlighter::<FreehandHighlight> {
                                 data: FreehandHighlight {
                                     points: vec![event.pos],
+                                    breaks: Vec::new(),
+                                    smoother: Smoother::new(self.smoothing_strength),
                                     shift_pressed,
                                 },
                                 style: self.style,
@@ -207,7 +315,7 @@ impl Tool for HighlightTool {
                 ToolUpdateResult::Redraw
             }
             MouseEventType::UpdateDrag | MouseEventType::EndDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
@@ -219,15 +327,16 @@ impl Tool for HighlightTool {
                     HighlightKind::Block(highlighter) => {
                         // When shift is pressed when using the block highlighter, it transforms
                         // the area into a perfect square (in the direction they intended).
-                        if shift_pressed {
-                            let max_size = event.pos.x.abs().max(event.pos.y.abs());
-                            highlighter.data.size = Some(Vec2D {
-                                x: max_size * event.pos.x.signum(),
-                                y: max_size * event.pos.y.signum(),
-                            });
-                        } else {
-                            highlighter.data.size = Some(event.pos);
-                        };
+                        // Holding ALT grows the area from its starting point outward, CTRL
+                        // already switches between block and freehand mode.
+                        let (top_left, size) = math::rect_from_drag(
+                            highlighter.data.origin,
+                            event.pos,
+                            event.modifier.intersects(ModifierType::ALT_MASK),
+                            event.proportional(),
+                        );
+                        highlighter.data.top_left = top_left;
+                        highlighter.data.size = Some(size);
                         ToolUpdateResult::Redraw
                     }
                     HighlightKind::Freehand(highlighter) => {
@@ -271,7 +380,8 @@ impl Tool for HighlightTool {
                             let snapped_pos = event.pos.sub(last).snapped_vector_15deg().add(last);
                             highlighter.data.points.push(snapped_pos);
                         } else {
-                            highlighter.data.points.push(event.pos);
+                            let point = highlighter.data.smoother.update(event.pos);
+                            highlighter.data.points.push(point);
                         }
 
                         highlighter.data.shift_pressed = shift_pressed;
@@ -295,6 +405,7 @@ impl Tool for HighlightTool {
             self.highlighter = None;
             return ToolUpdateResult::Redraw;
         }
+        adjust_smoothing_strength(&mut self.smoothing_strength, &event, &self.toasts);
         ToolUpdateResult::Unmodified
     }
 
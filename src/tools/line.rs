@@ -3,8 +3,9 @@ use femtovg::{FontId, Path};
 use relm4::gtk::gdk::{Key, ModifierType};
 
 use crate::{
-    math::Vec2D,
-    sketch_board::{MouseButton, MouseEventMsg, MouseEventType},
+    annotations::AnnotationSpec,
+    math::{self, Vec2D},
+    sketch_board::{MouseEventMsg, MouseEventType},
     style::Style,
 };
 
@@ -24,6 +25,16 @@ pub struct Line {
     style: Style,
 }
 
+impl Line {
+    pub fn new(start: Vec2D, end: Vec2D, style: Style) -> Self {
+        Self {
+            start,
+            direction: Some(end - start),
+            style,
+        }
+    }
+}
+
 impl Drawable for Line {
     fn draw(
         &self,
@@ -48,6 +59,32 @@ impl Drawable for Line {
 
         Ok(())
     }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.direction
+            .map(|direction| math::rect_around_points(&[self.start, self.start + direction]))
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.start += offset;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Line
+    }
+
+    fn color(&self) -> Option<crate::style::Color> {
+        Some(self.style.color)
+    }
+
+    fn to_spec(&self) -> Option<AnnotationSpec> {
+        let end = self.start + self.direction?;
+        Some(AnnotationSpec::Line {
+            start: [self.start.x, self.start.y],
+            end: [end.x, end.y],
+            style: self.style.into(),
+        })
+    }
 }
 
 impl Tool for LineTool {
@@ -62,7 +99,7 @@ impl Tool for LineTool {
     fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
         match event.type_ {
             MouseEventType::BeginDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
@@ -76,7 +113,7 @@ impl Tool for LineTool {
                 ToolUpdateResult::Redraw
             }
             MouseEventType::EndDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
@@ -101,7 +138,7 @@ impl Tool for LineTool {
                 }
             }
             MouseEventType::UpdateDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
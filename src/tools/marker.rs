@@ -102,6 +102,30 @@ impl Drawable for Marker {
     fn handle_redo(&mut self) {
         *self.tool_next_number.borrow_mut() = self.number + 1;
     }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        // the circle grows with the number, the text size is a close enough estimate
+        let radius = self
+            .style
+            .size
+            .to_text_size(self.style.annotation_size_factor) as f32;
+        Some((
+            self.pos - Vec2D::new(radius, radius),
+            Vec2D::new(radius, radius) * 2.0,
+        ))
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.pos += offset;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Marker
+    }
+
+    fn color(&self) -> Option<crate::style::Color> {
+        Some(self.style.color)
+    }
 }
 
 impl Tool for MarkerTool {
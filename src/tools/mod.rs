@@ -4,10 +4,11 @@ use std::{
     collections::HashMap,
     fmt::{Debug, Display},
     rc::Rc,
+    sync::Mutex,
 };
 
 use anyhow::Result;
-use femtovg::{renderer::OpenGl, Canvas, FontId};
+use femtovg::{renderer::OpenGl, Canvas, FontId, ImageId};
 use gdk_pixbuf::{
     glib::{Variant, VariantTy},
     prelude::{StaticVariantType, ToVariant},
@@ -18,22 +19,40 @@ use relm4::gtk::{self, IMMulticontext};
 use serde_derive::Deserialize;
 
 use crate::{
+    annotations::AnnotationSpec,
     command_line,
     math::Vec2D,
     sketch_board::{InputEvent, KeyEventMsg, MouseEventMsg, TextEventMsg},
-    style::Style,
+    style::{Color, Style},
+    ui::toasts::ToastsInput,
 };
 
 mod arrow;
 mod blur;
 mod brush;
+mod caption;
+mod connector;
 mod crop;
 mod ellipse;
+mod eraser;
+mod filter;
+mod flowchart;
 mod highlight;
 mod line;
 mod marker;
+mod ocr;
+mod patch;
+mod perspective;
+mod picture;
 mod pointer;
+mod polygon;
+mod qr;
+mod recolor;
 mod rectangle;
+mod ruler;
+mod shape_recognition;
+mod stamp;
+mod table;
 mod text;
 
 pub enum ToolEvent {
@@ -144,6 +163,143 @@ pub trait Drawable: DrawableClone + Debug {
         -> Result<()>;
     fn handle_undo(&mut self) {}
     fn handle_redo(&mut self) {}
+
+    /// The tool that created the drawable.
+    fn get_tool_type(&self) -> Tools;
+
+    /// Name of the drawable in the layers panel.
+    fn display_name(&self) -> &'static str {
+        self.get_tool_type().display_name()
+    }
+
+    fn color(&self) -> Option<Color> {
+        None
+    }
+
+    /// Position and size of the area covered by the drawable, in image coordinates.
+    /// Drawables without bounds can't be selected.
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        None
+    }
+
+    /// Moves the drawable by `offset`.
+    fn translate(&mut self, offset: Vec2D) {
+        let _ = offset;
+    }
+
+    /// Returns a copy scaled by `factor` around its center, or `None` if it can't be
+    /// resized.
+    fn scale(&self, factor: f32) -> Option<Box<dyn Drawable>> {
+        let _ = factor;
+        None
+    }
+
+    /// Returns a copy without the parts within `radius` of `center`, in image coordinates,
+    /// or `None` if nothing is removed. Only freehand strokes can be erased partially, a
+    /// copy without bounds is erased completely.
+    fn erase(&self, center: Vec2D, radius: f32) -> Option<Box<dyn Drawable>> {
+        let _ = (center, radius);
+        None
+    }
+
+    /// Returns a copy placed against the current `bounds` of the annotations it is
+    /// attached to, or `None` if it isn't attached to any.
+    fn attach(
+        &self,
+        bounds: &dyn Fn(usize) -> Option<(Vec2D, Vec2D)>,
+    ) -> Option<Box<dyn Drawable>> {
+        let _ = bounds;
+        None
+    }
+
+    /// Describes the drawable to save it as a snippet, `None` if that isn't supported.
+    fn to_spec(&self) -> Option<AnnotationSpec> {
+        None
+    }
+
+    /// Approximate memory held by the drawable in bytes, including pixels it cached. The
+    /// undo history is limited by it.
+    fn memory_size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    /// Drops pixels it cached of the image underneath, after that image changed.
+    fn clear_cache(&self) {}
+}
+
+// images of cleared or dropped caches, a drawable has no canvas to delete them with then
+static RELEASED_IMAGES: Mutex<Vec<ImageId>> = Mutex::new(Vec::new());
+
+/// An image a drawable creates on the first draw and reuses after, e.g. the blurred area.
+/// A copy starts without the image, so every image is deleted exactly once.
+#[derive(Debug, Default)]
+pub struct CachedImage(RefCell<Option<ImageId>>);
+
+impl CachedImage {
+    /// Returns the image, created by `create` unless there is one already.
+    pub fn get_or_create(&self, create: impl FnOnce() -> Result<ImageId>) -> Result<ImageId> {
+        if let Some(image_id) = *self.0.borrow() {
+            return Ok(image_id);
+        }
+        let image_id = create()?;
+        self.0.replace(Some(image_id));
+        Ok(image_id)
+    }
+
+    pub fn is_some(&self) -> bool {
+        self.0.borrow().is_some()
+    }
+
+    /// Drops the image, it is deleted with the next frame.
+    pub fn clear(&self) {
+        if let Some(image_id) = self.0.take() {
+            release_image(image_id);
+        }
+    }
+
+    /// Memory of the image of the area `bounds` in RGBA, once it was created.
+    pub fn memory_size(&self, bounds: Option<(Vec2D, Vec2D)>) -> usize {
+        match bounds {
+            Some((_, size)) if self.is_some() => (size.x.abs() * size.y.abs()) as usize * 4,
+            _ => 0,
+        }
+    }
+}
+
+impl Clone for CachedImage {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl Drop for CachedImage {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// Deletes `image_id` with the next frame, for owners without access to the canvas.
+pub fn release_image(image_id: ImageId) {
+    RELEASED_IMAGES.lock().unwrap().push(image_id);
+}
+
+/// Deletes the images released since the last call.
+pub fn delete_released_images(canvas: &mut Canvas<OpenGl>) {
+    for image_id in RELEASED_IMAGES.lock().unwrap().drain(..) {
+        canvas.delete_image(image_id);
+    }
+}
+
+/// The entry of `all` after `current`, or before it if not `forward`, wrapping around.
+pub fn cycle<T: Copy + PartialEq>(all: &[T], current: T, forward: bool) -> T {
+    let count = all.len();
+    let index = all.iter().position(|v| *v == current).unwrap_or_default();
+    let index = if forward {
+        (index + 1) % count
+    } else {
+        (index + count - 1) % count
+    };
+    all[index]
 }
 
 #[derive(Debug)]
@@ -153,16 +309,29 @@ pub enum ToolUpdateResult {
     Unmodified,
 }
 
-pub use arrow::ArrowTool;
+pub use arrow::{Arrow, ArrowTool};
 pub use blur::BlurTool;
-pub use crop::CropTool;
-pub use ellipse::EllipseTool;
+pub use brush::{Curve, DynamicsTarget, SmoothingMode};
+pub use caption::Caption;
+pub use connector::ConnectorTool;
+pub use crop::{Crop, CropTool};
+pub use ellipse::{Ellipse, EllipseTool};
+pub use eraser::EraserTool;
+pub use filter::{FilterKind, FilterTool};
+pub use flowchart::FlowchartTool;
 pub use highlight::{HighlightTool, Highlighters};
-pub use line::LineTool;
-pub use rectangle::RectangleTool;
-pub use text::TextTool;
-
-use self::{brush::BrushTool, marker::MarkerTool, pointer::PointerTool};
+pub use line::{Line, LineTool};
+pub use ocr::OcrTool;
+pub use perspective::PerspectiveTool;
+pub use picture::Picture;
+pub use rectangle::{Rectangle, RectangleTool};
+pub use text::{Text, TextTool};
+
+use self::{
+    brush::BrushTool, marker::MarkerTool, patch::PatchTool, pointer::PointerTool,
+    polygon::PolygonTool, qr::QrTool, recolor::RecolorTool, ruler::RulerTool, stamp::StampTool,
+    table::TableTool,
+};
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -178,6 +347,19 @@ pub enum Tools {
     Blur = 8,
     Highlight = 9,
     Brush = 10,
+    Ruler = 11,
+    Eraser = 12,
+    Patch = 13,
+    Recolor = 14,
+    Filter = 15,
+    Perspective = 16,
+    Polygon = 17,
+    Stamp = 18,
+    Connector = 19,
+    Flowchart = 20,
+    Table = 21,
+    Qr = 22,
+    Ocr = 23,
 }
 
 impl Tools {
@@ -194,6 +376,19 @@ impl Tools {
             Tools::Marker => "Numbered Marker",
             Tools::Blur => "Blur",
             Tools::Highlight => "Highlight",
+            Tools::Ruler => "Ruler",
+            Tools::Eraser => "Eraser",
+            Tools::Patch => "Patch",
+            Tools::Recolor => "Recolor",
+            Tools::Filter => "Filter",
+            Tools::Perspective => "Perspective",
+            Tools::Polygon => "Polygon",
+            Tools::Stamp => "Stamp",
+            Tools::Connector => "Connector",
+            Tools::Flowchart => "Flowchart",
+            Tools::Table => "Table",
+            Tools::Qr => "QR Code",
+            Tools::Ocr => "Text Selection",
         }
     }
 }
@@ -213,17 +408,37 @@ impl Display for Tools {
             Self::Blur => write!(f, "blur"),
             Self::Highlight => write!(f, "highlight"),
             Self::Brush => write!(f, "brush"),
+            Self::Ruler => write!(f, "ruler"),
+            Self::Eraser => write!(f, "eraser"),
+            Self::Patch => write!(f, "patch"),
+            Self::Recolor => write!(f, "recolor"),
+            Self::Filter => write!(f, "filter"),
+            Self::Perspective => write!(f, "perspective"),
+            Self::Polygon => write!(f, "polygon"),
+            Self::Stamp => write!(f, "stamp"),
+            Self::Connector => write!(f, "connector"),
+            Self::Flowchart => write!(f, "flowchart"),
+            Self::Table => write!(f, "table"),
+            Self::Qr => write!(f, "qr"),
+            Self::Ocr => write!(f, "ocr"),
         }
     }
 }
 
 pub struct ToolsManager {
     tools: HashMap<Tools, Rc<RefCell<dyn Tool>>>,
+    blur_tool: Rc<RefCell<BlurTool>>,
     crop_tool: Rc<RefCell<CropTool>>,
+    eraser_tool: Rc<RefCell<EraserTool>>,
+    filter_tool: Rc<RefCell<FilterTool>>,
+    perspective_tool: Rc<RefCell<PerspectiveTool>>,
+    connector_tool: Rc<RefCell<ConnectorTool>>,
+    flowchart_tool: Rc<RefCell<FlowchartTool>>,
+    ocr_tool: Rc<RefCell<OcrTool>>,
 }
 
 impl ToolsManager {
-    pub fn new() -> Self {
+    pub fn new(toasts: &relm4::Sender<ToastsInput>) -> Self {
         let mut tools: HashMap<Tools, Rc<RefCell<dyn Tool>>> = HashMap::new();
         //tools.insert(Tools::Crop, Rc::new(RefCell::new(CropTool::default())));
         tools.insert(
@@ -241,21 +456,57 @@ impl ToolsManager {
             Rc::new(RefCell::new(EllipseTool::default())),
         );
         tools.insert(Tools::Text, Rc::new(RefCell::new(TextTool::default())));
-        tools.insert(Tools::Blur, Rc::new(RefCell::new(BlurTool::default())));
         tools.insert(
             Tools::Highlight,
-            Rc::new(RefCell::new(HighlightTool::default())),
+            Rc::new(RefCell::new(HighlightTool::new(toasts))),
         );
         tools.insert(Tools::Marker, Rc::new(RefCell::new(MarkerTool::default())));
-        tools.insert(Tools::Brush, Rc::new(RefCell::new(BrushTool::default())));
+        tools.insert(Tools::Brush, Rc::new(RefCell::new(BrushTool::new(toasts))));
+        tools.insert(Tools::Ruler, Rc::new(RefCell::new(RulerTool::default())));
+        tools.insert(Tools::Patch, Rc::new(RefCell::new(PatchTool::default())));
+        tools.insert(
+            Tools::Recolor,
+            Rc::new(RefCell::new(RecolorTool::default())),
+        );
+        tools.insert(
+            Tools::Polygon,
+            Rc::new(RefCell::new(PolygonTool::default())),
+        );
+        tools.insert(Tools::Stamp, Rc::new(RefCell::new(StampTool::default())));
+        tools.insert(Tools::Table, Rc::new(RefCell::new(TableTool::default())));
+        tools.insert(Tools::Qr, Rc::new(RefCell::new(QrTool::default())));
 
+        let blur_tool = Rc::new(RefCell::new(BlurTool::default()));
         let crop_tool = Rc::new(RefCell::new(CropTool::default()));
-        Self { tools, crop_tool }
+        let eraser_tool = Rc::new(RefCell::new(EraserTool::default()));
+        let filter_tool = Rc::new(RefCell::new(FilterTool::default()));
+        let perspective_tool = Rc::new(RefCell::new(PerspectiveTool::default()));
+        let connector_tool = Rc::new(RefCell::new(ConnectorTool::default()));
+        let flowchart_tool = Rc::new(RefCell::new(FlowchartTool::default()));
+        let ocr_tool = Rc::new(RefCell::new(OcrTool::default()));
+        Self {
+            tools,
+            blur_tool,
+            crop_tool,
+            eraser_tool,
+            filter_tool,
+            perspective_tool,
+            connector_tool,
+            flowchart_tool,
+            ocr_tool,
+        }
     }
 
     pub fn get(&self, tool: &Tools) -> Rc<RefCell<dyn Tool>> {
         match tool {
+            Tools::Blur => self.blur_tool.clone(),
             Tools::Crop => self.crop_tool.clone(),
+            Tools::Eraser => self.eraser_tool.clone(),
+            Tools::Filter => self.filter_tool.clone(),
+            Tools::Perspective => self.perspective_tool.clone(),
+            Tools::Connector => self.connector_tool.clone(),
+            Tools::Flowchart => self.flowchart_tool.clone(),
+            Tools::Ocr => self.ocr_tool.clone(),
             _ => self
                 .tools
                 .get(tool)
@@ -266,9 +517,37 @@ impl ToolsManager {
         }
     }
 
+    pub fn get_blur_tool(&self) -> Rc<RefCell<BlurTool>> {
+        self.blur_tool.clone()
+    }
+
     pub fn get_crop_tool(&self) -> Rc<RefCell<CropTool>> {
         self.crop_tool.clone()
     }
+
+    pub fn get_eraser_tool(&self) -> Rc<RefCell<EraserTool>> {
+        self.eraser_tool.clone()
+    }
+
+    pub fn get_filter_tool(&self) -> Rc<RefCell<FilterTool>> {
+        self.filter_tool.clone()
+    }
+
+    pub fn get_perspective_tool(&self) -> Rc<RefCell<PerspectiveTool>> {
+        self.perspective_tool.clone()
+    }
+
+    pub fn get_connector_tool(&self) -> Rc<RefCell<ConnectorTool>> {
+        self.connector_tool.clone()
+    }
+
+    pub fn get_flowchart_tool(&self) -> Rc<RefCell<FlowchartTool>> {
+        self.flowchart_tool.clone()
+    }
+
+    pub fn get_ocr_tool(&self) -> Rc<RefCell<OcrTool>> {
+        self.ocr_tool.clone()
+    }
 }
 
 impl StaticVariantType for Tools {
@@ -297,6 +576,19 @@ impl FromVariant for Tools {
             8 => Some(Tools::Blur),
             9 => Some(Tools::Highlight),
             10 => Some(Tools::Brush),
+            11 => Some(Tools::Ruler),
+            12 => Some(Tools::Eraser),
+            13 => Some(Tools::Patch),
+            14 => Some(Tools::Recolor),
+            15 => Some(Tools::Filter),
+            16 => Some(Tools::Perspective),
+            17 => Some(Tools::Polygon),
+            18 => Some(Tools::Stamp),
+            19 => Some(Tools::Connector),
+            20 => Some(Tools::Flowchart),
+            21 => Some(Tools::Table),
+            22 => Some(Tools::Qr),
+            23 => Some(Tools::Ocr),
             _ => None,
         })
     }
@@ -316,6 +608,19 @@ impl From<command_line::Tools> for Tools {
             command_line::Tools::Blur => Self::Blur,
             command_line::Tools::Highlight => Self::Highlight,
             command_line::Tools::Brush => Self::Brush,
+            command_line::Tools::Ruler => Self::Ruler,
+            command_line::Tools::Eraser => Self::Eraser,
+            command_line::Tools::Patch => Self::Patch,
+            command_line::Tools::Recolor => Self::Recolor,
+            command_line::Tools::Filter => Self::Filter,
+            command_line::Tools::Perspective => Self::Perspective,
+            command_line::Tools::Polygon => Self::Polygon,
+            command_line::Tools::Stamp => Self::Stamp,
+            command_line::Tools::Connector => Self::Connector,
+            command_line::Tools::Flowchart => Self::Flowchart,
+            command_line::Tools::Table => Self::Table,
+            command_line::Tools::Qr => Self::Qr,
+            command_line::Tools::Ocr => Self::Ocr,
         }
     }
 }
@@ -0,0 +1,189 @@
+use anyhow::Result;
+use femtovg::{Color, FontId, Paint, Path};
+use relm4::gtk::gdk::Key;
+
+use crate::{
+    math::{self, Vec2D},
+    ocr::Word,
+    sketch_board::{KeyEventMsg, MouseButton, MouseEventMsg, MouseEventType},
+};
+
+use super::{Drawable, Tool, ToolUpdateResult, Tools};
+
+/// Recognized words with the ones between `selection` highlighted, in reading order.
+#[derive(Clone, Debug, Default)]
+pub struct TextOverlay {
+    words: Vec<Word>,
+    selection: Option<(usize, usize)>,
+}
+
+impl TextOverlay {
+    fn selected(&self) -> &[Word] {
+        match self.selection {
+            Some((a, b)) => &self.words[a.min(b)..=a.max(b)],
+            None => &[],
+        }
+    }
+
+    // the word under `pos`, or else the closest one
+    fn word_at(&self, pos: Vec2D) -> Option<usize> {
+        let distance = |word: &Word| {
+            if math::rect_contains((word.pos, word.size), pos, 0.0) {
+                0.0
+            } else {
+                (word.pos + word.size * 0.5).distance_to(&pos)
+            }
+        };
+        self.words
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+            .map(|(i, _)| i)
+    }
+}
+
+impl Drawable for TextOverlay {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        _font: FontId,
+        _bounds: (Vec2D, Vec2D),
+    ) -> Result<()> {
+        let scale = canvas.transform().average_scale();
+
+        let mut outlines = Path::new();
+        for word in &self.words {
+            outlines.rect(word.pos.x, word.pos.y, word.size.x, word.size.y);
+        }
+        let mut selected = Path::new();
+        for word in self.selected() {
+            selected.rect(word.pos.x, word.pos.y, word.size.x, word.size.y);
+        }
+
+        canvas.stroke_path(
+            &outlines,
+            &Paint::color(Color::rgba(53, 132, 228, 96)).with_line_width(1.0 / scale),
+        );
+        canvas.fill_path(&selected, &Paint::color(Color::rgba(53, 132, 228, 96)));
+        Ok(())
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Ocr
+    }
+}
+
+/// Selects recognized text by dragging over it, the sketch board runs the recognition and
+/// copies the selected text.
+#[derive(Default)]
+pub struct OcrTool {
+    // `None` until the recognition finished
+    overlay: Option<TextOverlay>,
+    requested: bool,
+    copy_pending: bool,
+    drag_start: Option<Vec2D>,
+    input_enabled: bool,
+}
+
+impl OcrTool {
+    /// Returns true the first time it is called after the image changed.
+    pub fn request_words(&mut self) -> bool {
+        !std::mem::replace(&mut self.requested, true)
+    }
+
+    pub fn set_words(&mut self, words: Vec<Word>) {
+        self.overlay = Some(TextOverlay {
+            words,
+            selection: None,
+        });
+    }
+
+    /// Forgets the recognized words, e.g. because the image changed.
+    pub fn clear_words(&mut self) {
+        self.overlay = None;
+        self.requested = false;
+    }
+
+    /// The text selected by the last finished drag, lines separated by newlines.
+    pub fn take_selected_text(&mut self) -> Option<String> {
+        if !std::mem::take(&mut self.copy_pending) {
+            return None;
+        }
+        let selected = self.overlay.as_ref()?.selected();
+        let mut text = String::new();
+        for (i, word) in selected.iter().enumerate() {
+            if i > 0 {
+                text.push(if word.same_line(&selected[i - 1]) {
+                    ' '
+                } else {
+                    '\n'
+                });
+            }
+            text.push_str(&word.text);
+        }
+        Some(text).filter(|t| !t.is_empty())
+    }
+}
+
+impl Tool for OcrTool {
+    fn input_enabled(&self) -> bool {
+        self.input_enabled
+    }
+
+    fn set_input_enabled(&mut self, value: bool) {
+        self.input_enabled = value;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Ocr
+    }
+
+    fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
+        let Some(overlay) = &mut self.overlay else {
+            return ToolUpdateResult::Unmodified;
+        };
+        if event.button != MouseButton::Primary {
+            return ToolUpdateResult::Unmodified;
+        }
+        let pos = match event.type_ {
+            MouseEventType::BeginDrag => {
+                self.drag_start = Some(event.pos);
+                event.pos
+            }
+            MouseEventType::UpdateDrag | MouseEventType::EndDrag => {
+                let Some(start) = self.drag_start else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                start + event.pos
+            }
+            _ => return ToolUpdateResult::Unmodified,
+        };
+
+        let Some(word) = overlay.word_at(pos) else {
+            return ToolUpdateResult::Unmodified;
+        };
+        overlay.selection = match (event.type_, overlay.selection) {
+            (MouseEventType::BeginDrag, _) | (_, None) => Some((word, word)),
+            (_, Some((start, _))) => Some((start, word)),
+        };
+        if event.type_ == MouseEventType::EndDrag {
+            self.drag_start = None;
+            self.copy_pending = true;
+        }
+        ToolUpdateResult::Redraw
+    }
+
+    fn handle_key_event(&mut self, event: KeyEventMsg) -> ToolUpdateResult {
+        match &mut self.overlay {
+            Some(overlay) if event.key == Key::Escape && overlay.selection.is_some() => {
+                overlay.selection = None;
+                ToolUpdateResult::Redraw
+            }
+            _ => ToolUpdateResult::Unmodified,
+        }
+    }
+
+    fn get_drawable(&self) -> Option<&dyn Drawable> {
+        self.overlay.as_ref().map(|o| o as &dyn Drawable)
+    }
+}
@@ -0,0 +1,221 @@
+use anyhow::Result;
+use femtovg::{Color, FontId, ImageFlags, Paint, Path};
+use relm4::gtk::gdk::{Key, ModifierType};
+
+use crate::{
+    math::{self, Vec2D},
+    sketch_board::{KeyEventMsg, MouseButton, MouseEventMsg, MouseEventType},
+};
+
+use super::{blur, CachedImage, Drawable, Tool, ToolUpdateResult, Tools};
+
+/// A copy of a region of the image placed somewhere else, like a clone stamp.
+#[derive(Clone, Debug)]
+pub struct Patch {
+    // top left corner and size of the copied region
+    source: (Vec2D, Vec2D),
+    // top left corner of the copy, `None` while only the region is shown
+    target: Option<Vec2D>,
+    editing: bool,
+    cached_image: CachedImage,
+}
+
+impl Patch {
+    fn new(source: (Vec2D, Vec2D)) -> Self {
+        Self {
+            source,
+            target: None,
+            editing: true,
+            cached_image: CachedImage::default(),
+        }
+    }
+
+    // the source within the image and the target moved along with it
+    fn clipped(&self, target: Vec2D, bounds: (Vec2D, Vec2D)) -> (Vec2D, Vec2D, Vec2D) {
+        let (source_pos, source_size) =
+            math::rect_ensure_positive_size(self.source.0, self.source.1);
+        let (pos, size) = math::rect_ensure_in_bounds((source_pos, source_size), bounds);
+        (pos, size, target + (pos - source_pos))
+    }
+
+    fn draw_outline(
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        pos: Vec2D,
+        size: Vec2D,
+    ) {
+        let mut path = Path::new();
+        path.rect(pos.x, pos.y, size.x, size.y);
+        canvas.stroke_path(
+            &path,
+            &Paint::color(Color::rgba(0, 0, 0, 153)).with_line_width(1.0),
+        );
+    }
+}
+
+impl Drawable for Patch {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        _font: FontId,
+        bounds: (Vec2D, Vec2D),
+    ) -> Result<()> {
+        let (source_pos, size, target) = self.clipped(self.target.unwrap_or_default(), bounds);
+        if size.x <= 0.0 || size.y <= 0.0 {
+            return Ok(());
+        }
+
+        if self.target.is_some() {
+            canvas.save();
+            canvas.flush();
+
+            let image_id = self.cached_image.get_or_create(|| {
+                let copy = blur::capture(canvas, source_pos, size)?;
+                Ok(canvas.create_image(copy.as_ref(), ImageFlags::empty())?)
+            })?;
+
+            let mut path = Path::new();
+            path.rect(target.x, target.y, size.x, size.y);
+            canvas.fill_path(
+                &path,
+                &Paint::image(image_id, target.x, target.y, size.x, size.y, 0f32, 1f32),
+            );
+            canvas.restore();
+        }
+
+        if self.editing {
+            Self::draw_outline(canvas, source_pos, size);
+            if self.target.is_some() {
+                Self::draw_outline(canvas, target, size);
+            }
+        }
+        Ok(())
+    }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.target.map(|target| (target, self.source.1))
+    }
+
+    fn memory_size(&self) -> usize {
+        std::mem::size_of_val(self) + self.cached_image.memory_size(self.bounds())
+    }
+
+    // only the copy moves, it keeps showing the same region
+    fn translate(&mut self, offset: Vec2D) {
+        if let Some(target) = &mut self.target {
+            *target += offset;
+        }
+    }
+
+    fn clear_cache(&self) {
+        self.cached_image.clear();
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Patch
+    }
+}
+
+#[derive(Default)]
+pub struct PatchTool {
+    // the selected region, with the copy while it is placed
+    patch: Option<Patch>,
+    // start of the current drag, drag updates are relative to it
+    drag_start: Option<Vec2D>,
+    selecting: bool,
+    input_enabled: bool,
+}
+
+impl Tool for PatchTool {
+    fn input_enabled(&self) -> bool {
+        self.input_enabled
+    }
+
+    fn set_input_enabled(&mut self, value: bool) {
+        self.input_enabled = value;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Patch
+    }
+
+    fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
+        if event.button != MouseButton::Primary {
+            return ToolUpdateResult::Unmodified;
+        }
+        match event.type_ {
+            MouseEventType::BeginDrag => {
+                self.drag_start = Some(event.pos);
+                match &mut self.patch {
+                    // place a copy centered on the pointer
+                    Some(patch) if !event.modifier.contains(ModifierType::SHIFT_MASK) => {
+                        self.selecting = false;
+                        patch.target = Some(event.pos - patch.source.1 * 0.5);
+                        // the region may have changed since the last copy
+                        patch.cached_image.clear();
+                    }
+                    _ => {
+                        self.selecting = true;
+                        self.patch = Some(Patch::new((event.pos, Vec2D::zero())));
+                    }
+                }
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::UpdateDrag => {
+                let (Some(start), Some(patch)) = (self.drag_start, &mut self.patch) else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                if self.selecting {
+                    patch.source =
+                        math::rect_from_drag(start, event.pos, event.from_center(), false);
+                } else {
+                    patch.target = Some(start + event.pos - patch.source.1 * 0.5);
+                }
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::EndDrag => {
+                let Some(start) = self.drag_start.take() else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                let Some(patch) = &mut self.patch else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                if self.selecting {
+                    patch.source =
+                        math::rect_from_drag(start, event.pos, event.from_center(), false);
+                    patch.source = math::rect_ensure_positive_size(patch.source.0, patch.source.1);
+                    if patch.source.1.x < 1.0 || patch.source.1.y < 1.0 {
+                        self.patch = None;
+                    }
+                    return ToolUpdateResult::Redraw;
+                }
+
+                patch.target = Some(start + event.pos - patch.source.1 * 0.5);
+                let mut result = patch.clone();
+                result.editing = false;
+                // keep the region selected for more copies
+                patch.target = None;
+                ToolUpdateResult::Commit(Box::new(result))
+            }
+            _ => ToolUpdateResult::Unmodified,
+        }
+    }
+
+    fn handle_key_event(&mut self, event: KeyEventMsg) -> ToolUpdateResult {
+        if event.key == Key::Escape && self.patch.is_some() {
+            self.patch = None;
+            self.drag_start = None;
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        }
+    }
+
+    fn handle_deactivated(&mut self) -> ToolUpdateResult {
+        self.drag_start = None;
+        ToolUpdateResult::Unmodified
+    }
+
+    fn get_drawable(&self) -> Option<&dyn Drawable> {
+        self.patch.as_ref().map(|p| p as &dyn Drawable)
+    }
+}
@@ -0,0 +1,286 @@
+use std::f32::consts::PI;
+
+use anyhow::Result;
+use femtovg::{Color, FontId, Paint, Path};
+use gdk_pixbuf::{glib::Bytes, Colorspace, Pixbuf};
+use relm4::gtk::gdk::Key;
+
+use crate::{
+    math::{self, Vec2D},
+    sketch_board::{KeyEventMsg, MouseButton, MouseEventMsg, MouseEventType},
+};
+
+use super::{Drawable, Tool, ToolUpdateResult, Tools};
+
+const HANDLE_RADIUS: f32 = 5.0;
+const HANDLE_BORDER: f32 = 2.0;
+// how far next to a corner a drag still grabs it, in image pixels
+const HANDLE_MARGIN: f32 = 15.0;
+
+/// Four corners of the region to straighten, clockwise from the one that becomes the top
+/// left corner.
+#[derive(Clone, Copy, Debug)]
+pub struct Quad {
+    corners: [Vec2D; 4],
+}
+
+impl Quad {
+    fn from_rect(pos: Vec2D, size: Vec2D) -> Self {
+        Self {
+            corners: [
+                pos,
+                pos + Vec2D::new(size.x, 0.0),
+                pos + size,
+                pos + Vec2D::new(0.0, size.y),
+            ],
+        }
+    }
+
+    fn closest_corner(&self, pos: Vec2D, margin: f32) -> Option<usize> {
+        self.corners
+            .iter()
+            .enumerate()
+            .map(|(i, corner)| (i, corner.distance_to(&pos)))
+            .filter(|(_, distance)| *distance <= margin)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    /// Width and height of the straightened rectangle, taken from the longer of the
+    /// opposite edges.
+    fn target_size(&self) -> (i32, i32) {
+        let [tl, tr, br, bl] = self.corners;
+        let width = tl.distance_to(&tr).max(bl.distance_to(&br));
+        let height = tl.distance_to(&bl).max(tr.distance_to(&br));
+        (width.round() as i32, height.round() as i32)
+    }
+
+    /// Projective mapping from the unit square onto the quad, as coefficients
+    /// `[a, b, c, d, e, f, g, h]` of `x = (a u + b v + c) / (g u + h v + 1)` and
+    /// `y = (d u + e v + f) / (g u + h v + 1)`.
+    fn square_to_quad(&self) -> Option<[f32; 8]> {
+        let [p0, p1, p2, p3] = self.corners;
+        let d1 = p1 - p2;
+        let d2 = p3 - p2;
+        let d3 = p0 - p1 + p2 - p3;
+
+        let denominator = d1.x * d2.y - d2.x * d1.y;
+        if denominator.abs() < f32::EPSILON {
+            return None;
+        }
+        let g = (d3.x * d2.y - d2.x * d3.y) / denominator;
+        let h = (d1.x * d3.y - d3.x * d1.y) / denominator;
+
+        Some([
+            p1.x - p0.x + g * p1.x,
+            p3.x - p0.x + h * p3.x,
+            p0.x,
+            p1.y - p0.y + g * p1.y,
+            p3.y - p0.y + h * p3.y,
+            p0.y,
+            g,
+            h,
+        ])
+    }
+
+    /// Maps the quad of `image` onto an upright rectangle, sampling bilinearly.
+    /// Returns `None` if the quad is degenerate.
+    pub fn straighten(&self, image: &Pixbuf) -> Option<Pixbuf> {
+        let [a, b, c, d, e, f, g, h] = self.square_to_quad()?;
+        let (width, height) = self.target_size();
+        if width < 1 || height < 1 {
+            return None;
+        }
+
+        let channels = image.n_channels() as usize;
+        let stride = image.rowstride() as usize;
+        let source = image.read_pixel_bytes();
+        let (source_width, source_height) = (image.width() as f32, image.height() as f32);
+        let sample = |x: usize, y: usize, channel: usize| -> f32 {
+            source[y * stride + x * channels + channel] as f32
+        };
+
+        let mut pixels = vec![0u8; width as usize * height as usize * channels];
+        for (row, line) in pixels
+            .chunks_exact_mut(width as usize * channels)
+            .enumerate()
+        {
+            let v = (row as f32 + 0.5) / height as f32;
+            for (column, pixel) in line.chunks_exact_mut(channels).enumerate() {
+                let u = (column as f32 + 0.5) / width as f32;
+                let w = g * u + h * v + 1.0;
+                let x = (a * u + b * v + c) / w - 0.5;
+                let y = (d * u + e * v + f) / w - 0.5;
+                if x < 0.0 || y < 0.0 || x > source_width - 1.0 || y > source_height - 1.0 {
+                    continue;
+                }
+
+                let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+                let (x1, y1) = (
+                    (x0 + 1).min(source_width as usize - 1),
+                    (y0 + 1).min(source_height as usize - 1),
+                );
+                let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+                for (channel, value) in pixel.iter_mut().enumerate() {
+                    let top = sample(x0, y0, channel) * (1.0 - fx) + sample(x1, y0, channel) * fx;
+                    let bottom =
+                        sample(x0, y1, channel) * (1.0 - fx) + sample(x1, y1, channel) * fx;
+                    *value = (top * (1.0 - fy) + bottom * fy).round() as u8;
+                }
+            }
+        }
+
+        Some(Pixbuf::from_bytes(
+            &Bytes::from_owned(pixels),
+            Colorspace::Rgb,
+            image.has_alpha(),
+            8,
+            width,
+            height,
+            width * channels as i32,
+        ))
+    }
+}
+
+impl Drawable for Quad {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        _font: FontId,
+        _bounds: (Vec2D, Vec2D),
+    ) -> Result<()> {
+        let scale = canvas.transform().average_scale();
+
+        let mut outline = Path::new();
+        outline.move_to(self.corners[0].x, self.corners[0].y);
+        for corner in &self.corners[1..] {
+            outline.line_to(corner.x, corner.y);
+        }
+        outline.close();
+        canvas.stroke_path(
+            &outline,
+            &Paint::color(Color::rgbf(0.1, 0.1, 0.1)).with_line_width(2.0 / scale),
+        );
+
+        for corner in self.corners {
+            let mut handle = Path::new();
+            handle.arc(
+                corner.x,
+                corner.y,
+                HANDLE_RADIUS / scale,
+                0.0,
+                2.0 * PI,
+                femtovg::Solidity::Solid,
+            );
+            canvas.fill_path(&handle, &Paint::color(Color::rgbaf(0.0, 0.0, 0.0, 0.4)));
+            canvas.stroke_path(
+                &handle,
+                &Paint::color(Color::rgbf(0.9, 0.9, 0.9)).with_line_width(HANDLE_BORDER / scale),
+            );
+        }
+        Ok(())
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Perspective
+    }
+}
+
+enum PerspectiveAction {
+    NewQuad(Vec2D),
+    MoveCorner { index: usize, start: Vec2D },
+}
+
+/// Marks a skewed region with four corners, which the sketch board straightens on Enter.
+#[derive(Default)]
+pub struct PerspectiveTool {
+    quad: Option<Quad>,
+    action: Option<PerspectiveAction>,
+    input_enabled: bool,
+}
+
+impl PerspectiveTool {
+    pub fn get_quad(&self) -> Option<Quad> {
+        self.quad.filter(|_| self.action.is_none())
+    }
+
+    pub fn clear_quad(&mut self) {
+        self.quad = None;
+        self.action = None;
+    }
+}
+
+impl Tool for PerspectiveTool {
+    fn input_enabled(&self) -> bool {
+        self.input_enabled
+    }
+
+    fn set_input_enabled(&mut self, value: bool) {
+        self.input_enabled = value;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Perspective
+    }
+
+    fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
+        if event.button != MouseButton::Primary {
+            return ToolUpdateResult::Unmodified;
+        }
+        match event.type_ {
+            MouseEventType::BeginDrag => {
+                let corner = self
+                    .quad
+                    .and_then(|q| q.closest_corner(event.pos, HANDLE_MARGIN));
+                self.action = Some(match (self.quad, corner) {
+                    (Some(quad), Some(index)) => PerspectiveAction::MoveCorner {
+                        index,
+                        start: quad.corners[index],
+                    },
+                    _ => {
+                        self.quad = Some(Quad::from_rect(event.pos, Vec2D::zero()));
+                        PerspectiveAction::NewQuad(event.pos)
+                    }
+                });
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::UpdateDrag | MouseEventType::EndDrag => {
+                let (Some(quad), Some(action)) = (&mut self.quad, &self.action) else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                match *action {
+                    PerspectiveAction::NewQuad(origin) => {
+                        let (pos, size) = math::rect_ensure_positive_size(origin, event.pos);
+                        *quad = Quad::from_rect(pos, size);
+                    }
+                    PerspectiveAction::MoveCorner { index, start } => {
+                        quad.corners[index] = start + event.pos;
+                    }
+                }
+                if event.type_ == MouseEventType::EndDrag {
+                    self.action = None;
+                }
+                ToolUpdateResult::Redraw
+            }
+            _ => ToolUpdateResult::Unmodified,
+        }
+    }
+
+    fn handle_key_event(&mut self, event: KeyEventMsg) -> ToolUpdateResult {
+        if event.key == Key::Escape && self.quad.is_some() {
+            self.clear_quad();
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        }
+    }
+
+    fn handle_deactivated(&mut self) -> ToolUpdateResult {
+        self.clear_quad();
+        ToolUpdateResult::Redraw
+    }
+
+    fn get_drawable(&self) -> Option<&dyn Drawable> {
+        self.quad.as_ref().map(|q| q as &dyn Drawable)
+    }
+}
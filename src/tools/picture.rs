@@ -0,0 +1,128 @@
+use std::rc::Rc;
+
+use anyhow::Result;
+use femtovg::{imgref::ImgVec, rgb::RGBA8, FontId, ImageFlags, Paint, Path};
+use gdk_pixbuf::Pixbuf;
+
+use crate::math::Vec2D;
+
+use super::{CachedImage, Drawable, Tools};
+
+/// Smallest width or height a picture can be scaled down to, in image pixels.
+const MIN_SIZE: f32 = 4.0;
+
+/// An image placed on top of the screenshot, such as one pasted from the clipboard.
+#[derive(Clone, Debug)]
+pub struct Picture {
+    // shared by the versions of the picture in the history
+    pixels: Rc<ImgVec<RGBA8>>,
+    // top left corner and the size it is shown at, the pixels are stretched to it
+    pos: Vec2D,
+    size: Vec2D,
+    cached_image: CachedImage,
+}
+
+impl Picture {
+    /// Places `pixbuf` centered on `center`, scaled down to fit into `max_size`.
+    pub fn new(pixbuf: &Pixbuf, center: Vec2D, max_size: Vec2D) -> Result<Self> {
+        let pixels = rgba_pixels(pixbuf)?;
+        let natural = Vec2D::new(pixels.width() as f32, pixels.height() as f32);
+        let scale = (max_size.x / natural.x)
+            .min(max_size.y / natural.y)
+            .min(1.0);
+        let size = natural * scale;
+        Ok(Self {
+            pixels: Rc::new(pixels),
+            pos: center - size * 0.5,
+            size,
+            cached_image: CachedImage::default(),
+        })
+    }
+}
+
+fn rgba_pixels(pixbuf: &Pixbuf) -> Result<ImgVec<RGBA8>> {
+    let pixbuf = if pixbuf.has_alpha() {
+        pixbuf.clone()
+    } else {
+        pixbuf.add_alpha(false, 0, 0, 0)?
+    };
+    let width = pixbuf.width() as usize;
+    let height = pixbuf.height() as usize;
+    let stride = pixbuf.rowstride() as usize;
+    let bytes = pixbuf.read_pixel_bytes();
+
+    // rows may be padded, the last one isn't
+    let pixels = (0..height)
+        .flat_map(|row| bytes[row * stride..row * stride + width * 4].chunks_exact(4))
+        .map(|p| RGBA8::new(p[0], p[1], p[2], p[3]))
+        .collect();
+    Ok(ImgVec::new(pixels, width, height))
+}
+
+impl Drawable for Picture {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        _font: FontId,
+        _bounds: (Vec2D, Vec2D),
+    ) -> Result<()> {
+        let image_id = self.cached_image.get_or_create(|| {
+            Ok(canvas.create_image(self.pixels.as_ref().as_ref(), ImageFlags::empty())?)
+        })?;
+
+        let mut path = Path::new();
+        path.rect(self.pos.x, self.pos.y, self.size.x, self.size.y);
+        canvas.fill_path(
+            &path,
+            &Paint::image(
+                image_id,
+                self.pos.x,
+                self.pos.y,
+                self.size.x,
+                self.size.y,
+                0f32,
+                1f32,
+            ),
+        );
+        Ok(())
+    }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        Some((self.pos, self.size))
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.pos += offset;
+    }
+
+    fn scale(&self, factor: f32) -> Option<Box<dyn Drawable>> {
+        let size = self.size * factor;
+        if size.x.min(size.y) < MIN_SIZE {
+            return None;
+        }
+        let mut scaled = self.clone();
+        scaled.pos = self.pos + (self.size - size) * 0.5;
+        scaled.size = size;
+        Some(Box::new(scaled))
+    }
+
+    // the pixels are counted in parts by each version sharing them, each has its own image
+    fn memory_size(&self) -> usize {
+        let pixels = self.pixels.buf().len() * 4;
+        let cached = if self.cached_image.is_some() {
+            pixels
+        } else {
+            0
+        };
+        std::mem::size_of_val(self) + pixels / Rc::strong_count(&self.pixels) + cached
+    }
+
+    // like a patch, it's an image on top of the image
+    fn get_tool_type(&self) -> Tools {
+        Tools::Patch
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Picture"
+    }
+}
@@ -0,0 +1,233 @@
+use std::f32::consts::PI;
+
+use anyhow::Result;
+use femtovg::{FontId, Path};
+use relm4::gtk::gdk::{Key, ModifierType};
+
+use crate::{
+    configuration::APP_CONFIG,
+    math::{Angle, Vec2D},
+    sketch_board::{KeyEventMsg, MouseEventMsg, MouseEventType},
+    style::Style,
+};
+
+use super::{Drawable, DrawableClone, Tool, ToolUpdateResult, Tools};
+
+const MIN_CORNERS: u8 = 3;
+const MAX_CORNERS: u8 = 24;
+// radius of the inner corners of a star relative to the outer ones
+const STAR_INNER_RADIUS: f32 = 0.5;
+
+/// A regular polygon or star around `center`, with the first corner at `center + corner`.
+#[derive(Clone, Copy, Debug)]
+pub struct Polygon {
+    center: Vec2D,
+    corner: Option<Vec2D>,
+    corners: u8,
+    star: bool,
+    style: Style,
+}
+
+impl Polygon {
+    fn points(&self, corner: Vec2D) -> Vec<Vec2D> {
+        let radius = corner.norm();
+        let start = corner.angle().radians;
+        let count = if self.star {
+            self.corners as usize * 2
+        } else {
+            self.corners as usize
+        };
+        (0..count)
+            .map(|i| {
+                let angle = Angle::from_radians(start + 2.0 * PI * i as f32 / count as f32);
+                let radius = if self.star && i % 2 == 1 {
+                    radius * STAR_INNER_RADIUS
+                } else {
+                    radius
+                };
+                self.center + Vec2D::from_angle(angle) * radius
+            })
+            .collect()
+    }
+
+    fn update(&mut self, event: &MouseEventMsg) {
+        self.corner = Some(if event.modifier.intersects(ModifierType::SHIFT_MASK) {
+            event.pos.snapped_vector_15deg()
+        } else {
+            event.pos
+        });
+    }
+}
+
+impl Drawable for Polygon {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        _font: FontId,
+        _bounds: (Vec2D, Vec2D),
+    ) -> Result<()> {
+        let Some(corner) = self.corner else {
+            return Ok(());
+        };
+
+        let points = self.points(corner);
+        let mut path = Path::new();
+        path.move_to(points[0].x, points[0].y);
+        for point in &points[1..] {
+            path.line_to(point.x, point.y);
+        }
+        path.close();
+
+        canvas.save();
+        if self.style.fill {
+            canvas.fill_path(&path, &self.style.into());
+        } else {
+            canvas.stroke_path(&path, &self.style.into());
+        }
+        canvas.restore();
+
+        Ok(())
+    }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        let radius = self.corner?.norm();
+        Some((
+            self.center - Vec2D::new(radius, radius),
+            Vec2D::new(radius, radius) * 2.0,
+        ))
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.center += offset;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Polygon
+    }
+
+    fn color(&self) -> Option<crate::style::Color> {
+        Some(self.style.color)
+    }
+}
+
+pub struct PolygonTool {
+    polygon: Option<Polygon>,
+    corners: u8,
+    star: bool,
+    style: Style,
+    input_enabled: bool,
+}
+
+impl Default for PolygonTool {
+    fn default() -> Self {
+        Self {
+            polygon: None,
+            corners: APP_CONFIG
+                .read()
+                .polygon_corners()
+                .clamp(MIN_CORNERS, MAX_CORNERS),
+            star: false,
+            style: Style::default(),
+            input_enabled: false,
+        }
+    }
+}
+
+impl Tool for PolygonTool {
+    fn input_enabled(&self) -> bool {
+        self.input_enabled
+    }
+
+    fn set_input_enabled(&mut self, value: bool) {
+        self.input_enabled = value;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Polygon
+    }
+
+    fn active(&self) -> bool {
+        self.polygon.is_some()
+    }
+
+    fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
+        match event.type_ {
+            MouseEventType::BeginDrag if !event.pans() => {
+                self.polygon = Some(Polygon {
+                    center: event.pos,
+                    corner: None,
+                    corners: self.corners,
+                    star: self.star,
+                    style: self.style,
+                });
+
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::UpdateDrag if !event.pans() => {
+                let Some(p) = &mut self.polygon else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                if event.pos == Vec2D::zero() {
+                    return ToolUpdateResult::Unmodified;
+                }
+                p.update(&event);
+
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::EndDrag if !event.pans() => {
+                let Some(p) = &mut self.polygon else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                if event.pos == Vec2D::zero() {
+                    self.polygon = None;
+                    return ToolUpdateResult::Redraw;
+                }
+                p.update(&event);
+
+                let result = p.clone_box();
+                self.polygon = None;
+
+                ToolUpdateResult::Commit(result)
+            }
+            // scrolling while drawing changes the number of corners
+            MouseEventType::Scroll => {
+                let Some(p) = &mut self.polygon else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                self.corners = match event.pos.y {
+                    v if v < 0.0 => (self.corners + 1).min(MAX_CORNERS),
+                    v if v > 0.0 => (self.corners - 1).max(MIN_CORNERS),
+                    _ => self.corners,
+                };
+                p.corners = self.corners;
+
+                ToolUpdateResult::Redraw
+            }
+            _ => ToolUpdateResult::Unmodified,
+        }
+    }
+
+    fn handle_key_event(&mut self, event: KeyEventMsg) -> ToolUpdateResult {
+        if event.key == Key::Escape && self.polygon.is_some() {
+            self.polygon = None;
+            ToolUpdateResult::Redraw
+        } else if event.key == Key::Tab && event.modifier.is_empty() {
+            self.star = !self.star;
+            if let Some(p) = &mut self.polygon {
+                p.star = self.star;
+            }
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        }
+    }
+
+    fn handle_style_event(&mut self, style: Style) -> ToolUpdateResult {
+        self.style = style;
+        ToolUpdateResult::Unmodified
+    }
+
+    fn get_drawable(&self) -> Option<&dyn Drawable> {
+        self.polygon.as_ref().map(|p| p as &dyn Drawable)
+    }
+}
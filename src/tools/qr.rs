@@ -0,0 +1,232 @@
+use anyhow::Result;
+use femtovg::{Color, FontId, Paint, Path};
+use qrcode::QrCode;
+use relm4::gtk::gdk::Key;
+
+use crate::{
+    math::Vec2D,
+    sketch_board::{KeyEventMsg, MouseButton, MouseEventMsg, MouseEventType, TextEventMsg},
+    style::Style,
+};
+
+use super::{Drawable, DrawableClone, Tool, ToolUpdateResult, Tools};
+
+// light border around the code that scanners need, in modules
+const QUIET_ZONE: usize = 4;
+// width of the smallest code, used as placeholder while nothing is entered
+const MIN_WIDTH: usize = 21;
+
+/// A QR code of `text`, centered on `pos`.
+#[derive(Clone, Debug)]
+pub struct QrStamp {
+    pos: Vec2D,
+    text: String,
+    // dark modules row by row, `width` per row
+    modules: Vec<bool>,
+    width: usize,
+    style: Style,
+    editing: bool,
+}
+
+impl QrStamp {
+    fn new(pos: Vec2D, style: Style) -> Self {
+        Self {
+            pos,
+            text: String::new(),
+            modules: Vec::new(),
+            width: 0,
+            style,
+            editing: true,
+        }
+    }
+
+    /// Encodes `text`, returns false and keeps the previous code if it doesn't fit.
+    fn set_text(&mut self, text: String) -> bool {
+        if text.is_empty() {
+            self.modules.clear();
+            self.width = 0;
+        } else {
+            let Ok(code) = QrCode::new(text.as_bytes()) else {
+                return false;
+            };
+            self.width = code.width();
+            self.modules = code
+                .to_colors()
+                .into_iter()
+                .map(|c| c == qrcode::Color::Dark)
+                .collect();
+        }
+        self.text = text;
+        true
+    }
+
+    fn module_size(&self) -> f32 {
+        self.style
+            .size
+            .to_line_width(self.style.annotation_size_factor)
+    }
+
+    fn side(&self) -> f32 {
+        (self.width.max(MIN_WIDTH) + 2 * QUIET_ZONE) as f32 * self.module_size()
+    }
+}
+
+impl Drawable for QrStamp {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        _font: FontId,
+        _bounds: (Vec2D, Vec2D),
+    ) -> Result<()> {
+        let side = self.side();
+        let module = self.module_size();
+        let top_left = self.pos - Vec2D::new(side, side) * 0.5;
+
+        let mut background = Path::new();
+        background.rect(top_left.x, top_left.y, side, side);
+
+        let mut dark = Path::new();
+        let origin = top_left + Vec2D::new(module, module) * QUIET_ZONE as f32;
+        for (i, _) in self.modules.iter().enumerate().filter(|(_, d)| **d) {
+            let (x, y) = ((i % self.width) as f32, (i / self.width) as f32);
+            dark.rect(origin.x + x * module, origin.y + y * module, module, module);
+        }
+
+        canvas.save();
+        canvas.fill_path(&background, &Paint::color(Color::white()));
+        canvas.fill_path(&dark, &Paint::color(Color::black()).with_anti_alias(false));
+        if self.editing {
+            canvas.stroke_path(
+                &background,
+                &Paint::color(Color::rgbf(0.5, 0.5, 0.5)).with_line_width(1.0),
+            );
+        }
+        canvas.restore();
+
+        Ok(())
+    }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        let side = self.side();
+        Some((
+            self.pos - Vec2D::new(side, side) * 0.5,
+            Vec2D::new(side, side),
+        ))
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.pos += offset;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Qr
+    }
+}
+
+#[derive(Default)]
+pub struct QrTool {
+    qr: Option<QrStamp>,
+    style: Style,
+    input_enabled: bool,
+}
+
+impl QrTool {
+    // the code being entered, unless nothing was entered yet
+    fn finish(&mut self) -> ToolUpdateResult {
+        match self.qr.take() {
+            Some(mut q) if !q.text.is_empty() => {
+                q.editing = false;
+                ToolUpdateResult::Commit(q.clone_box())
+            }
+            Some(_) => ToolUpdateResult::Redraw,
+            None => ToolUpdateResult::Unmodified,
+        }
+    }
+}
+
+impl Tool for QrTool {
+    fn input_enabled(&self) -> bool {
+        self.input_enabled
+    }
+
+    fn set_input_enabled(&mut self, value: bool) {
+        self.input_enabled = value;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Qr
+    }
+
+    fn active(&self) -> bool {
+        self.qr.is_some()
+    }
+
+    fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
+        if event.type_ != MouseEventType::Click || event.button != MouseButton::Primary {
+            return ToolUpdateResult::Unmodified;
+        }
+        let result = match self.finish() {
+            ToolUpdateResult::Unmodified => ToolUpdateResult::Redraw,
+            result => result,
+        };
+        self.qr = Some(QrStamp::new(event.pos, self.style));
+        self.input_enabled = true;
+        result
+    }
+
+    fn handle_text_event(&mut self, event: TextEventMsg) -> ToolUpdateResult {
+        let (Some(q), TextEventMsg::Commit(text)) = (&mut self.qr, event) else {
+            return ToolUpdateResult::Unmodified;
+        };
+        if q.set_text(q.text.clone() + &text) {
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        }
+    }
+
+    fn handle_key_event(&mut self, event: KeyEventMsg) -> ToolUpdateResult {
+        let Some(q) = &mut self.qr else {
+            return ToolUpdateResult::Unmodified;
+        };
+        match event.key {
+            Key::Return | Key::KP_Enter => {
+                self.input_enabled = false;
+                self.finish()
+            }
+            Key::Escape => {
+                self.qr = None;
+                self.input_enabled = false;
+                ToolUpdateResult::Redraw
+            }
+            Key::BackSpace => {
+                let mut text = q.text.clone();
+                if text.pop().is_some() && q.set_text(text) {
+                    ToolUpdateResult::Redraw
+                } else {
+                    ToolUpdateResult::Unmodified
+                }
+            }
+            _ => ToolUpdateResult::Unmodified,
+        }
+    }
+
+    fn handle_deactivated(&mut self) -> ToolUpdateResult {
+        self.input_enabled = false;
+        self.finish()
+    }
+
+    fn handle_style_event(&mut self, style: Style) -> ToolUpdateResult {
+        self.style = style;
+        if let Some(q) = &mut self.qr {
+            q.style = style;
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        }
+    }
+
+    fn get_drawable(&self) -> Option<&dyn Drawable> {
+        self.qr.as_ref().map(|q| q as &dyn Drawable)
+    }
+}
@@ -0,0 +1,227 @@
+use std::cell::RefCell;
+
+use anyhow::Result;
+use femtovg::{imgref::ImgVec, rgb::RGBA8, Color, FontId, ImageFlags, Paint, Path};
+use relm4::gtk::gdk::Key;
+
+use crate::{
+    configuration::APP_CONFIG,
+    math::{self, Vec2D},
+    sketch_board::{KeyEventMsg, MouseEventMsg, MouseEventType},
+    style::{self, Style},
+};
+
+use super::{blur, CachedImage, Drawable, DrawableClone, Tool, ToolUpdateResult, Tools};
+
+/// Replaces the color found at `origin` with the style color within a region.
+#[derive(Clone, Debug)]
+pub struct Recolor {
+    origin: Vec2D,
+    top_left: Vec2D,
+    size: Option<Vec2D>,
+    style: Style,
+    tolerance: u8,
+    editing: bool,
+    // the color picked at `origin` when first drawn, kept when moving the region
+    replaced: RefCell<Option<RGBA8>>,
+    cached_image: CachedImage,
+}
+
+impl Recolor {
+    fn update(&mut self, event: &MouseEventMsg) {
+        let (top_left, size) = math::rect_from_drag(
+            self.origin,
+            event.pos,
+            event.from_center(),
+            event.proportional(),
+        );
+        self.top_left = top_left;
+        self.size = Some(size);
+    }
+
+    /// Shifts every pixel close to `from` by the difference between `from` and `to`, which
+    /// keeps shading and anti-aliased edges intact.
+    fn replace(image: &mut ImgVec<RGBA8>, from: RGBA8, to: style::Color, tolerance: u8) {
+        let shift = |from: u8, to: u8, value: u8| {
+            (value as i16 + to as i16 - from as i16).clamp(0, 255) as u8
+        };
+        for pixel in image.buf_mut().iter_mut() {
+            let distance = pixel
+                .r
+                .abs_diff(from.r)
+                .max(pixel.g.abs_diff(from.g))
+                .max(pixel.b.abs_diff(from.b));
+            if distance <= tolerance {
+                pixel.r = shift(from.r, to.r, pixel.r);
+                pixel.g = shift(from.g, to.g, pixel.g);
+                pixel.b = shift(from.b, to.b, pixel.b);
+            }
+        }
+    }
+}
+
+impl Drawable for Recolor {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        _font: FontId,
+        bounds: (Vec2D, Vec2D),
+    ) -> Result<()> {
+        let Some(size) = self.size else {
+            return Ok(());
+        };
+        let (pos, size) = math::rect_ensure_in_bounds(
+            math::rect_ensure_positive_size(self.top_left, size),
+            bounds,
+        );
+        if self.editing {
+            let mut path = Path::new();
+            path.rect(pos.x, pos.y, size.x, size.y);
+            canvas.fill_path(&path, &Paint::color(Color::rgba(0, 0, 0, 153)));
+            return Ok(());
+        }
+        if size.x <= 0.0 || size.y <= 0.0 {
+            return Ok(());
+        }
+
+        canvas.save();
+        canvas.flush();
+
+        let image_id = self.cached_image.get_or_create(|| {
+            if self.replaced.borrow().is_none() {
+                let picked = blur::capture(canvas, self.origin, Vec2D::new(1.0, 1.0))?;
+                *self.replaced.borrow_mut() = picked.buf().first().copied();
+            }
+            let mut image = blur::capture(canvas, pos, size)?;
+            if let Some(from) = *self.replaced.borrow() {
+                Self::replace(&mut image, from, self.style.color, self.tolerance);
+            }
+            Ok(canvas.create_image(image.as_ref(), ImageFlags::empty())?)
+        })?;
+
+        let mut path = Path::new();
+        path.rect(pos.x, pos.y, size.x, size.y);
+        canvas.fill_path(
+            &path,
+            &Paint::image(image_id, pos.x, pos.y, size.x, size.y, 0f32, 1f32),
+        );
+        canvas.restore();
+        Ok(())
+    }
+
+    fn color(&self) -> Option<style::Color> {
+        Some(self.style.color)
+    }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.size.map(|size| (self.top_left, size))
+    }
+
+    fn memory_size(&self) -> usize {
+        std::mem::size_of_val(self) + self.cached_image.memory_size(self.bounds())
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.origin += offset;
+        self.top_left += offset;
+        // the cached image shows what was underneath the old position
+        self.cached_image.clear();
+    }
+
+    fn clear_cache(&self) {
+        self.cached_image.clear();
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Recolor
+    }
+}
+
+#[derive(Default)]
+pub struct RecolorTool {
+    recolor: Option<Recolor>,
+    style: Style,
+    input_enabled: bool,
+}
+
+impl Tool for RecolorTool {
+    fn input_enabled(&self) -> bool {
+        self.input_enabled
+    }
+
+    fn set_input_enabled(&mut self, value: bool) {
+        self.input_enabled = value;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Recolor
+    }
+
+    fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
+        if event.pans() {
+            return ToolUpdateResult::Unmodified;
+        }
+        match event.type_ {
+            MouseEventType::BeginDrag => {
+                self.recolor = Some(Recolor {
+                    origin: event.pos,
+                    top_left: event.pos,
+                    size: None,
+                    style: self.style,
+                    tolerance: APP_CONFIG.read().recolor_tolerance(),
+                    editing: true,
+                    replaced: RefCell::new(None),
+                    cached_image: CachedImage::default(),
+                });
+
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::UpdateDrag => {
+                let Some(r) = &mut self.recolor else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                if event.pos == Vec2D::zero() {
+                    return ToolUpdateResult::Unmodified;
+                }
+                r.update(&event);
+
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::EndDrag => {
+                let Some(r) = &mut self.recolor else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                if event.pos == Vec2D::zero() {
+                    self.recolor = None;
+                    return ToolUpdateResult::Redraw;
+                }
+                r.update(&event);
+                r.editing = false;
+
+                let result = r.clone_box();
+                self.recolor = None;
+
+                ToolUpdateResult::Commit(result)
+            }
+            _ => ToolUpdateResult::Unmodified,
+        }
+    }
+
+    fn handle_key_event(&mut self, event: KeyEventMsg) -> ToolUpdateResult {
+        if event.key == Key::Escape && self.recolor.is_some() {
+            self.recolor = None;
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        }
+    }
+
+    fn handle_style_event(&mut self, style: Style) -> ToolUpdateResult {
+        self.style = style;
+        ToolUpdateResult::Unmodified
+    }
+
+    fn get_drawable(&self) -> Option<&dyn Drawable> {
+        self.recolor.as_ref().map(|r| r as &dyn Drawable)
+    }
+}
@@ -1,11 +1,11 @@
 use anyhow::Result;
 use femtovg::{FontId, Path};
-use relm4::gtk::gdk::{Key, ModifierType};
+use relm4::gtk::gdk::Key;
 
 use crate::{
-    configuration::APP_CONFIG,
-    math::Vec2D,
-    sketch_board::{MouseButton, MouseEventMsg, MouseEventType},
+    annotations::AnnotationSpec,
+    math::{self, Vec2D},
+    sketch_board::{MouseEventMsg, MouseEventType},
     style::Style,
 };
 
@@ -40,7 +40,7 @@ impl Drawable for Rectangle {
             self.top_left.y,
             size.x,
             size.y,
-            APP_CONFIG.read().corner_roundness(),
+            self.style.corner_radius,
         );
 
         if !self.finishing && self.centered {
@@ -61,40 +61,58 @@ impl Drawable for Rectangle {
 
         Ok(())
     }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.size.map(|size| (self.top_left, size))
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.origin += offset;
+        self.top_left += offset;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Rectangle
+    }
+
+    fn color(&self) -> Option<crate::style::Color> {
+        Some(self.style.color)
+    }
+
+    fn to_spec(&self) -> Option<AnnotationSpec> {
+        let end = self.top_left + self.size?;
+        Some(AnnotationSpec::Rectangle {
+            start: [self.top_left.x, self.top_left.y],
+            end: [end.x, end.y],
+            corner_radius: Some(self.style.corner_radius),
+            style: self.style.into(),
+        })
+    }
 }
 
 impl Rectangle {
-    fn calculate_shape(&mut self, event: &MouseEventMsg) {
-        self.centered = event.modifier & ModifierType::ALT_MASK == ModifierType::ALT_MASK;
-        match event.modifier & (ModifierType::ALT_MASK | ModifierType::SHIFT_MASK) {
-            v if v == ModifierType::ALT_MASK | ModifierType::SHIFT_MASK => {
-                let max_size = event.pos.x.abs().max(event.pos.y.abs());
-                self.top_left.x = self.origin.x - max_size * event.pos.x.signum() / 2.0;
-                self.top_left.y = self.origin.y - max_size * event.pos.y.signum() / 2.0;
-                self.size = Some(Vec2D {
-                    x: max_size * event.pos.x.signum(),
-                    y: max_size * event.pos.y.signum(),
-                });
-            }
-            ModifierType::ALT_MASK => {
-                self.top_left.x = self.origin.x - event.pos.x / 2.0;
-                self.top_left.y = self.origin.y - event.pos.y / 2.0;
-                self.size = Some(event.pos);
-            }
-            ModifierType::SHIFT_MASK => {
-                self.top_left = self.origin;
-                let max_size = event.pos.x.abs().max(event.pos.y.abs());
-                self.size = Some(Vec2D {
-                    x: max_size * event.pos.x.signum(),
-                    y: max_size * event.pos.y.signum(),
-                });
-            }
-            _ => {
-                self.top_left = self.origin;
-                self.size = Some(event.pos);
-            }
+    pub fn new(top_left: Vec2D, size: Vec2D, style: Style) -> Self {
+        Self {
+            origin: top_left,
+            top_left,
+            size: Some(size),
+            style,
+            centered: false,
+            finishing: true,
         }
     }
+
+    fn calculate_shape(&mut self, event: &MouseEventMsg) {
+        self.centered = event.from_center();
+        let (top_left, size) = math::rect_from_drag(
+            self.origin,
+            event.pos,
+            event.from_center(),
+            event.proportional(),
+        );
+        self.top_left = top_left;
+        self.size = Some(size);
+    }
 }
 
 #[derive(Default)]
@@ -116,7 +134,7 @@ impl Tool for RectangleTool {
     fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
         match event.type_ {
             MouseEventType::BeginDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
                 // start new
@@ -132,7 +150,7 @@ impl Tool for RectangleTool {
                 ToolUpdateResult::Redraw
             }
             MouseEventType::EndDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
@@ -153,7 +171,7 @@ impl Tool for RectangleTool {
                 }
             }
             MouseEventType::UpdateDrag => {
-                if event.button == MouseButton::Middle {
+                if event.pans() {
                     return ToolUpdateResult::Unmodified;
                 }
 
@@ -0,0 +1,289 @@
+use anyhow::Result;
+use femtovg::{Color, FontId, Paint, Path};
+use relm4::gtk::gdk::{Key, ModifierType};
+
+use crate::{
+    configuration::APP_CONFIG,
+    math::{self, Vec2D},
+    sketch_board::{MouseEventMsg, MouseEventType},
+    style::Style,
+};
+
+use super::{Drawable, DrawableClone, Tool, ToolUpdateResult, Tools};
+
+#[derive(Default)]
+pub struct RulerTool {
+    measurement: Option<Measurement>,
+    dimension: bool,
+    style: Style,
+    input_enabled: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Measurement {
+    start: Vec2D,
+    end: Option<Vec2D>,
+    // measure width and height of the spanned rectangle instead of the distance
+    area: bool,
+    // engineering style dimension line with arrowheads against the end ticks
+    dimension: bool,
+    style: Style,
+}
+
+impl Measurement {
+    fn update(&mut self, event: &MouseEventMsg) {
+        self.area = event.modifier.intersects(ModifierType::CONTROL_MASK);
+        let offset = if !self.area && event.modifier.intersects(ModifierType::SHIFT_MASK) {
+            event.pos.snapped_vector_15deg()
+        } else {
+            event.pos
+        };
+        self.end = Some(self.start + offset);
+    }
+
+    fn format_length(pixels: f32) -> String {
+        match APP_CONFIG.read().ruler_dpi() {
+            Some(dpi) if dpi > 0.0 => format!("{pixels:.0} px ({:.1} mm)", pixels / dpi * 25.4),
+            _ => format!("{pixels:.0} px"),
+        }
+    }
+
+    fn draw_label(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        font: FontId,
+        pos: Vec2D,
+        text: &str,
+    ) -> Result<()> {
+        let background: Color = self.style.color.into();
+        // https://en.wikipedia.org/wiki/Luma_(video)
+        let luminance = 0.2126 * background.r + 0.7152 * background.g + 0.0722 * background.b;
+        let text_color = if luminance > 0.5 {
+            Color::black()
+        } else {
+            Color::white()
+        };
+
+        let mut paint = Paint::color(text_color);
+        paint.set_font(&[font]);
+        paint.set_font_size(
+            self.style
+                .size
+                .to_text_size(self.style.annotation_size_factor) as f32
+                * 0.6,
+        );
+        paint.set_text_align(femtovg::Align::Center);
+        paint.set_text_baseline(femtovg::Baseline::Middle);
+
+        let metrics = canvas.measure_text(pos.x, pos.y, text, &paint)?;
+        let padding = metrics.height() * 0.3;
+        let mut path = Path::new();
+        path.rounded_rect(
+            pos.x - metrics.width() / 2.0 - padding,
+            pos.y - metrics.height() / 2.0 - padding,
+            metrics.width() + 2.0 * padding,
+            metrics.height() + 2.0 * padding,
+            padding,
+        );
+
+        canvas.fill_path(&path, &Paint::color(background));
+        canvas.fill_text(pos.x, pos.y, text, &paint)?;
+        Ok(())
+    }
+}
+
+impl Drawable for Measurement {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        font: FontId,
+        _bounds: (Vec2D, Vec2D),
+    ) -> Result<()> {
+        let end = match self.end {
+            Some(e) => e,
+            None => return Ok(()), // exit early if no end
+        };
+        let offset = end - self.start;
+
+        canvas.save();
+        let mut path = Path::new();
+
+        if self.area {
+            path.rect(self.start.x, self.start.y, offset.x, offset.y);
+            canvas.stroke_path(&path, &self.style.into());
+
+            let width = Self::format_length(offset.x.abs());
+            let height = Self::format_length(offset.y.abs());
+            self.draw_label(
+                canvas,
+                font,
+                self.start + offset * 0.5,
+                &format!("{width} × {height}"),
+            )?;
+        } else {
+            let length = offset.norm();
+            if length > 0.0 {
+                // ticks at both ends, perpendicular to the measured line
+                let tick_length = self
+                    .style
+                    .size
+                    .to_line_width(self.style.annotation_size_factor)
+                    * 3.0;
+                let tick = Vec2D::new(-offset.y, offset.x) * (tick_length / length);
+                for p in [self.start, end] {
+                    path.move_to(p.x - tick.x, p.y - tick.y);
+                    path.line_to(p.x + tick.x, p.y + tick.y);
+                }
+
+                if self.dimension {
+                    // arrowheads pointing outward, so their tips touch the ticks
+                    let along = offset * (tick_length * 1.5 / length);
+                    let across = tick * 0.5;
+                    let mut arrows = Path::new();
+                    for (tip, direction) in [(self.start, along), (end, along * -1.0)] {
+                        arrows.move_to(tip.x, tip.y);
+                        arrows.line_to(
+                            tip.x + direction.x + across.x,
+                            tip.y + direction.y + across.y,
+                        );
+                        arrows.line_to(
+                            tip.x + direction.x - across.x,
+                            tip.y + direction.y - across.y,
+                        );
+                        arrows.close();
+                    }
+                    canvas.fill_path(&arrows, &self.style.into());
+                }
+            }
+            path.move_to(self.start.x, self.start.y);
+            path.line_to(end.x, end.y);
+            canvas.stroke_path(&path, &self.style.into());
+
+            self.draw_label(
+                canvas,
+                font,
+                self.start + offset * 0.5,
+                &Self::format_length(length),
+            )?;
+        }
+
+        canvas.restore();
+        Ok(())
+    }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.end
+            .map(|end| math::rect_around_points(&[self.start, end]))
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.start += offset;
+        if let Some(end) = &mut self.end {
+            *end += offset;
+        }
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Ruler
+    }
+
+    fn color(&self) -> Option<crate::style::Color> {
+        Some(self.style.color)
+    }
+}
+
+impl Tool for RulerTool {
+    fn input_enabled(&self) -> bool {
+        self.input_enabled
+    }
+
+    fn set_input_enabled(&mut self, value: bool) {
+        self.input_enabled = value;
+    }
+
+    fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
+        match event.type_ {
+            MouseEventType::BeginDrag => {
+                if event.pans() {
+                    return ToolUpdateResult::Unmodified;
+                }
+
+                // start new
+                self.measurement = Some(Measurement {
+                    start: event.pos,
+                    end: None,
+                    area: false,
+                    dimension: self.dimension,
+                    style: self.style,
+                });
+
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::EndDrag => {
+                if event.pans() {
+                    return ToolUpdateResult::Unmodified;
+                }
+
+                if let Some(m) = &mut self.measurement {
+                    if event.pos == Vec2D::zero() {
+                        self.measurement = None;
+
+                        ToolUpdateResult::Redraw
+                    } else {
+                        m.update(&event);
+                        let result = m.clone_box();
+                        self.measurement = None;
+
+                        ToolUpdateResult::Commit(result)
+                    }
+                } else {
+                    ToolUpdateResult::Unmodified
+                }
+            }
+            MouseEventType::UpdateDrag => {
+                if event.pans() {
+                    return ToolUpdateResult::Unmodified;
+                }
+
+                if let Some(m) = &mut self.measurement {
+                    m.update(&event);
+                    ToolUpdateResult::Redraw
+                } else {
+                    ToolUpdateResult::Unmodified
+                }
+            }
+            _ => ToolUpdateResult::Unmodified,
+        }
+    }
+
+    fn handle_key_event(&mut self, event: crate::sketch_board::KeyEventMsg) -> ToolUpdateResult {
+        if event.key == Key::Escape && self.measurement.is_some() {
+            self.measurement = None;
+            ToolUpdateResult::Redraw
+        } else if event.key == Key::Tab && event.modifier.is_empty() {
+            self.dimension = !self.dimension;
+            if let Some(m) = &mut self.measurement {
+                m.dimension = self.dimension;
+            }
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        }
+    }
+
+    fn handle_style_event(&mut self, style: Style) -> ToolUpdateResult {
+        self.style = style;
+        ToolUpdateResult::Unmodified
+    }
+
+    fn get_drawable(&self) -> Option<&dyn Drawable> {
+        match &self.measurement {
+            Some(d) => Some(d),
+            None => None,
+        }
+    }
+
+    fn get_tool_type(&self) -> super::Tools {
+        Tools::Ruler
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{math::Vec2D, style::Style};
+
+use super::{arrow::Arrow, ellipse::Ellipse, line::Line, rectangle::Rectangle, Drawable};
+
+/// Strokes with a smaller bounding box diagonal are never recognized.
+const MIN_SHAPE_SIZE: f32 = 10.0;
+
+/// A stroke is considered closed if start and end are closer than this
+/// share of the bounding box diagonal.
+const CLOSED_DISTANCE: f32 = 0.2;
+
+/// The head of an arrow may be at most this long compared to its shaft.
+const MAX_ARROW_HEAD: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy)]
+pub enum RecognizedShape {
+    Line { start: Vec2D, end: Vec2D },
+    Arrow { start: Vec2D, end: Vec2D },
+    Ellipse { middle: Vec2D, radii: Vec2D },
+    Rectangle { top_left: Vec2D, size: Vec2D },
+}
+
+impl RecognizedShape {
+    pub fn into_drawable(self, style: Style) -> Box<dyn Drawable> {
+        match self {
+            Self::Line { start, end } => Box::new(Line::new(start, end, style)),
+            Self::Arrow { start, end } => Box::new(Arrow::new(start, end, style)),
+            Self::Ellipse { middle, radii } => Box::new(Ellipse::new(middle, radii, style)),
+            Self::Rectangle { top_left, size } => Box::new(Rectangle::new(top_left, size, style)),
+        }
+    }
+}
+
+/// Try to match a freehand stroke (absolute points) to a clean shape.
+/// `tolerance` is the allowed deviation relative to the shape size.
+pub fn recognize(points: &[Vec2D], tolerance: f32) -> Option<RecognizedShape> {
+    let (first, last) = (*points.first()?, *points.last()?);
+    let (min, max) = bounding_box(points);
+    let diagonal = (max - min).norm();
+    if diagonal < MIN_SHAPE_SIZE {
+        return None;
+    }
+
+    if first.distance_to(&last) < CLOSED_DISTANCE * diagonal {
+        return recognize_closed(points, min, max, tolerance);
+    }
+
+    if line_deviation(points, first, last) < tolerance / 2.0 {
+        return Some(RecognizedShape::Line {
+            start: first,
+            end: last,
+        });
+    }
+
+    // an arrow is a straight shaft up to the point farthest from the start,
+    // followed by a short stroke for the head
+    let (tip_index, tip) = points
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.distance_to(&first).total_cmp(&b.distance_to(&first)))?;
+    let shaft_length = tip.distance_to(&first);
+    let head_length = path_length(&points[tip_index..]);
+    if tip_index > 0
+        && head_length > 0.0
+        && head_length < MAX_ARROW_HEAD * shaft_length
+        && line_deviation(&points[..=tip_index], first, tip) < tolerance / 2.0
+    {
+        return Some(RecognizedShape::Arrow {
+            start: first,
+            end: tip,
+        });
+    }
+
+    None
+}
+
+fn recognize_closed(
+    points: &[Vec2D],
+    min: Vec2D,
+    max: Vec2D,
+    tolerance: f32,
+) -> Option<RecognizedShape> {
+    let size = max - min;
+    let radii = size * 0.5;
+    let middle = min + radii;
+    if radii.x < f32::EPSILON || radii.y < f32::EPSILON {
+        return None;
+    }
+
+    let count = points.len() as f32;
+
+    // normalized distance to the ellipse outline, 0 means on the outline
+    let ellipse_error = points
+        .iter()
+        .map(|p| {
+            let d = *p - middle;
+            ((d.x / radii.x).powi(2) + (d.y / radii.y).powi(2)).sqrt() - 1.0
+        })
+        .map(f32::abs)
+        .sum::<f32>()
+        / count;
+
+    // normalized distance to the closest side of the bounding box
+    let rectangle_error = points
+        .iter()
+        .map(|p| {
+            (p.x - min.x)
+                .abs()
+                .min((max.x - p.x).abs())
+                .min((p.y - min.y).abs())
+                .min((max.y - p.y).abs())
+        })
+        .sum::<f32>()
+        / count
+        / radii.x.min(radii.y);
+
+    if ellipse_error.min(rectangle_error) > tolerance {
+        None
+    } else if ellipse_error < rectangle_error {
+        Some(RecognizedShape::Ellipse { middle, radii })
+    } else {
+        Some(RecognizedShape::Rectangle {
+            top_left: min,
+            size,
+        })
+    }
+}
+
+fn bounding_box(points: &[Vec2D]) -> (Vec2D, Vec2D) {
+    points.iter().fold(
+        (
+            Vec2D::new(f32::MAX, f32::MAX),
+            Vec2D::new(f32::MIN, f32::MIN),
+        ),
+        |(min, max), p| {
+            (
+                Vec2D::new(min.x.min(p.x), min.y.min(p.y)),
+                Vec2D::new(max.x.max(p.x), max.y.max(p.y)),
+            )
+        },
+    )
+}
+
+fn path_length(points: &[Vec2D]) -> f32 {
+    points.windows(2).map(|w| w[0].distance_to(&w[1])).sum()
+}
+
+// largest distance of any point to the segment from start to end, relative to its length
+fn line_deviation(points: &[Vec2D], start: Vec2D, end: Vec2D) -> f32 {
+    let direction = end - start;
+    let length = direction.norm();
+    if length < f32::EPSILON {
+        return f32::MAX;
+    }
+
+    points
+        .iter()
+        .map(|p| {
+            let d = *p - start;
+            (d.x * direction.y - d.y * direction.x).abs() / length
+        })
+        .fold(0.0, f32::max)
+        / length
+}
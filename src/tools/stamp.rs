@@ -0,0 +1,179 @@
+use std::f32::consts::PI;
+
+use femtovg::{Color, LineCap, LineJoin, Paint, Path};
+use relm4::gtk::gdk::{Key, ModifierType};
+
+use crate::{
+    configuration::APP_CONFIG,
+    math::Vec2D,
+    sketch_board::{KeyEventMsg, MouseButton, MouseEventMsg, MouseEventType},
+    style::{self, Style},
+};
+
+use super::{Drawable, DrawableClone, Tool, ToolUpdateResult, Tools};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StampKind {
+    Check,
+    Cross,
+}
+
+impl StampKind {
+    fn other(self) -> Self {
+        match self {
+            Self::Check => Self::Cross,
+            Self::Cross => Self::Check,
+        }
+    }
+
+    fn color(self) -> style::Color {
+        match self {
+            Self::Check => APP_CONFIG.read().check_color(),
+            Self::Cross => APP_CONFIG.read().cross_color(),
+        }
+    }
+}
+
+/// A ✓ or ✗ badge for marking right and wrong.
+#[derive(Clone, Copy, Debug)]
+pub struct Stamp {
+    pos: Vec2D,
+    kind: StampKind,
+    color: style::Color,
+    style: Style,
+}
+
+impl Stamp {
+    fn radius(&self) -> f32 {
+        self.style
+            .size
+            .to_text_size(self.style.annotation_size_factor) as f32
+            * 0.8
+    }
+}
+
+impl Drawable for Stamp {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        _font: femtovg::FontId,
+        _bounds: (Vec2D, Vec2D),
+    ) -> anyhow::Result<()> {
+        let radius = self.radius();
+        let (x, y) = (self.pos.x, self.pos.y);
+
+        let mut circle = Path::new();
+        circle.arc(x, y, radius, 0.0, 2.0 * PI, femtovg::Solidity::Solid);
+
+        let mut glyph = Path::new();
+        match self.kind {
+            StampKind::Check => {
+                glyph.move_to(x - radius * 0.45, y + radius * 0.05);
+                glyph.line_to(x - radius * 0.12, y + radius * 0.38);
+                glyph.line_to(x + radius * 0.48, y - radius * 0.32);
+            }
+            StampKind::Cross => {
+                let arm = radius * 0.38;
+                glyph.move_to(x - arm, y - arm);
+                glyph.line_to(x + arm, y + arm);
+                glyph.move_to(x + arm, y - arm);
+                glyph.line_to(x - arm, y + arm);
+            }
+        }
+
+        let mut glyph_paint = Paint::color(Color::white()).with_line_width(radius * 0.25);
+        glyph_paint.set_line_cap(LineCap::Round);
+        glyph_paint.set_line_join(LineJoin::Round);
+
+        canvas.save();
+        canvas.fill_path(&circle, &Paint::color(self.color.into()));
+        canvas.stroke_path(&glyph, &glyph_paint);
+        canvas.restore();
+        Ok(())
+    }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        let radius = self.radius();
+        Some((
+            self.pos - Vec2D::new(radius, radius),
+            Vec2D::new(radius, radius) * 2.0,
+        ))
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.pos += offset;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Stamp
+    }
+
+    fn color(&self) -> Option<style::Color> {
+        Some(self.color)
+    }
+}
+
+pub struct StampTool {
+    kind: StampKind,
+    style: Style,
+    input_enabled: bool,
+}
+
+impl Default for StampTool {
+    fn default() -> Self {
+        Self {
+            kind: StampKind::Check,
+            style: Style::default(),
+            input_enabled: true,
+        }
+    }
+}
+
+impl Tool for StampTool {
+    fn input_enabled(&self) -> bool {
+        self.input_enabled
+    }
+
+    fn set_input_enabled(&mut self, value: bool) {
+        self.input_enabled = value;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Stamp
+    }
+
+    fn get_drawable(&self) -> Option<&dyn Drawable> {
+        None
+    }
+
+    fn handle_style_event(&mut self, style: Style) -> ToolUpdateResult {
+        self.style = style;
+        ToolUpdateResult::Unmodified
+    }
+
+    fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
+        if event.type_ != MouseEventType::Click || event.button != MouseButton::Primary {
+            return ToolUpdateResult::Unmodified;
+        }
+        // Shift places the other stamp without switching
+        let kind = if event.modifier.intersects(ModifierType::SHIFT_MASK) {
+            self.kind.other()
+        } else {
+            self.kind
+        };
+        let stamp = Stamp {
+            pos: event.pos,
+            kind,
+            color: kind.color(),
+            style: self.style,
+        };
+        ToolUpdateResult::Commit(stamp.clone_box())
+    }
+
+    fn handle_key_event(&mut self, event: KeyEventMsg) -> ToolUpdateResult {
+        if event.key == Key::Tab && event.modifier.is_empty() {
+            self.kind = self.kind.other();
+        }
+        ToolUpdateResult::Unmodified
+    }
+}
@@ -0,0 +1,266 @@
+use anyhow::Result;
+use femtovg::{FontId, Paint, Path};
+use relm4::gtk::gdk::{Key, ModifierType};
+
+use crate::{
+    configuration::APP_CONFIG,
+    math::{self, Vec2D},
+    sketch_board::{KeyEventMsg, MouseEventMsg, MouseEventType},
+    style::Style,
+};
+
+use super::{Drawable, DrawableClone, Tool, ToolUpdateResult, Tools};
+
+const MIN_CELLS: u8 = 1;
+const MAX_CELLS: u8 = 50;
+// length of dashes and the gaps between them relative to the line width
+const DASH_LENGTH: f32 = 3.0;
+const DASH_GAP: f32 = 2.0;
+// opacity of the header row when filled
+const HEADER_ALPHA: f32 = 0.3;
+
+/// A grid of `rows` by `columns` cells over the rectangle spanned by the drag.
+#[derive(Clone, Copy, Debug)]
+pub struct Table {
+    origin: Vec2D,
+    top_left: Vec2D,
+    size: Option<Vec2D>,
+    rows: u8,
+    columns: u8,
+    dashed: bool,
+    style: Style,
+}
+
+impl Table {
+    fn update(&mut self, event: &MouseEventMsg) {
+        let (top_left, size) = math::rect_from_drag(
+            self.origin,
+            event.pos,
+            event.from_center(),
+            event.proportional(),
+        );
+        self.top_left = top_left;
+        self.size = Some(size);
+    }
+
+    fn add_line(&self, path: &mut Path, from: Vec2D, to: Vec2D) {
+        if !self.dashed {
+            path.move_to(from.x, from.y);
+            path.line_to(to.x, to.y);
+            return;
+        }
+
+        let width = self
+            .style
+            .size
+            .to_line_width(self.style.annotation_size_factor);
+        let (dash, gap) = (width * DASH_LENGTH, width * DASH_GAP);
+        let length = from.distance_to(&to);
+        if length <= 0.0 {
+            return;
+        }
+        let direction = (to - from) * (1.0 / length);
+        let mut start = 0.0;
+        while start < length {
+            let a = from + direction * start;
+            let b = from + direction * (start + dash).min(length);
+            path.move_to(a.x, a.y);
+            path.line_to(b.x, b.y);
+            start += dash + gap;
+        }
+    }
+}
+
+impl Drawable for Table {
+    fn draw(
+        &self,
+        canvas: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+        _font: FontId,
+        _bounds: (Vec2D, Vec2D),
+    ) -> Result<()> {
+        let Some(size) = self.size else {
+            return Ok(());
+        };
+        let (pos, size) = math::rect_ensure_positive_size(self.top_left, size);
+        let cell = Vec2D::new(size.x / self.columns as f32, size.y / self.rows as f32);
+
+        let mut path = Path::new();
+        for row in 0..=self.rows {
+            let y = pos.y + cell.y * row as f32;
+            self.add_line(
+                &mut path,
+                Vec2D::new(pos.x, y),
+                Vec2D::new(pos.x + size.x, y),
+            );
+        }
+        for column in 0..=self.columns {
+            let x = pos.x + cell.x * column as f32;
+            self.add_line(
+                &mut path,
+                Vec2D::new(x, pos.y),
+                Vec2D::new(x, pos.y + size.y),
+            );
+        }
+
+        canvas.save();
+        // filling marks the first row as header
+        if self.style.fill {
+            let mut header = Path::new();
+            header.rect(pos.x, pos.y, size.x, cell.y);
+            let mut color: femtovg::Color = self.style.color.into();
+            color.a *= HEADER_ALPHA;
+            canvas.fill_path(&header, &Paint::color(color));
+        }
+        canvas.stroke_path(&path, &self.style.into());
+        canvas.restore();
+
+        Ok(())
+    }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.size.map(|size| (self.top_left, size))
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.origin += offset;
+        self.top_left += offset;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Table
+    }
+
+    fn color(&self) -> Option<crate::style::Color> {
+        Some(self.style.color)
+    }
+}
+
+pub struct TableTool {
+    table: Option<Table>,
+    rows: u8,
+    columns: u8,
+    dashed: bool,
+    style: Style,
+    input_enabled: bool,
+}
+
+impl Default for TableTool {
+    fn default() -> Self {
+        let config = APP_CONFIG.read();
+        Self {
+            table: None,
+            rows: config.table_rows().clamp(MIN_CELLS, MAX_CELLS),
+            columns: config.table_columns().clamp(MIN_CELLS, MAX_CELLS),
+            dashed: false,
+            style: Style::default(),
+            input_enabled: false,
+        }
+    }
+}
+
+impl Tool for TableTool {
+    fn input_enabled(&self) -> bool {
+        self.input_enabled
+    }
+
+    fn set_input_enabled(&mut self, value: bool) {
+        self.input_enabled = value;
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Table
+    }
+
+    fn active(&self) -> bool {
+        self.table.is_some()
+    }
+
+    fn handle_mouse_event(&mut self, event: MouseEventMsg) -> ToolUpdateResult {
+        match event.type_ {
+            MouseEventType::BeginDrag if !event.pans() => {
+                self.table = Some(Table {
+                    origin: event.pos,
+                    top_left: event.pos,
+                    size: None,
+                    rows: self.rows,
+                    columns: self.columns,
+                    dashed: self.dashed,
+                    style: self.style,
+                });
+
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::UpdateDrag if !event.pans() => {
+                let Some(t) = &mut self.table else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                if event.pos == Vec2D::zero() {
+                    return ToolUpdateResult::Unmodified;
+                }
+                t.update(&event);
+
+                ToolUpdateResult::Redraw
+            }
+            MouseEventType::EndDrag if !event.pans() => {
+                let Some(t) = &mut self.table else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                if event.pos == Vec2D::zero() {
+                    self.table = None;
+                    return ToolUpdateResult::Redraw;
+                }
+                t.update(&event);
+
+                let result = t.clone_box();
+                self.table = None;
+
+                ToolUpdateResult::Commit(result)
+            }
+            // scrolling while drawing changes the number of rows, or columns with Shift
+            MouseEventType::Scroll => {
+                let Some(t) = &mut self.table else {
+                    return ToolUpdateResult::Unmodified;
+                };
+                let count = if event.modifier.contains(ModifierType::SHIFT_MASK) {
+                    &mut self.columns
+                } else {
+                    &mut self.rows
+                };
+                *count = match event.pos.y {
+                    v if v < 0.0 => (*count + 1).min(MAX_CELLS),
+                    v if v > 0.0 => (*count - 1).max(MIN_CELLS),
+                    _ => *count,
+                };
+                t.rows = self.rows;
+                t.columns = self.columns;
+
+                ToolUpdateResult::Redraw
+            }
+            _ => ToolUpdateResult::Unmodified,
+        }
+    }
+
+    fn handle_key_event(&mut self, event: KeyEventMsg) -> ToolUpdateResult {
+        if event.key == Key::Escape && self.table.is_some() {
+            self.table = None;
+            ToolUpdateResult::Redraw
+        } else if event.key == Key::Tab && event.modifier.is_empty() {
+            self.dashed = !self.dashed;
+            if let Some(t) = &mut self.table {
+                t.dashed = self.dashed;
+            }
+            ToolUpdateResult::Redraw
+        } else {
+            ToolUpdateResult::Unmodified
+        }
+    }
+
+    fn handle_style_event(&mut self, style: Style) -> ToolUpdateResult {
+        self.style = style;
+        ToolUpdateResult::Unmodified
+    }
+
+    fn get_drawable(&self) -> Option<&dyn Drawable> {
+        self.table.as_ref().map(|t| t as &dyn Drawable)
+    }
+}
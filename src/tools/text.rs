@@ -5,11 +5,12 @@ use relm4::gtk::{
     gdk::{Key, ModifierType, Rectangle},
     TextBuffer,
 };
-use std::{borrow::Cow, ops::Range};
+use std::{borrow::Cow, cell::Cell, ops::Range};
 
 use relm4::gtk::prelude::*;
 
 use crate::{
+    annotations::AnnotationSpec,
     ime::preedit::{Preedit, UnderlineKind},
     math::Vec2D,
     sketch_board::{KeyEventMsg, MouseButton, MouseEventMsg, MouseEventType, TextEventMsg},
@@ -26,6 +27,8 @@ pub struct Text {
     style: Style,
     preedit: Option<Preedit>,
     im_context: Option<InputContext>,
+    // area covered by the rendered text, only known after it has been drawn once
+    measured_bounds: Cell<Option<(Vec2D, Vec2D)>>,
 }
 
 struct DisplayContent<'a> {
@@ -64,9 +67,18 @@ impl Text {
             style,
             preedit: None,
             im_context,
+            measured_bounds: Cell::new(None),
         }
     }
 
+    /// Creates a finished text annotation, e.g. one added over IPC.
+    pub fn with_text(pos: Vec2D, text: &str, style: Style) -> Self {
+        let mut result = Self::new(pos, style, None);
+        result.text_buffer.set_text(text);
+        result.editing = false;
+        result
+    }
+
     fn byte_index_from_char_index(text: &str, char_index: usize) -> usize {
         text.char_indices()
             .nth(char_index)
@@ -213,6 +225,7 @@ impl Drawable for Text {
         }
 
         let mut draw_baseline = self.pos.y;
+        let mut max_width: f32 = 0.0;
         for line_range in &lines {
             canvas.fill_text(
                 self.pos.x,
@@ -221,7 +234,17 @@ impl Drawable for Text {
                 &base_paint,
             )?;
             draw_baseline += line_height;
+            max_width = max_width.max(Self::text_width(
+                canvas,
+                &base_paint,
+                &text[line_range.clone()],
+            ));
         }
+        // the position is on the baseline of the first line
+        self.measured_bounds.set(Some((
+            Vec2D::new(self.pos.x, self.pos.y - line_height),
+            Vec2D::new(max_width, line_height * lines.len() as f32),
+        )));
 
         if self.editing {
             if let (Some(preedit), Some(preedit_range)) = (&self.preedit, &display.preedit_range) {
@@ -248,6 +271,38 @@ impl Drawable for Text {
 
         Ok(())
     }
+
+    fn bounds(&self) -> Option<(Vec2D, Vec2D)> {
+        self.measured_bounds.get()
+    }
+
+    fn translate(&mut self, offset: Vec2D) {
+        self.pos += offset;
+        if let Some((pos, size)) = self.measured_bounds.get() {
+            self.measured_bounds.set(Some((pos + offset, size)));
+        }
+    }
+
+    fn get_tool_type(&self) -> Tools {
+        Tools::Text
+    }
+
+    fn color(&self) -> Option<crate::style::Color> {
+        Some(self.style.color)
+    }
+
+    fn to_spec(&self) -> Option<AnnotationSpec> {
+        let text = self.text_buffer.text(
+            &self.text_buffer.start_iter(),
+            &self.text_buffer.end_iter(),
+            false,
+        );
+        (!text.is_empty()).then(|| AnnotationSpec::Text {
+            position: [self.pos.x, self.pos.y],
+            text: text.to_string(),
+            style: self.style.into(),
+        })
+    }
 }
 
 impl Text {
@@ -325,7 +380,7 @@ impl Text {
                             continue;
                         }
                         canvas.save();
-                        canvas.scissor(
+                        canvas.intersect_scissor(
                             (*start_x - 1.0).floor(),
                             (line.baseline + cursor.top_offset - 1.0).floor(),
                             (width + 2.0).ceil(),
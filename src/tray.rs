@@ -0,0 +1,296 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use anyhow::{Context, Result};
+use gdk_pixbuf::gio;
+use gio::prelude::*;
+use gio::{DBusConnection, DBusMethodInvocation};
+use glib::variant::ObjectPath;
+use glib::{Variant, VariantDict, VariantTy};
+
+const ITEM_INTERFACE_XML: &str = r#"
+<node>
+  <interface name='org.kde.StatusNotifierItem'>
+    <property name='Category' type='s' access='read'/>
+    <property name='Id' type='s' access='read'/>
+    <property name='Title' type='s' access='read'/>
+    <property name='Status' type='s' access='read'/>
+    <property name='IconName' type='s' access='read'/>
+    <property name='ItemIsMenu' type='b' access='read'/>
+    <property name='Menu' type='o' access='read'/>
+    <method name='Activate'>
+      <arg type='i' name='x' direction='in'/>
+      <arg type='i' name='y' direction='in'/>
+    </method>
+    <method name='SecondaryActivate'>
+      <arg type='i' name='x' direction='in'/>
+      <arg type='i' name='y' direction='in'/>
+    </method>
+    <method name='ContextMenu'>
+      <arg type='i' name='x' direction='in'/>
+      <arg type='i' name='y' direction='in'/>
+    </method>
+    <method name='Scroll'>
+      <arg type='i' name='delta' direction='in'/>
+      <arg type='s' name='orientation' direction='in'/>
+    </method>
+    <signal name='NewTitle'/>
+  </interface>
+</node>
+"#;
+
+const MENU_INTERFACE_XML: &str = r#"
+<node>
+  <interface name='com.canonical.dbusmenu'>
+    <property name='Version' type='u' access='read'/>
+    <property name='Status' type='s' access='read'/>
+    <property name='TextDirection' type='s' access='read'/>
+    <method name='GetLayout'>
+      <arg type='i' name='parentId' direction='in'/>
+      <arg type='i' name='recursionDepth' direction='in'/>
+      <arg type='as' name='propertyNames' direction='in'/>
+      <arg type='u' name='revision' direction='out'/>
+      <arg type='(ia{sv}av)' name='layout' direction='out'/>
+    </method>
+    <method name='GetGroupProperties'>
+      <arg type='ai' name='ids' direction='in'/>
+      <arg type='as' name='propertyNames' direction='in'/>
+      <arg type='a(ia{sv})' name='properties' direction='out'/>
+    </method>
+    <method name='Event'>
+      <arg type='i' name='id' direction='in'/>
+      <arg type='s' name='eventId' direction='in'/>
+      <arg type='v' name='data' direction='in'/>
+      <arg type='u' name='timestamp' direction='in'/>
+    </method>
+    <method name='AboutToShow'>
+      <arg type='i' name='id' direction='in'/>
+      <arg type='b' name='needUpdate' direction='out'/>
+    </method>
+    <signal name='LayoutUpdated'>
+      <arg type='u' name='revision'/>
+      <arg type='i' name='parent'/>
+    </signal>
+  </interface>
+</node>
+"#;
+
+const ITEM_INTERFACE_NAME: &str = "org.kde.StatusNotifierItem";
+const ITEM_OBJECT_PATH: &str = "/StatusNotifierItem";
+const MENU_INTERFACE_NAME: &str = "com.canonical.dbusmenu";
+const MENU_OBJECT_PATH: &str = "/MenuBar";
+
+const MENU_OPEN_LAST_IMAGE: i32 = 1;
+const MENU_PAUSE: i32 = 2;
+const MENU_SHUTDOWN: i32 = 3;
+
+#[derive(Debug, Clone, Copy)]
+pub enum TrayAction {
+    OpenLastImage,
+    /// Whether the daemon should stop accepting images.
+    Pause(bool),
+    Shutdown,
+}
+
+/// State shown by the tray icon, shared between the item and its menu.
+#[derive(Default)]
+struct TrayState {
+    paused: Cell<bool>,
+    revision: Cell<u32>,
+}
+
+impl TrayState {
+    fn title(&self) -> &'static str {
+        if self.paused.get() {
+            "Satty (paused)"
+        } else {
+            "Satty"
+        }
+    }
+
+    fn menu_items(&self) -> Vec<Variant> {
+        vec![
+            menu_item(MENU_OPEN_LAST_IMAGE, "Open Last Image", None),
+            menu_item(MENU_PAUSE, "Pause", Some(self.paused.get())),
+            menu_item(MENU_SHUTDOWN, "Shut Down", None),
+        ]
+    }
+}
+
+/// A menu entry of type `(ia{sv}av)`, without children.
+fn menu_item(id: i32, label: &str, checked: Option<bool>) -> Variant {
+    let properties = VariantDict::new(None);
+    properties.insert_value("label", &label.to_variant());
+    if let Some(checked) = checked {
+        properties.insert_value("toggle-type", &"checkmark".to_variant());
+        properties.insert_value("toggle-state", &i32::from(checked).to_variant());
+    }
+    Variant::tuple_from_iter([
+        id.to_variant(),
+        properties.end(),
+        Vec::<Variant>::new().to_variant(),
+    ])
+}
+
+/// Shows a StatusNotifierItem tray icon for the daemon and reports the chosen menu entries to
+/// `callback`.
+pub async fn register<F>(connection: &DBusConnection, callback: F) -> Result<()>
+where
+    F: Fn(TrayAction) + 'static,
+{
+    let state = Rc::new(TrayState::default());
+    let callback = Rc::new(callback);
+
+    let item_info = gio::DBusNodeInfo::for_xml(ITEM_INTERFACE_XML)
+        .ok()
+        .and_then(|e| e.lookup_interface(ITEM_INTERFACE_NAME))
+        .context("Failed to parse tray item interface XML")?;
+    let menu_info = gio::DBusNodeInfo::for_xml(MENU_INTERFACE_XML)
+        .ok()
+        .and_then(|e| e.lookup_interface(MENU_INTERFACE_NAME))
+        .context("Failed to parse tray menu interface XML")?;
+
+    connection
+        .register_object(ITEM_OBJECT_PATH, &item_info)
+        .method_call(glib::clone!(
+            #[strong]
+            callback,
+            move |_connection, _sender, _path, _interface, method, _params, invocation| {
+                if method == "Activate" {
+                    callback(TrayAction::OpenLastImage);
+                }
+                invocation.return_value(None);
+            }
+        ))
+        .property(glib::clone!(
+            #[strong]
+            state,
+            move |_connection, _sender, _path, _interface, property| match property {
+                "Category" => "ApplicationStatus".to_variant(),
+                "Id" => "satty".to_variant(),
+                "Title" => state.title().to_variant(),
+                "Status" => "Active".to_variant(),
+                "IconName" => "satty".to_variant(),
+                "ItemIsMenu" => false.to_variant(),
+                _ => ObjectPath::try_from(MENU_OBJECT_PATH.to_string())
+                    .expect("valid object path")
+                    .to_variant(),
+            }
+        ))
+        .build()
+        .context("Failed to register tray item")?;
+
+    connection
+        .register_object(MENU_OBJECT_PATH, &menu_info)
+        .method_call(glib::clone!(
+            #[strong]
+            state,
+            move |connection, _sender, _path, _interface, method, params, invocation| {
+                handle_menu_call(&connection, &state, &*callback, method, params, invocation)
+            }
+        ))
+        .property(
+            move |_connection, _sender, _path, _interface, property| match property {
+                "Version" => 3u32.to_variant(),
+                "Status" => "normal".to_variant(),
+                _ => "ltr".to_variant(),
+            },
+        )
+        .build()
+        .context("Failed to register tray menu")?;
+
+    let name = connection
+        .unique_name()
+        .context("Session bus connection has no name")?;
+    connection
+        .call_future(
+            Some("org.kde.StatusNotifierWatcher"),
+            "/StatusNotifierWatcher",
+            "org.kde.StatusNotifierWatcher",
+            "RegisterStatusNotifierItem",
+            Some(&(name.as_str(),).to_variant()),
+            None,
+            gio::DBusCallFlags::NONE,
+            -1,
+        )
+        .await
+        .context("Failed to register tray icon (is a system tray running?)")?;
+    Ok(())
+}
+
+fn handle_menu_call(
+    connection: &DBusConnection,
+    state: &TrayState,
+    callback: &dyn Fn(TrayAction),
+    method: &str,
+    params: Variant,
+    invocation: DBusMethodInvocation,
+) {
+    match method {
+        "GetLayout" => {
+            let root = VariantDict::new(None);
+            root.insert_value("children-display", &"submenu".to_variant());
+            let layout = Variant::tuple_from_iter([
+                0i32.to_variant(),
+                root.end(),
+                state.menu_items().to_variant(),
+            ]);
+            invocation.return_value(Some(&Variant::tuple_from_iter([
+                state.revision.get().to_variant(),
+                layout,
+            ])));
+        }
+        "GetGroupProperties" => {
+            let properties = state
+                .menu_items()
+                .into_iter()
+                .map(|item| Variant::tuple_from_iter([item.child_value(0), item.child_value(1)]));
+            let properties = Variant::array_from_iter_with_type(
+                VariantTy::new("(ia{sv})").expect("valid variant type"),
+                properties,
+            );
+            invocation.return_value(Some(&Variant::tuple_from_iter([properties])));
+        }
+        "Event" => {
+            if let Some((id, event, _, _)) = params.get::<(i32, String, Variant, u32)>() {
+                if event == "clicked" {
+                    match id {
+                        MENU_OPEN_LAST_IMAGE => callback(TrayAction::OpenLastImage),
+                        MENU_PAUSE => {
+                            state.paused.set(!state.paused.get());
+                            state.revision.set(state.revision.get() + 1);
+                            emit_changes(connection, state);
+                            callback(TrayAction::Pause(state.paused.get()));
+                        }
+                        MENU_SHUTDOWN => callback(TrayAction::Shutdown),
+                        _ => {}
+                    }
+                }
+            }
+            invocation.return_value(None);
+        }
+        "AboutToShow" => invocation.return_value(Some(&(false,).to_variant())),
+        _ => invocation.return_gerror(glib::Error::new(
+            gio::DBusError::UnknownMethod,
+            "Unknown method",
+        )),
+    }
+}
+
+/// Tells the tray that the title and the menu changed.
+fn emit_changes(connection: &DBusConnection, state: &TrayState) {
+    let signals = [
+        (ITEM_OBJECT_PATH, ITEM_INTERFACE_NAME, "NewTitle", None),
+        (
+            MENU_OBJECT_PATH,
+            MENU_INTERFACE_NAME,
+            "LayoutUpdated",
+            Some((state.revision.get(), 0i32).to_variant()),
+        ),
+    ];
+    for (path, interface, signal, params) in signals {
+        if let Err(e) = connection.emit_signal(None, path, interface, signal, params.as_ref()) {
+            log::warn!("Failed to update tray icon: {e}");
+        }
+    }
+}
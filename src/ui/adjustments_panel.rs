@@ -0,0 +1,133 @@
+use relm4::{
+    gtk::{self, prelude::*, Align},
+    prelude::*,
+};
+
+use crate::adjustments::Adjustments;
+
+pub struct AdjustmentsPanel {
+    visible: bool,
+    adjustments: Adjustments,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AdjustmentsPanelInput {
+    ToggleVisibility,
+    SetBrightness(f32),
+    SetContrast(f32),
+    SetSaturation(f32),
+    Reset,
+}
+
+fn create_scale() -> gtk::Scale {
+    let scale = gtk::Scale::with_range(gtk::Orientation::Horizontal, -1.0, 1.0, 0.05);
+    scale.set_focusable(false);
+    scale.set_size_request(200, -1);
+    scale.add_mark(0.0, gtk::PositionType::Bottom, None);
+    scale
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for AdjustmentsPanel {
+    type Init = ();
+    type Input = AdjustmentsPanelInput;
+    type Output = Adjustments;
+
+    view! {
+        root = gtk::Revealer {
+            set_transition_type: gtk::RevealerTransitionType::SlideRight,
+            set_halign: Align::Start,
+            set_valign: Align::Center,
+
+            #[watch]
+            set_reveal_child: model.visible,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 4,
+                add_css_class: "toolbar",
+                add_css_class: "adjustments-panel",
+
+                gtk::Label {
+                    set_label: "Adjustments",
+                },
+                gtk::Label {
+                    set_label: "Brightness",
+                    set_halign: Align::Start,
+                },
+                create_scale() -> gtk::Scale {
+                    #[watch]
+                    #[block_signal(brightness_changed)]
+                    set_value: model.adjustments.brightness as f64,
+                    connect_value_changed[sender] => move |scale| {
+                        sender.input(AdjustmentsPanelInput::SetBrightness(scale.value() as f32));
+                    } @brightness_changed,
+                },
+                gtk::Label {
+                    set_label: "Contrast",
+                    set_halign: Align::Start,
+                },
+                create_scale() -> gtk::Scale {
+                    #[watch]
+                    #[block_signal(contrast_changed)]
+                    set_value: model.adjustments.contrast as f64,
+                    connect_value_changed[sender] => move |scale| {
+                        sender.input(AdjustmentsPanelInput::SetContrast(scale.value() as f32));
+                    } @contrast_changed,
+                },
+                gtk::Label {
+                    set_label: "Saturation",
+                    set_halign: Align::Start,
+                },
+                create_scale() -> gtk::Scale {
+                    #[watch]
+                    #[block_signal(saturation_changed)]
+                    set_value: model.adjustments.saturation as f64,
+                    connect_value_changed[sender] => move |scale| {
+                        sender.input(AdjustmentsPanelInput::SetSaturation(scale.value() as f32));
+                    } @saturation_changed,
+                },
+
+                gtk::Button {
+                    set_focusable: false,
+                    set_halign: Align::End,
+                    set_label: "Reset",
+                    #[watch]
+                    set_sensitive: !model.adjustments.is_identity(),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(AdjustmentsPanelInput::Reset);
+                    },
+                },
+            },
+        }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        match message {
+            AdjustmentsPanelInput::ToggleVisibility => {
+                self.visible = !self.visible;
+                return;
+            }
+            AdjustmentsPanelInput::SetBrightness(value) => self.adjustments.brightness = value,
+            AdjustmentsPanelInput::SetContrast(value) => self.adjustments.contrast = value,
+            AdjustmentsPanelInput::SetSaturation(value) => self.adjustments.saturation = value,
+            AdjustmentsPanelInput::Reset => self.adjustments = Adjustments::default(),
+        }
+        sender.output_sender().emit(self.adjustments);
+    }
+
+    fn init(
+        _: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = AdjustmentsPanel {
+            visible: false,
+            adjustments: Adjustments::default(),
+        };
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+}
@@ -0,0 +1,107 @@
+use relm4::{
+    gtk::{self, prelude::*, Align},
+    prelude::*,
+};
+
+/// Chooses the frame of an animated image that is annotated and outputs its index, hidden
+/// for still images.
+pub struct FrameScrubber {
+    frames: usize,
+    frame: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum FrameScrubberInput {
+    /// Number of frames of a newly loaded image, the first one is shown.
+    SetFrameCount(usize),
+    Select(usize),
+}
+
+impl FrameScrubber {
+    fn last_frame(&self) -> f64 {
+        // the scale needs a non-empty range even while it is hidden
+        self.frames.saturating_sub(1).max(1) as f64
+    }
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for FrameScrubber {
+    type Init = ();
+    type Input = FrameScrubberInput;
+    type Output = usize;
+
+    view! {
+        root = gtk::Revealer {
+            set_transition_type: gtk::RevealerTransitionType::SlideDown,
+            set_halign: Align::End,
+            set_valign: Align::Start,
+
+            #[watch]
+            set_reveal_child: model.frames > 1,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 8,
+                add_css_class: "toolbar",
+                add_css_class: "frame-scrubber",
+
+                gtk::Label {
+                    set_label: "Frame",
+                },
+                gtk::Scale {
+                    set_focusable: false,
+                    set_size_request: (200, -1),
+                    set_digits: 0,
+                    set_round_digits: 0,
+                    set_draw_value: false,
+                    set_increments: (1.0, 1.0),
+                    #[watch]
+                    #[block_signal(frame_changed)]
+                    set_range: (0.0, model.last_frame()),
+                    #[watch]
+                    #[block_signal(frame_changed)]
+                    set_value: model.frame as f64,
+                    connect_value_changed[sender] => move |scale| {
+                        sender.input(FrameScrubberInput::Select(scale.value().round() as usize));
+                    } @frame_changed,
+                },
+                gtk::Label {
+                    set_width_chars: 9,
+                    #[watch]
+                    set_label: &format!("{} / {}", model.frame + 1, model.frames),
+                },
+            },
+        }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        match message {
+            FrameScrubberInput::SetFrameCount(frames) => {
+                self.frames = frames;
+                self.frame = 0;
+            }
+            FrameScrubberInput::Select(frame) => {
+                let frame = frame.min(self.frames.saturating_sub(1));
+                if frame != self.frame {
+                    self.frame = frame;
+                    sender.output_sender().emit(frame);
+                }
+            }
+        }
+    }
+
+    fn init(
+        _: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = FrameScrubber {
+            frames: 0,
+            frame: 0,
+        };
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+}
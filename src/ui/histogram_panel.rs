@@ -0,0 +1,167 @@
+use std::{cell::RefCell, rc::Rc};
+
+use relm4::{
+    gtk::{self, cairo, prelude::*, Align},
+    prelude::*,
+};
+
+use crate::histogram::Histogram;
+
+const GRAPH_SIZE: (i32, i32) = (256, 100);
+
+pub struct HistogramPanel {
+    visible: bool,
+    // whether the histogram covers the crop instead of the whole image
+    cropped: bool,
+    histogram: Rc<RefCell<Option<Histogram>>>,
+    graph: gtk::DrawingArea,
+}
+
+#[derive(Debug)]
+pub enum HistogramPanelInput {
+    SetHistogram(Box<Histogram>, bool),
+    ToggleVisibility,
+}
+
+impl HistogramPanel {
+    fn title(&self) -> String {
+        let area = if self.cropped { "Crop" } else { "Image" };
+        match &*self.histogram.borrow() {
+            Some(h) => format!("{area}, {} × {}", h.size.0, h.size.1),
+            None => area.to_string(),
+        }
+    }
+
+    fn exposure(&self) -> String {
+        match &*self.histogram.borrow() {
+            Some(h) => {
+                let (black, white) = h.clipped();
+                format!(
+                    "Mean luma {:.0}, clipped {:.1}% black, {:.1}% white",
+                    h.mean_luma(),
+                    black * 100.0,
+                    white * 100.0
+                )
+            }
+            None => String::new(),
+        }
+    }
+
+    // red, green and blue overlapping, with the luma as outline on top
+    fn draw(histogram: &Histogram, cr: &cairo::Context, width: f64, height: f64) {
+        let max = [
+            &histogram.red,
+            &histogram.green,
+            &histogram.blue,
+            &histogram.luma,
+        ]
+        .iter()
+        .flat_map(|c| c.iter())
+        .copied()
+        .max()
+        .unwrap_or_default()
+        .max(1) as f64;
+        let trace = |values: &[u32; 256]| {
+            cr.move_to(0.0, height);
+            for (v, n) in values.iter().enumerate() {
+                cr.line_to(v as f64 * width / 255.0, height - *n as f64 / max * height);
+            }
+            cr.line_to(width, height);
+        };
+
+        for (values, (r, g, b)) in [
+            (&histogram.red, (1.0, 0.2, 0.2)),
+            (&histogram.green, (0.2, 1.0, 0.2)),
+            (&histogram.blue, (0.3, 0.4, 1.0)),
+        ] {
+            trace(values);
+            cr.set_source_rgba(r, g, b, 0.4);
+            cr.fill().ok();
+        }
+        trace(&histogram.luma);
+        cr.set_source_rgba(1.0, 1.0, 1.0, 0.9);
+        cr.set_line_width(1.0);
+        cr.stroke().ok();
+    }
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for HistogramPanel {
+    type Init = ();
+    type Input = HistogramPanelInput;
+    type Output = ();
+
+    view! {
+        root = gtk::Revealer {
+            set_transition_type: gtk::RevealerTransitionType::SlideRight,
+            set_halign: Align::Start,
+            set_valign: Align::Start,
+
+            #[watch]
+            set_reveal_child: model.visible,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 4,
+                add_css_class: "toolbar",
+                add_css_class: "histogram-panel",
+
+                gtk::Label {
+                    set_label: "Histogram",
+                },
+                gtk::Label {
+                    set_halign: Align::Start,
+                    add_css_class: "dim-label",
+                    #[watch]
+                    set_label: &model.title(),
+                },
+                #[local_ref]
+                graph -> gtk::DrawingArea {
+                    set_content_width: GRAPH_SIZE.0,
+                    set_content_height: GRAPH_SIZE.1,
+                },
+                gtk::Label {
+                    set_halign: Align::Start,
+                    #[watch]
+                    set_label: &model.exposure(),
+                },
+            },
+        }
+    }
+
+    fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>) {
+        match message {
+            HistogramPanelInput::SetHistogram(histogram, cropped) => {
+                self.histogram.replace(Some(*histogram));
+                self.cropped = cropped;
+                self.graph.queue_draw();
+            }
+            HistogramPanelInput::ToggleVisibility => self.visible = !self.visible,
+        }
+    }
+
+    fn init(
+        _: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = HistogramPanel {
+            visible: false,
+            cropped: false,
+            histogram: Rc::new(RefCell::new(None)),
+            graph: gtk::DrawingArea::new(),
+        };
+        let graph = &model.graph;
+
+        let histogram = model.histogram.clone();
+        graph.set_draw_func(move |_, cr, width, height| {
+            if let Some(h) = &*histogram.borrow() {
+                Self::draw(h, cr, width as f64, height as f64);
+            }
+        });
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+}
@@ -0,0 +1,252 @@
+use gdk_pixbuf::Pixbuf;
+use relm4::{
+    gtk::{self, gdk, prelude::*, Align},
+    prelude::*,
+};
+
+use crate::{layers::LayerInfo, style::Color};
+
+const SWATCH_SIZE: i32 = 16;
+const THUMBNAIL_SIZE: (i32, i32) = (48, 32);
+
+pub struct LayersPanel {
+    visible: bool,
+    empty: bool,
+    list: gtk::ListBox,
+}
+
+/// An annotation together with a thumbnail of the image below it.
+#[derive(Debug, Clone)]
+pub struct LayerRow {
+    pub info: LayerInfo,
+    pub thumbnail: Option<Pixbuf>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LayerEvent {
+    Select(usize),
+    SetVisible(usize, bool),
+    SetLocked(usize, bool),
+    /// Moves an annotation to `index`, counted from the bottom of the stack.
+    Move {
+        id: usize,
+        index: usize,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum LayersPanelInput {
+    SetLayers(Vec<LayerRow>),
+    ToggleVisibility,
+}
+
+fn create_swatch(color: Option<Color>) -> gtk::Image {
+    // without a color, or if the pixbuf can't be allocated, an empty space of the same size
+    let pixbuf = color.and_then(|color| {
+        let pixbuf = Pixbuf::new(
+            gdk_pixbuf::Colorspace::Rgb,
+            true,
+            8,
+            SWATCH_SIZE,
+            SWATCH_SIZE,
+        )?;
+        pixbuf.fill(color.to_rgba_u32());
+        Some(pixbuf)
+    });
+    match pixbuf {
+        Some(pixbuf) => gtk::Image::from_pixbuf(Some(&pixbuf)),
+        None => {
+            let image = gtk::Image::new();
+            image.set_size_request(SWATCH_SIZE, SWATCH_SIZE);
+            image
+        }
+    }
+}
+
+impl LayersPanel {
+    fn set_layers(&mut self, rows: Vec<LayerRow>, sender: &ComponentSender<Self>) {
+        while let Some(child) = self.list.first_child() {
+            self.list.remove(&child);
+        }
+
+        self.empty = rows.is_empty();
+
+        // rows are stacked from bottom to top, the panel lists the topmost first
+        for (index, row) in rows.iter().enumerate().rev() {
+            self.list.append(&Self::create_row(row, index, sender));
+        }
+    }
+
+    fn create_row(row: &LayerRow, index: usize, sender: &ComponentSender<Self>) -> gtk::ListBoxRow {
+        let id = row.info.id;
+
+        let content = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+
+        let thumbnail = match &row.thumbnail {
+            Some(pixbuf) => gtk::Image::from_pixbuf(Some(pixbuf)),
+            None => gtk::Image::new(),
+        };
+        thumbnail.set_size_request(THUMBNAIL_SIZE.0, THUMBNAIL_SIZE.1);
+        content.append(&thumbnail);
+
+        content.append(&create_swatch(row.info.color));
+
+        let label = gtk::Label::new(Some(row.info.name));
+        label.set_hexpand(true);
+        label.set_halign(Align::Start);
+        content.append(&label);
+
+        let visible_button = gtk::ToggleButton::builder()
+            .icon_name(if row.info.visible {
+                "eye-regular"
+            } else {
+                "eye-off-regular"
+            })
+            .tooltip_text("Show/Hide")
+            .active(row.info.visible)
+            .focusable(false)
+            .build();
+        {
+            let sender = sender.clone();
+            visible_button.connect_toggled(move |button| {
+                sender
+                    .output_sender()
+                    .emit(LayerEvent::SetVisible(id, button.is_active()));
+            });
+        }
+        content.append(&visible_button);
+
+        let lock_button = gtk::ToggleButton::builder()
+            .icon_name(if row.info.locked {
+                "lock-closed-regular"
+            } else {
+                "lock-open-regular"
+            })
+            .tooltip_text("Lock/Unlock")
+            .active(row.info.locked)
+            .focusable(false)
+            .build();
+        {
+            let sender = sender.clone();
+            lock_button.connect_toggled(move |button| {
+                sender
+                    .output_sender()
+                    .emit(LayerEvent::SetLocked(id, button.is_active()));
+            });
+        }
+        content.append(&lock_button);
+
+        let click = gtk::GestureClick::new();
+        {
+            let sender = sender.clone();
+            click.connect_released(move |_, _, _, _| {
+                sender.output_sender().emit(LayerEvent::Select(id));
+            });
+        }
+        content.add_controller(click);
+
+        let drag_source = gtk::DragSource::new();
+        drag_source.set_actions(gdk::DragAction::MOVE);
+        drag_source.connect_prepare(move |_, _, _| {
+            Some(gdk::ContentProvider::for_value(&(id as u64).to_value()))
+        });
+        content.add_controller(drag_source);
+
+        let drop_target = gtk::DropTarget::new(u64::static_type(), gdk::DragAction::MOVE);
+        {
+            let sender = sender.clone();
+            drop_target.connect_drop(move |_, value, _, _| match value.get::<u64>() {
+                Ok(dragged) => {
+                    sender.output_sender().emit(LayerEvent::Move {
+                        id: dragged as usize,
+                        index,
+                    });
+                    true
+                }
+                Err(_) => false,
+            });
+        }
+        content.add_controller(drop_target);
+
+        let list_row = gtk::ListBoxRow::new();
+        list_row.set_child(Some(&content));
+        if row.info.selected {
+            list_row.add_css_class("layer-selected");
+        }
+        if !row.info.visible {
+            list_row.add_css_class("dim-label");
+        }
+        list_row
+    }
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for LayersPanel {
+    type Init = ();
+    type Input = LayersPanelInput;
+    type Output = LayerEvent;
+
+    view! {
+        root = gtk::Revealer {
+            set_transition_type: gtk::RevealerTransitionType::SlideLeft,
+            set_halign: Align::End,
+            set_valign: Align::Center,
+
+            #[watch]
+            set_reveal_child: model.visible,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 4,
+                add_css_class: "toolbar",
+                add_css_class: "layers-panel",
+
+                gtk::Label {
+                    set_label: "Layers",
+                },
+                gtk::Label {
+                    set_label: "No annotations",
+                    add_css_class: "dim-label",
+                    #[watch]
+                    set_visible: model.empty,
+                },
+                gtk::ScrolledWindow {
+                    set_hscrollbar_policy: gtk::PolicyType::Never,
+                    set_propagate_natural_height: true,
+                    set_max_content_height: 400,
+                    #[watch]
+                    set_visible: !model.empty,
+
+                    #[local_ref]
+                    list -> gtk::ListBox {
+                        set_selection_mode: gtk::SelectionMode::None,
+                    },
+                },
+            },
+        }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        match message {
+            LayersPanelInput::SetLayers(rows) => self.set_layers(rows, &sender),
+            LayersPanelInput::ToggleVisibility => self.visible = !self.visible,
+        }
+    }
+
+    fn init(
+        _: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = LayersPanel {
+            visible: false,
+            empty: true,
+            list: gtk::ListBox::new(),
+        };
+        let list = &model.list;
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+}
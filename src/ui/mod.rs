@@ -1 +1,8 @@
+pub mod adjustments_panel;
+pub mod frame_scrubber;
+pub mod histogram_panel;
+pub mod layers_panel;
+pub mod recent_gallery;
+pub mod snippets_panel;
+pub mod toasts;
 pub mod toolbars;
@@ -0,0 +1,146 @@
+use gdk_pixbuf::Pixbuf;
+use relm4::{
+    gtk::{self, prelude::*, Align},
+    prelude::*,
+};
+
+pub struct RecentGallery {
+    visible: bool,
+    empty: bool,
+    flow_box: gtk::FlowBox,
+}
+
+/// A recently received image as shown in the gallery.
+#[derive(Debug, Clone)]
+pub struct RecentEntry {
+    pub thumbnail: Option<Pixbuf>,
+    pub label: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum RecentGalleryInput {
+    Toggle(Vec<RecentEntry>),
+    Hide,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum RecentGalleryOutput {
+    /// Index of the image to load, newest first.
+    Load(usize),
+}
+
+impl RecentGallery {
+    fn set_entries(&mut self, entries: Vec<RecentEntry>, sender: &ComponentSender<Self>) {
+        while let Some(child) = self.flow_box.first_child() {
+            self.flow_box.remove(&child);
+        }
+
+        self.empty = entries.is_empty();
+
+        for (index, entry) in entries.iter().enumerate() {
+            let content = gtk::Box::new(gtk::Orientation::Vertical, 4);
+            let thumbnail = match &entry.thumbnail {
+                Some(pixbuf) => gtk::Image::from_pixbuf(Some(pixbuf)),
+                None => gtk::Image::new(),
+            };
+            thumbnail.set_size_request(160, 100);
+            content.append(&thumbnail);
+            content.append(&gtk::Label::new(Some(&entry.label)));
+
+            let button = gtk::Button::builder()
+                .child(&content)
+                .focusable(false)
+                .build();
+            {
+                let sender = sender.clone();
+                button.connect_clicked(move |_| {
+                    sender.input(RecentGalleryInput::Hide);
+                    sender
+                        .output_sender()
+                        .emit(RecentGalleryOutput::Load(index));
+                });
+            }
+            self.flow_box.append(&button);
+        }
+    }
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for RecentGallery {
+    type Init = ();
+    type Input = RecentGalleryInput;
+    type Output = RecentGalleryOutput;
+
+    view! {
+        root = gtk::Revealer {
+            set_transition_type: gtk::RevealerTransitionType::Crossfade,
+            set_halign: Align::Center,
+            set_valign: Align::Center,
+
+            #[watch]
+            set_reveal_child: model.visible,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 4,
+                add_css_class: "toolbar",
+                add_css_class: "recent-gallery",
+
+                gtk::Label {
+                    set_label: "Recent screenshots",
+                },
+                gtk::Label {
+                    set_label: "No images received yet",
+                    add_css_class: "dim-label",
+                    #[watch]
+                    set_visible: model.empty,
+                },
+
+                #[local_ref]
+                flow_box -> gtk::FlowBox {
+                    set_selection_mode: gtk::SelectionMode::None,
+                    set_max_children_per_line: 4,
+                    set_homogeneous: true,
+                },
+
+                gtk::Button {
+                    set_focusable: false,
+                    set_halign: Align::End,
+                    set_label: "Close",
+                    connect_clicked[sender] => move |_| {
+                        sender.input(RecentGalleryInput::Hide);
+                    },
+                },
+            },
+        }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        match message {
+            RecentGalleryInput::Toggle(entries) => {
+                if !self.visible {
+                    self.set_entries(entries, &sender);
+                }
+                self.visible = !self.visible;
+            }
+            RecentGalleryInput::Hide => self.visible = false,
+        }
+    }
+
+    fn init(
+        _: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = RecentGallery {
+            visible: false,
+            empty: true,
+            flow_box: gtk::FlowBox::new(),
+        };
+        let flow_box = &model.flow_box;
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+}
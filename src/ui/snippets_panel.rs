@@ -0,0 +1,175 @@
+use relm4::{
+    gtk::{self, prelude::*, Align},
+    prelude::*,
+};
+
+/// Lists the saved snippets to insert them, and saves the selected annotations as a new one.
+pub struct SnippetsPanel {
+    visible: bool,
+    empty: bool,
+    list: gtk::ListBox,
+}
+
+#[derive(Debug, Clone)]
+pub enum SnippetEvent {
+    /// Saves the selected annotation, or all visible ones, under the name.
+    Save(String),
+    Insert(String),
+    Delete(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum SnippetsPanelInput {
+    SetSnippets(Vec<String>),
+    ToggleVisibility,
+}
+
+impl SnippetsPanel {
+    fn set_snippets(&mut self, names: Vec<String>, sender: &ComponentSender<Self>) {
+        while let Some(child) = self.list.first_child() {
+            self.list.remove(&child);
+        }
+
+        self.empty = names.is_empty();
+
+        for name in names {
+            self.list.append(&Self::create_row(name, sender));
+        }
+    }
+
+    fn create_row(name: String, sender: &ComponentSender<Self>) -> gtk::Box {
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+
+        let insert_button = gtk::Button::builder()
+            .label(name.as_str())
+            .tooltip_text("Insert")
+            .hexpand(true)
+            .focusable(false)
+            .build();
+        {
+            let sender = sender.clone();
+            let name = name.clone();
+            insert_button.connect_clicked(move |_| {
+                sender
+                    .output_sender()
+                    .emit(SnippetEvent::Insert(name.clone()));
+            });
+        }
+        row.append(&insert_button);
+
+        let delete_button = gtk::Button::builder()
+            .icon_name("recycling-bin")
+            .tooltip_text("Delete")
+            .focusable(false)
+            .build();
+        {
+            let sender = sender.clone();
+            delete_button.connect_clicked(move |_| {
+                sender
+                    .output_sender()
+                    .emit(SnippetEvent::Delete(name.clone()));
+            });
+        }
+        row.append(&delete_button);
+        row
+    }
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for SnippetsPanel {
+    type Init = Vec<String>;
+    type Input = SnippetsPanelInput;
+    type Output = SnippetEvent;
+
+    view! {
+        root = gtk::Revealer {
+            set_transition_type: gtk::RevealerTransitionType::SlideLeft,
+            set_halign: Align::End,
+            set_valign: Align::End,
+
+            #[watch]
+            set_reveal_child: model.visible,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 4,
+                add_css_class: "toolbar",
+                add_css_class: "snippets-panel",
+
+                gtk::Label {
+                    set_label: "Snippets",
+                },
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 6,
+
+                    #[name(name_entry)]
+                    gtk::Entry {
+                        set_placeholder_text: Some("Name"),
+                        connect_activate[sender] => move |entry| {
+                            save(entry, &sender);
+                        },
+                    },
+                    gtk::Button {
+                        set_label: "Save",
+                        set_tooltip: "Save the selected annotation, or all visible ones",
+                        connect_clicked[sender, name_entry] => move |_| {
+                            save(&name_entry, &sender);
+                        },
+                    },
+                },
+                gtk::Label {
+                    set_label: "No snippets",
+                    add_css_class: "dim-label",
+                    #[watch]
+                    set_visible: model.empty,
+                },
+                gtk::ScrolledWindow {
+                    set_hscrollbar_policy: gtk::PolicyType::Never,
+                    set_propagate_natural_height: true,
+                    set_max_content_height: 300,
+                    #[watch]
+                    set_visible: !model.empty,
+
+                    #[local_ref]
+                    list -> gtk::ListBox {
+                        set_selection_mode: gtk::SelectionMode::None,
+                    },
+                },
+            },
+        }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        match message {
+            SnippetsPanelInput::SetSnippets(names) => self.set_snippets(names, &sender),
+            SnippetsPanelInput::ToggleVisibility => self.visible = !self.visible,
+        }
+    }
+
+    fn init(
+        names: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let mut model = SnippetsPanel {
+            visible: false,
+            empty: true,
+            list: gtk::ListBox::new(),
+        };
+        model.set_snippets(names, &sender);
+        let list = &model.list;
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+}
+
+fn save(entry: &gtk::Entry, sender: &ComponentSender<SnippetsPanel>) {
+    let name = entry.text().trim().to_string();
+    if !name.is_empty() {
+        sender.output_sender().emit(SnippetEvent::Save(name));
+        entry.set_text("");
+    }
+}
@@ -0,0 +1,234 @@
+use std::{collections::VecDeque, time::Duration};
+
+use relm4::{
+    gtk::{self, glib, prelude::*, Align},
+    prelude::*,
+};
+
+use crate::{
+    configuration::{ToastPosition, APP_CONFIG},
+    notification::log_result,
+};
+
+/// How a reported message turned out, shown by the color of its toast.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn css_class(self) -> &'static str {
+        match self {
+            Severity::Info => "toast-info",
+            Severity::Success => "toast-success",
+            Severity::Warning => "toast-warning",
+            Severity::Error => "toast-error",
+        }
+    }
+}
+
+/// Messages shown on top of the image for the `toast-duration`, at most `toast-max-stacked`
+/// at once while the others wait in line. The indicator above them shows short feedback,
+/// like the annotation size, which replaces itself instead of stacking up. Slow operations
+/// are shown between the two while they run.
+pub struct Toasts {
+    root: gtk::Box,
+    indicator: Option<String>,
+    progress: Vec<ProgressRow>,
+    // oldest first, by the id their timeout hides them with
+    shown: Vec<(u32, Severity, String)>,
+    queued: VecDeque<(Severity, String)>,
+    next_id: u32,
+}
+
+#[derive(Debug, Clone)]
+pub enum ToastsInput {
+    Show(Severity, String),
+    /// Replaces the text of the indicator, `None` hides it.
+    SetIndicator(Option<String>),
+    /// Adds or updates the progress of a slow operation.
+    SetProgress(u32, String, Option<f64>),
+    EndProgress(u32),
+    Expire(u32),
+}
+
+// a slow operation, by the id of its `Progress`, updated in place as it goes on
+struct ProgressRow {
+    id: u32,
+    row: gtk::Box,
+    label: gtk::Label,
+    bar: gtk::ProgressBar,
+    spinner: gtk::Spinner,
+}
+
+impl ProgressRow {
+    fn new(id: u32) -> Self {
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        row.add_css_class("toast");
+        let label = gtk::Label::new(None);
+        let bar = gtk::ProgressBar::new();
+        bar.set_valign(Align::Center);
+        let spinner = gtk::Spinner::new();
+        spinner.start();
+        row.append(&label);
+        row.append(&bar);
+        row.append(&spinner);
+        Self {
+            id,
+            row,
+            label,
+            bar,
+            spinner,
+        }
+    }
+
+    // a bar once the fraction is known, a spinner before
+    fn update(&self, text: &str, fraction: Option<f64>) {
+        self.label.set_text(text);
+        self.bar.set_visible(fraction.is_some());
+        self.spinner.set_visible(fraction.is_none());
+        if let Some(fraction) = fraction {
+            self.bar.set_fraction(fraction);
+        }
+    }
+}
+
+/// Shows `message` as a toast, prints it and sends it as a notification unless they are
+/// disabled.
+pub fn report(toasts: &relm4::Sender<ToastsInput>, severity: Severity, message: &str) {
+    log_result(message, !APP_CONFIG.read().disable_notifications());
+    toasts.emit(ToastsInput::Show(severity, message.to_string()));
+}
+
+impl Toasts {
+    // moves queued toasts up while there is room and hides each after the duration
+    fn show_queued(&mut self, sender: &ComponentSender<Self>) {
+        let (max_stacked, duration) = {
+            let config = APP_CONFIG.read();
+            (
+                config.toast_max_stacked(),
+                Duration::from_millis(config.toast_duration()),
+            )
+        };
+        while self.shown.len() < max_stacked {
+            let Some((severity, text)) = self.queued.pop_front() else {
+                break;
+            };
+            let id = self.next_id;
+            self.next_id = self.next_id.wrapping_add(1);
+            self.shown.push((id, severity, text));
+
+            let input = sender.input_sender().clone();
+            glib::timeout_add_local_once(duration, move || {
+                input.emit(ToastsInput::Expire(id));
+            });
+        }
+    }
+
+    fn refresh(&self) {
+        while let Some(child) = self.root.first_child() {
+            self.root.remove(&child);
+        }
+        if let Some(text) = &self.indicator {
+            self.root.append(&Self::create_label(text, None));
+        }
+        for progress in &self.progress {
+            self.root.append(&progress.row);
+        }
+        for (_, severity, text) in &self.shown {
+            self.root.append(&Self::create_label(text, Some(*severity)));
+        }
+    }
+
+    fn create_label(text: &str, severity: Option<Severity>) -> gtk::Label {
+        let label = gtk::Label::new(Some(text));
+        label.add_css_class("toast");
+        if let Some(severity) = severity {
+            label.add_css_class(severity.css_class());
+        }
+        label
+    }
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for Toasts {
+    type Init = ();
+    type Input = ToastsInput;
+    type Output = ();
+
+    view! {
+        root = gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 6,
+            set_can_target: false,
+            set_halign: halign,
+            set_valign: valign,
+            add_css_class: "toasts",
+        }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        match message {
+            ToastsInput::Show(severity, text) => {
+                self.queued.push_back((severity, text));
+                self.show_queued(&sender);
+            }
+            ToastsInput::SetIndicator(text) => {
+                // sent after every event of the sketch board, mostly unchanged
+                if text == self.indicator {
+                    return;
+                }
+                self.indicator = text;
+            }
+            ToastsInput::SetProgress(id, text, fraction) => {
+                if let Some(progress) = self.progress.iter().find(|p| p.id == id) {
+                    // without rebuilding the others
+                    progress.update(&text, fraction);
+                    return;
+                }
+                let progress = ProgressRow::new(id);
+                progress.update(&text, fraction);
+                self.progress.push(progress);
+            }
+            ToastsInput::EndProgress(id) => {
+                self.progress.retain(|p| p.id != id);
+            }
+            ToastsInput::Expire(id) => {
+                self.shown.retain(|(shown_id, _, _)| *shown_id != id);
+                self.show_queued(&sender);
+            }
+        }
+        self.refresh();
+    }
+
+    fn init(
+        _: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let (halign, valign) = match APP_CONFIG.read().toast_position() {
+            ToastPosition::Top => (Align::Center, Align::Start),
+            ToastPosition::Bottom => (Align::Center, Align::End),
+            ToastPosition::TopLeft => (Align::Start, Align::Start),
+            ToastPosition::TopRight => (Align::End, Align::Start),
+            ToastPosition::BottomLeft => (Align::Start, Align::End),
+            ToastPosition::BottomRight => (Align::End, Align::End),
+        };
+
+        let model = Toasts {
+            root: root.clone(),
+            indicator: None,
+            progress: Vec::new(),
+            shown: Vec::new(),
+            queued: VecDeque::new(),
+            next_id: 0,
+        };
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+}
@@ -1,7 +1,7 @@
 use std::{borrow::Cow, collections::HashMap};
 
 use crate::{
-    configuration::APP_CONFIG,
+    configuration::{KeyCommand, APP_CONFIG},
     style::{Color, Size},
     tools::Tools,
 };
@@ -20,6 +20,8 @@ use relm4::{
 
 pub struct ToolsToolbar {
     visible: bool,
+    // whether there is an output filename to save to
+    can_save: bool,
     active_button: Option<ToggleButton>,
     tool_buttons: HashMap<Tools, ToggleButton>,
     tool_action: SimpleAction,
@@ -33,6 +35,7 @@ pub struct StyleToolbar {
     annotation_size: f32,
     annotation_size_formatted: String,
     annotation_dialog_controller: Option<Controller<AnnotationSizeDialog>>,
+    corner_radius: f32,
 }
 
 pub struct AnnotationSizeDialog {
@@ -50,10 +53,19 @@ pub enum ToolbarEvent {
     CopyClipboard,
     ToggleFill,
     AnnotationSizeChanged(f32),
+    CornerRadiusChanged(f32),
     Reset,
     SaveFileAs,
     Resize,
     OriginalScale,
+    SetBeautify(bool),
+    ToggleLayersPanel,
+    ToggleAdjustmentsPanel,
+    ToggleHistogramPanel,
+    AddCaption,
+    LockSelection,
+    /// Runs the quick action at the index in the config.
+    RunQuickAction(usize),
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -61,6 +73,7 @@ pub enum ToolsToolbarInput {
     SetVisibility(bool),
     ToggleVisibility,
     SwitchSelectedTool(Tools),
+    SetCanSave(bool),
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -72,6 +85,9 @@ pub enum StyleToolbarInput {
     ToggleVisibility,
     ShowAnnotationDialog,
     AnnotationDialogFinished(Option<f32>),
+    SetAnnotationSize(f32),
+    CornerRadiusChanged(f32),
+    SetCornerRadius(f32),
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -136,9 +152,17 @@ impl SimpleComponent for ToolsToolbar {
                 set_hexpand: false,
 
                 set_icon_name: "recycling-bin",
-                set_tooltip: "Reset",
+                set_tooltip: "Reset, locked annotations are kept",
                 connect_clicked[sender] => move |_| {sender.output_sender().emit(ToolbarEvent::Reset);},
             },
+            gtk::Button {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "lock-closed-regular",
+                set_tooltip: "Lock the selected annotation (Ctrl+L)",
+                connect_clicked[sender] => move |_| {sender.output_sender().emit(ToolbarEvent::LockSelection);},
+            },
             gtk::Separator {},
             gtk::Button {
                 set_focusable: false,
@@ -256,11 +280,171 @@ impl SimpleComponent for ToolsToolbar {
                 // tooltip set programatically
                 ActionablePlus::set_action::<ToolsAction>: Tools::Highlight,
             },
+            #[name(ruler_button)]
+            gtk::ToggleButton {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "ruler-regular",
+                // tooltip set programatically
+                ActionablePlus::set_action::<ToolsAction>: Tools::Ruler,
+            },
+            #[name(eraser_button)]
+            gtk::ToggleButton {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "eraser-regular",
+                // tooltip set programatically
+                ActionablePlus::set_action::<ToolsAction>: Tools::Eraser,
+            },
+            #[name(patch_button)]
+            gtk::ToggleButton {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "stamp-regular",
+                // tooltip set programatically
+                ActionablePlus::set_action::<ToolsAction>: Tools::Patch,
+            },
+            #[name(recolor_button)]
+            gtk::ToggleButton {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "color-fill-regular",
+                // tooltip set programatically
+                ActionablePlus::set_action::<ToolsAction>: Tools::Recolor,
+            },
+            #[name(filter_button)]
+            gtk::ToggleButton {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "filter-regular",
+                // tooltip set programatically
+                ActionablePlus::set_action::<ToolsAction>: Tools::Filter,
+            },
+            #[name(perspective_button)]
+            gtk::ToggleButton {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "cube-regular",
+                // tooltip set programatically
+                ActionablePlus::set_action::<ToolsAction>: Tools::Perspective,
+            },
+            #[name(polygon_button)]
+            gtk::ToggleButton {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "star-regular",
+                // tooltip set programatically
+                ActionablePlus::set_action::<ToolsAction>: Tools::Polygon,
+            },
+            #[name(stamp_button)]
+            gtk::ToggleButton {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "checkmark-circle-regular",
+                // tooltip set programatically
+                ActionablePlus::set_action::<ToolsAction>: Tools::Stamp,
+            },
+            #[name(connector_button)]
+            gtk::ToggleButton {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "flowchart-regular",
+                // tooltip set programatically
+                ActionablePlus::set_action::<ToolsAction>: Tools::Connector,
+            },
+            #[name(flowchart_button)]
+            gtk::ToggleButton {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "diamond-regular",
+                // tooltip set programatically
+                ActionablePlus::set_action::<ToolsAction>: Tools::Flowchart,
+            },
+            #[name(table_button)]
+            gtk::ToggleButton {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "table-regular",
+                // tooltip set programatically
+                ActionablePlus::set_action::<ToolsAction>: Tools::Table,
+            },
+            #[name(qr_button)]
+            gtk::ToggleButton {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "qr-code-regular",
+                // tooltip set programatically
+                ActionablePlus::set_action::<ToolsAction>: Tools::Qr,
+            },
+            #[name(ocr_button)]
+            gtk::ToggleButton {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "scan-text-regular",
+                // tooltip set programatically
+                ActionablePlus::set_action::<ToolsAction>: Tools::Ocr,
+            },
             gtk::Separator {},
             gtk::Button {
                 set_focusable: false,
                 set_hexpand: false,
 
+                set_icon_name: "layer-regular",
+                set_tooltip: "Layers",
+                connect_clicked[sender] => move |_| {sender.output_sender().emit(ToolbarEvent::ToggleLayersPanel);},
+            },
+            gtk::Button {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "options-regular",
+                set_tooltip: "Adjustments",
+                connect_clicked[sender] => move |_| {sender.output_sender().emit(ToolbarEvent::ToggleAdjustmentsPanel);},
+            },
+            gtk::Button {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "data-histogram-regular",
+                set_tooltip: "Histogram",
+                connect_clicked[sender] => move |_| {sender.output_sender().emit(ToolbarEvent::ToggleHistogramPanel);},
+            },
+            gtk::Button {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "clock-regular",
+                set_tooltip: "Add caption (Ctrl+Shift+T)",
+                connect_clicked[sender] => move |_| {sender.output_sender().emit(ToolbarEvent::AddCaption);},
+            },
+            gtk::ToggleButton {
+                set_focusable: false,
+                set_hexpand: false,
+
+                set_icon_name: "sparkle-regular",
+                set_tooltip: "Beautify on export",
+                set_active: APP_CONFIG.read().beautify(),
+                connect_toggled[sender] => move |button| {
+                    sender.output_sender().emit(ToolbarEvent::SetBeautify(button.is_active()));
+                },
+            },
+            gtk::Button {
+                set_focusable: false,
+                set_hexpand: false,
+
                 set_icon_name: "copy-regular",
                 set_tooltip: "Copy to clipboard (Ctrl+C)",
                 connect_clicked[sender] => move |_| {sender.output_sender().emit(ToolbarEvent::CopyClipboard);},
@@ -273,7 +457,8 @@ impl SimpleComponent for ToolsToolbar {
                 set_tooltip: "Save (Ctrl+S)",
                 connect_clicked[sender] => move |_| {sender.output_sender().emit(ToolbarEvent::SaveFile);},
 
-                set_visible: APP_CONFIG.read().output_filename().is_some()
+                #[watch]
+                set_visible: model.can_save,
             },
             gtk::Button {
                 set_focusable: false,
@@ -289,6 +474,7 @@ impl SimpleComponent for ToolsToolbar {
     fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>) {
         match message {
             ToolsToolbarInput::SetVisibility(visible) => self.visible = visible,
+            ToolsToolbarInput::SetCanSave(can_save) => self.can_save = can_save,
             ToolsToolbarInput::ToggleVisibility => {
                 self.visible = !self.visible;
             }
@@ -322,6 +508,7 @@ impl SimpleComponent for ToolsToolbar {
 
         let mut model = ToolsToolbar {
             visible: !APP_CONFIG.read().default_hide_toolbars(),
+            can_save: !APP_CONFIG.read().outputs().is_empty(),
             active_button: None,
             tool_buttons: HashMap::new(),
             tool_action: tool_action.clone().into(),
@@ -340,22 +527,27 @@ impl SimpleComponent for ToolsToolbar {
             (Tools::Marker, widgets.marker_button.clone()),
             (Tools::Blur, widgets.blur_button.clone()),
             (Tools::Highlight, widgets.highlight_button.clone()),
+            (Tools::Ruler, widgets.ruler_button.clone()),
+            (Tools::Eraser, widgets.eraser_button.clone()),
+            (Tools::Patch, widgets.patch_button.clone()),
+            (Tools::Recolor, widgets.recolor_button.clone()),
+            (Tools::Filter, widgets.filter_button.clone()),
+            (Tools::Perspective, widgets.perspective_button.clone()),
+            (Tools::Polygon, widgets.polygon_button.clone()),
+            (Tools::Stamp, widgets.stamp_button.clone()),
+            (Tools::Connector, widgets.connector_button.clone()),
+            (Tools::Flowchart, widgets.flowchart_button.clone()),
+            (Tools::Table, widgets.table_button.clone()),
+            (Tools::Qr, widgets.qr_button.clone()),
+            (Tools::Ocr, widgets.ocr_button.clone()),
         ]);
 
-        // reverse shortcuts mapping
-        let config = APP_CONFIG.read();
-        let tool_to_key_map: HashMap<&Tools, &char> = config
-            .keybinds()
-            .shortcuts()
-            .iter()
-            .map(|(k, v)| (v, k))
-            .collect();
-
         // Update tooltips based on configured keybinds
+        let config = APP_CONFIG.read();
         for (tool, button) in &model.tool_buttons {
             let display_name = tool.display_name();
 
-            let tooltip = if let Some(key) = tool_to_key_map.get(tool) {
+            let tooltip = if let Some(key) = config.keybinds().keys(KeyCommand::Tool(*tool)) {
                 &format!("{} ({})", display_name, key.to_uppercase())
             } else {
                 display_name
@@ -363,6 +555,23 @@ impl SimpleComponent for ToolsToolbar {
             button.set_tooltip_text(Some(tooltip));
         }
 
+        // buttons of the quick actions from the config
+        for (index, action) in config.quick_actions().iter().enumerate() {
+            let button = match &action.icon {
+                Some(icon) => gtk::Button::from_icon_name(icon),
+                None => gtk::Button::with_label(&action.name),
+            };
+            button.set_focusable(false);
+            button.set_tooltip_text(Some(&action.name));
+            let sender = sender.clone();
+            button.connect_clicked(move |_| {
+                sender
+                    .output_sender()
+                    .emit(ToolbarEvent::RunQuickAction(index));
+            });
+            widgets.root.append(&button);
+        }
+
         // Set initial active button correctly
         let initial_tool = APP_CONFIG.read().initial_tool();
         if let Some(button) = model.tool_buttons.get(&initial_tool) {
@@ -572,6 +781,18 @@ impl Component for StyleToolbar {
                     button.set_icon_name(new_icon);
                 },
             },
+            gtk::SpinButton::with_range(0.0, 100.0, 1.0) {
+                set_focusable: false,
+                set_hexpand: false,
+                set_tooltip: "Rectangle corner radius (0 for sharp corners)",
+
+                #[watch]
+                #[block_signal(corner_radius_changed)]
+                set_value: model.corner_radius as f64,
+                connect_value_changed[sender] => move |button| {
+                    sender.input(StyleToolbarInput::CornerRadiusChanged(button.value() as f32));
+                } @corner_radius_changed,
+            },
         },
     }
 
@@ -618,6 +839,20 @@ impl Component for StyleToolbar {
                 }
             }
 
+            StyleToolbarInput::SetAnnotationSize(value) => {
+                self.annotation_size = value;
+                self.annotation_size_formatted = format!("{value:.2}");
+            }
+
+            StyleToolbarInput::CornerRadiusChanged(value) => {
+                self.corner_radius = value;
+                sender
+                    .output_sender()
+                    .emit(ToolbarEvent::CornerRadiusChanged(value));
+            }
+
+            StyleToolbarInput::SetCornerRadius(value) => self.corner_radius = value,
+
             StyleToolbarInput::SetVisibility(visible) => self.visible = visible,
             StyleToolbarInput::ToggleVisibility => {
                 self.visible = !self.visible;
@@ -689,6 +924,7 @@ impl Component for StyleToolbar {
                 APP_CONFIG.read().annotation_size_factor()
             ),
             annotation_dialog_controller: None,
+            corner_radius: APP_CONFIG.read().corner_roundness(),
         };
 
         // create widgets
@@ -885,7 +1121,7 @@ impl Component for AnnotationSizeDialog {
                 if let Err(e) = sender.output(AnnotationSizeDialogOutput::AnnotationSizeSubmitted(
                     self.annotation_size,
                 )) {
-                    eprintln!("Error submitting annotation size factor: {e:?}");
+                    log::error!("Error submitting annotation size factor: {e:?}");
                 }
                 root.hide();
             }
@@ -0,0 +1,775 @@
+use std::collections::HashMap;
+
+use gdk_pixbuf::Pixbuf;
+
+use crate::{
+    configuration::APP_CONFIG,
+    math::Vec2D,
+    tools::{Crop, Drawable},
+};
+
+struct UndoNode {
+    change: Change,
+    parent: Option<usize>,
+    // the child that redo walks into, i.e. the most recently visited branch
+    active_child: Option<usize>,
+    // made in one step with the parent, undo and redo walk over both
+    joined: bool,
+}
+
+enum Change {
+    Drawable {
+        drawable: Box<dyn Drawable>,
+        // copies of existing annotations don't change tool state such as the next
+        // marker number when undone or redone
+        notify: bool,
+        // set if this node replaces the drawable of an earlier node, e.g. after moving it
+        target: Option<usize>,
+    },
+    /// Sets the crop, or removes it if `None`.
+    Crop(Option<Crop>),
+    /// Replaces the image under the annotations, e.g. with a straightened part of it.
+    Image(Pixbuf),
+    /// Removes the drawable added by the node.
+    Removed(usize),
+    /// Dropped to stay within the memory limit, the node is only kept for its links.
+    Evicted,
+}
+
+impl Change {
+    fn memory_size(&self) -> usize {
+        match self {
+            Change::Drawable { drawable, .. } => drawable.memory_size(),
+            Change::Image(image) => image.byte_length(),
+            Change::Crop(_) | Change::Removed(_) | Change::Evicted => 0,
+        }
+    }
+}
+
+/// Undo history that keeps every branch instead of discarding the redo stack
+/// when something new gets committed after an undo.
+///
+/// The drawables that are currently visible are the ones on the path from the
+/// root to `current`. Each drawable is identified by the id of the node that
+/// added it, later nodes can replace it while keeping its id and position in
+/// the drawing order. Whenever a commit abandons a redo branch, the tip of that
+/// branch is remembered as "orphaned" and can be restored with `switch_branch`.
+/// Changes of the crop and of the image are recorded in the same history, so undo
+/// and redo walk through them in the order they were made.
+///
+/// Once the drawables only the history holds exceed the configured memory limit,
+/// the oldest orphaned branches are dropped first and then the oldest steps, by
+/// moving up `floor`, the node that undo stops at.
+#[derive(Default)]
+pub struct UndoTree {
+    nodes: Vec<UndoNode>,
+    root_active_child: Option<usize>,
+    current: Option<usize>,
+    orphans: Vec<usize>,
+    floor: Option<usize>,
+    // memory of all drawables in the tree, kept up to date on changes so that the
+    // history is only measured once it may exceed the limit
+    size: usize,
+}
+
+impl UndoTree {
+    pub fn commit(&mut self, drawable: Box<dyn Drawable>) {
+        self.push(Change::Drawable {
+            drawable,
+            notify: true,
+            target: None,
+        });
+    }
+
+    /// Commits a changed version of the visible drawable `id`.
+    pub fn update(&mut self, id: usize, drawable: Box<dyn Drawable>) {
+        self.push(Change::Drawable {
+            drawable,
+            notify: false,
+            target: Some(id),
+        });
+    }
+
+    /// Commits changed versions of several visible drawables as a single undo step, the
+    /// ones without a version are removed.
+    pub fn update_all(&mut self, changes: Vec<(usize, Option<Box<dyn Drawable>>)>) {
+        for (i, (id, drawable)) in changes.into_iter().enumerate() {
+            let change = match drawable {
+                Some(drawable) => Change::Drawable {
+                    drawable,
+                    notify: false,
+                    target: Some(id),
+                },
+                None => Change::Removed(id),
+            };
+            self.push_joined(change, i > 0);
+        }
+        // only once the step is complete, the floor must not split it
+        self.limit_memory();
+    }
+
+    pub fn commit_crop(&mut self, crop: Option<Crop>) {
+        self.push(Change::Crop(crop));
+    }
+
+    /// Replaces the image as a single undo step, along with removing the visible drawables
+    /// and the crop that were placed on the previous one.
+    pub fn replace_image(&mut self, image: Pixbuf) {
+        let mut changes: Vec<Change> = self
+            .annotations()
+            .map(|(id, _)| Change::Removed(id))
+            .collect();
+        if self.crop().is_some() {
+            changes.push(Change::Crop(None));
+        }
+        changes.push(Change::Image(image));
+        for (i, change) in changes.into_iter().enumerate() {
+            self.push_joined(change, i > 0);
+        }
+        self.limit_memory();
+    }
+
+    /// Replaces the drawable of the current node without adding an undo step.
+    pub fn amend(&mut self, drawable: Box<dyn Drawable>) {
+        if let Some(id) = self.current {
+            if let Change::Drawable { drawable: d, .. } = &mut self.nodes[id].change {
+                self.size = self.size.saturating_sub(d.memory_size()) + drawable.memory_size();
+                *d = drawable;
+            }
+        }
+        self.limit_memory();
+    }
+
+    /// Removes the visible drawable `id`.
+    pub fn remove(&mut self, id: usize) -> bool {
+        if self.get(id).is_none() {
+            return false;
+        }
+        self.push(Change::Removed(id));
+        true
+    }
+
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Commits a copy of the visible drawable `id`, moved by `offset`.
+    /// Returns the id of the copy.
+    pub fn duplicate(&mut self, id: usize, offset: Vec2D) -> Option<usize> {
+        let mut copy = self.get(id)?.clone_box();
+        copy.translate(offset);
+        Some(self.push(Change::Drawable {
+            drawable: copy,
+            notify: false,
+            target: None,
+        }))
+    }
+
+    fn push(&mut self, change: Change) -> usize {
+        let id = self.push_joined(change, false);
+        self.limit_memory();
+        id
+    }
+
+    fn push_joined(&mut self, change: Change, joined: bool) -> usize {
+        // whatever redo would have walked into is now an orphaned branch
+        if let Some(abandoned) = self.active_child(self.current) {
+            let tip = self.branch_tip(abandoned);
+            self.orphans.push(tip);
+        }
+
+        let id = self.nodes.len();
+        self.size += change.memory_size();
+        self.nodes.push(UndoNode {
+            change,
+            parent: self.current,
+            active_child: None,
+            joined,
+        });
+        self.set_active_child(self.current, Some(id));
+        self.current = Some(id);
+        id
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let mut undone = false;
+        while let Some(id) = self.current.filter(|&id| self.floor != Some(id)) {
+            // notify of the undo action
+            if let Change::Drawable {
+                drawable,
+                notify: true,
+                ..
+            } = &mut self.nodes[id].change
+            {
+                drawable.handle_undo();
+            }
+            self.current = self.nodes[id].parent;
+            undone = true;
+            if !self.nodes[id].joined {
+                break;
+            }
+        }
+        undone
+    }
+
+    pub fn redo(&mut self) -> bool {
+        if !self.redo_node() {
+            return false;
+        }
+        while self
+            .active_child(self.current)
+            .is_some_and(|id| self.nodes[id].joined)
+        {
+            self.redo_node();
+        }
+        true
+    }
+
+    // redoes the active child alone, even if more nodes were made in the same step
+    fn redo_node(&mut self) -> bool {
+        match self.active_child(self.current) {
+            Some(id) => {
+                // notify of the redo action
+                if let Change::Drawable {
+                    drawable,
+                    notify: true,
+                    ..
+                } = &mut self.nodes[id].change
+                {
+                    drawable.handle_redo();
+                }
+                self.current = Some(id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn reset(&mut self) -> bool {
+        let mut any_undone = false;
+        while self.undo() {
+            any_undone = true;
+        }
+        any_undone
+    }
+
+    /// Like `reset`, but the visible drawables that are `kept` stay. The others are removed
+    /// as a single undo step instead, along with the crop and a replaced image, which is set
+    /// back to `original`.
+    pub fn reset_keeping(&mut self, kept: impl Fn(usize) -> bool, original: &Pixbuf) -> bool {
+        if !self.annotations().any(|(id, _)| kept(id)) {
+            return self.reset();
+        }
+        let mut changes: Vec<Change> = self
+            .annotations()
+            .filter(|&(id, _)| !kept(id))
+            .map(|(id, _)| Change::Removed(id))
+            .collect();
+        if self.crop().is_some() {
+            changes.push(Change::Crop(None));
+        }
+        if self.image().is_some_and(|image| image != original) {
+            changes.push(Change::Image(original.clone()));
+        }
+        if changes.is_empty() {
+            return false;
+        }
+        for (i, change) in changes.into_iter().enumerate() {
+            self.push_joined(change, i > 0);
+        }
+        self.limit_memory();
+        true
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Jump to the most recently orphaned branch. The branch that was active
+    /// before becomes an orphan itself, so calling this again jumps back.
+    pub fn switch_branch(&mut self) -> bool {
+        let Some(target) = self.orphans.pop() else {
+            return false;
+        };
+
+        // remember the line we are leaving
+        if let Some(first) = self.path_start_for_tip() {
+            let tip = self.branch_tip(first);
+            self.orphans.push(tip);
+        }
+
+        // walk back to the common ancestor
+        let target_path = self.path_to(Some(target));
+        while let Some(id) = self.current {
+            if target_path.contains(&id) || !self.undo() {
+                break;
+            }
+        }
+
+        // and forward along the target branch
+        let start = match self.current {
+            Some(id) => target_path.iter().position(|&n| n == id).unwrap() + 1,
+            None => 0,
+        };
+        for &id in &target_path[start..] {
+            self.set_active_child(self.current, Some(id));
+            self.redo_node();
+        }
+        true
+    }
+
+    /// The states from the oldest one undo can return to up to the current one, without
+    /// crop changes and only since the image was last replaced. `None` is the image
+    /// without annotations.
+    pub fn steps(&self) -> Vec<Option<usize>> {
+        let path = self.path_to(self.current);
+        let first = path.iter().rposition(|&id| {
+            self.floor == Some(id) || matches!(self.nodes[id].change, Change::Image(_))
+        });
+        // nodes made in one step only count once
+        let start = first.unwrap_or(0);
+        let steps = path[start..]
+            .iter()
+            .enumerate()
+            .filter(|&(i, &id)| {
+                !matches!(self.nodes[id].change, Change::Crop(_))
+                    && path
+                        .get(start + i + 1)
+                        .is_none_or(|&next| !self.nodes[next].joined)
+            })
+            .map(|(_, &id)| Some(id));
+        match first {
+            Some(_) => steps.collect(),
+            None => std::iter::once(None).chain(steps).collect(),
+        }
+    }
+
+    /// Shows the state of `step` without notifying the drawables, to replay the history.
+    /// The previous state has to be restored the same way afterwards.
+    pub fn show_step(&mut self, step: Option<usize>) {
+        self.current = step;
+    }
+
+    /// The visible drawables along with their ids, in drawing order.
+    pub fn annotations(&self) -> impl Iterator<Item = (usize, &dyn Drawable)> {
+        let mut visible: Vec<(usize, &dyn Drawable)> = Vec::new();
+        for id in self.path_to(self.current) {
+            match &self.nodes[id].change {
+                Change::Drawable {
+                    drawable,
+                    target: Some(target),
+                    ..
+                } => {
+                    if let Some(entry) = visible.iter_mut().find(|(t, _)| t == target) {
+                        entry.1 = drawable.as_ref();
+                    }
+                }
+                Change::Drawable {
+                    drawable,
+                    target: None,
+                    ..
+                } => visible.push((id, drawable.as_ref())),
+                Change::Removed(target) => visible.retain(|(t, _)| t != target),
+                Change::Crop(_) | Change::Image(_) | Change::Evicted => (),
+            }
+        }
+        visible.into_iter()
+    }
+
+    /// The crop as of the current node.
+    pub fn crop(&self) -> Option<&Crop> {
+        self.path_to(self.current)
+            .into_iter()
+            .rev()
+            .find_map(|id| match &self.nodes[id].change {
+                Change::Crop(crop) => Some(crop.as_ref()),
+                Change::Drawable { .. }
+                | Change::Removed(_)
+                | Change::Image(_)
+                | Change::Evicted => None,
+            })
+            .flatten()
+    }
+
+    /// The image as of the current node, `None` if it wasn't replaced.
+    pub fn image(&self) -> Option<&Pixbuf> {
+        self.path_to(self.current)
+            .into_iter()
+            .rev()
+            .find_map(|id| match &self.nodes[id].change {
+                Change::Image(image) => Some(image),
+                Change::Drawable { .. }
+                | Change::Crop(_)
+                | Change::Removed(_)
+                | Change::Evicted => None,
+            })
+    }
+
+    /// Drops the pixels the drawables cached of the image, after it changed.
+    pub fn clear_caches(&self) {
+        for node in &self.nodes {
+            if let Change::Drawable { drawable, .. } = &node.change {
+                drawable.clear_cache();
+            }
+        }
+    }
+
+    /// The drawable with the given id, if it is currently visible.
+    pub fn get(&self, id: usize) -> Option<&dyn Drawable> {
+        self.annotations()
+            .find(|&(node, _)| node == id)
+            .map(|(_, drawable)| drawable)
+    }
+
+    // memory that only the history holds, which dropping orphaned branches or raising the
+    // floor can free: the drawables off the current line and redo line, and on the current
+    // line the ones replaced or removed since, along with replaced images
+    fn history_size(&self) -> usize {
+        let path = self.path_to(self.current);
+        let mut on_line = vec![false; self.nodes.len()];
+        for &id in &path {
+            on_line[id] = true;
+        }
+        let mut node = self.active_child(self.current);
+        while let Some(id) = node {
+            on_line[id] = true;
+            node = self.nodes[id].active_child;
+        }
+
+        let mut size: usize = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|&(id, _)| !on_line[id])
+            .map(|(_, node)| node.change.memory_size())
+            .sum();
+
+        // memory of the visible version of each drawable by its id
+        let mut visible = HashMap::new();
+        let mut image = 0;
+        for id in path {
+            match &self.nodes[id].change {
+                Change::Drawable {
+                    drawable,
+                    target: Some(target),
+                    ..
+                } => {
+                    if let Some(shown) = visible.get_mut(target) {
+                        size += std::mem::replace(shown, drawable.memory_size());
+                    }
+                }
+                Change::Drawable {
+                    drawable,
+                    target: None,
+                    ..
+                } => {
+                    visible.insert(id, drawable.memory_size());
+                }
+                Change::Removed(target) => size += visible.remove(target).unwrap_or_default(),
+                Change::Image(replaced) => {
+                    size += std::mem::replace(&mut image, replaced.byte_length());
+                }
+                Change::Crop(_) | Change::Evicted => (),
+            }
+        }
+        size
+    }
+
+    fn limit_memory(&mut self) {
+        if let Some(limit) = APP_CONFIG.read().undo_memory_limit() {
+            self.limit_memory_to(limit);
+        }
+    }
+
+    fn limit_memory_to(&mut self, limit: usize) {
+        // the history is part of all drawables, so it is only measured above the limit
+        if self.size <= limit {
+            return;
+        }
+        self.size = self
+            .nodes
+            .iter()
+            .map(|node| node.change.memory_size())
+            .sum();
+        while self.size > limit && self.history_size() > limit {
+            if !self.orphans.is_empty() {
+                self.orphans.remove(0);
+            } else if !self.raise_floor() {
+                // only what is visible or can be redone is left
+                break;
+            }
+            self.drop_unreachable();
+        }
+    }
+
+    // makes the steps on the current line permanent up to the oldest one after which a
+    // drawable or an image is no longer visible, raising it further would disable undo for
+    // nothing
+    fn raise_floor(&mut self) -> bool {
+        let path = self.path_to(self.current);
+        let start = match self.floor {
+            Some(floor) => path.iter().position(|&id| id == floor).map_or(0, |i| i + 1),
+            None => 0,
+        };
+        let next = path[start..].iter().enumerate().find(|&(i, &id)| {
+            matches!(
+                self.nodes[id].change,
+                Change::Drawable {
+                    target: Some(_),
+                    ..
+                } | Change::Removed(_)
+                    | Change::Image(_)
+            ) && path
+                .get(start + i + 1)
+                .is_none_or(|&next| !self.nodes[next].joined)
+        });
+        match next {
+            Some(&id) => {
+                self.floor = Some(id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // evicts the nodes that neither undo, redo nor `switch_branch` can reach anymore
+    fn drop_unreachable(&mut self) {
+        let current_path = self.path_to(self.current);
+        if let Some(floor) = self.floor {
+            let orphans = std::mem::take(&mut self.orphans);
+            self.orphans = orphans
+                .into_iter()
+                .filter(|&tip| self.path_to(Some(tip)).contains(&floor))
+                .collect();
+
+            // replaced versions of drawables up to the floor can't be shown again, and
+            // neither can removed ones or replaced images
+            let permanent = current_path
+                .iter()
+                .position(|&id| id == floor)
+                .map_or(0, |i| i + 1);
+            let mut image = None;
+            for &id in &current_path[..permanent] {
+                match self.nodes[id].change {
+                    Change::Drawable {
+                        target: Some(target),
+                        ..
+                    } => {
+                        let Change::Drawable { drawable, .. } =
+                            std::mem::replace(&mut self.nodes[id].change, Change::Evicted)
+                        else {
+                            continue;
+                        };
+                        if let Change::Drawable { drawable: d, .. } = &mut self.nodes[target].change
+                        {
+                            self.size = self.size.saturating_sub(d.memory_size());
+                            *d = drawable;
+                        }
+                    }
+                    Change::Removed(target) => self.evict(target),
+                    Change::Image(_) => {
+                        if let Some(replaced) = image.replace(id) {
+                            self.evict(replaced);
+                        }
+                    }
+                    Change::Drawable { .. } | Change::Crop(_) | Change::Evicted => (),
+                }
+            }
+        }
+
+        let mut kept = vec![false; self.nodes.len()];
+        for id in current_path {
+            kept[id] = true;
+        }
+        if let Some(start) = self.active_child(self.current) {
+            let mut node = Some(start);
+            while let Some(id) = node {
+                kept[id] = true;
+                node = self.nodes[id].active_child;
+            }
+        }
+        for &tip in &self.orphans {
+            for id in self.path_to(Some(tip)) {
+                kept[id] = true;
+            }
+        }
+
+        for id in 0..self.nodes.len() {
+            if !kept[id] {
+                self.evict(id);
+            }
+            let node = &mut self.nodes[id];
+            if node.active_child.is_some_and(|child| !kept[child]) {
+                node.active_child = None;
+            }
+        }
+        if self.root_active_child.is_some_and(|child| !kept[child]) {
+            self.root_active_child = None;
+        }
+    }
+
+    fn evict(&mut self, id: usize) {
+        let change = std::mem::replace(&mut self.nodes[id].change, Change::Evicted);
+        self.size = self.size.saturating_sub(change.memory_size());
+    }
+
+    fn path_to(&self, node: Option<usize>) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut node = node;
+        while let Some(id) = node {
+            path.push(id);
+            node = self.nodes[id].parent;
+        }
+        path.reverse();
+        path
+    }
+
+    // node from which the tip of the currently active line can be reached
+    fn path_start_for_tip(&self) -> Option<usize> {
+        self.current.or(self.root_active_child)
+    }
+
+    fn branch_tip(&self, node: usize) -> usize {
+        let mut tip = node;
+        while let Some(child) = self.nodes[tip].active_child {
+            tip = child;
+        }
+        tip
+    }
+
+    fn active_child(&self, node: Option<usize>) -> Option<usize> {
+        match node {
+            Some(id) => self.nodes[id].active_child,
+            None => self.root_active_child,
+        }
+    }
+
+    fn set_active_child(&mut self, node: Option<usize>, child: Option<usize>) {
+        match node {
+            Some(id) => self.nodes[id].active_child = child,
+            None => self.root_active_child = child,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use femtovg::{renderer::OpenGl, Canvas, FontId};
+
+    use super::*;
+    use crate::tools::Tools;
+
+    const LIMIT: usize = 100;
+
+    // drawable that only holds memory, told apart by its size
+    #[derive(Clone, Debug)]
+    struct Blob(usize);
+
+    impl Drawable for Blob {
+        fn draw(&self, _: &mut Canvas<OpenGl>, _: FontId, _: (Vec2D, Vec2D)) -> Result<()> {
+            Ok(())
+        }
+
+        fn get_tool_type(&self) -> Tools {
+            Tools::Rectangle
+        }
+
+        fn memory_size(&self) -> usize {
+            self.0
+        }
+    }
+
+    fn size_of(tree: &UndoTree, id: usize) -> Option<usize> {
+        tree.get(id).map(|drawable| drawable.memory_size())
+    }
+
+    #[test]
+    fn switches_to_the_abandoned_branch() {
+        let mut tree = UndoTree::default();
+        tree.commit(Box::new(Blob(1)));
+        tree.undo();
+        tree.commit(Box::new(Blob(2)));
+        assert_eq!(size_of(&tree, 0), None);
+
+        assert!(tree.switch_branch());
+        assert_eq!(size_of(&tree, 0), Some(1));
+        assert_eq!(size_of(&tree, 1), None);
+
+        // the branch that was left became the orphan
+        assert!(tree.switch_branch());
+        assert_eq!(size_of(&tree, 1), Some(2));
+    }
+
+    // visible drawables and the redo line are never dropped, however much they hold
+    #[test]
+    fn keeps_what_is_visible_above_the_limit() {
+        let mut tree = UndoTree::default();
+        for _ in 0..3 {
+            tree.commit(Box::new(Blob(60)));
+            tree.limit_memory_to(LIMIT);
+        }
+        assert!(tree.undo());
+        tree.limit_memory_to(LIMIT);
+        assert_eq!(tree.annotations().count(), 2);
+        assert!(tree.reset());
+        assert!(tree.redo() && tree.redo() && tree.redo());
+        assert_eq!(tree.annotations().count(), 3);
+    }
+
+    #[test]
+    fn drops_the_oldest_orphan_first() {
+        let mut tree = UndoTree::default();
+        tree.commit(Box::new(Blob(60)));
+        tree.undo();
+        tree.commit(Box::new(Blob(61)));
+        tree.limit_memory_to(LIMIT);
+        tree.undo();
+        tree.commit(Box::new(Blob(62)));
+        tree.limit_memory_to(LIMIT);
+
+        assert!(tree.switch_branch());
+        assert_eq!(size_of(&tree, 1), Some(61));
+        assert!(tree.switch_branch());
+        assert_eq!(size_of(&tree, 2), Some(62));
+        assert!(tree.switch_branch());
+        assert_eq!(size_of(&tree, 1), Some(61));
+        assert_eq!(size_of(&tree, 0), None);
+    }
+
+    // the replaced versions are dropped by making the oldest steps permanent
+    #[test]
+    fn raises_the_floor_above_the_limit() {
+        let mut tree = UndoTree::default();
+        tree.commit(Box::new(Blob(50)));
+        tree.update(0, Box::new(Blob(60)));
+        tree.limit_memory_to(LIMIT);
+        tree.update(0, Box::new(Blob(70)));
+        tree.limit_memory_to(LIMIT);
+        assert_eq!(size_of(&tree, 0), Some(70));
+
+        assert!(tree.undo());
+        assert_eq!(size_of(&tree, 0), Some(60));
+        assert!(!tree.undo());
+        assert_eq!(tree.steps(), vec![Some(1)]);
+
+        assert!(tree.redo());
+        assert_eq!(size_of(&tree, 0), Some(70));
+    }
+
+    // a step of several nodes is undone as a whole
+    #[test]
+    fn undoes_joined_steps_together() {
+        let mut tree = UndoTree::default();
+        tree.commit(Box::new(Blob(1)));
+        tree.commit(Box::new(Blob(2)));
+        let changed: Box<dyn Drawable> = Box::new(Blob(3));
+        tree.update_all(vec![(0, Some(changed)), (1, None)]);
+        assert_eq!(size_of(&tree, 0), Some(3));
+        assert_eq!(size_of(&tree, 1), None);
+
+        assert!(tree.undo());
+        assert_eq!(size_of(&tree, 0), Some(1));
+        assert_eq!(size_of(&tree, 1), Some(2));
+    }
+}